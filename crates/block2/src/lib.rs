@@ -371,12 +371,20 @@ mod debug;
 mod encoding;
 pub mod ffi;
 mod global;
+#[cfg(not(feature = "unstable-objfw"))]
+mod method;
+#[cfg(not(feature = "unstable-objfw"))]
+mod protocol;
 mod rc_block;
 mod stack;
 mod traits;
 
 pub use self::block::Block;
 pub use self::global::GlobalBlock;
+#[cfg(not(feature = "unstable-objfw"))]
+pub use self::method::{ClassBuilderExt, MethodClosure, MethodExt};
+#[cfg(not(feature = "unstable-objfw"))]
+pub use self::protocol::ProtocolObjectBuilder;
 pub use self::rc_block::RcBlock;
 pub use self::stack::StackBlock;
 pub use self::traits::{BlockFn, IntoBlock, ManualBlockEncoding};
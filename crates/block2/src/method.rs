@@ -0,0 +1,408 @@
+//! Using closures as the implementation of Objective-C methods.
+//!
+//! The runtime function `imp_implementationWithBlock` turns a block into a
+//! method implementation, which calls the block with the receiver followed
+//! by the method's arguments (the selector is dropped).
+use core::ffi::c_void;
+use core::mem;
+use core::ptr::NonNull;
+
+#[cfg(any(target_arch = "arm", target_arch = "x86"))]
+use objc2::encode::Encoding;
+use objc2::encode::{EncodeArgument, EncodeArguments, EncodeReturn};
+use objc2::ffi;
+use objc2::runtime::{AnyObject, ClassBuilder, Imp, Method, Sel};
+
+use crate::{Block, RcBlock};
+
+mod private {
+    pub trait Sealed<A, R> {}
+    pub trait SealedClassBuilder {}
+    pub trait SealedMethod {}
+}
+
+/// Closures that can be used as the implementation of an Objective-C
+/// method.
+///
+/// This is implemented for [`Fn`] closures that take `&AnyObject` (the
+/// receiver) followed by up to 11 parameters, where each parameter
+/// implements [`EncodeArgument`] and the return type implements
+/// [`EncodeReturn`].
+///
+/// The closures must be `Send + Sync`, since the method may be called from
+/// any thread, and `'static`, since the method may be called at any point
+/// after it has been added.
+///
+/// This is a sealed trait, and should not need to be implemented.
+pub trait MethodClosure<A, R>: private::Sealed<A, R>
+where
+    A: EncodeArguments,
+    R: EncodeReturn,
+{
+    #[doc(hidden)]
+    fn __into_imp(self) -> Imp;
+
+    /// Convert the closure into a heap block, along with an implementation
+    /// that looks up the block for the receiver and selector in the
+    /// instance variable that `ProtocolObjectBuilder` uses, and calls it.
+    #[doc(hidden)]
+    fn __into_block_and_trampoline(self) -> (NonNull<c_void>, Imp);
+}
+
+macro_rules! impl_method_closure {
+    ($($a:ident: $t:ident),*) => {
+        impl<$($t: EncodeArgument,)* R: EncodeReturn, Closure> private::Sealed<($($t,)*), R> for Closure
+        where
+            Closure: Fn(&AnyObject $(, $t)*) -> R + Send + Sync + 'static,
+        {}
+
+        impl<$($t: EncodeArgument,)* R: EncodeReturn, Closure> MethodClosure<($($t,)*), R> for Closure
+        where
+            Closure: Fn(&AnyObject $(, $t)*) -> R + Send + Sync + 'static,
+        {
+            fn __into_imp(self) -> Imp {
+                let block = RcBlock::new(move |this: *mut AnyObject $(, $a: $t)*| {
+                    // SAFETY: The runtime always passes a valid receiver.
+                    let this = unsafe { &*this };
+                    (self)(this $(, $a)*)
+                });
+                // SAFETY: The block is valid, and has the signature that the
+                // runtime expects. The runtime copies the block, so the
+                // implementation stays valid after our reference is dropped.
+                unsafe { ffi::imp_implementationWithBlock(RcBlock::as_ptr(&block).cast()) }
+            }
+
+            fn __into_block_and_trampoline(self) -> (NonNull<c_void>, Imp) {
+                unsafe extern "C-unwind" fn trampoline<$($t: EncodeArgument,)* R: EncodeReturn>(
+                    this: *mut AnyObject,
+                    cmd: Sel,
+                    $($a: $t,)*
+                ) -> R {
+                    // SAFETY: The trampoline is only used in classes created
+                    // by `ProtocolObjectBuilder`, which stores a block with
+                    // this signature for every selector it implements.
+                    let block = unsafe { crate::protocol::closure_block(this, cmd) };
+                    let block: &Block<dyn Fn(*mut AnyObject $(, $t)*) -> R> =
+                        unsafe { block.cast().as_ref() };
+                    block.call((this, $($a,)*))
+                }
+
+                let block = RcBlock::new(move |this: *mut AnyObject $(, $a: $t)*| {
+                    // SAFETY: The runtime always passes a valid receiver.
+                    let this = unsafe { &*this };
+                    (self)(this $(, $a)*)
+                });
+                let block = NonNull::new(RcBlock::into_raw(block)).unwrap().cast();
+
+                type Trampoline<$($t,)* R> = unsafe extern "C-unwind" fn(*mut AnyObject, Sel $(, $t)*) -> R;
+                // SAFETY: Transmuting to an `unsafe` function pointer.
+                let imp = unsafe {
+                    mem::transmute::<Trampoline<$($t,)* R>, Imp>(trampoline::<$($t,)* R>)
+                };
+                (block, imp)
+            }
+        }
+    };
+}
+
+/// Whether the method implementation returns `R` via a pointer passed in by
+/// the caller.
+///
+/// This mirrors the choice between `objc_msgSend` and `objc_msgSend_stret`
+/// in `objc2`.
+fn uses_stret<R: EncodeReturn>() -> bool {
+    #[cfg(target_arch = "aarch64")]
+    {
+        false
+    }
+    #[cfg(target_arch = "arm")]
+    {
+        !matches!(
+            R::ENCODING_RETURN,
+            Encoding::LongLong | Encoding::ULongLong | Encoding::Double
+        ) && mem::size_of::<R>() > 4
+    }
+    #[cfg(target_arch = "x86")]
+    {
+        !matches!(
+            R::ENCODING_RETURN,
+            Encoding::Float | Encoding::Double | Encoding::LongDouble
+        ) && !matches!(mem::size_of::<R>(), 0 | 1 | 2 | 4 | 8)
+    }
+    #[cfg(target_arch = "x86_64")]
+    {
+        mem::size_of::<R>() > 16
+    }
+    #[cfg(not(any(
+        target_arch = "aarch64",
+        target_arch = "arm",
+        target_arch = "x86",
+        target_arch = "x86_64"
+    )))]
+    {
+        false
+    }
+}
+
+/// `imp_implementationWithBlock` only uses the stret calling convention if
+/// the block is flagged with both `BLOCK_USE_STRET` and `BLOCK_HAS_SIGNATURE`,
+/// which `RcBlock::new` doesn't set. Reject such return types instead of
+/// creating an implementation with a mismatched calling convention.
+#[track_caller]
+fn assert_no_stret<R: EncodeReturn>() {
+    if uses_stret::<R>() {
+        panic!(
+            "closures returning {} via a hidden pointer (stret) are not supported on this target",
+            R::ENCODING_RETURN
+        );
+    }
+}
+
+impl_method_closure!();
+impl_method_closure!(t0: T0);
+impl_method_closure!(t0: T0, t1: T1);
+impl_method_closure!(t0: T0, t1: T1, t2: T2);
+impl_method_closure!(t0: T0, t1: T1, t2: T2, t3: T3);
+impl_method_closure!(t0: T0, t1: T1, t2: T2, t3: T3, t4: T4);
+impl_method_closure!(t0: T0, t1: T1, t2: T2, t3: T3, t4: T4, t5: T5);
+impl_method_closure!(t0: T0, t1: T1, t2: T2, t3: T3, t4: T4, t5: T5, t6: T6);
+impl_method_closure!(t0: T0, t1: T1, t2: T2, t3: T3, t4: T4, t5: T5, t6: T6, t7: T7);
+impl_method_closure!(t0: T0, t1: T1, t2: T2, t3: T3, t4: T4, t5: T5, t6: T6, t7: T7, t8: T8);
+impl_method_closure!(t0: T0, t1: T1, t2: T2, t3: T3, t4: T4, t5: T5, t6: T6, t7: T7, t8: T8, t9: T9);
+impl_method_closure!(t0: T0, t1: T1, t2: T2, t3: T3, t4: T4, t5: T5, t6: T6, t7: T7, t8: T8, t9: T9, t10: T10);
+
+/// Extension methods on [`ClassBuilder`] for implementing methods with
+/// closures.
+///
+/// This is a sealed trait, and is only implemented for [`ClassBuilder`].
+pub trait ClassBuilderExt: private::SealedClassBuilder {
+    /// Adds a method with the given name, implemented by the given closure.
+    ///
+    /// The closure is called with the receiver followed by the arguments of
+    /// the method, and may capture state, which is useful when building
+    /// classes at runtime.
+    ///
+    ///
+    /// # Panics
+    ///
+    /// Panics in the same cases as [`ClassBuilder::add_method`], and if the
+    /// return type is a structure that is returned via a hidden pointer on
+    /// the current target (such as `NSRect` on x86_64).
+    ///
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the types match those that are expected
+    /// when the method is invoked from Objective-C.
+    ///
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ffi::CStr;
+    /// use std::sync::atomic::{AtomicU32, Ordering};
+    ///
+    /// use block2::ClassBuilderExt;
+    /// use objc2::runtime::{AnyObject, ClassBuilder, NSObject};
+    /// use objc2::{msg_send, msg_send_id, sel, ClassType};
+    /// use objc2::rc::Retained;
+    ///
+    /// let name = CStr::from_bytes_with_nul(b"ClassBuilderExtExample\0").unwrap();
+    /// let mut builder = ClassBuilder::new(name, NSObject::class()).unwrap();
+    ///
+    /// let counter = AtomicU32::new(0);
+    /// // SAFETY: The signature is correct.
+    /// unsafe {
+    ///     builder.add_method_closure(sel!(next), move |_this: &AnyObject| {
+    ///         counter.fetch_add(1, Ordering::Relaxed)
+    ///     });
+    /// }
+    /// let cls = builder.register();
+    ///
+    /// let obj: Retained<AnyObject> = unsafe { msg_send_id![cls, new] };
+    /// let first: u32 = unsafe { msg_send![&obj, next] };
+    /// let second: u32 = unsafe { msg_send![&obj, next] };
+    /// assert_eq!((first, second), (0, 1));
+    /// ```
+    unsafe fn add_method_closure<A, R, F>(&mut self, sel: Sel, closure: F)
+    where
+        A: EncodeArguments,
+        R: EncodeReturn,
+        F: MethodClosure<A, R>;
+}
+
+impl private::SealedClassBuilder for ClassBuilder {}
+
+impl ClassBuilderExt for ClassBuilder {
+    #[track_caller]
+    unsafe fn add_method_closure<A, R, F>(&mut self, sel: Sel, closure: F)
+    where
+        A: EncodeArguments,
+        R: EncodeReturn,
+        F: MethodClosure<A, R>,
+    {
+        assert_no_stret::<R>();
+        let imp = closure.__into_imp();
+        // SAFETY: The implementation takes the receiver, the selector and
+        // arguments of type `A`, and returns `R`. The caller upholds the
+        // rest.
+        unsafe { self.add_method_imp::<A, R>(sel, imp) }
+    }
+}
+
+/// Extension methods on [`Method`] for replacing its implementation with a
+/// closure.
+///
+/// This is a sealed trait, and is only implemented for [`Method`].
+pub trait MethodExt: private::SealedMethod {
+    /// Set the implementation of this method to the given closure.
+    ///
+    /// The closure is called with the receiver followed by the arguments of
+    /// the method. The previous implementation is returned, see
+    /// [`Method::set_implementation`].
+    ///
+    /// The previous implementation is never freed, since other threads may
+    /// still be calling it.
+    ///
+    ///
+    /// # Panics
+    ///
+    /// Panics if the argument or return types of the closure do not match
+    /// the type encoding of the method, and if the return type is a
+    /// structure that is returned via a hidden pointer on the current target
+    /// (such as `NSRect` on x86_64).
+    ///
+    ///
+    /// # Safety
+    ///
+    /// The same requirements as in [`Method::set_implementation`] apply,
+    /// except that the signature is checked against the method's encoding.
+    unsafe fn set_implementation_closure<A, R, F>(&self, closure: F) -> Imp
+    where
+        A: EncodeArguments,
+        R: EncodeReturn,
+        F: MethodClosure<A, R>;
+}
+
+impl private::SealedMethod for Method {}
+
+impl MethodExt for Method {
+    #[track_caller]
+    unsafe fn set_implementation_closure<A, R, F>(&self, closure: F) -> Imp
+    where
+        A: EncodeArguments,
+        R: EncodeReturn,
+        F: MethodClosure<A, R>,
+    {
+        if let Err(err) = self.verify::<A, R>() {
+            panic!(
+                "closure does not match the signature of method {}: {err}",
+                self.name()
+            );
+        }
+        assert_no_stret::<R>();
+        let imp = closure.__into_imp();
+        // SAFETY: The signature was verified above, the caller upholds the
+        // rest.
+        unsafe { self.set_implementation(imp) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicI32, Ordering};
+    use std::ffi::CStr;
+
+    use objc2::encode::{Encode, Encoding};
+    use objc2::rc::Retained;
+    use objc2::runtime::NSObject;
+    use objc2::{msg_send, msg_send_id, sel, ClassType};
+
+    use super::*;
+
+    #[test]
+    fn add_method_closure() {
+        let name = CStr::from_bytes_with_nul(b"Block2AddMethodClosure\0").unwrap();
+        let mut builder = ClassBuilder::new(name, NSObject::class()).unwrap();
+
+        let total = Arc::new(AtomicI32::new(0));
+        let total_clone = Arc::clone(&total);
+        unsafe {
+            builder.add_method_closure(sel!(add:), move |_this: &AnyObject, value: i32| {
+                total_clone.fetch_add(value, Ordering::Relaxed) + value
+            });
+        }
+        let cls = builder.register();
+
+        let obj: Retained<AnyObject> = unsafe { msg_send_id![cls, new] };
+        let res: i32 = unsafe { msg_send![&obj, add: 2] };
+        assert_eq!(res, 2);
+        let res: i32 = unsafe { msg_send![&obj, add: 40] };
+        assert_eq!(res, 42);
+        assert_eq!(total.load(Ordering::Relaxed), 42);
+    }
+
+    #[test]
+    fn set_implementation_closure() {
+        let name = CStr::from_bytes_with_nul(b"Block2SetImplementationClosure\0").unwrap();
+        let mut builder = ClassBuilder::new(name, NSObject::class()).unwrap();
+        unsafe {
+            builder.add_method_closure(sel!(number), |_this: &AnyObject| 1u32);
+        }
+        let cls = builder.register();
+
+        let method = cls.instance_method(sel!(number)).unwrap();
+        let _previous = unsafe { method.set_implementation_closure(|_this: &AnyObject| 2u32) };
+
+        let obj: Retained<AnyObject> = unsafe { msg_send_id![cls, new] };
+        let number: u32 = unsafe { msg_send![&obj, number] };
+        assert_eq!(number, 2);
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct NSRect {
+        origin: [f64; 2],
+        size: [f64; 2],
+    }
+
+    unsafe impl Encode for NSRect {
+        const ENCODING: Encoding = Encoding::Struct(
+            "CGRect",
+            &[
+                Encoding::Struct("CGPoint", &[f64::ENCODING, f64::ENCODING]),
+                Encoding::Struct("CGSize", &[f64::ENCODING, f64::ENCODING]),
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg_attr(
+        not(target_arch = "aarch64"),
+        should_panic = "via a hidden pointer (stret) are not supported on this target"
+    )]
+    fn add_method_closure_stret() {
+        let name = CStr::from_bytes_with_nul(b"Block2AddMethodClosureStret\0").unwrap();
+        let mut builder = ClassBuilder::new(name, NSObject::class()).unwrap();
+        let expected = NSRect {
+            origin: [1.0, 2.0],
+            size: [3.0, 4.0],
+        };
+        unsafe {
+            builder.add_method_closure(sel!(frame), move |_this: &AnyObject| expected);
+        }
+        let cls = builder.register();
+
+        let obj: Retained<AnyObject> = unsafe { msg_send_id![cls, new] };
+        let frame: NSRect = unsafe { msg_send![&obj, frame] };
+        assert_eq!(frame, expected);
+    }
+
+    #[test]
+    #[should_panic = "closure does not match the signature of method hash"]
+    fn set_implementation_closure_wrong_signature() {
+        let method = NSObject::class().instance_method(sel!(hash)).unwrap();
+        let _ = unsafe { method.set_implementation_closure(|_this: &AnyObject| 1.0f32) };
+    }
+}
@@ -0,0 +1,447 @@
+//! Creating objects that implement a protocol with closures.
+//!
+//! The objects are instances of classes that are created at runtime, one for
+//! each protocol and set of implemented selectors. Every method of such a
+//! class is a trampoline, which looks up the block that the instance stores
+//! for the selector and calls it. This allows instances with different
+//! closures to share the same class.
+use alloc::boxed::Box;
+use alloc::ffi::CString;
+use alloc::format;
+use alloc::vec::Vec;
+use core::ffi::{c_void, CStr};
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use std::sync::Mutex;
+
+use objc2::encode::{EncodeArguments, EncodeReturn};
+use objc2::rc::Retained;
+use objc2::runtime::{
+    AnyClass, AnyObject, AnyProtocol, ClassBuilder, Imp, NSObject, ProtocolObject, Sel,
+};
+use objc2::{msg_send, msg_send_id, sel, ClassType, ProtocolType};
+
+use crate::{ffi, MethodClosure};
+
+/// The name of the instance variable that holds the closures.
+const CLOSURES_IVAR: &[u8] = b"closures\0";
+
+/// A heap block, released when dropped.
+struct OwnedBlock(NonNull<c_void>);
+
+impl Drop for OwnedBlock {
+    fn drop(&mut self) {
+        // SAFETY: The block was created on the heap, and we own a reference
+        // to it.
+        unsafe { ffi::_Block_release(self.0.as_ptr()) };
+    }
+}
+
+struct ClosureMethod {
+    sel: Sel,
+    block: OwnedBlock,
+    imp: Imp,
+    add: unsafe fn(&mut ClassBuilder, Sel, Imp),
+}
+
+/// Builder for objects that implement a protocol with Rust closures.
+///
+/// Satisfying a delegate protocol would otherwise require declaring a class
+/// with [`declare_class!`], even if only a single callback is needed. This
+/// instead creates an anonymous class that conforms to the protocol at
+/// runtime, and implements the selected methods of it with closures.
+///
+/// Classes are cached per protocol and set of implemented selectors, so
+/// building several objects with the same methods only creates one class.
+///
+/// Methods that are not given a closure are not implemented, so calling
+/// required methods that [`NSObject`] doesn't implement will throw an
+/// exception.
+///
+/// [`declare_class!`]: objc2::declare_class
+///
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicU32, Ordering};
+/// use std::sync::Arc;
+///
+/// use block2::ProtocolObjectBuilder;
+/// use objc2::runtime::AnyObject;
+/// use objc2::{declare_protocol, sel, ProtocolType};
+///
+/// declare_protocol!(
+///     unsafe trait CounterDelegate {
+///         #[method(counterDidChange:)]
+///         fn counter_did_change(&self, value: u32);
+///     }
+///
+///     unsafe impl ProtocolType for dyn CounterDelegate {}
+/// );
+///
+/// let last = Arc::new(AtomicU32::new(0));
+/// let last_clone = Arc::clone(&last);
+///
+/// // SAFETY: The method doesn't return anything.
+/// let delegate = unsafe {
+///     ProtocolObjectBuilder::<dyn CounterDelegate>::new()
+///         .method(sel!(counterDidChange:), move |_this: &AnyObject, value: u32| {
+///             last_clone.store(value, Ordering::Relaxed);
+///         })
+///         .build()
+/// };
+///
+/// delegate.counter_did_change(42);
+/// assert_eq!(last.load(Ordering::Relaxed), 42);
+/// ```
+pub struct ProtocolObjectBuilder<P: ?Sized> {
+    protocol: &'static AnyProtocol,
+    methods: Vec<ClosureMethod>,
+    p: PhantomData<P>,
+}
+
+impl<P: ?Sized + ProtocolType> ProtocolObjectBuilder<P> {
+    /// Start building an object that implements the protocol `P`.
+    ///
+    ///
+    /// # Panics
+    ///
+    /// Panics if the protocol is not registered with the runtime.
+    #[track_caller]
+    pub fn new() -> Self {
+        let protocol =
+            P::protocol().unwrap_or_else(|| panic!("protocol {} is not registered", P::NAME));
+        Self {
+            protocol,
+            methods: Vec::new(),
+            p: PhantomData,
+        }
+    }
+}
+
+impl<P: ?Sized + ProtocolType> Default for ProtocolObjectBuilder<P> {
+    #[inline]
+    #[track_caller]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: ?Sized> ProtocolObjectBuilder<P> {
+    /// Implement the instance method with the given selector with the
+    /// closure.
+    ///
+    /// The closure is called with the receiver followed by the arguments of
+    /// the method. Setting a closure for a selector that already has one
+    /// replaces it.
+    ///
+    ///
+    /// # Panics
+    ///
+    /// Panics if the protocol (or the protocols it adopts) does not contain
+    /// an instance method with the given selector, or if the argument or
+    /// return types of the closure do not match the type encoding of it.
+    ///
+    ///
+    /// # Safety
+    ///
+    /// The types are checked against the protocol's encoding, but the
+    /// closure must uphold the memory management rules that callers of the
+    /// method expect, e.g. return a retained object for methods in the `new`
+    /// and `copy` families.
+    #[track_caller]
+    pub unsafe fn method<A, R, F>(mut self, sel: Sel, closure: F) -> Self
+    where
+        A: EncodeArguments,
+        R: EncodeReturn,
+        F: MethodClosure<A, R>,
+    {
+        if let Err(err) = self.protocol.verify_sel::<A, R>(sel) {
+            panic!(
+                "closure does not match method {sel} of protocol {}: {err}",
+                self.protocol
+            );
+        }
+
+        unsafe fn add<A: EncodeArguments, R: EncodeReturn>(
+            builder: &mut ClassBuilder,
+            sel: Sel,
+            imp: Imp,
+        ) {
+            // SAFETY: The implementation takes arguments `A` and returns
+            // `R`, which was verified against the protocol.
+            unsafe { builder.add_method_imp::<A, R>(sel, imp) }
+        }
+
+        let (block, imp) = closure.__into_block_and_trampoline();
+        self.methods.retain(|method| method.sel != sel);
+        self.methods.push(ClosureMethod {
+            sel,
+            block: OwnedBlock(block),
+            imp,
+            add: add::<A, R>,
+        });
+        self
+    }
+
+    /// Create the object.
+    pub fn build(mut self) -> Retained<ProtocolObject<P>> {
+        // Sort, such that the order the closures were given in doesn't matter
+        // for the cache.
+        self.methods
+            .sort_unstable_by_key(|method| method.sel.name());
+        let cls = protocol_class(self.protocol, &self.methods);
+
+        let obj: Retained<AnyObject> = unsafe { msg_send_id![cls, new] };
+        let closures: Box<Vec<(Sel, OwnedBlock)>> = Box::new(
+            self.methods
+                .into_iter()
+                .map(|method| (method.sel, method.block))
+                .collect(),
+        );
+        // SAFETY: The ivar has the right type, and is not yet in use.
+        unsafe { *closures_ivar(&obj) = Box::into_raw(closures).cast() };
+
+        // SAFETY: The class conforms to the protocol, and implements every
+        // method that was given a closure.
+        unsafe { Retained::cast_unchecked(obj) }
+    }
+}
+
+impl<P: ?Sized> fmt::Debug for ProtocolObjectBuilder<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtocolObjectBuilder")
+            .field("protocol", &self.protocol)
+            .field(
+                "methods",
+                &self
+                    .methods
+                    .iter()
+                    .map(|method| method.sel)
+                    .collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+struct CachedClass {
+    protocol: &'static AnyProtocol,
+    /// The selectors and the trampolines that implement them.
+    ///
+    /// The trampolines are generic over the argument and return types of
+    /// the closures, so comparing them ensures that a class is only reused
+    /// for closures with the same types.
+    methods: Vec<(Sel, usize)>,
+    cls: &'static AnyClass,
+}
+
+static CLASSES: Mutex<Vec<CachedClass>> = Mutex::new(Vec::new());
+
+fn cache_key(method: &ClosureMethod) -> (Sel, usize) {
+    (method.sel, method.imp as usize)
+}
+
+/// Get the class for the protocol that implements the given (sorted)
+/// methods with the given trampolines, creating it if necessary.
+fn protocol_class(protocol: &'static AnyProtocol, methods: &[ClosureMethod]) -> &'static AnyClass {
+    let mut classes = CLASSES.lock().unwrap();
+    if let Some(cached) = classes.iter().find(|cached| {
+        cached.protocol == protocol
+            && cached
+                .methods
+                .iter()
+                .copied()
+                .eq(methods.iter().map(cache_key))
+    }) {
+        return cached.cls;
+    }
+
+    // Find an unused name; another version of this crate may have created
+    // classes too.
+    let mut index = classes.len();
+    let mut builder = loop {
+        let name = format!("__Block2ProtocolObject_{protocol}_{index}");
+        let name = CString::new(name).expect("protocol name must not contain NUL");
+        if let Some(builder) = ClassBuilder::new(&name, NSObject::class()) {
+            break builder;
+        }
+        index += 1;
+    };
+
+    builder.add_protocol(protocol);
+    builder.add_ivar::<*mut c_void>(ivar_name());
+    for method in methods {
+        // SAFETY: The trampolines call the closures stored in the ivar with
+        // the signature that `add` was created with.
+        //
+        // The class is only reused for instances whose closures have the
+        // same trampolines, and thus the same types.
+        unsafe { (method.add)(&mut builder, method.sel, method.imp) };
+    }
+    // SAFETY: The signature is correct.
+    unsafe {
+        builder.add_method(sel!(dealloc), dealloc as unsafe extern "C-unwind" fn(_, _));
+    }
+    let cls = builder.register();
+
+    classes.push(CachedClass {
+        protocol,
+        methods: methods.iter().map(cache_key).collect(),
+        cls,
+    });
+    cls
+}
+
+fn ivar_name() -> &'static CStr {
+    CStr::from_bytes_with_nul(CLOSURES_IVAR).unwrap()
+}
+
+fn closures_ivar(obj: &AnyObject) -> *mut *mut c_void {
+    let ivar = obj
+        .class()
+        .instance_variable(ivar_name())
+        .expect("object was not created by ProtocolObjectBuilder");
+    // SAFETY: The ivar has type `*mut c_void`.
+    unsafe { ivar.load_ptr::<*mut c_void>(obj) }
+}
+
+unsafe extern "C-unwind" fn dealloc(this: *mut AnyObject, _cmd: Sel) {
+    // SAFETY: The receiver is valid until the superclass' `dealloc`.
+    let closures = unsafe { *closures_ivar(&*this) };
+    if !closures.is_null() {
+        // SAFETY: The pointer was created with `Box::into_raw` in `build`.
+        drop(unsafe { Box::from_raw(closures.cast::<Vec<(Sel, OwnedBlock)>>()) });
+    }
+    // SAFETY: The superclass is `NSObject`.
+    unsafe { msg_send![super(this, NSObject::class()), dealloc] }
+}
+
+/// Get the block that implements `cmd` on `this`.
+///
+/// # Safety
+///
+/// The object must be an instance of a class created by
+/// [`ProtocolObjectBuilder`], that implements `cmd`.
+pub(crate) unsafe fn closure_block(this: *mut AnyObject, cmd: Sel) -> NonNull<c_void> {
+    // SAFETY: Checked by caller.
+    let closures = unsafe { *closures_ivar(&*this) };
+    let closures: &Vec<(Sel, OwnedBlock)> = unsafe { &*closures.cast() };
+    closures
+        .iter()
+        .find(|(sel, _)| *sel == cmd)
+        .map(|(_, block)| block.0)
+        .unwrap_or_else(|| unreachable!("no closure for {cmd}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use core::num::NonZeroU32;
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    use objc2::declare_protocol;
+
+    use super::*;
+
+    declare_protocol!(
+        #[allow(clippy::missing_safety_doc)]
+        unsafe trait Block2TestDelegate {
+            #[method(didChange:)]
+            fn did_change(&self, value: u32);
+
+            #[method(value)]
+            fn value(&self) -> u32;
+        }
+
+        unsafe impl ProtocolType for dyn Block2TestDelegate {}
+    );
+
+    fn class_of<P: ?Sized>(obj: &ProtocolObject<P>) -> &AnyClass {
+        let ptr: *const ProtocolObject<P> = obj;
+        // SAFETY: Protocol objects are objects.
+        unsafe { &*ptr.cast::<AnyObject>() }.class()
+    }
+
+    fn delegate(value: u32) -> Retained<ProtocolObject<dyn Block2TestDelegate>> {
+        unsafe {
+            ProtocolObjectBuilder::new()
+                .method(sel!(value), move |_this: &AnyObject| value)
+                .build()
+        }
+    }
+
+    #[test]
+    fn closures() {
+        let changed = Arc::new(AtomicU32::new(0));
+        let changed_clone = Arc::clone(&changed);
+        let obj = unsafe {
+            ProtocolObjectBuilder::<dyn Block2TestDelegate>::new()
+                .method(sel!(didChange:), move |_this: &AnyObject, value: u32| {
+                    changed_clone.store(value, Ordering::Relaxed);
+                })
+                .method(sel!(value), |_this: &AnyObject| 7u32)
+                .build()
+        };
+
+        obj.did_change(3);
+        assert_eq!(changed.load(Ordering::Relaxed), 3);
+        assert_eq!(obj.value(), 7);
+
+        let cls = class_of(&obj);
+        assert!(cls.conforms_to(<dyn Block2TestDelegate>::protocol().unwrap()));
+        assert!(cls.responds_to(sel!(didChange:)));
+    }
+
+    #[test]
+    fn class_is_cached() {
+        let a = delegate(1);
+        let b = delegate(2);
+        assert_eq!(a.value(), 1);
+        assert_eq!(b.value(), 2);
+        assert_eq!(class_of(&a), class_of(&b));
+        assert!(!class_of(&a).responds_to(sel!(didChange:)));
+    }
+
+    #[test]
+    fn class_is_not_shared_between_types() {
+        // `NonZeroU32` has the same encoding as `u32`, but the trampoline
+        // for it is different.
+        let a = delegate(1);
+        let b = unsafe {
+            ProtocolObjectBuilder::<dyn Block2TestDelegate>::new()
+                .method(sel!(value), |_this: &AnyObject| NonZeroU32::new(2).unwrap())
+                .build()
+        };
+        assert_eq!(a.value(), 1);
+        assert_eq!(b.value(), 2);
+        assert_ne!(class_of(&a), class_of(&b));
+    }
+
+    #[test]
+    fn closures_are_dropped() {
+        let state = Arc::new(());
+        let state_clone = Arc::clone(&state);
+        let obj = unsafe {
+            ProtocolObjectBuilder::<dyn Block2TestDelegate>::new()
+                .method(sel!(value), move |_this: &AnyObject| {
+                    let _ = &state_clone;
+                    0u32
+                })
+                .build()
+        };
+        assert_eq!(Arc::strong_count(&state), 2);
+        drop(obj);
+        assert_eq!(Arc::strong_count(&state), 1);
+    }
+
+    #[test]
+    #[should_panic = "closure does not match method value of protocol Block2TestDelegate"]
+    fn wrong_signature() {
+        let _ = unsafe {
+            ProtocolObjectBuilder::<dyn Block2TestDelegate>::new()
+                .method(sel!(value), |_this: &AnyObject| 1.0f32)
+        };
+    }
+}
@@ -3,7 +3,9 @@ use core::mem::ManuallyDrop;
 use core::ops::Deref;
 use core::ptr::NonNull;
 
+use objc2::debug::StrongReferences;
 use objc2::encode::{EncodeArguments, EncodeReturn};
+use objc2::runtime::AnyObject;
 
 use crate::abi::BlockHeader;
 use crate::debug::debug_block_header;
@@ -273,6 +275,18 @@ impl<F: ?Sized> Drop for RcBlock<F> {
     }
 }
 
+/// Allows [`find_retain_cycles`] to find cycles through objects that store
+/// the block.
+///
+/// [`find_retain_cycles`]: objc2::debug::find_retain_cycles
+impl<F: ?Sized> StrongReferences for RcBlock<F> {
+    #[inline]
+    fn strong_references(&self, f: &mut dyn FnMut(&AnyObject)) {
+        // SAFETY: Blocks on the heap are Objective-C objects.
+        f(unsafe { self.ptr.cast::<AnyObject>().as_ref() });
+    }
+}
+
 impl<F: ?Sized> fmt::Debug for RcBlock<F> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut f = f.debug_struct("RcBlock");
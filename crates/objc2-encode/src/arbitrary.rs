@@ -0,0 +1,328 @@
+//! Generating arbitrary encodings, for use in fuzzing.
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+#[cfg(any(test, feature = "unstable-fuzzing"))]
+use crate::Encoding;
+use crate::EncodingBox;
+
+/// The maximum depth of nested encodings that are generated.
+///
+/// Parsing and formatting is recursive, so we limit this to avoid stack
+/// overflows.
+const MAX_DEPTH: usize = 8;
+
+/// The maximum number of fields in a generated struct or union.
+const MAX_FIELDS: usize = 8;
+
+const PRIMITIVES: &[EncodingBox] = &[
+    EncodingBox::Char,
+    EncodingBox::Short,
+    EncodingBox::Int,
+    EncodingBox::Long,
+    EncodingBox::LongLong,
+    EncodingBox::UChar,
+    EncodingBox::UShort,
+    EncodingBox::UInt,
+    EncodingBox::ULong,
+    EncodingBox::ULongLong,
+    EncodingBox::Float,
+    EncodingBox::Double,
+    EncodingBox::LongDouble,
+    EncodingBox::FloatComplex,
+    EncodingBox::DoubleComplex,
+    EncodingBox::LongDoubleComplex,
+    EncodingBox::Bool,
+    EncodingBox::Void,
+    EncodingBox::String,
+    EncodingBox::Object,
+    EncodingBox::Block,
+    EncodingBox::Class,
+    EncodingBox::Sel,
+    EncodingBox::Unknown,
+];
+
+/// The types that are allowed in a GNUStep-style bitfield.
+const INTEGRAL: &[EncodingBox] = &[
+    EncodingBox::Char,
+    EncodingBox::Short,
+    EncodingBox::Int,
+    EncodingBox::Long,
+    EncodingBox::LongLong,
+    EncodingBox::UChar,
+    EncodingBox::UShort,
+    EncodingBox::UInt,
+    EncodingBox::ULong,
+    EncodingBox::ULongLong,
+    EncodingBox::Bool,
+];
+
+const NAME_BYTES: &[u8] = b"abcxyzABCXYZ_0123456789";
+
+fn arbitrary_name(u: &mut Unstructured<'_>) -> Result<String> {
+    if u.ratio(1, 4)? {
+        return Ok(String::from("?"));
+    }
+    let len = u.int_in_range(1..=8)?;
+    (0..len)
+        .map(|_| u.choose(NAME_BYTES).map(|b| char::from(*b)))
+        .collect()
+}
+
+fn arbitrary_encoding(u: &mut Unstructured<'_>, depth: usize) -> Result<EncodingBox> {
+    // Only generate primitives once we're too deeply nested.
+    let max_kind = if depth >= MAX_DEPTH { 0 } else { 6 };
+
+    Ok(match u.int_in_range(0..=max_kind)? {
+        0 => u.choose(PRIMITIVES)?.clone(),
+        1 => {
+            let size = u.arbitrary()?;
+            let ty = if u.arbitrary()? {
+                Some(Box::new((u.arbitrary()?, u.choose(INTEGRAL)?.clone())))
+            } else {
+                None
+            };
+            EncodingBox::BitField(size, ty)
+        }
+        2 => EncodingBox::Pointer(Box::new(arbitrary_encoding(u, depth + 1)?)),
+        3 => EncodingBox::Atomic(Box::new(arbitrary_encoding(u, depth + 1)?)),
+        4 => EncodingBox::Array(u.arbitrary()?, Box::new(arbitrary_encoding(u, depth + 1)?)),
+        kind => {
+            let name = arbitrary_name(u)?;
+            let len = u.int_in_range(0..=MAX_FIELDS)?;
+            let fields = (0..len)
+                .map(|_| arbitrary_encoding(u, depth + 1))
+                .collect::<Result<Vec<_>>>()?;
+            if kind == 5 {
+                EncodingBox::Struct(name, fields)
+            } else {
+                EncodingBox::Union(name, fields)
+            }
+        }
+    })
+}
+
+/// Convert a boxed encoding to a static one by leaking its allocations.
+///
+/// The result must be freed with [`free`] afterwards.
+#[cfg(any(test, feature = "unstable-fuzzing"))]
+fn leak(enc: &EncodingBox) -> Encoding {
+    fn leak_box<T>(value: T) -> &'static T {
+        Box::leak(Box::new(value))
+    }
+
+    match enc {
+        EncodingBox::Char => Encoding::Char,
+        EncodingBox::Short => Encoding::Short,
+        EncodingBox::Int => Encoding::Int,
+        EncodingBox::Long => Encoding::Long,
+        EncodingBox::LongLong => Encoding::LongLong,
+        EncodingBox::UChar => Encoding::UChar,
+        EncodingBox::UShort => Encoding::UShort,
+        EncodingBox::UInt => Encoding::UInt,
+        EncodingBox::ULong => Encoding::ULong,
+        EncodingBox::ULongLong => Encoding::ULongLong,
+        EncodingBox::Float => Encoding::Float,
+        EncodingBox::Double => Encoding::Double,
+        EncodingBox::LongDouble => Encoding::LongDouble,
+        EncodingBox::FloatComplex => Encoding::FloatComplex,
+        EncodingBox::DoubleComplex => Encoding::DoubleComplex,
+        EncodingBox::LongDoubleComplex => Encoding::LongDoubleComplex,
+        EncodingBox::Bool => Encoding::Bool,
+        EncodingBox::Void => Encoding::Void,
+        EncodingBox::String => Encoding::String,
+        EncodingBox::Object => Encoding::Object,
+        EncodingBox::Block => Encoding::Block,
+        EncodingBox::Class => Encoding::Class,
+        EncodingBox::Sel => Encoding::Sel,
+        EncodingBox::Unknown => Encoding::Unknown,
+        EncodingBox::BitField(size, None) => Encoding::BitField(*size, None),
+        EncodingBox::BitField(size, Some(b)) => {
+            Encoding::BitField(*size, Some(leak_box((b.0, leak(&b.1)))))
+        }
+        EncodingBox::Pointer(t) => Encoding::Pointer(leak_box(leak(t))),
+        EncodingBox::Atomic(t) => Encoding::Atomic(leak_box(leak(t))),
+        EncodingBox::Array(len, item) => Encoding::Array(*len, leak_box(leak(item))),
+        EncodingBox::Struct(name, fields) => Encoding::Struct(
+            Box::leak(name.clone().into_boxed_str()),
+            Box::leak(fields.iter().map(leak).collect()),
+        ),
+        EncodingBox::Union(name, fields) => Encoding::Union(
+            Box::leak(name.clone().into_boxed_str()),
+            Box::leak(fields.iter().map(leak).collect()),
+        ),
+        EncodingBox::None => Encoding::None,
+    }
+}
+
+/// Free the allocations leaked by [`leak`].
+///
+/// # Safety
+///
+/// The encoding must have been created by `leak`, and no references to it
+/// may be used afterwards.
+#[cfg(any(test, feature = "unstable-fuzzing"))]
+unsafe fn free(enc: &Encoding) {
+    match enc {
+        Encoding::BitField(_, Some(b)) => {
+            // SAFETY: The inner encoding was created by `leak` as well, and
+            // is only reachable through `enc`.
+            unsafe { free(&b.1) };
+            // SAFETY: `leak` allocated the pair with `Box::new`, and it is
+            // not used after this.
+            drop(unsafe { Box::from_raw(*b as *const (u64, Encoding) as *mut (u64, Encoding)) });
+        }
+        Encoding::Pointer(t) | Encoding::Atomic(t) | Encoding::Array(_, t) => {
+            // SAFETY: Same as above.
+            unsafe { free(t) };
+            // SAFETY: `leak` allocated the item with `Box::new`, and it is
+            // not used after this.
+            drop(unsafe { Box::from_raw(*t as *const Encoding as *mut Encoding) });
+        }
+        Encoding::Struct(name, fields) | Encoding::Union(name, fields) => {
+            for field in *fields {
+                // SAFETY: Same as above.
+                unsafe { free(field) };
+            }
+            // SAFETY: `leak` allocated the fields and the name as boxed
+            // slices, with the same length as they have here, and they are
+            // not used after this.
+            drop(unsafe { Box::from_raw(*fields as *const [Encoding] as *mut [Encoding]) });
+            drop(unsafe { Box::from_raw(*name as *const str as *mut str) });
+        }
+        _ => {}
+    }
+}
+
+/// Call the closure with an [`Encoding`] that is equal to the given
+/// [`EncodingBox`].
+///
+/// This is used by the fuzz targets to check that the two representations
+/// behave the same.
+///
+/// # Safety
+///
+/// The closure must not let any of the references inside the encoding
+/// escape, since they are deallocated afterwards.
+#[cfg(any(test, feature = "unstable-fuzzing"))]
+#[cfg_attr(not(feature = "unstable-fuzzing"), allow(unreachable_pub))]
+#[doc(hidden)]
+pub unsafe fn __with_static_encoding<R>(enc: &EncodingBox, f: impl FnOnce(&Encoding) -> R) -> R {
+    struct Free(Encoding);
+
+    impl Drop for Free {
+        fn drop(&mut self) {
+            // SAFETY: Created by `leak`, and the closure has finished using
+            // it.
+            unsafe { free(&self.0) };
+        }
+    }
+
+    let enc = Free(leak(enc));
+    f(&enc.0)
+}
+
+/// Generates well-formed encodings, that is, encodings that can be formatted
+/// and parsed again.
+///
+/// Notably, this never generates [`EncodingBox::None`], and only generates
+/// integral types in bitfields.
+impl<'a> Arbitrary<'a> for EncodingBox {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_encoding(u, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EquivalenceOptions;
+    use alloc::string::ToString;
+    use core::str::FromStr;
+
+    /// A simple deterministic pseudo-random number generator (xorshift), to
+    /// avoid depending on `rand`.
+    fn random_bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn for_each_arbitrary(mut f: impl FnMut(&EncodingBox, &EncodingBox)) {
+        for seed in 0..500 {
+            let bytes = random_bytes(seed, 512);
+            let mut u = Unstructured::new(&bytes);
+            let a = EncodingBox::arbitrary(&mut u).unwrap();
+            let b = EncodingBox::arbitrary(&mut u).unwrap();
+            f(&a, &b);
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        for_each_arbitrary(|enc, _| {
+            let s = enc.to_string();
+            let parsed = EncodingBox::from_str(&s).unwrap();
+            assert_eq!(parsed.to_string(), s);
+        });
+    }
+
+    #[test]
+    fn equivalence() {
+        let options = [
+            EquivalenceOptions::new(),
+            EquivalenceOptions::new()
+                .ignore_signedness(true)
+                .bool_matches_char(true),
+            EquivalenceOptions::new()
+                .void_pointer_matches_any(true)
+                .unknown_pointer_matches_any(true)
+                .ignore_container_names(true),
+        ];
+        for_each_arbitrary(|a, b| unsafe {
+            __with_static_encoding(a, |static_a| {
+                __with_static_encoding(b, |static_b| {
+                    // Reflexive
+                    assert!(static_a.equivalent_to_box(a), "{a}");
+                    assert!(static_a.equivalent_to_str(&a.to_string()), "{a}");
+                    assert!(static_a.equivalent_to(static_a), "{a}");
+
+                    // Symmetric
+                    for options in options {
+                        assert_eq!(
+                            static_a.equivalent_to_box_with(b, options),
+                            static_b.equivalent_to_box_with(a, options),
+                            "{a} and {b} with {options:?}",
+                        );
+                    }
+                });
+            });
+        });
+    }
+
+    #[test]
+    fn static_str_matches_display() {
+        const LEN: usize = 4096;
+
+        for_each_arbitrary(|enc, _| unsafe {
+            __with_static_encoding(enc, |static_enc| {
+                let len = static_enc.str_len();
+                let expected = enc.to_string();
+                assert_eq!(len, expected.len(), "{enc}");
+                if len <= LEN {
+                    let array = static_enc.str_array::<LEN>();
+                    assert_eq!(&array[..len], expected.as_bytes());
+                }
+            });
+        });
+    }
+}
@@ -1,8 +1,11 @@
+use core::alloc::Layout;
 use core::fmt;
 
 use crate::helper::{compare_encodings, Helper, NestingLevel};
 use crate::parse::Parser;
-use crate::EncodingBox;
+use crate::static_str::{static_encoding_str_array, static_encoding_str_len};
+use crate::value::{read_value, write_value, Value, ValueError};
+use crate::{EncodingBox, EquivalenceOptions};
 
 /// An Objective-C type-encoding.
 ///
@@ -34,9 +37,13 @@ use crate::EncodingBox;
 /// use objc2_encode::Encoding;
 /// assert!(Encoding::Array(10, &Encoding::FloatComplex).equivalent_to_str("[10jf]"));
 /// ```
+///
+/// With the `"serde"` feature enabled, this implements `Serialize` in the
+/// same format as [`EncodingBox`], so it can be deserialized as that.
 // Not `Copy`, since this may one day be merged with `EncodingBox`
 #[allow(missing_copy_implementations)]
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 // See <https://en.cppreference.com/w/c/language/type>
 #[non_exhaustive] // Maybe we're missing some encodings?
 pub enum Encoding {
@@ -219,8 +226,21 @@ impl Encoding {
     ///
     /// For example, you should not rely on two equivalent encodings to have
     /// the same size or ABI - that is provided on a best-effort basis.
+    ///
+    /// See [`Encoding::equivalent_to_with`] for relaxing the comparison
+    /// further.
     pub fn equivalent_to(&self, other: &Self) -> bool {
-        compare_encodings(self, other, NestingLevel::new(), false)
+        self.equivalent_to_with(other, EquivalenceOptions::new())
+    }
+
+    /// Check if one encoding is equivalent to another, with the given
+    /// options for relaxing the comparison.
+    ///
+    /// See [`Encoding::equivalent_to`] for details about the meaning of
+    /// "equivalence", and [`EquivalenceOptions`] for the possible
+    /// relaxations.
+    pub fn equivalent_to_with(&self, other: &Self, options: EquivalenceOptions) -> bool {
+        compare_encodings(self, other, NestingLevel::new(), false, options)
     }
 
     /// Check if an encoding is equivalent to the given string representation.
@@ -228,11 +248,19 @@ impl Encoding {
     /// See [`Encoding::equivalent_to`] for details about the meaning of
     /// "equivalence".
     pub fn equivalent_to_str(&self, s: &str) -> bool {
+        self.equivalent_to_str_with(s, EquivalenceOptions::new())
+    }
+
+    /// Check if an encoding is equivalent to the given string
+    /// representation, with the given options for relaxing the comparison.
+    ///
+    /// See [`Encoding::equivalent_to_with`] for details.
+    pub fn equivalent_to_str_with(&self, s: &str, options: EquivalenceOptions) -> bool {
         let mut parser = Parser::new(s);
 
         parser.strip_leading_qualifiers();
 
-        if let Some(()) = parser.expect_encoding(self, NestingLevel::new()) {
+        if let Some(()) = parser.expect_encoding(self, NestingLevel::new(), options) {
             // if the given encoding can be successfully removed from the
             // start and an empty string remains, they were fully equivalent!
             parser.is_empty()
@@ -246,7 +274,47 @@ impl Encoding {
     /// See [`Encoding::equivalent_to`] for details about the meaning of
     /// "equivalence".
     pub fn equivalent_to_box(&self, other: &EncodingBox) -> bool {
-        compare_encodings(self, other, NestingLevel::new(), false)
+        self.equivalent_to_box_with(other, EquivalenceOptions::new())
+    }
+
+    /// Check if an encoding is equivalent to a boxed encoding, with the
+    /// given options for relaxing the comparison.
+    ///
+    /// See [`Encoding::equivalent_to_with`] for details.
+    pub fn equivalent_to_box_with(&self, other: &EncodingBox, options: EquivalenceOptions) -> bool {
+        compare_encodings(self, other, NestingLevel::new(), false, options)
+    }
+
+    /// The length of the string representation of the encoding.
+    ///
+    /// Unlike formatting the encoding with [`Display`][fmt::Display],
+    /// this can be used in `const` contexts, see [`Encoding::str_array`].
+    pub const fn str_len(&self) -> usize {
+        static_encoding_str_len(self, NestingLevel::new())
+    }
+
+    /// Write the string representation of the encoding to the start of an
+    /// array, in `const` contexts.
+    ///
+    /// The rest of the array is filled with zeroes.
+    ///
+    ///
+    /// # Panics
+    ///
+    /// Panics if `LEN` is smaller than [`str_len`][Self::str_len].
+    ///
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use objc2_encode::Encoding;
+    ///
+    /// const ENC: Encoding = Encoding::Pointer(&Encoding::Struct("S", &[Encoding::Int]));
+    /// const ARRAY: [u8; ENC.str_len()] = ENC.str_array();
+    /// assert_eq!(&ARRAY, b"^{S=i}");
+    /// ```
+    pub const fn str_array<const LEN: usize>(&self) -> [u8; LEN] {
+        static_encoding_str_array(self, NestingLevel::new())
     }
 
     /// Computes the theoretical size in bytes of the represented value type.
@@ -258,6 +326,62 @@ impl Encoding {
     pub fn size(&self) -> Option<usize> {
         Helper::new(self).size(NestingLevel::new())
     }
+
+    /// Computes the layout (size and alignment) of the represented value
+    /// type, as laid out by a C compiler on the current target.
+    ///
+    /// Unlike [`Encoding::size`], this takes alignment and padding into
+    /// account. Returns `None` if the type is unsized (e.g. `void`) or
+    /// opaque (e.g. a struct without fields).
+    ///
+    /// Note that the layout of bitfields cannot always be determined from
+    /// the encoding, so this is provided on a best-effort basis.
+    pub fn layout(&self) -> Option<Layout> {
+        Helper::new(self).layout(NestingLevel::new())
+    }
+
+    /// Read a value of the represented type from the start of the given
+    /// bytes.
+    ///
+    /// This is useful for decoding untyped data, such as the contents of an
+    /// instance variable or an `NSValue`.
+    ///
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the encoding does not have a known
+    /// [layout][Self::layout], or if the buffer is too small.
+    ///
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use objc2_encode::{Encoding, Value};
+    ///
+    /// let enc = Encoding::Struct("Pair", &[Encoding::Short, Encoding::UShort]);
+    /// let bytes = [1i16.to_ne_bytes(), 2u16.to_ne_bytes()].concat();
+    /// let value = enc.read_value(&bytes)?;
+    /// assert_eq!(value, Value::Struct(vec![Value::Int(1), Value::UInt(2)]));
+    /// # Ok::<(), objc2_encode::ValueError>(())
+    /// ```
+    pub fn read_value<'a>(&self, bytes: &'a [u8]) -> Result<Value<'a>, ValueError> {
+        read_value(self, bytes)
+    }
+
+    /// Write a value of the represented type to the start of the given
+    /// bytes.
+    ///
+    /// Padding bytes are left untouched.
+    ///
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the encoding does not have a known
+    /// [layout][Self::layout], if the buffer is too small, or if the value
+    /// does not match the encoding.
+    pub fn write_value(&self, value: &Value<'_>, bytes: &mut [u8]) -> Result<(), ValueError> {
+        write_value(self, value, bytes)
+    }
 }
 
 /// Formats this [`Encoding`] in a similar way that the `@encode` directive
@@ -275,7 +399,6 @@ impl fmt::Display for Encoding {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::static_str::{static_encoding_str_array, static_encoding_str_len};
     use alloc::boxed::Box;
     use alloc::string::ToString;
     use alloc::vec;
@@ -1,12 +1,16 @@
 use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::alloc::Layout;
 use core::fmt;
 use core::str::FromStr;
 
 use crate::helper::{compare_encodings, Helper, NestingLevel};
+#[cfg(feature = "serde")]
+use crate::parse::verify_name;
 use crate::parse::{ErrorKind, ParseError, Parser};
-use crate::Encoding;
+use crate::value::{read_value, write_value, Value, ValueError};
+use crate::{Encoding, EquivalenceOptions};
 
 /// The boxed version of [`Encoding`].
 ///
@@ -25,7 +29,17 @@ use crate::Encoding;
 /// is possible.
 ///
 /// [`Struct`]: Self::Struct
+///
+///
+/// # Serialization
+///
+/// With the `"serde"` feature enabled, this implements `Serialize` and
+/// `Deserialize` as a tree of the encoding's items (instead of as the
+/// encoding string), in the same format as [`Encoding`]'s `Serialize`
+/// implementation. Deserializing a serialized encoding is guaranteed to
+/// produce an equal value.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive] // Maybe we're missing some encodings?
 pub enum EncodingBox {
     /// Same as [`Encoding::Char`].
@@ -85,9 +99,15 @@ pub enum EncodingBox {
     /// Same as [`Encoding::Array`].
     Array(u64, Box<Self>),
     /// Same as [`Encoding::Struct`].
-    Struct(String, Vec<Self>),
+    Struct(
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_name"))] String,
+        Vec<Self>,
+    ),
     /// Same as [`Encoding::Union`].
-    Union(String, Vec<Self>),
+    Union(
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_name"))] String,
+        Vec<Self>,
+    ),
     /// Same as [`Encoding::None`].
     None,
 }
@@ -138,6 +158,52 @@ impl EncodingBox {
             }
         }
     }
+
+    /// Same as [`Encoding::layout`].
+    pub fn layout(&self) -> Option<Layout> {
+        Helper::from_box(self).layout(NestingLevel::new())
+    }
+
+    /// Same as [`Encoding::read_value`].
+    ///
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the encoding does not have a known
+    /// [layout][Self::layout], or if the buffer is too small.
+    pub fn read_value<'a>(&self, bytes: &'a [u8]) -> Result<Value<'a>, ValueError> {
+        read_value(self, bytes)
+    }
+
+    /// Same as [`Encoding::write_value`].
+    ///
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the encoding does not have a known
+    /// [layout][Self::layout], if the buffer is too small, or if the value
+    /// does not match the encoding.
+    pub fn write_value(&self, value: &Value<'_>, bytes: &mut [u8]) -> Result<(), ValueError> {
+        write_value(self, value, bytes)
+    }
+}
+
+/// Deserialize a struct or union name, and verify that it is valid.
+///
+/// This is needed to uphold that the deserialized encoding can be formatted.
+#[cfg(feature = "serde")]
+fn deserialize_name<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    use serde::de::{Deserialize, Error, Unexpected};
+
+    let name = String::deserialize(deserializer)?;
+    if verify_name(&name) {
+        Ok(name)
+    } else {
+        Err(D::Error::invalid_value(
+            Unexpected::Str(&name),
+            &"a valid struct or union identifier",
+        ))
+    }
 }
 
 /// Same formatting as [`Encoding`]'s `Display` implementation.
@@ -149,7 +215,13 @@ impl fmt::Display for EncodingBox {
 
 impl PartialEq<Encoding> for EncodingBox {
     fn eq(&self, other: &Encoding) -> bool {
-        compare_encodings(self, other, NestingLevel::new(), true)
+        compare_encodings(
+            self,
+            other,
+            NestingLevel::new(),
+            true,
+            EquivalenceOptions::new(),
+        )
     }
 }
 
@@ -262,4 +334,60 @@ mod tests {
 
         assert_eq!(s, "");
     }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_structured() {
+        let enc = EncodingBox::from_str("{CGRect={CGPoint=dd}^v[2B]b8}").unwrap();
+        let json = serde_json::to_string(&enc).unwrap();
+        assert_eq!(
+            json,
+            r#"{"Struct":["CGRect",[{"Struct":["CGPoint",["Double","Double"]]},{"Pointer":"Void"},{"Array":[2,"Bool"]},{"BitField":[8,null]}]]}"#
+        );
+        let deserialized: EncodingBox = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, enc);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_roundtrip() {
+        for s in [
+            "i",
+            "@?",
+            "b16B4",
+            "^^{SomeStruct}",
+            "AA{a}",
+            "(Onion=ci)",
+            "[42^]",
+            "{?=^[8B](def=@?)^^b255c?}",
+            "",
+        ] {
+            let enc = EncodingBox::from_str(s).unwrap();
+            let json = serde_json::to_string(&enc).unwrap();
+            let deserialized: EncodingBox = serde_json::from_str(&json).unwrap();
+            assert_eq!(deserialized, enc, "{json}");
+            assert_eq!(deserialized.to_string(), enc.to_string());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_same_as_encoding() {
+        const ENC: Encoding = Encoding::Pointer(&Encoding::Struct(
+            "S",
+            &[Encoding::BitField(4, Some(&(16, Encoding::Bool)))],
+        ));
+        let json = serde_json::to_string(&ENC).unwrap();
+        let deserialized: EncodingBox = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, ENC);
+        assert_eq!(serde_json::to_string(&deserialized).unwrap(), json);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_invalid_name() {
+        let res = serde_json::from_str::<EncodingBox>(r#"{"Struct":["a-b",[]]}"#);
+        let err = res.unwrap_err().to_string();
+        assert!(err.contains("a valid struct or union identifier"), "{err}");
+    }
 }
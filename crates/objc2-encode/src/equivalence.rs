@@ -0,0 +1,315 @@
+use crate::helper::Primitive;
+
+/// Options for relaxing when two encodings are considered equivalent.
+///
+/// By default, this does not relax anything, and comparing with
+/// `EquivalenceOptions::new()` is the same as using the plain comparison
+/// methods like [`Encoding::equivalent_to`].
+///
+/// This is useful when dealing with Objective-C code that doesn't declare its
+/// types exactly as you'd expect; for example, Swift code may use `Int` where
+/// `UInt` would've been more appropriate, and older code may use `void*`
+/// where a more specific pointer would be correct.
+///
+/// [`Encoding::equivalent_to`]: crate::Encoding::equivalent_to
+///
+///
+/// # Examples
+///
+/// Ignore the signedness of integers.
+///
+/// ```
+/// use objc2_encode::{Encoding, EquivalenceOptions};
+///
+/// let options = EquivalenceOptions::new().ignore_signedness(true);
+/// assert!(!Encoding::UInt.equivalent_to(&Encoding::Int));
+/// assert!(Encoding::UInt.equivalent_to_with(&Encoding::Int, options));
+/// assert!(Encoding::UInt.equivalent_to_str_with("i", options));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct EquivalenceOptions {
+    ignore_signedness: bool,
+    void_pointer_matches_any: bool,
+    unknown_pointer_matches_any: bool,
+    ignore_container_names: bool,
+    ignore_qualifiers: bool,
+    bool_matches_char: bool,
+}
+
+impl EquivalenceOptions {
+    /// Create a new set of options that doesn't relax anything.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            ignore_signedness: false,
+            void_pointer_matches_any: false,
+            unknown_pointer_matches_any: false,
+            ignore_container_names: false,
+            ignore_qualifiers: false,
+            bool_matches_char: false,
+        }
+    }
+
+    /// Consider signed and unsigned integers of the same width equivalent.
+    ///
+    /// E.g. `"i"` and `"I"` compare equivalent.
+    #[inline]
+    pub const fn ignore_signedness(mut self, value: bool) -> Self {
+        self.ignore_signedness = value;
+        self
+    }
+
+    /// Consider a pointer to `void` equivalent to any other pointer.
+    ///
+    /// E.g. `"^v"` and `"^{CGPoint=dd}"` compare equivalent.
+    #[inline]
+    pub const fn void_pointer_matches_any(mut self, value: bool) -> Self {
+        self.void_pointer_matches_any = value;
+        self
+    }
+
+    /// Consider a pointer to an unknown type equivalent to any other pointer.
+    ///
+    /// E.g. `"^?"` (usually used for function pointers) and `"^i"` compare
+    /// equivalent.
+    #[inline]
+    pub const fn unknown_pointer_matches_any(mut self, value: bool) -> Self {
+        self.unknown_pointer_matches_any = value;
+        self
+    }
+
+    /// Ignore the names of structs and unions.
+    ///
+    /// E.g. `"{CGPoint=dd}"` and `"{NSPoint=dd}"` compare equivalent.
+    #[inline]
+    pub const fn ignore_container_names(mut self, value: bool) -> Self {
+        self.ignore_container_names = value;
+        self
+    }
+
+    /// Ignore type qualifiers everywhere in string encodings, not just at
+    /// the start.
+    ///
+    /// E.g. `"^ri"` and `"^i"` compare equivalent. Leading qualifiers are
+    /// always ignored.
+    #[inline]
+    pub const fn ignore_qualifiers(mut self, value: bool) -> Self {
+        self.ignore_qualifiers = value;
+        self
+    }
+
+    /// Consider `_Bool` equivalent to `char`.
+    ///
+    /// Objective-C's `BOOL` is a `signed char` on some platforms, and a
+    /// `_Bool` on others, so `"c"` and `"B"` are often used interchangeably.
+    #[inline]
+    pub const fn bool_matches_char(mut self, value: bool) -> Self {
+        self.bool_matches_char = value;
+        self
+    }
+
+    pub(crate) const fn ignores_container_names(self) -> bool {
+        self.ignore_container_names
+    }
+
+    pub(crate) const fn ignores_qualifiers(self) -> bool {
+        self.ignore_qualifiers
+    }
+
+    /// Whether a pointer to the given primitive should match any pointer.
+    pub(crate) fn pointee_matches_any(self, primitive: Primitive) -> bool {
+        match primitive {
+            Primitive::Void => self.void_pointer_matches_any,
+            Primitive::Unknown => self.unknown_pointer_matches_any,
+            _ => false,
+        }
+    }
+
+    /// Map the primitive to a canonical representative, such that two
+    /// primitives are equivalent if their canonical forms are equal.
+    pub(crate) fn canonical_primitive(self, primitive: Primitive) -> Primitive {
+        let primitive = if self.ignore_signedness {
+            match primitive {
+                Primitive::UChar => Primitive::Char,
+                Primitive::UShort => Primitive::Short,
+                Primitive::UInt => Primitive::Int,
+                Primitive::ULong => Primitive::Long,
+                Primitive::ULongLong => Primitive::LongLong,
+                primitive => primitive,
+            }
+        } else {
+            primitive
+        };
+
+        if self.bool_matches_char && primitive == Primitive::Bool {
+            Primitive::Char
+        } else {
+            primitive
+        }
+    }
+
+    pub(crate) fn primitives_equivalent(self, p1: Primitive, p2: Primitive) -> bool {
+        self.canonical_primitive(p1) == self.canonical_primitive(p2)
+    }
+
+    /// The primitives that are equivalent to the given primitive, including
+    /// the primitive itself (which is always the first).
+    pub(crate) fn equivalent_primitives(
+        self,
+        primitive: Primitive,
+    ) -> impl Iterator<Item = Primitive> {
+        use Primitive::*;
+        let candidates: &[Primitive] = match primitive {
+            Char | UChar | Bool => &[Char, UChar, Bool],
+            Short | UShort => &[Short, UShort],
+            Int | UInt => &[Int, UInt],
+            Long | ULong => &[Long, ULong],
+            LongLong | ULongLong => &[LongLong, ULongLong],
+            _ => &[],
+        };
+        core::iter::once(primitive).chain(
+            candidates
+                .iter()
+                .copied()
+                .filter(move |p| *p != primitive && self.primitives_equivalent(primitive, *p)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Encoding, EncodingBox};
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[track_caller]
+    fn assert_equivalent(enc: &Encoding, s: &str, options: EquivalenceOptions, expected: bool) {
+        assert_eq!(
+            enc.equivalent_to_str_with(s, options),
+            expected,
+            "{enc} and {s:?} as string"
+        );
+        let boxed: EncodingBox = s.parse().unwrap();
+        assert_eq!(
+            enc.equivalent_to_box_with(&boxed, options),
+            expected,
+            "{enc} and {s:?} as box"
+        );
+    }
+
+    #[test]
+    fn default_is_strict() {
+        let options = EquivalenceOptions::new();
+        assert_eq!(options, EquivalenceOptions::default());
+        assert_equivalent(&Encoding::Int, "i", options, true);
+        assert_equivalent(&Encoding::Int, "I", options, false);
+        assert_equivalent(&Encoding::Char, "B", options, false);
+        assert_equivalent(&Encoding::Pointer(&Encoding::Void), "^i", options, false);
+    }
+
+    #[test]
+    fn signedness() {
+        let options = EquivalenceOptions::new().ignore_signedness(true);
+        assert_equivalent(&Encoding::Int, "I", options, true);
+        assert_equivalent(&Encoding::UInt, "i", options, true);
+        assert_equivalent(&Encoding::ULongLong, "q", options, true);
+        assert_equivalent(&Encoding::Pointer(&Encoding::UChar), "^c", options, true);
+        assert_equivalent(&Encoding::Int, "q", options, false);
+        assert_equivalent(&Encoding::UChar, "B", options, false);
+        assert!(Encoding::Struct("S", &[Encoding::UShort])
+            .equivalent_to_with(&Encoding::Struct("S", &[Encoding::Short]), options));
+    }
+
+    #[test]
+    fn bool_char() {
+        let options = EquivalenceOptions::new().bool_matches_char(true);
+        assert_equivalent(&Encoding::Bool, "c", options, true);
+        assert_equivalent(&Encoding::Char, "B", options, true);
+        assert_equivalent(&Encoding::UChar, "B", options, false);
+
+        let options = options.ignore_signedness(true);
+        assert_equivalent(&Encoding::UChar, "B", options, true);
+    }
+
+    #[test]
+    fn void_pointer() {
+        let options = EquivalenceOptions::new().void_pointer_matches_any(true);
+        let void_ptr = Encoding::Pointer(&Encoding::Void);
+        assert_equivalent(&void_ptr, "^i", options, true);
+        assert_equivalent(&void_ptr, "^{CGPoint=dd}", options, true);
+        assert_equivalent(&void_ptr, "^^{CGPoint}", options, true);
+        assert_equivalent(&Encoding::Pointer(&Encoding::Int), "^v", options, true);
+        assert_equivalent(&void_ptr, "i", options, false);
+        assert_equivalent(&void_ptr, "*", options, false);
+        assert_equivalent(&void_ptr, "^?", options, true);
+        assert_equivalent(&Encoding::Atomic(&Encoding::Void), "Ai", options, false);
+
+        // The pointee must still be present.
+        assert!(!void_ptr.equivalent_to_str_with("^", options));
+        const VOID_PTR: Encoding = Encoding::Pointer(&Encoding::Void);
+        assert!(!Encoding::Struct("S", &[VOID_PTR]).equivalent_to_str_with("{S=^}", options));
+        assert!(!Encoding::Array(1, &VOID_PTR).equivalent_to_str_with("[1^]", options));
+    }
+
+    #[test]
+    fn unknown_pointer() {
+        let options = EquivalenceOptions::new().unknown_pointer_matches_any(true);
+        let unknown_ptr = Encoding::Pointer(&Encoding::Unknown);
+        assert_equivalent(&unknown_ptr, "^i", options, true);
+        assert_equivalent(&unknown_ptr, "^v", options, true);
+        assert_equivalent(&Encoding::Pointer(&Encoding::Int), "^?", options, true);
+        assert_equivalent(&unknown_ptr, "?", options, false);
+        assert!(!unknown_ptr.equivalent_to_str_with("^", options));
+    }
+
+    #[test]
+    fn container_names() {
+        const POINT: Encoding = Encoding::Struct("CGPoint", &[Encoding::Double, Encoding::Double]);
+        let options = EquivalenceOptions::new().ignore_container_names(true);
+        assert_equivalent(&POINT, "{NSPoint=dd}", options, true);
+        assert_equivalent(&POINT, "{?=dd}", options, true);
+        assert_equivalent(&Encoding::Pointer(&POINT), "^{_NSPoint=dd}", options, true);
+        assert_equivalent(&POINT, "{NSPoint=df}", options, false);
+        assert_equivalent(&POINT, "(NSPoint=dd)", options, false);
+    }
+
+    #[test]
+    fn qualifiers() {
+        let enc = Encoding::Pointer(&Encoding::Int);
+        assert!(!enc.equivalent_to_str("^ri"));
+
+        let options = EquivalenceOptions::new().ignore_qualifiers(true);
+        assert!(enc.equivalent_to_str_with("^ri", options));
+        assert!(enc.equivalent_to_str_with("r^ri", options));
+        assert!(Encoding::Struct("S", &[Encoding::Int, Encoding::String])
+            .equivalent_to_str_with("{S=ir*}", options));
+        assert!(!enc.equivalent_to_str_with("^rc", options));
+    }
+
+    #[test]
+    fn combined() {
+        let options = EquivalenceOptions::new()
+            .ignore_signedness(true)
+            .ignore_container_names(true);
+        let enc = Encoding::Struct("A", &[Encoding::UInt, Encoding::Int]);
+        assert_equivalent(&enc, "{B=Ii}", options, true);
+        assert_equivalent(&enc, "{B=ii}", options, true);
+        assert_equivalent(&enc, "{B=id}", options, false);
+    }
+
+    #[test]
+    fn equivalent_primitives() {
+        let options = EquivalenceOptions::new();
+        let all: Vec<_> = options.equivalent_primitives(Primitive::Int).collect();
+        assert_eq!(all, vec![Primitive::Int]);
+
+        let options = options.ignore_signedness(true).bool_matches_char(true);
+        let all: Vec<_> = options.equivalent_primitives(Primitive::Bool).collect();
+        assert_eq!(
+            all,
+            vec![Primitive::Bool, Primitive::Char, Primitive::UChar]
+        );
+    }
+}
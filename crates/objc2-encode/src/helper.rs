@@ -6,6 +6,7 @@ use core::write;
 use crate::parse::verify_name;
 use crate::Encoding;
 use crate::EncodingBox;
+use crate::EquivalenceOptions;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub(crate) enum NestingLevel {
@@ -58,6 +59,7 @@ pub(crate) fn compare_encodings<E1: EncodingType, E2: EncodingType>(
     enc2: &E2,
     level: NestingLevel,
     include_all: bool,
+    options: EquivalenceOptions,
 ) -> bool {
     use Helper::*;
     // Note: Ideally `Block` and sequence of `Object, Unknown` in struct
@@ -71,25 +73,31 @@ pub(crate) fn compare_encodings<E1: EncodingType, E2: EncodingType>(
     };
 
     match (enc1.helper(), enc2.helper()) {
-        (Primitive(p1), Primitive(p2)) => p1 == p2,
+        (Primitive(p1), Primitive(p2)) => options.primitives_equivalent(p1, p2),
         (BitField(size1, Some((offset1, type1))), BitField(size2, Some((offset2, type2)))) => {
             size1 == size2
                 && offset1 == offset2
-                && compare_encodings(type1, type2, level.bitfield(), include_all)
+                && compare_encodings(type1, type2, level.bitfield(), include_all, options)
         }
         (BitField(size1, None), BitField(size2, None)) => size1 == size2,
         // The type-encoding of a bitfield is always either available, or it
         // is not (depends on platform); so if it was available in one, but
         // not the other, we should compare the encodings unequal.
         (BitField(_, _), BitField(_, _)) => false,
+        (Indirection(IndirectionKind::Pointer, t1), Indirection(IndirectionKind::Pointer, t2))
+            if pointee_matches_any(t1, options) || pointee_matches_any(t2, options) =>
+        {
+            true
+        }
         (Indirection(kind1, t1), Indirection(kind2, t2)) => {
-            kind1 == kind2 && compare_encodings(t1, t2, level.indirection(kind1), include_all)
+            kind1 == kind2
+                && compare_encodings(t1, t2, level.indirection(kind1), include_all, options)
         }
         (Array(len1, item1), Array(len2, item2)) => {
-            len1 == len2 && compare_encodings(item1, item2, level.array(), include_all)
+            len1 == len2 && compare_encodings(item1, item2, level.array(), include_all, options)
         }
         (Container(kind1, name1, items1), Container(kind2, name2, items2)) => {
-            kind1 == kind2 && name1 == name2 && {
+            kind1 == kind2 && (options.ignores_container_names() || name1 == name2) && {
                 if let Some(level) = level.container_include_fields() {
                     // If either container is empty, then they are equivalent
                     if items1.is_empty() || items2.is_empty() {
@@ -99,7 +107,7 @@ pub(crate) fn compare_encodings<E1: EncodingType, E2: EncodingType>(
                         return false;
                     }
                     for (item1, item2) in items1.iter().zip(items2.iter()) {
-                        if !compare_encodings(item1, item2, level, include_all) {
+                        if !compare_encodings(item1, item2, level, include_all, options) {
                             return false;
                         }
                     }
@@ -114,6 +122,17 @@ pub(crate) fn compare_encodings<E1: EncodingType, E2: EncodingType>(
     }
 }
 
+/// Whether the pointee of a pointer should match any other pointee.
+pub(crate) fn pointee_matches_any<E: EncodingType>(
+    pointee: &E,
+    options: EquivalenceOptions,
+) -> bool {
+    match pointee.helper() {
+        Helper::Primitive(primitive) => options.pointee_matches_any(primitive),
+        _ => false,
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[non_exhaustive]
 pub(crate) enum Primitive {
@@ -245,6 +264,7 @@ impl IndirectionKind {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) enum ContainerKind {
     Struct,
     Union,
@@ -0,0 +1,305 @@
+//! Computing the in-memory layout of encoded types.
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::ffi;
+use core::mem;
+
+use crate::helper::{
+    ContainerKind, EncodingType, Helper, IndirectionKind, NestingLevel, Primitive,
+};
+
+impl Primitive {
+    pub(crate) const fn align(self) -> Option<usize> {
+        match self {
+            Self::Bool => Some(1),
+            // Numbers.
+            Self::Char => Some(mem::align_of::<ffi::c_char>()),
+            Self::UChar => Some(mem::align_of::<ffi::c_uchar>()),
+            Self::Short => Some(mem::align_of::<ffi::c_short>()),
+            Self::UShort => Some(mem::align_of::<ffi::c_ushort>()),
+            Self::Int => Some(mem::align_of::<ffi::c_int>()),
+            Self::UInt => Some(mem::align_of::<ffi::c_uint>()),
+            Self::Long => Some(mem::align_of::<ffi::c_long>()),
+            Self::ULong => Some(mem::align_of::<ffi::c_ulong>()),
+            Self::LongLong => Some(mem::align_of::<ffi::c_longlong>()),
+            Self::ULongLong => Some(mem::align_of::<ffi::c_ulonglong>()),
+            Self::Float => Some(mem::align_of::<ffi::c_float>()),
+            Self::Double => Some(mem::align_of::<ffi::c_double>()),
+            // https://github.com/search?q=repo%3Allvm%2Fllvm-project+path%3Aclang%2Flib%2FBasic%2FTargets+LongDoubleAlign&type=code
+            #[cfg(any(
+                target_arch = "x86_64",
+                all(target_arch = "x86", target_vendor = "apple"),
+                all(target_arch = "aarch64", not(target_vendor = "apple")),
+            ))]
+            Self::LongDouble => Some(16),
+            #[cfg(all(target_arch = "x86", not(target_vendor = "apple")))]
+            Self::LongDouble => Some(4),
+            #[cfg(any(
+                target_arch = "arm",
+                all(target_arch = "aarch64", target_vendor = "apple"),
+            ))]
+            Self::LongDouble => Some(8),
+            // Complex numbers are aligned like their components.
+            Self::FloatComplex => Self::Float.align(),
+            Self::DoubleComplex => Self::Double.align(),
+            Self::LongDoubleComplex => Self::LongDouble.align(),
+            // Pointers.
+            Self::String | Self::Object | Self::Block | Self::Class | Self::Sel => {
+                Some(mem::align_of::<*const ()>())
+            }
+            // Nothing.
+            Self::Void | Self::Unknown => None,
+        }
+    }
+
+    pub(crate) fn layout(self) -> Option<Layout> {
+        Layout::from_size_align(self.size()?, self.align()?).ok()
+    }
+}
+
+/// The position of a field within a struct.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FieldPosition {
+    /// The field starts at the given byte offset.
+    Byte(usize),
+    /// The field is a bitfield starting at the given bit offset.
+    Bit(usize),
+}
+
+/// Round the offset up to the given alignment (which is a power of two).
+fn align_up(offset: usize, align: usize) -> Option<usize> {
+    Some(offset.checked_add(align - 1)? & !(align - 1))
+}
+
+/// Convert a number of bits to the number of bytes required to store them.
+const fn bits_to_bytes(bits: usize) -> usize {
+    (bits + 7) / 8
+}
+
+impl<E: EncodingType> Helper<'_, E> {
+    /// The layout of the type, including alignment and trailing padding.
+    ///
+    /// Returns `None` if the type is unsized, opaque, or otherwise does not
+    /// have a known layout.
+    pub(crate) fn layout(&self, level: NestingLevel) -> Option<Layout> {
+        match self {
+            Self::NoneInvalid => None,
+            Self::Primitive(primitive) => primitive.layout(),
+            // A bitfield on its own is assumed stored in its type if
+            // available, or otherwise in the fewest bytes that fit it.
+            Self::BitField(size, Some((_, t))) => {
+                let layout = t.helper().layout(level.bitfield())?;
+                let size = layout.size().max(bits_to_bytes(usize::from(*size)));
+                Layout::from_size_align(size, layout.align())
+                    .ok()
+                    .map(|layout| layout.pad_to_align())
+            }
+            Self::BitField(size, None) => {
+                Layout::from_size_align(bits_to_bytes(usize::from(*size)), 1).ok()
+            }
+            Self::Indirection(IndirectionKind::Pointer, _) => Some(Layout::new::<*const ()>()),
+            Self::Indirection(kind @ IndirectionKind::Atomic, t) => {
+                t.helper().layout(level.indirection(*kind))
+            }
+            Self::Array(len, item) => {
+                let item = item.helper().layout(level.array())?;
+                let len = usize::try_from(*len).ok()?;
+                Layout::from_size_align(item.size().checked_mul(len)?, item.align()).ok()
+            }
+            Self::Container(kind, _, fields) => {
+                let level = level.container_include_fields()?;
+                match kind {
+                    ContainerKind::Struct => struct_layout(fields, level).map(|(layout, _)| layout),
+                    ContainerKind::Union => union_layout(fields, level),
+                }
+            }
+        }
+    }
+}
+
+/// Compute the layout of a struct with the given fields, along with the
+/// position of each field.
+///
+/// Bitfields without type information (as emitted on Apple platforms) are
+/// packed directly after each other, and are considered to only require
+/// byte-alignment. This matches Clang when the bitfields' declared types have
+/// the same alignment as the surrounding fields, but may be wrong otherwise.
+pub(crate) fn struct_layout<E: EncodingType>(
+    fields: &[E],
+    level: NestingLevel,
+) -> Option<(Layout, Vec<FieldPosition>)> {
+    // Structs without fields are opaque.
+    if fields.is_empty() {
+        return None;
+    }
+
+    let mut positions = Vec::with_capacity(fields.len());
+    let mut bit_offset: usize = 0;
+    let mut align = 1;
+
+    for field in fields {
+        match field.helper() {
+            Helper::BitField(size, None) => {
+                positions.push(FieldPosition::Bit(bit_offset));
+                bit_offset = bit_offset.checked_add(usize::from(size))?;
+            }
+            // GNUStep stores the offset of the bitfield explicitly.
+            Helper::BitField(size, Some((offset, t))) => {
+                align = align.max(t.helper().layout(level.bitfield())?.align());
+                let offset = usize::try_from(*offset).ok()?;
+                positions.push(FieldPosition::Bit(offset));
+                bit_offset = bit_offset.max(offset.checked_add(usize::from(size))?);
+            }
+            helper => {
+                let layout = helper.layout(level)?;
+                align = align.max(layout.align());
+                let byte_offset = align_up(bits_to_bytes(bit_offset), layout.align())?;
+                positions.push(FieldPosition::Byte(byte_offset));
+                bit_offset = byte_offset.checked_add(layout.size())?.checked_mul(8)?;
+            }
+        }
+    }
+
+    let layout = Layout::from_size_align(bits_to_bytes(bit_offset), align).ok()?;
+    Some((layout.pad_to_align(), positions))
+}
+
+fn union_layout<E: EncodingType>(members: &[E], level: NestingLevel) -> Option<Layout> {
+    // Unions without members are opaque.
+    if members.is_empty() {
+        return None;
+    }
+
+    let mut size = 0;
+    let mut align = 1;
+    for member in members {
+        let layout = member.helper().layout(level)?;
+        size = size.max(layout.size());
+        align = align.max(layout.align());
+    }
+    Layout::from_size_align(size, align)
+        .ok()
+        .map(|layout| layout.pad_to_align())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Encoding, EncodingBox};
+    use alloc::vec;
+    use core::str::FromStr;
+
+    #[test]
+    fn primitives() {
+        #[track_caller]
+        fn assert_layout(enc: Encoding, expected: Layout) {
+            assert_eq!(enc.layout(), Some(expected), "{enc}");
+        }
+
+        assert_layout(Encoding::Char, Layout::new::<ffi::c_char>());
+        assert_layout(Encoding::Int, Layout::new::<ffi::c_int>());
+        assert_layout(Encoding::C_LONG, Layout::new::<ffi::c_long>());
+        assert_layout(Encoding::ULongLong, Layout::new::<ffi::c_ulonglong>());
+        assert_layout(Encoding::Double, Layout::new::<ffi::c_double>());
+        assert_layout(Encoding::DoubleComplex, Layout::new::<[ffi::c_double; 2]>());
+        assert_layout(Encoding::Bool, Layout::new::<bool>());
+        assert_layout(Encoding::Object, Layout::new::<*const ()>());
+        assert_layout(
+            Encoding::Pointer(&Encoding::Void),
+            Layout::new::<*const ()>(),
+        );
+        assert_layout(
+            Encoding::Atomic(&Encoding::Int),
+            Layout::new::<ffi::c_int>(),
+        );
+
+        assert_eq!(Encoding::Void.layout(), None);
+        assert_eq!(Encoding::Unknown.layout(), None);
+        assert_eq!(Encoding::None.layout(), None);
+    }
+
+    #[test]
+    fn containers() {
+        #[repr(C)]
+        struct Padded {
+            a: u8,
+            b: f64,
+            c: u16,
+        }
+        const PADDED: Encoding = Encoding::Struct(
+            "Padded",
+            &[Encoding::UChar, Encoding::Double, Encoding::UShort],
+        );
+        assert_eq!(PADDED.layout(), Some(Layout::new::<Padded>()));
+
+        #[repr(C)]
+        struct Nested {
+            a: [Padded; 3],
+            b: u8,
+        }
+        let nested = Encoding::Struct("Nested", &[Encoding::Array(3, &PADDED), Encoding::UChar]);
+        assert_eq!(nested.layout(), Some(Layout::new::<Nested>()));
+
+        #[repr(C)]
+        union Onion {
+            a: u8,
+            b: [u16; 3],
+        }
+        let onion = Encoding::Union(
+            "Onion",
+            &[Encoding::UChar, Encoding::Array(3, &Encoding::UShort)],
+        );
+        assert_eq!(onion.layout(), Some(Layout::new::<Onion>()));
+
+        // Opaque
+        assert_eq!(Encoding::Struct("Opaque", &[]).layout(), None);
+        assert_eq!(
+            Encoding::Struct("A", &[Encoding::Int, Encoding::Void]).layout(),
+            None
+        );
+    }
+
+    #[test]
+    fn struct_positions() {
+        let enc = EncodingBox::from_str("{S=cb4b3sb9}").unwrap();
+        let EncodingBox::Struct(_, fields) = &enc else {
+            unreachable!()
+        };
+        let (layout, positions) = struct_layout(
+            fields,
+            NestingLevel::new().container_include_fields().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            positions,
+            vec![
+                FieldPosition::Byte(0),
+                FieldPosition::Bit(8),
+                FieldPosition::Bit(12),
+                FieldPosition::Byte(2),
+                FieldPosition::Bit(32),
+            ]
+        );
+        assert_eq!(layout, Layout::from_size_align(6, 2).unwrap());
+
+        // GNUStep-style
+        let enc = EncodingBox::from_str("{S=ib32i4b36i8}").unwrap();
+        let EncodingBox::Struct(_, fields) = &enc else {
+            unreachable!()
+        };
+        let (layout, positions) = struct_layout(
+            fields,
+            NestingLevel::new().container_include_fields().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            positions,
+            vec![
+                FieldPosition::Byte(0),
+                FieldPosition::Bit(32),
+                FieldPosition::Bit(36),
+            ]
+        );
+        assert_eq!(layout, Layout::new::<[ffi::c_int; 2]>());
+    }
+}
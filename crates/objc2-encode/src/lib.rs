@@ -51,15 +51,23 @@ extern crate std;
 #[cfg(any(feature = "alloc", test))]
 extern crate alloc;
 
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
 mod encoding;
 mod encoding_box;
+mod equivalence;
 mod helper;
+mod layout;
 mod parse;
-
-// Will be used at some point when generic constants are available
-#[allow(dead_code)]
 mod static_str;
+mod value;
 
 pub use self::encoding::Encoding;
 pub use self::encoding_box::EncodingBox;
+pub use self::equivalence::EquivalenceOptions;
 pub use self::parse::ParseError;
+pub use self::value::{Value, ValueError};
+
+#[cfg(feature = "unstable-fuzzing")]
+#[doc(hidden)]
+pub use self::arbitrary::__with_static_encoding;
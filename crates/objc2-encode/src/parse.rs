@@ -5,8 +5,11 @@ use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use core::fmt;
 
-use crate::helper::{ContainerKind, EncodingType, Helper, NestingLevel, Primitive};
-use crate::{Encoding, EncodingBox};
+use crate::helper::{
+    pointee_matches_any, ContainerKind, EncodingType, Helper, IndirectionKind, NestingLevel,
+    Primitive,
+};
+use crate::{Encoding, EncodingBox, EquivalenceOptions};
 
 /// Check whether a struct or union name is a valid identifier
 pub(crate) const fn verify_name(name: &str) -> bool {
@@ -32,10 +35,16 @@ pub(crate) const fn verify_name(name: &str) -> bool {
 }
 
 /// The error that was encountered while parsing an encoding string.
+///
+/// With the `"serde"` feature enabled, this implements `Serialize` and
+/// `Deserialize` as a structure containing the kind of error, the string
+/// that failed parsing, and the byte-index where the error occurred.
 #[derive(Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ParseError {
     kind: ErrorKind,
     data: String,
+    #[cfg_attr(feature = "serde", serde(rename = "position"))]
     split_point: usize,
 }
 
@@ -63,6 +72,7 @@ impl fmt::Display for ParseError {
 impl std::error::Error for ParseError {}
 
 #[derive(Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) enum ErrorKind {
     UnexpectedEnd,
     Unknown(u8),
@@ -261,10 +271,27 @@ impl Parser<'_> {
         }
     }
 
-    pub(crate) fn expect_encoding(&mut self, enc: &Encoding, level: NestingLevel) -> Option<()> {
+    pub(crate) fn expect_encoding(
+        &mut self,
+        enc: &Encoding,
+        level: NestingLevel,
+        options: EquivalenceOptions,
+    ) -> Option<()> {
+        if options.ignores_qualifiers() {
+            self.strip_leading_qualifiers();
+        }
+
         match enc.helper() {
             Helper::Primitive(primitive) => {
-                self.expect_str(primitive.to_str())?;
+                let split_point = self.split_point;
+                // Try each of the primitives that are considered equivalent
+                let found = options.equivalent_primitives(primitive).any(|primitive| {
+                    self.split_point = split_point;
+                    self.expect_str(primitive.to_str()).is_some()
+                });
+                if !found {
+                    return None;
+                }
 
                 if primitive == Primitive::Object && self.try_peek() == Some(b'"') {
                     self.advance();
@@ -276,26 +303,37 @@ impl Parser<'_> {
             Helper::BitField(size, Some((offset, t))) => {
                 self.expect_byte(b'b')?;
                 self.expect_u64(*offset)?;
-                self.expect_encoding(t, level.bitfield())?;
+                self.expect_encoding(t, level.bitfield(), options)?;
                 self.expect_u8(size)
             }
             Helper::BitField(size, None) => {
                 self.expect_byte(b'b')?;
                 self.expect_u8(size)
             }
+            Helper::Indirection(IndirectionKind::Pointer, t) if pointee_matches_any(t, options) => {
+                self.expect_byte(b'^')?;
+                self.skip_pointee()
+            }
             Helper::Indirection(kind, t) => {
                 self.expect_byte(kind.prefix_byte())?;
-                self.expect_encoding(t, level.indirection(kind))
+                if kind == IndirectionKind::Pointer && self.try_expect_any_pointee(options) {
+                    return Some(());
+                }
+                self.expect_encoding(t, level.indirection(kind), options)
             }
             Helper::Array(len, item) => {
                 self.expect_byte(b'[')?;
                 self.expect_u64(len)?;
-                self.expect_encoding(item, level.array())?;
+                self.expect_encoding(item, level.array(), options)?;
                 self.expect_byte(b']')
             }
             Helper::Container(kind, name, items) => {
                 self.expect_byte(kind.start_byte())?;
-                self.expect_str(name)?;
+                if options.ignores_container_names() {
+                    self.skip_container_name(kind)?;
+                } else {
+                    self.expect_str(name)?;
+                }
                 if let Some(level) = level.container_include_fields() {
                     self.expect_byte(b'=')?;
                     // Parse as equal if the container is empty
@@ -326,7 +364,7 @@ impl Parser<'_> {
                         return Some(());
                     }
                     for item in items {
-                        self.expect_encoding(item, level)?;
+                        self.expect_encoding(item, level, options)?;
                     }
                 }
                 self.expect_byte(kind.end_byte())
@@ -334,6 +372,47 @@ impl Parser<'_> {
             Helper::NoneInvalid => Some(()),
         }
     }
+
+    /// Consume the pointee of a pointer, whatever it may be.
+    ///
+    /// The pointee must still be a complete encoding, so e.g. `"^"` alone is
+    /// rejected.
+    fn skip_pointee(&mut self) -> Option<()> {
+        match self.parse_inner().ok()? {
+            ParseInner::Encoding(_) => Some(()),
+            ParseInner::Empty | ParseInner::ContainerEnd(_) | ParseInner::ArrayEnd => None,
+        }
+    }
+
+    /// Consume the pointee if the string's pointee is one that matches any
+    /// other pointee with the given options.
+    fn try_expect_any_pointee(&mut self, options: EquivalenceOptions) -> bool {
+        if options.ignores_qualifiers() {
+            self.strip_leading_qualifiers();
+        }
+        let primitive = match self.try_peek() {
+            Some(b'v') => Primitive::Void,
+            Some(b'?') => Primitive::Unknown,
+            _ => return false,
+        };
+        if options.pointee_matches_any(primitive) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consume a struct or union name, without checking what it is.
+    fn skip_container_name(&mut self, kind: ContainerKind) -> Option<()> {
+        let old_split_point = self.split_point;
+        self.consume_while(|b| b != b'=' && b != kind.end_byte());
+        if verify_name(&self.data[old_split_point..self.split_point]) {
+            Some(())
+        } else {
+            None
+        }
+    }
 }
 
 impl Parser<'_> {
@@ -629,4 +708,28 @@ mod tests {
             Err(ErrorKind::Unknown(b']'))
         );
     }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_error() {
+        use core::str::FromStr;
+
+        let err = EncodingBox::from_str("{a=i]").unwrap_err();
+        let json = serde_json::to_string(&err).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":{"Unknown":93},"data":"{a=i]","position":5}"#
+        );
+        let deserialized: ParseError = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, err);
+
+        let err = EncodingBox::from_str("(a=i").unwrap_err();
+        let json = serde_json::to_string(&err).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":{"WrongEndContainer":"Union"},"data":"(a=i","position":4}"#
+        );
+        let deserialized: ParseError = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, err);
+    }
 }
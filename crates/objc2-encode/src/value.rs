@@ -0,0 +1,632 @@
+//! Reading and writing values from raw bytes, as described by an encoding.
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::ffi::c_void;
+use core::fmt;
+
+use crate::helper::{
+    ContainerKind, EncodingType, Helper, IndirectionKind, NestingLevel, Primitive,
+};
+use crate::layout::{struct_layout, FieldPosition};
+
+/// A value read from raw bytes according to an encoding.
+///
+/// This is created by [`EncodingBox::read_value`] and
+/// [`Encoding::read_value`], and can be written back with the corresponding
+/// `write_value` methods.
+///
+/// The value borrows from the buffer it was read from where it cannot
+/// represent the data itself; e.g. the contents of a union are returned as
+/// raw bytes, since it is not known which member is active.
+///
+/// [`EncodingBox::read_value`]: crate::EncodingBox::read_value
+/// [`Encoding::read_value`]: crate::Encoding::read_value
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Value<'a> {
+    /// A signed integer; `char`, `short`, `int`, `long` or `long long`.
+    Int(i64),
+    /// An unsigned integer; `unsigned char`, `unsigned short`,
+    /// `unsigned int`, `unsigned long` or `unsigned long long`.
+    UInt(u64),
+    /// A C `float`.
+    Float(f32),
+    /// A C `double`.
+    Double(f64),
+    /// A C++ `bool` / C99 `_Bool`.
+    Bool(bool),
+    /// The address of a pointer, object, block, class, selector or C string.
+    Pointer(*const c_void),
+    /// The raw bits of a bitfield, without sign-extension.
+    BitField(u64),
+    /// The elements of an array, or the real and imaginary parts of a
+    /// `float _Complex` or `double _Complex`.
+    Array(Vec<Value<'a>>),
+    /// The fields of a struct, in order.
+    Struct(Vec<Value<'a>>),
+    /// The bytes of a union, or of a type that cannot be represented in
+    /// Rust (`long double` and `long double _Complex`).
+    Bytes(&'a [u8]),
+}
+
+/// An error encountered while reading or writing a [`Value`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ValueError {
+    kind: ValueErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ValueErrorKind {
+    UnknownLayout,
+    BufferTooSmall { needed: usize, len: usize },
+    Mismatch,
+    OutOfRange,
+}
+
+impl ValueError {
+    const fn new(kind: ValueErrorKind) -> Self {
+        Self { kind }
+    }
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ValueErrorKind::UnknownLayout => write!(f, "the encoding has no known layout"),
+            ValueErrorKind::BufferTooSmall { needed, len } => {
+                write!(f, "buffer of {len} bytes is too small, needed {needed}")
+            }
+            ValueErrorKind::Mismatch => write!(f, "the value does not match the encoding"),
+            ValueErrorKind::OutOfRange => {
+                write!(f, "the value is out of range for the encoding")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ValueError {}
+
+type Result<T, E = ValueError> = core::result::Result<T, E>;
+
+fn layout_of<E: EncodingType>(helper: &Helper<'_, E>, level: NestingLevel) -> Result<Layout> {
+    helper
+        .layout(level)
+        .ok_or(ValueError::new(ValueErrorKind::UnknownLayout))
+}
+
+fn check_len(needed: usize, len: usize) -> Result<()> {
+    if needed <= len {
+        Ok(())
+    } else {
+        Err(ValueError::new(ValueErrorKind::BufferTooSmall {
+            needed,
+            len,
+        }))
+    }
+}
+
+pub(crate) fn read_value<'a, E: EncodingType>(enc: &E, bytes: &'a [u8]) -> Result<Value<'a>> {
+    let helper = enc.helper();
+    let level = NestingLevel::new();
+    check_len(layout_of(&helper, level)?.size(), bytes.len())?;
+    read(&helper, bytes, level)
+}
+
+pub(crate) fn write_value<E: EncodingType>(
+    enc: &E,
+    value: &Value<'_>,
+    bytes: &mut [u8],
+) -> Result<()> {
+    let helper = enc.helper();
+    let level = NestingLevel::new();
+    check_len(layout_of(&helper, level)?.size(), bytes.len())?;
+    write(&helper, value, bytes, level)
+}
+
+/// Read `N` bytes from the start of the buffer.
+///
+/// The buffer is guaranteed to be large enough by the caller.
+fn read_array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    let mut res = [0; N];
+    res.copy_from_slice(&bytes[..N]);
+    res
+}
+
+fn read_int(bytes: &[u8], size: usize) -> Result<i64> {
+    Ok(match size {
+        1 => i8::from_ne_bytes(read_array(bytes)).into(),
+        2 => i16::from_ne_bytes(read_array(bytes)).into(),
+        4 => i32::from_ne_bytes(read_array(bytes)).into(),
+        8 => i64::from_ne_bytes(read_array(bytes)),
+        _ => return Err(ValueError::new(ValueErrorKind::UnknownLayout)),
+    })
+}
+
+fn read_uint(bytes: &[u8], size: usize) -> Result<u64> {
+    Ok(match size {
+        1 => u8::from_ne_bytes(read_array(bytes)).into(),
+        2 => u16::from_ne_bytes(read_array(bytes)).into(),
+        4 => u32::from_ne_bytes(read_array(bytes)).into(),
+        8 => u64::from_ne_bytes(read_array(bytes)),
+        _ => return Err(ValueError::new(ValueErrorKind::UnknownLayout)),
+    })
+}
+
+/// Write the lowest `size` bytes of the integer's representation.
+fn write_truncated(bytes: &mut [u8], size: usize, data: [u8; 8]) {
+    if cfg!(target_endian = "little") {
+        bytes[..size].copy_from_slice(&data[..size]);
+    } else {
+        bytes[..size].copy_from_slice(&data[8 - size..]);
+    }
+}
+
+fn write_int(bytes: &mut [u8], size: usize, value: i64) -> Result<()> {
+    let in_range = match size {
+        1 => i8::try_from(value).is_ok(),
+        2 => i16::try_from(value).is_ok(),
+        4 => i32::try_from(value).is_ok(),
+        8 => true,
+        _ => return Err(ValueError::new(ValueErrorKind::UnknownLayout)),
+    };
+    if !in_range {
+        return Err(ValueError::new(ValueErrorKind::OutOfRange));
+    }
+    write_truncated(bytes, size, value.to_ne_bytes());
+    Ok(())
+}
+
+fn write_uint(bytes: &mut [u8], size: usize, value: u64) -> Result<()> {
+    let in_range = match size {
+        1 => u8::try_from(value).is_ok(),
+        2 => u16::try_from(value).is_ok(),
+        4 => u32::try_from(value).is_ok(),
+        8 => true,
+        _ => return Err(ValueError::new(ValueErrorKind::UnknownLayout)),
+    };
+    if !in_range {
+        return Err(ValueError::new(ValueErrorKind::OutOfRange));
+    }
+    write_truncated(bytes, size, value.to_ne_bytes());
+    Ok(())
+}
+
+/// Read `size` bits starting at the given bit offset.
+///
+/// Bitfields are allocated starting from the least significant bit on all
+/// supported (little-endian) targets.
+fn read_bits(bytes: &[u8], bit_offset: usize, size: u8) -> Result<u64> {
+    if size > 64 {
+        return Err(ValueError::new(ValueErrorKind::OutOfRange));
+    }
+    check_len((bit_offset + usize::from(size) + 7) / 8, bytes.len())?;
+    let mut res = 0;
+    for i in 0..usize::from(size) {
+        let bit = bit_offset + i;
+        if bytes[bit / 8] & (1 << (bit % 8)) != 0 {
+            res |= 1 << i;
+        }
+    }
+    Ok(res)
+}
+
+fn write_bits(bytes: &mut [u8], bit_offset: usize, size: u8, value: u64) -> Result<()> {
+    if size > 64 || (size < 64 && value >> size != 0) {
+        return Err(ValueError::new(ValueErrorKind::OutOfRange));
+    }
+    check_len((bit_offset + usize::from(size) + 7) / 8, bytes.len())?;
+    for i in 0..usize::from(size) {
+        let bit = bit_offset + i;
+        if value & (1 << i) != 0 {
+            bytes[bit / 8] |= 1 << (bit % 8);
+        } else {
+            bytes[bit / 8] &= !(1 << (bit % 8));
+        }
+    }
+    Ok(())
+}
+
+fn read_primitive(primitive: Primitive, bytes: &[u8]) -> Result<Value<'_>> {
+    let size = primitive
+        .size()
+        .ok_or(ValueError::new(ValueErrorKind::UnknownLayout))?;
+    Ok(match primitive {
+        Primitive::Char
+        | Primitive::Short
+        | Primitive::Int
+        | Primitive::Long
+        | Primitive::LongLong => Value::Int(read_int(bytes, size)?),
+        Primitive::UChar
+        | Primitive::UShort
+        | Primitive::UInt
+        | Primitive::ULong
+        | Primitive::ULongLong => Value::UInt(read_uint(bytes, size)?),
+        Primitive::Float => Value::Float(f32::from_ne_bytes(read_array(bytes))),
+        Primitive::Double => Value::Double(f64::from_ne_bytes(read_array(bytes))),
+        Primitive::FloatComplex => Value::Array(
+            bytes[..size]
+                .chunks_exact(4)
+                .map(|chunk| Value::Float(f32::from_ne_bytes(read_array(chunk))))
+                .collect(),
+        ),
+        Primitive::DoubleComplex => Value::Array(
+            bytes[..size]
+                .chunks_exact(8)
+                .map(|chunk| Value::Double(f64::from_ne_bytes(read_array(chunk))))
+                .collect(),
+        ),
+        Primitive::LongDouble | Primitive::LongDoubleComplex => Value::Bytes(&bytes[..size]),
+        Primitive::Bool => Value::Bool(bytes[0] != 0),
+        Primitive::String
+        | Primitive::Object
+        | Primitive::Block
+        | Primitive::Class
+        | Primitive::Sel => Value::Pointer(read_pointer(bytes)),
+        Primitive::Void | Primitive::Unknown => {
+            return Err(ValueError::new(ValueErrorKind::UnknownLayout))
+        }
+    })
+}
+
+fn read_pointer(bytes: &[u8]) -> *const c_void {
+    usize::from_ne_bytes(read_array(bytes)) as *const c_void
+}
+
+fn write_pointer(bytes: &mut [u8], ptr: *const c_void) {
+    let size = core::mem::size_of::<usize>();
+    bytes[..size].copy_from_slice(&(ptr as usize).to_ne_bytes());
+}
+
+fn write_primitive(primitive: Primitive, value: &Value<'_>, bytes: &mut [u8]) -> Result<()> {
+    let size = primitive
+        .size()
+        .ok_or(ValueError::new(ValueErrorKind::UnknownLayout))?;
+    match (primitive, value) {
+        (
+            Primitive::Char
+            | Primitive::Short
+            | Primitive::Int
+            | Primitive::Long
+            | Primitive::LongLong,
+            Value::Int(value),
+        ) => write_int(bytes, size, *value),
+        (
+            Primitive::UChar
+            | Primitive::UShort
+            | Primitive::UInt
+            | Primitive::ULong
+            | Primitive::ULongLong,
+            Value::UInt(value),
+        ) => write_uint(bytes, size, *value),
+        (Primitive::Float, Value::Float(value)) => {
+            bytes[..4].copy_from_slice(&value.to_ne_bytes());
+            Ok(())
+        }
+        (Primitive::Double, Value::Double(value)) => {
+            bytes[..8].copy_from_slice(&value.to_ne_bytes());
+            Ok(())
+        }
+        (Primitive::FloatComplex, Value::Array(parts)) => match &**parts {
+            [Value::Float(re), Value::Float(im)] => {
+                bytes[..4].copy_from_slice(&re.to_ne_bytes());
+                bytes[4..8].copy_from_slice(&im.to_ne_bytes());
+                Ok(())
+            }
+            _ => Err(ValueError::new(ValueErrorKind::Mismatch)),
+        },
+        (Primitive::DoubleComplex, Value::Array(parts)) => match &**parts {
+            [Value::Double(re), Value::Double(im)] => {
+                bytes[..8].copy_from_slice(&re.to_ne_bytes());
+                bytes[8..16].copy_from_slice(&im.to_ne_bytes());
+                Ok(())
+            }
+            _ => Err(ValueError::new(ValueErrorKind::Mismatch)),
+        },
+        (Primitive::LongDouble | Primitive::LongDoubleComplex, Value::Bytes(data))
+            if data.len() == size =>
+        {
+            bytes[..size].copy_from_slice(data);
+            Ok(())
+        }
+        (Primitive::Bool, Value::Bool(value)) => {
+            bytes[0] = u8::from(*value);
+            Ok(())
+        }
+        (
+            Primitive::String
+            | Primitive::Object
+            | Primitive::Block
+            | Primitive::Class
+            | Primitive::Sel,
+            Value::Pointer(ptr),
+        ) => {
+            write_pointer(bytes, *ptr);
+            Ok(())
+        }
+        _ => Err(ValueError::new(ValueErrorKind::Mismatch)),
+    }
+}
+
+fn read<'a, E: EncodingType>(
+    helper: &Helper<'_, E>,
+    bytes: &'a [u8],
+    level: NestingLevel,
+) -> Result<Value<'a>> {
+    match helper {
+        Helper::Primitive(primitive) => read_primitive(*primitive, bytes),
+        Helper::BitField(size, _) => Ok(Value::BitField(read_bits(bytes, 0, *size)?)),
+        Helper::Indirection(IndirectionKind::Pointer, _) => Ok(Value::Pointer(read_pointer(bytes))),
+        Helper::Indirection(kind @ IndirectionKind::Atomic, t) => {
+            read(&t.helper(), bytes, level.indirection(*kind))
+        }
+        Helper::Array(len, item) => {
+            let level = level.array();
+            let item = item.helper();
+            let stride = layout_of(&item, level)?.size();
+            (0..*len as usize)
+                .map(|i| read(&item, &bytes[i * stride..], level))
+                .collect::<Result<_>>()
+                .map(Value::Array)
+        }
+        Helper::Container(ContainerKind::Struct, _, fields) => {
+            let level = level
+                .container_include_fields()
+                .ok_or(ValueError::new(ValueErrorKind::UnknownLayout))?;
+            let (_, positions) = struct_layout(fields, level)
+                .ok_or(ValueError::new(ValueErrorKind::UnknownLayout))?;
+            fields
+                .iter()
+                .zip(positions)
+                .map(|(field, position)| match (field.helper(), position) {
+                    (Helper::BitField(size, _), FieldPosition::Bit(offset)) => {
+                        Ok(Value::BitField(read_bits(bytes, offset, size)?))
+                    }
+                    (helper, FieldPosition::Byte(offset)) => read(&helper, &bytes[offset..], level),
+                    (_, FieldPosition::Bit(_)) => unreachable!("only bitfields have bit positions"),
+                })
+                .collect::<Result<_>>()
+                .map(Value::Struct)
+        }
+        Helper::Container(ContainerKind::Union, _, _) => {
+            let size = layout_of(helper, level)?.size();
+            Ok(Value::Bytes(&bytes[..size]))
+        }
+        Helper::NoneInvalid => Err(ValueError::new(ValueErrorKind::UnknownLayout)),
+    }
+}
+
+fn write<E: EncodingType>(
+    helper: &Helper<'_, E>,
+    value: &Value<'_>,
+    bytes: &mut [u8],
+    level: NestingLevel,
+) -> Result<()> {
+    match (helper, value) {
+        (Helper::Primitive(primitive), value) => write_primitive(*primitive, value, bytes),
+        (Helper::BitField(size, _), Value::BitField(value)) => write_bits(bytes, 0, *size, *value),
+        (Helper::Indirection(IndirectionKind::Pointer, _), Value::Pointer(ptr)) => {
+            write_pointer(bytes, *ptr);
+            Ok(())
+        }
+        (Helper::Indirection(kind @ IndirectionKind::Atomic, t), value) => {
+            write(&t.helper(), value, bytes, level.indirection(*kind))
+        }
+        (Helper::Array(len, item), Value::Array(items)) => {
+            if *len != items.len() as u64 {
+                return Err(ValueError::new(ValueErrorKind::Mismatch));
+            }
+            let level = level.array();
+            let item = item.helper();
+            let stride = layout_of(&item, level)?.size();
+            for (i, value) in items.iter().enumerate() {
+                write(&item, value, &mut bytes[i * stride..], level)?;
+            }
+            Ok(())
+        }
+        (Helper::Container(ContainerKind::Struct, _, fields), Value::Struct(values)) => {
+            if fields.len() != values.len() {
+                return Err(ValueError::new(ValueErrorKind::Mismatch));
+            }
+            let level = level
+                .container_include_fields()
+                .ok_or(ValueError::new(ValueErrorKind::UnknownLayout))?;
+            let (_, positions) = struct_layout(fields, level)
+                .ok_or(ValueError::new(ValueErrorKind::UnknownLayout))?;
+            for ((field, value), position) in fields.iter().zip(values).zip(positions) {
+                match (field.helper(), value, position) {
+                    (
+                        Helper::BitField(size, _),
+                        Value::BitField(value),
+                        FieldPosition::Bit(offset),
+                    ) => {
+                        write_bits(bytes, offset, size, *value)?;
+                    }
+                    (Helper::BitField(..), _, _) => {
+                        return Err(ValueError::new(ValueErrorKind::Mismatch));
+                    }
+                    (helper, value, FieldPosition::Byte(offset)) => {
+                        write(&helper, value, &mut bytes[offset..], level)?;
+                    }
+                    (_, _, FieldPosition::Bit(_)) => {
+                        unreachable!("only bitfields have bit positions")
+                    }
+                }
+            }
+            Ok(())
+        }
+        (Helper::Container(ContainerKind::Union, _, _), Value::Bytes(data)) => {
+            let size = layout_of(helper, level)?.size();
+            if data.len() != size {
+                return Err(ValueError::new(ValueErrorKind::Mismatch));
+            }
+            bytes[..size].copy_from_slice(data);
+            Ok(())
+        }
+        (Helper::NoneInvalid, _) => Err(ValueError::new(ValueErrorKind::UnknownLayout)),
+        _ => Err(ValueError::new(ValueErrorKind::Mismatch)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Encoding, EncodingBox};
+    use alloc::string::ToString;
+    use alloc::vec;
+    use core::ffi::c_int;
+    use core::mem;
+    use core::ptr;
+    use core::str::FromStr;
+
+    fn as_bytes<T>(value: &T) -> &[u8] {
+        let ptr: *const T = value;
+        // SAFETY: The types used in these tests have no padding.
+        unsafe { core::slice::from_raw_parts(ptr.cast(), mem::size_of::<T>()) }
+    }
+
+    #[test]
+    fn primitives() {
+        let enc = Encoding::Int;
+        let value = enc.read_value(as_bytes(&-42_i32)).unwrap();
+        assert_eq!(value, Value::Int(-42));
+
+        let enc = Encoding::UShort;
+        let value = enc.read_value(as_bytes(&0xbeef_u16)).unwrap();
+        assert_eq!(value, Value::UInt(0xbeef));
+
+        let enc = Encoding::Double;
+        let value = enc.read_value(as_bytes(&1.5_f64)).unwrap();
+        assert_eq!(value, Value::Double(1.5));
+
+        let enc = Encoding::Bool;
+        assert_eq!(enc.read_value(&[1]).unwrap(), Value::Bool(true));
+
+        let enc = Encoding::FloatComplex;
+        let value = enc.read_value(as_bytes(&[1.0_f32, 2.0])).unwrap();
+        assert_eq!(
+            value,
+            Value::Array(vec![Value::Float(1.0), Value::Float(2.0)])
+        );
+
+        let x = 5;
+        let ptr: *const c_int = &x;
+        let enc = Encoding::Pointer(&Encoding::Int);
+        let value = enc.read_value(as_bytes(&ptr)).unwrap();
+        assert_eq!(value, Value::Pointer(ptr.cast()));
+    }
+
+    #[test]
+    fn structs() {
+        #[repr(C)]
+        struct Inner {
+            a: u8,
+            // 3 bytes padding
+            b: c_int,
+        }
+        #[repr(C)]
+        struct Outer {
+            x: [Inner; 2],
+            y: f32,
+            z: i8,
+            // 3 bytes padding
+        }
+        let enc = EncodingBox::from_str("{Outer=[2{Inner=Ci}]fc}").unwrap();
+        assert_eq!(enc.layout(), Some(Layout::new::<Outer>()));
+
+        let mut bytes = vec![0xff_u8; mem::size_of::<Outer>()];
+        let value = Value::Struct(vec![
+            Value::Array(vec![
+                Value::Struct(vec![Value::UInt(1), Value::Int(-2)]),
+                Value::Struct(vec![Value::UInt(3), Value::Int(4)]),
+            ]),
+            Value::Float(5.0),
+            Value::Int(-6),
+        ]);
+        enc.write_value(&value, &mut bytes).unwrap();
+        assert_eq!(enc.read_value(&bytes).unwrap(), value);
+
+        // SAFETY: The buffer has the correct size, and all fields were
+        // written to (padding is not read). `read_unaligned` is used since
+        // the `Vec<u8>` may not be sufficiently aligned.
+        let outer: Outer = unsafe { ptr::read_unaligned(bytes.as_ptr().cast()) };
+        assert_eq!(outer.x[0].a, 1);
+        assert_eq!(outer.x[0].b, -2);
+        assert_eq!(outer.x[1].a, 3);
+        assert_eq!(outer.x[1].b, 4);
+        assert_eq!(outer.y, 5.0);
+        assert_eq!(outer.z, -6);
+    }
+
+    #[test]
+    fn bitfields() {
+        let enc = EncodingBox::from_str("{S=b3b5Cb9}").unwrap();
+        let mut bytes = [0; 4];
+        let value = Value::Struct(vec![
+            Value::BitField(0b101),
+            Value::BitField(0b11111),
+            Value::UInt(42),
+            Value::BitField(0x1ff),
+        ]);
+        enc.write_value(&value, &mut bytes).unwrap();
+        assert_eq!(bytes, [0b1111_1101, 42, 0xff, 0b1]);
+        assert_eq!(enc.read_value(&bytes).unwrap(), value);
+
+        let value = Value::Struct(vec![
+            Value::BitField(0b1000),
+            Value::BitField(0),
+            Value::UInt(0),
+            Value::BitField(0),
+        ]);
+        let err = enc.write_value(&value, &mut bytes).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the value is out of range for the encoding"
+        );
+    }
+
+    #[test]
+    fn unions_borrow() {
+        let enc = Encoding::Union("U", &[Encoding::Char, Encoding::Short]);
+        let bytes = [1, 2, 3];
+        let value = enc.read_value(&bytes).unwrap();
+        assert_eq!(value, Value::Bytes(&bytes[..2]));
+        let Value::Bytes(inner) = value else {
+            unreachable!()
+        };
+        assert_eq!(inner.as_ptr(), bytes.as_ptr());
+    }
+
+    #[test]
+    fn errors() {
+        let err = Encoding::Int.read_value(&[0; 2]).unwrap_err();
+        assert_eq!(err.to_string(), "buffer of 2 bytes is too small, needed 4");
+
+        let err = Encoding::Void.read_value(&[0; 8]).unwrap_err();
+        assert_eq!(err.to_string(), "the encoding has no known layout");
+
+        let err = Encoding::Struct("Opaque", &[])
+            .read_value(&[0; 8])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "the encoding has no known layout");
+
+        let mut bytes = [0; 4];
+        let err = Encoding::Int
+            .write_value(&Value::UInt(1), &mut bytes)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "the value does not match the encoding");
+
+        let err = Encoding::Char
+            .write_value(&Value::Int(300), &mut bytes)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the value is out of range for the encoding"
+        );
+
+        let err = Encoding::Array(2, &Encoding::Char)
+            .write_value(&Value::Array(vec![Value::Int(1)]), &mut bytes)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "the value does not match the encoding");
+    }
+}
@@ -0,0 +1,990 @@
+//! The `#[declared_class]` and `#[methods]` attributes.
+//!
+//! These are an alternative syntax for `declare_class!`. The input is
+//! validated here, so that errors can be reported at the offending token,
+//! and is then passed on to helper macros in `objc2` that share the code
+//! generation with `declare_class!`.
+//!
+//! We intentionally don't depend on `syn` to keep compile-times down, so the
+//! parsing is done directly on the token trees.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+/// An error that is reported at a specific token.
+struct Error {
+    span: Span,
+    message: String,
+}
+
+impl Error {
+    fn new(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+        }
+    }
+
+    /// Create `::core::compile_error! { "..." }`, with the span of the
+    /// offending token.
+    fn into_compile_error(self) -> TokenStream {
+        let span = self.span;
+        let mut message = Literal::string(&self.message);
+        message.set_span(span);
+        let mut tokens = path(&["core", "compile_error"], span);
+        tokens.extend([
+            TokenTree::Punct(with_span(Punct::new('!', Spacing::Alone), span)),
+            TokenTree::Group(with_span(
+                Group::new(Delimiter::Brace, TokenTree::Literal(message).into()),
+                span,
+            )),
+        ]);
+        tokens
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+trait SetSpan {
+    fn set_span(&mut self, span: Span);
+}
+
+impl SetSpan for Punct {
+    fn set_span(&mut self, span: Span) {
+        Punct::set_span(self, span);
+    }
+}
+
+impl SetSpan for Group {
+    fn set_span(&mut self, span: Span) {
+        Group::set_span(self, span);
+    }
+}
+
+fn with_span<T: SetSpan>(mut token: T, span: Span) -> T {
+    token.set_span(span);
+    token
+}
+
+/// Create an absolute path like `::objc2::__declared_class`.
+fn path(segments: &[&str], span: Span) -> TokenStream {
+    let mut tokens = TokenStream::new();
+    for segment in segments {
+        tokens.extend([
+            TokenTree::Punct(with_span(Punct::new(':', Spacing::Joint), span)),
+            TokenTree::Punct(with_span(Punct::new(':', Spacing::Alone), span)),
+            TokenTree::Ident(Ident::new(segment, span)),
+        ]);
+    }
+    tokens
+}
+
+/// Invoke a helper macro in `objc2`.
+///
+/// `krate` is the path given with `crate = ...`, or `::objc2` by default.
+fn call_helper_macro(krate: Option<&[TokenTree]>, name: &str, input: TokenStream) -> TokenStream {
+    let span = Span::call_site();
+    let mut tokens = match krate {
+        Some(krate) => krate.iter().cloned().collect(),
+        None => path(&["objc2"], span),
+    };
+    tokens.extend(path(&[name], span));
+    tokens.extend([
+        TokenTree::Punct(Punct::new('!', Spacing::Alone)),
+        TokenTree::Group(Group::new(Delimiter::Brace, input)),
+    ]);
+    tokens
+}
+
+fn parens(tokens: impl IntoIterator<Item = TokenTree>) -> TokenTree {
+    TokenTree::Group(Group::new(
+        Delimiter::Parenthesis,
+        tokens.into_iter().collect(),
+    ))
+}
+
+fn is_punct(token: Option<&TokenTree>, ch: char) -> bool {
+    matches!(token, Some(TokenTree::Punct(punct)) if punct.as_char() == ch)
+}
+
+fn is_ident(token: Option<&TokenTree>, name: &str) -> bool {
+    matches!(token, Some(TokenTree::Ident(ident)) if ident.to_string() == name)
+}
+
+fn is_group(token: Option<&TokenTree>, delimiter: Delimiter) -> bool {
+    matches!(token, Some(TokenTree::Group(group)) if group.delimiter() == delimiter)
+}
+
+/// Split the tokens on commas that are not nested inside generics.
+///
+/// A trailing comma is allowed.
+fn split_commas(tokens: &[TokenTree]) -> Vec<&[TokenTree]> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if let TokenTree::Punct(punct) = token {
+            match punct.as_char() {
+                '<' => depth += 1,
+                // Don't count the `>` in `->`
+                '>' if !is_punct(i.checked_sub(1).and_then(|i| tokens.get(i)), '-') => {
+                    depth = depth.saturating_sub(1);
+                }
+                ',' if depth == 0 => {
+                    parts.push(&tokens[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+    }
+    if start < tokens.len() {
+        parts.push(&tokens[start..]);
+    }
+    parts
+}
+
+/// Parse outer attributes, `#[...]`, starting at `*i`.
+fn parse_attributes(tokens: &[TokenTree], i: &mut usize) -> Vec<Group> {
+    let mut attributes = Vec::new();
+    while is_punct(tokens.get(*i), '#') {
+        match tokens.get(*i + 1) {
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Bracket => {
+                attributes.push(group.clone());
+                *i += 2;
+            }
+            _ => break,
+        }
+    }
+    attributes
+}
+
+/// Parse a visibility, `pub` or `pub(...)`, starting at `*i`.
+fn parse_visibility(tokens: &[TokenTree], i: &mut usize) -> Option<Span> {
+    if !is_ident(tokens.get(*i), "pub") {
+        return None;
+    }
+    let span = tokens[*i].span();
+    *i += 1;
+    if is_group(tokens.get(*i), Delimiter::Parenthesis) {
+        *i += 1;
+    }
+    Some(span)
+}
+
+fn output_attributes(attributes: &[Group]) -> TokenStream {
+    let mut tokens = TokenStream::new();
+    for attribute in attributes {
+        tokens.extend([
+            TokenTree::Punct(Punct::new('#', Spacing::Alone)),
+            TokenTree::Group(attribute.clone()),
+        ]);
+    }
+    tokens
+}
+
+/// The span of the first token, or of the call site if there are no tokens.
+fn first_span(tokens: &[TokenTree]) -> Span {
+    tokens
+        .first()
+        .map(TokenTree::span)
+        .unwrap_or_else(Span::call_site)
+}
+
+fn expect_end(tokens: &[TokenTree], i: usize, message: &str) -> Result<()> {
+    match tokens.get(i) {
+        Some(token) => Err(Error::new(token.span(), message)),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn declared_class(attr: TokenStream, item: TokenStream) -> TokenStream {
+    match parse_declared_class(attr, item.clone()) {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            // Output the struct too, to avoid unnecessary errors about it
+            // not existing.
+            let mut tokens = err.into_compile_error();
+            tokens.extend(item);
+            tokens
+        }
+    }
+}
+
+/// The arguments to `#[declared_class(...)]`.
+#[derive(Default)]
+struct ClassArgs {
+    superclass: Option<Vec<TokenTree>>,
+    name: Option<Vec<TokenTree>>,
+    thread_kind: Option<Vec<TokenTree>>,
+    inherits: Option<Vec<Vec<TokenTree>>>,
+    protocols: Option<Vec<Ident>>,
+    conflict: Option<Vec<TokenTree>>,
+    krate: Option<Vec<TokenTree>>,
+}
+
+fn parse_bracketed_list(value: &[TokenTree], key: &Ident) -> Result<Vec<Vec<TokenTree>>> {
+    match value {
+        [TokenTree::Group(group)] if group.delimiter() == Delimiter::Bracket => {
+            let tokens: Vec<_> = group.stream().into_iter().collect();
+            Ok(split_commas(&tokens)
+                .into_iter()
+                .map(|part| part.to_vec())
+                .collect())
+        }
+        _ => Err(Error::new(
+            first_span(value),
+            format!("expected a list, as in `{key} = [...]`"),
+        )),
+    }
+}
+
+fn parse_class_args(attr: TokenStream) -> Result<ClassArgs> {
+    let tokens: Vec<_> = attr.into_iter().collect();
+    let mut args = ClassArgs::default();
+
+    for part in split_commas(&tokens) {
+        let key = match part {
+            [TokenTree::Ident(key), rest @ ..] if is_punct(rest.first(), '=') => key,
+            _ => {
+                return Err(Error::new(
+                    first_span(part),
+                    "expected an argument of the form `key = value`",
+                ))
+            }
+        };
+        let value = &part[2..];
+        if value.is_empty() {
+            return Err(Error::new(
+                part[1].span(),
+                format!("expected a value for `{key}`"),
+            ));
+        }
+
+        let duplicate = match &*key.to_string() {
+            "super" => args.superclass.replace(value.to_vec()).is_some(),
+            "name" => args.name.replace(value.to_vec()).is_some(),
+            "thread_kind" => args.thread_kind.replace(value.to_vec()).is_some(),
+            "conflict" => args.conflict.replace(value.to_vec()).is_some(),
+            "crate" => args.krate.replace(value.to_vec()).is_some(),
+            "inherits" => {
+                let inherits = parse_bracketed_list(value, key)?;
+                args.inherits.replace(inherits).is_some()
+            }
+            "protocols" => {
+                let protocols = parse_bracketed_list(value, key)?
+                    .into_iter()
+                    .map(|protocol| match &*protocol {
+                        [TokenTree::Ident(protocol)] => Ok(protocol.clone()),
+                        _ => Err(Error::new(
+                            first_span(&protocol),
+                            "expected the name of a protocol; protocols must be imported \
+                             and referred to by a single identifier",
+                        )),
+                    })
+                    .collect::<Result<_>>()?;
+                args.protocols.replace(protocols).is_some()
+            }
+            _ => {
+                return Err(Error::new(
+                    key.span(),
+                    format!(
+                        "unknown argument `{key}`, expected one of `super`, `name`, \
+                         `thread_kind`, `inherits`, `protocols`, `conflict` or `crate`"
+                    ),
+                ))
+            }
+        };
+        if duplicate {
+            return Err(Error::new(
+                key.span(),
+                format!("cannot specify `{key}` twice"),
+            ));
+        }
+    }
+
+    Ok(args)
+}
+
+fn parse_declared_class(attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
+    let args = parse_class_args(attr)?;
+    let tokens: Vec<_> = item.into_iter().collect();
+
+    let mut i = 0;
+    let attributes = parse_attributes(&tokens, &mut i);
+    let vis_start = i;
+    parse_visibility(&tokens, &mut i);
+    let vis = &tokens[vis_start..i];
+
+    if !is_ident(tokens.get(i), "struct") {
+        return Err(Error::new(
+            tokens.get(i).map_or_else(Span::call_site, TokenTree::span),
+            "`#[declared_class]` can only be applied to a unit struct",
+        ));
+    }
+    i += 1;
+
+    let name = match tokens.get(i) {
+        Some(TokenTree::Ident(name)) => name.clone(),
+        token => {
+            return Err(Error::new(
+                token.map_or_else(Span::call_site, TokenTree::span),
+                "expected the name of the class",
+            ))
+        }
+    };
+    i += 1;
+
+    match tokens.get(i) {
+        Some(TokenTree::Punct(punct)) if punct.as_char() == ';' => {}
+        Some(TokenTree::Punct(punct)) if punct.as_char() == '<' => {
+            return Err(Error::new(
+                punct.span(),
+                "generic declared classes are not supported",
+            ))
+        }
+        Some(TokenTree::Group(group)) => {
+            return Err(Error::new(
+                group.span(),
+                "declared classes cannot have fields, use `DeclaredClass::Ivars` to store data \
+                 in the class instead",
+            ))
+        }
+        token => {
+            return Err(Error::new(
+                token.map_or_else(Span::call_site, TokenTree::span),
+                "expected `;`",
+            ))
+        }
+    }
+    expect_end(&tokens, i + 1, "unexpected token after struct")?;
+
+    let superclass = args
+        .superclass
+        .ok_or_else(|| Error::new(Span::call_site(), "must specify `super = ...`"))?;
+    let class_name = args.name.ok_or_else(|| {
+        Error::new(
+            Span::call_site(),
+            "must specify the name of the class with `name = \"...\"`",
+        )
+    })?;
+
+    let mut inherits = TokenStream::new();
+    for (i, class) in args.inherits.unwrap_or_default().into_iter().enumerate() {
+        if i != 0 {
+            inherits.extend([TokenTree::Punct(Punct::new(',', Spacing::Alone))]);
+        }
+        inherits.extend(class);
+    }
+
+    let mut protocols = TokenStream::new();
+    for (i, protocol) in args.protocols.unwrap_or_default().into_iter().enumerate() {
+        if i != 0 {
+            protocols.extend([TokenTree::Punct(Punct::new(',', Spacing::Alone))]);
+        }
+        protocols.extend([TokenTree::Ident(protocol)]);
+    }
+
+    let keyword = |key: &str, value: TokenStream| {
+        let mut tokens: TokenStream = key.parse().unwrap();
+        tokens.extend([TokenTree::Punct(Punct::new('=', Spacing::Alone))]);
+        tokens.extend(value);
+        parens(tokens)
+    };
+
+    let input = [
+        parens(output_attributes(&attributes)),
+        parens(vis.iter().cloned()),
+        parens([TokenTree::Ident(name)]),
+        keyword("super", superclass.into_iter().collect()),
+        keyword("inherits", inherits),
+        keyword(
+            "thread_kind",
+            args.thread_kind.unwrap_or_default().into_iter().collect(),
+        ),
+        keyword("name", class_name.into_iter().collect()),
+        keyword("protocols", protocols),
+        keyword(
+            "conflict",
+            args.conflict.unwrap_or_default().into_iter().collect(),
+        ),
+    ];
+
+    Ok(call_helper_macro(
+        args.krate.as_deref(),
+        "__declared_class",
+        input.into_iter().collect(),
+    ))
+}
+
+pub(crate) fn methods(attr: TokenStream, item: TokenStream) -> TokenStream {
+    match parse_methods(attr, item) {
+        Ok(tokens) => tokens,
+        // The methods are not valid Rust without the attributes being
+        // processed, so we can't output them here.
+        Err(err) => err.into_compile_error(),
+    }
+}
+
+/// A parsed item in an `impl` block.
+struct Item<'a> {
+    attributes: Vec<Group>,
+    /// The tokens after the attributes.
+    tokens: &'a [TokenTree],
+    /// The name of the item.
+    name: Ident,
+}
+
+impl Item<'_> {
+    fn output(&self) -> TokenStream {
+        let mut tokens = output_attributes(&self.attributes);
+        tokens.extend(self.tokens.iter().cloned());
+        tokens
+    }
+}
+
+/// Split the contents of an `impl` block into items.
+///
+/// Functions end at their body, while other items end at a `;`.
+fn parse_items(tokens: &[TokenTree]) -> Result<Vec<Item<'_>>> {
+    let mut items = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let attributes = parse_attributes(tokens, &mut i);
+        let start = i;
+        let mut name = None;
+        loop {
+            match tokens.get(i) {
+                Some(TokenTree::Ident(ident)) if name.is_none() => {
+                    let keyword = matches!(
+                        &*ident.to_string(),
+                        "pub" | "unsafe" | "fn" | "type" | "const" | "extern" | "async"
+                    );
+                    if !keyword {
+                        name = Some(ident.clone());
+                    }
+                }
+                Some(TokenTree::Group(group))
+                    if group.delimiter() == Delimiter::Brace && name.is_some() =>
+                {
+                    i += 1;
+                    break;
+                }
+                Some(TokenTree::Punct(punct)) if punct.as_char() == ';' => {
+                    i += 1;
+                    break;
+                }
+                Some(_) => {}
+                None => {
+                    return Err(Error::new(
+                        tokens.last().map_or_else(Span::call_site, TokenTree::span),
+                        "unexpected end of item",
+                    ))
+                }
+            }
+            i += 1;
+        }
+        let name = name.ok_or_else(|| Error::new(tokens[start].span(), "expected an item"))?;
+        items.push(Item {
+            attributes,
+            tokens: &tokens[start..i],
+            name,
+        });
+    }
+    Ok(items)
+}
+
+/// Parse the arguments to `#[methods(...)]`, which only supports
+/// `crate = ...`.
+fn parse_methods_args(attr: TokenStream) -> Result<Option<Vec<TokenTree>>> {
+    let tokens: Vec<_> = attr.into_iter().collect();
+    match &*tokens {
+        [] => Ok(None),
+        [TokenTree::Ident(key), TokenTree::Punct(eq), value @ ..]
+            if key.to_string() == "crate" && eq.as_char() == '=' && !value.is_empty() =>
+        {
+            Ok(Some(value.to_vec()))
+        }
+        _ => Err(Error::new(
+            first_span(&tokens),
+            "expected no arguments, or `crate = ...`",
+        )),
+    }
+}
+
+fn parse_methods(attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
+    let krate = parse_methods_args(attr)?;
+
+    let tokens: Vec<_> = item.into_iter().collect();
+
+    let mut i = 0;
+    let attributes = parse_attributes(&tokens, &mut i);
+    let unsafe_span = if is_ident(tokens.get(i), "unsafe") {
+        i += 1;
+        Some(tokens[i - 1].span())
+    } else {
+        None
+    };
+    if !is_ident(tokens.get(i), "impl") {
+        return Err(Error::new(
+            tokens.get(i).map_or_else(Span::call_site, TokenTree::span),
+            "`#[methods]` can only be applied to `impl` blocks",
+        ));
+    }
+    let impl_span = tokens[i].span();
+    i += 1;
+    if is_punct(tokens.get(i), '<') {
+        return Err(Error::new(
+            tokens[i].span(),
+            "generic declared classes are not supported",
+        ));
+    }
+
+    let body = match tokens.last() {
+        Some(TokenTree::Group(group))
+            if group.delimiter() == Delimiter::Brace && i < tokens.len() =>
+        {
+            group
+        }
+        _ => return Err(Error::new(impl_span, "expected an `impl` block")),
+    };
+    let header = &tokens[i..tokens.len() - 1];
+    if header.is_empty() {
+        return Err(Error::new(body.span(), "expected a type"));
+    }
+    if let Some(token) = header.iter().find(|token| is_ident(Some(token), "where")) {
+        return Err(Error::new(
+            token.span(),
+            "`where` clauses are not supported in `#[methods]`",
+        ));
+    }
+
+    let for_position = header.iter().position(|token| is_ident(Some(token), "for"));
+    let (protocol, ty) = match for_position {
+        Some(position) => (Some(&header[..position]), &header[position + 1..]),
+        None => (None, header),
+    };
+    let body_tokens: Vec<_> = body.stream().into_iter().collect();
+    let items = parse_items(&body_tokens)?;
+
+    // `impl DeclaredClass for T`
+    if let Some(protocol) = protocol {
+        if protocol
+            .last()
+            .is_some_and(|token| is_ident(Some(token), "DeclaredClass"))
+        {
+            if let Some(span) = unsafe_span {
+                return Err(Error::new(
+                    span,
+                    "`DeclaredClass` is implemented without `unsafe`",
+                ));
+            }
+            return parse_declared_class_impl(krate.as_deref(), &attributes, protocol, ty, &items);
+        }
+    }
+
+    let Some(unsafe_span) = unsafe_span else {
+        return Err(Error::new(
+            impl_span,
+            "the `impl` block must be `unsafe`, since the method signatures cannot be \
+             checked against what Objective-C expects",
+        ));
+    };
+
+    let mut input = output_attributes(&attributes);
+    input.extend([
+        TokenTree::Ident(Ident::new("unsafe", unsafe_span)),
+        TokenTree::Ident(Ident::new("impl", impl_span)),
+    ]);
+    if let Some(protocol) = protocol {
+        match protocol {
+            [TokenTree::Ident(_)] => {}
+            _ => {
+                return Err(Error::new(
+                    first_span(protocol),
+                    "expected the name of a protocol; protocols must be imported and \
+                     referred to by a single identifier",
+                ))
+            }
+        }
+        input.extend(protocol.iter().cloned());
+        input.extend([TokenTree::Ident(Ident::new("for", Span::call_site()))]);
+    }
+    input.extend(ty.iter().cloned());
+
+    let mut selectors = HashSet::new();
+    let mut methods = TokenStream::new();
+    for item in &items {
+        validate_method(item, &mut selectors)?;
+        methods.extend(item.output());
+    }
+    input.extend([TokenTree::Group(with_span(
+        Group::new(Delimiter::Brace, methods),
+        body.span(),
+    ))]);
+
+    Ok(call_helper_macro(
+        krate.as_deref(),
+        "__declared_class_methods",
+        input,
+    ))
+}
+
+/// Validate the hooks in `impl DeclaredClass for T`, and reorder them for
+/// the helper macro.
+fn parse_declared_class_impl(
+    krate: Option<&[TokenTree]>,
+    attributes: &[Group],
+    declared_class: &[TokenTree],
+    ty: &[TokenTree],
+    items: &[Item<'_>],
+) -> Result<TokenStream> {
+    const ITEMS: [&str; 4] = ["Ivars", "load", "initialize", "dealloc"];
+    let mut found: [Option<&Item<'_>>; 4] = [None; 4];
+
+    for item in items {
+        if let Some(attribute) = item.attributes.first() {
+            return Err(Error::new(
+                attribute.span(),
+                "attributes are not supported on items in `impl DeclaredClass`",
+            ));
+        }
+        let name = item.name.to_string();
+        let Some(index) = ITEMS.iter().position(|expected| *expected == name) else {
+            return Err(Error::new(
+                item.name.span(),
+                format!(
+                    "unknown item `{name}`, expected one of `Ivars`, `load`, `initialize` or \
+                     `dealloc`"
+                ),
+            ));
+        };
+        if found[index].replace(item).is_some() {
+            return Err(Error::new(
+                item.name.span(),
+                format!("cannot specify `{name}` twice"),
+            ));
+        }
+    }
+
+    if let Some(dealloc) = found[3] {
+        let params = dealloc
+            .tokens
+            .iter()
+            .find(|token| is_group(Some(token), Delimiter::Parenthesis));
+        if let Some(TokenTree::Group(params)) = params {
+            let params: Vec<_> = params.stream().into_iter().collect();
+            if !(params.len() == 2
+                && is_punct(params.first(), '&')
+                && is_ident(params.get(1), "self"))
+            {
+                return Err(Error::new(
+                    first_span(&params),
+                    "the `dealloc` hook must take `&self`",
+                ));
+            }
+        }
+    }
+
+    let mut body = TokenStream::new();
+    for item in found.into_iter().flatten() {
+        body.extend(item.output());
+    }
+
+    // The path to `DeclaredClass` is passed on as written, so that it is
+    // resolved like the user would expect.
+    let mut input = output_attributes(attributes);
+    input.extend([
+        TokenTree::Ident(Ident::new("impl", Span::call_site())),
+        parens(declared_class.iter().cloned()),
+        TokenTree::Ident(Ident::new("for", Span::call_site())),
+    ]);
+    input.extend(ty.iter().cloned());
+    input.extend([TokenTree::Group(Group::new(Delimiter::Brace, body))]);
+
+    Ok(call_helper_macro(
+        krate,
+        "__declare_class_declared_class",
+        input,
+    ))
+}
+
+/// The kinds of method attributes.
+#[derive(Clone, Copy, PartialEq)]
+enum MethodKind {
+    Method,
+    MethodId,
+}
+
+impl MethodKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Method => "method",
+            Self::MethodId => "method_id",
+        }
+    }
+}
+
+fn validate_method(item: &Item<'_>, selectors: &mut HashSet<(bool, String)>) -> Result<()> {
+    let tokens = item.tokens;
+    let fn_position = tokens
+        .iter()
+        .position(|token| is_ident(Some(token), "fn"))
+        .ok_or_else(|| {
+            Error::new(
+                first_span(tokens),
+                "only methods are supported in `#[methods]` blocks",
+            )
+        })?;
+    if let Some(span) = parse_visibility(tokens, &mut 0) {
+        return Err(Error::new(
+            span,
+            "declared methods cannot have a visibility, since they are only meant to be \
+             called from Objective-C; use `extern_methods!` to create a public interface",
+        ));
+    }
+
+    // Find the method attribute
+    let mut method = None;
+    let mut method_family = None;
+    for attribute in &item.attributes {
+        let content: Vec<_> = attribute.stream().into_iter().collect();
+        let kind = match content.first() {
+            Some(TokenTree::Ident(ident)) if ident.to_string() == "method" => MethodKind::Method,
+            Some(TokenTree::Ident(ident)) if ident.to_string() == "method_id" => {
+                MethodKind::MethodId
+            }
+            Some(TokenTree::Ident(ident)) if ident.to_string() == "method_family" => {
+                match content.get(2) {
+                    Some(TokenTree::Ident(family))
+                        if is_punct(content.get(1), '=') && content.len() == 3 =>
+                    {
+                        let known = ["none", "new", "alloc", "init", "copy", "mutableCopy"];
+                        if !known.contains(&&*family.to_string()) {
+                            return Err(Error::new(
+                                family.span(),
+                                format!(
+                                    "unknown method family `{family}`, expected one of \
+                                     `none`, `new`, `alloc`, `init`, `copy` or `mutableCopy`"
+                                ),
+                            ));
+                        }
+                    }
+                    _ => {
+                        return Err(Error::new(
+                            attribute.span(),
+                            "expected `#[method_family = ...]`",
+                        ))
+                    }
+                }
+                if method_family.replace(attribute.span()).is_some() {
+                    return Err(Error::new(
+                        attribute.span(),
+                        "`method_family` specified more than once",
+                    ));
+                }
+                continue;
+            }
+            Some(TokenTree::Ident(ident)) if ident.to_string() == "optional" => {
+                return Err(Error::new(
+                    attribute.span(),
+                    "`#[optional]` is only supported in `extern_protocol!`",
+                ));
+            }
+            _ => continue,
+        };
+        let selector = match content.get(1) {
+            Some(TokenTree::Group(group))
+                if group.delimiter() == Delimiter::Parenthesis && content.len() == 2 =>
+            {
+                group.clone()
+            }
+            _ => {
+                return Err(Error::new(
+                    attribute.span(),
+                    format!("expected `#[{}(selector)]`", kind.name()),
+                ))
+            }
+        };
+        if method.replace((kind, selector)).is_some() {
+            return Err(Error::new(
+                attribute.span(),
+                "cannot specify the `method`/`method_id` attribute twice",
+            ));
+        }
+    }
+
+    let Some((kind, selector)) = method else {
+        return Err(Error::new(
+            item.name.span(),
+            "must specify the desired selector using `#[method(...)]` or `#[method_id(...)]`",
+        ));
+    };
+    if let (MethodKind::Method, Some(span)) = (kind, method_family) {
+        return Err(Error::new(
+            span,
+            "`#[method_family = ...]` is only supported together with `#[method_id(...)]`",
+        ));
+    }
+
+    let (sel, num_args) = parse_selector(&selector)?;
+
+    match (kind, &*sel) {
+        (_, "dealloc") => {
+            return Err(Error::new(
+                selector.span(),
+                format!(
+                    "`#[{}(dealloc)]` is not supported. Implement `Drop` or \
+                     `DeclaredClass::dealloc` for the type instead",
+                    kind.name(),
+                ),
+            ))
+        }
+        (MethodKind::MethodId, "alloc" | "retain" | "release" | "autorelease") => {
+            return Err(Error::new(
+                selector.span(),
+                format!(
+                    "`#[method_id({sel})]` is not supported. Use `#[method({sel})]` and do \
+                     the memory management yourself"
+                ),
+            ))
+        }
+        _ => {}
+    }
+
+    // Check the parameters against the selector
+    let params = match tokens.get(fn_position + 2) {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => group,
+        _ => return Err(Error::new(item.name.span(), "expected parameters")),
+    };
+    let params_tokens: Vec<_> = params.stream().into_iter().collect();
+    let mut params_list = split_commas(&params_tokens);
+    let is_instance_method = params_list
+        .first()
+        .is_some_and(|receiver| is_receiver(receiver));
+    if is_instance_method {
+        let receiver = params_list.remove(0);
+        if let [TokenTree::Punct(and), TokenTree::Ident(mutability), ..] = receiver {
+            if and.as_char() == '&' && mutability.to_string() == "mut" {
+                return Err(Error::new(
+                    mutability.span(),
+                    "`&mut self` is not supported in declared methods, use interior mutability \
+                     in the ivars instead",
+                ));
+            }
+        }
+    }
+    if params_list.len() != num_args {
+        return Err(Error::new(
+            selector.span(),
+            format!(
+                "selector `{sel}` takes {num_args} argument{}, but the method has {} parameter{}",
+                if num_args == 1 { "" } else { "s" },
+                params_list.len(),
+                if params_list.len() == 1 { "" } else { "s" },
+            ),
+        ));
+    }
+
+    if kind == MethodKind::MethodId {
+        let has_return_type = tokens[fn_position..]
+            .windows(2)
+            .any(|window| is_punct(window.first(), '-') && is_punct(window.get(1), '>'));
+        if !has_return_type {
+            return Err(Error::new(
+                item.name.span(),
+                "`#[method_id(...)]` must have a return type",
+            ));
+        }
+    }
+
+    if !selectors.insert((is_instance_method, sel.clone())) {
+        return Err(Error::new(
+            selector.span(),
+            format!(
+                "{} method with selector `{sel}` is already declared in this block",
+                if is_instance_method {
+                    "an instance"
+                } else {
+                    "a class"
+                },
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Whether the first parameter is a receiver, in the same way as
+/// `__rewrite_self_param!` decides it.
+fn is_receiver(param: &[TokenTree]) -> bool {
+    let param = match param {
+        [TokenTree::Punct(and), rest @ ..] if and.as_char() == '&' => rest,
+        param => param,
+    };
+    let param = match param {
+        [TokenTree::Punct(lifetime), TokenTree::Ident(_), rest @ ..]
+            if lifetime.as_char() == '\'' =>
+        {
+            rest
+        }
+        param => param,
+    };
+    let param = match param {
+        [TokenTree::Ident(mutability), rest @ ..] if mutability.to_string() == "mut" => rest,
+        param => param,
+    };
+    matches!(
+        param.first(),
+        Some(TokenTree::Ident(ident)) if matches!(&*ident.to_string(), "self" | "this" | "_this")
+    )
+}
+
+/// Parse a selector, and return it as a string together with the number of
+/// arguments it takes.
+fn parse_selector(selector: &Group) -> Result<(String, usize)> {
+    let tokens: Vec<_> = selector.stream().into_iter().collect();
+    let mut sel = String::new();
+    let mut num_args = 0;
+
+    match &*tokens {
+        [] => return Err(Error::new(selector.span(), "expected a selector")),
+        // Selectors without arguments
+        [TokenTree::Ident(ident)] => {
+            write!(sel, "{ident}").unwrap();
+            return Ok((sel, 0));
+        }
+        _ => {}
+    }
+
+    let mut previous_was_ident = false;
+    for token in &tokens {
+        match token {
+            TokenTree::Ident(ident) if !previous_was_ident => {
+                write!(sel, "{ident}").unwrap();
+                previous_was_ident = true;
+            }
+            TokenTree::Punct(punct) if punct.as_char() == ':' => {
+                sel.push(':');
+                num_args += 1;
+                previous_was_ident = false;
+            }
+            token => {
+                return Err(Error::new(
+                    token.span(),
+                    "invalid selector, expected a selector like `foo` or `foo:bar:`",
+                ))
+            }
+        }
+    }
+    if previous_was_ident {
+        return Err(Error::new(
+            tokens.last().unwrap().span(),
+            "invalid selector, a selector with arguments must end with `:`",
+        ));
+    }
+
+    Ok((sel, num_args))
+}
@@ -9,6 +9,8 @@
 // Update in Cargo.toml as well.
 #![doc(html_root_url = "https://docs.rs/objc2-proc-macros/0.1.3")]
 
+mod declared_class;
+
 use core::hash::{Hash, Hasher};
 
 use proc_macro::Ident;
@@ -64,3 +66,23 @@ pub fn __hash_idents(input: TokenStream) -> TokenStream {
     let s = format!("{:016x}", hasher.finish());
     TokenTree::Literal(Literal::string(&s)).into()
 }
+
+/// Declare a new Objective-C class.
+///
+/// See [`objc2::declared_class`] for details.
+///
+/// [`objc2::declared_class`]: https://docs.rs/objc2/latest/objc2/attr.declared_class.html
+#[proc_macro_attribute]
+pub fn declared_class(attr: TokenStream, item: TokenStream) -> TokenStream {
+    declared_class::declared_class(attr, item)
+}
+
+/// Add methods to a class declared with `#[declared_class]`.
+///
+/// See [`objc2::methods`] for details.
+///
+/// [`objc2::methods`]: https://docs.rs/objc2/latest/objc2/attr.methods.html
+#[proc_macro_attribute]
+pub fn methods(attr: TokenStream, item: TokenStream) -> TokenStream {
+    declared_class::methods(attr, item)
+}
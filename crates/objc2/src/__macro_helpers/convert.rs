@@ -1,4 +1,4 @@
-use crate::encode::{EncodeArgument, EncodeArguments, EncodeReturn};
+use crate::encode::{EncodeArgument, EncodeArguments, EncodeReturn, VarArgs, VariadicArguments};
 use crate::rc::Retained;
 use crate::runtime::Bool;
 use crate::Message;
@@ -283,6 +283,39 @@ args_impl!(
     p: P
 );
 
+/// Variadic arguments are passed as-is, and are put after the (possibly
+/// converted) normal arguments.
+macro_rules! variadic_args_impl {
+    ($($a:ident: $t:ident),*) => (
+        impl<$($t: ConvertArgument,)* V: VariadicArguments> ConvertArguments for ($($t,)* VarArgs<V>,) {
+            type __Inner = ($($t::__Inner,)* VarArgs<V>,);
+
+            type __StoredBeforeMessage = ($($t::__StoredBeforeMessage,)*);
+
+            #[inline]
+            fn __into_arguments(self) -> (Self::__Inner, Self::__StoredBeforeMessage) {
+                let ($($a,)* varargs,) = self;
+                $(let $a = ConvertArgument::__into_argument($a);)*
+
+                (($($a.0,)* varargs,), ($($a.1,)*))
+            }
+
+            #[inline]
+            unsafe fn __process_after_message_send(($($a,)*): Self::__StoredBeforeMessage) {
+                $(
+                    unsafe { <$t as ConvertArgument>::__process_after_message_send($a) };
+                )*
+            }
+        }
+    );
+}
+
+variadic_args_impl!();
+variadic_args_impl!(a: A);
+variadic_args_impl!(a: A, b: B);
+variadic_args_impl!(a: A, b: B, c: C);
+variadic_args_impl!(a: A, b: B, c: C, d: D);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,5 +1,4 @@
 use alloc::ffi::CString;
-#[cfg(debug_assertions)]
 use alloc::vec::Vec;
 use core::marker::PhantomData;
 #[cfg(debug_assertions)]
@@ -7,14 +6,19 @@ use std::collections::HashSet;
 
 use crate::encode::{Encode, Encoding};
 use crate::rc::{Allocated, Retained};
+#[cfg(debug_assertions)]
+use crate::runtime::MethodDescription;
 use crate::runtime::{
-    AnyClass, AnyObject, ClassBuilder, MessageReceiver, MethodImplementation, Sel,
+    AnyClass, AnyObject, AnyProtocol, Category, CategoryBuilder, ClassBuilder, ClassConflict,
+    MessageReceiver, MethodConflict, MethodImplementation, ProtocolBuilder, Sel,
 };
-#[cfg(debug_assertions)]
-use crate::runtime::{AnyProtocol, MethodDescription};
-use crate::{ClassType, DeclaredClass, Message, ProtocolType};
+use crate::{sel, ClassType, DeclaredClass, Message, ProtocolType};
 
-use super::declared_ivars::{register_with_ivars, setup_dealloc};
+use super::declared_ivars::{existing_with_ivars, register_with_ivars, setup_dealloc};
+#[cfg(any(doc, not(feature = "unstable-objfw")))]
+use super::property::{
+    default_setter, property_attributes, property_getter, property_setter, DeclaredProperty,
+};
 use super::{CopyOrMutCopy, Init, MaybeUnwrap, New, Other};
 
 /// Helper type for implementing `MethodImplementation` with a receiver of
@@ -152,6 +156,39 @@ fn create_builder(name: &str, superclass: &AnyClass) -> ClassBuilder {
     }
 }
 
+// Outlined for code size
+#[track_caller]
+pub fn create_protocol_builder(name: &str) -> ProtocolBuilder {
+    let c_name = CString::new(name).expect("protocol name must be UTF-8");
+    match ProtocolBuilder::new(&c_name) {
+        Some(builder) => builder,
+        None => panic!(
+            "could not create new protocol {name}. Perhaps a protocol with that name already exists?"
+        ),
+    }
+}
+
+/// Find an already registered class to use instead of declaring `T`, if the
+/// conflict policy allows it.
+#[track_caller]
+pub fn reuse_existing_class<T: DeclaredClass>(conflict: ClassConflict) -> Option<RegisteredOffsets>
+where
+    T::Super: ClassType,
+{
+    match conflict {
+        ClassConflict::Error => None,
+        ClassConflict::Reuse => {
+            let c_name = CString::new(T::__name()).expect("class name must be UTF-8");
+            AnyClass::get(&c_name).map(existing_with_ivars::<T>)
+        }
+    }
+}
+
+#[track_caller]
+pub fn inherited_protocol<P: ?Sized + ProtocolType>() -> &'static AnyProtocol {
+    P::protocol().unwrap_or_else(|| panic!("could not find inherited protocol {}", P::NAME))
+}
+
 impl<T: DeclaredClass> ClassBuilderHelper<T> {
     #[inline]
     #[track_caller]
@@ -160,7 +197,7 @@ impl<T: DeclaredClass> ClassBuilderHelper<T> {
     where
         T::Super: ClassType,
     {
-        let mut builder = create_builder(T::NAME, <T::Super as ClassType>::class());
+        let mut builder = create_builder(T::__name(), <T::Super as ClassType>::class());
 
         setup_dealloc::<T>(&mut builder);
 
@@ -230,12 +267,190 @@ impl<T: DeclaredClass> ClassBuilderHelper<T> {
         unsafe { self.builder.add_class_method(sel, func) }
     }
 
+    /// Add `+initialize`, which calls [`DeclaredClass::initialize`].
     #[inline]
-    pub fn register(self) -> (&'static AnyClass, isize, isize) {
+    fn add_initialize(&mut self) {
+        extern "C-unwind" fn initialize<T: DeclaredClass>(cls: &AnyClass, _cmd: Sel) {
+            // `+initialize` is also sent to subclasses that don't implement
+            // it themselves, so check that this is actually our class.
+            //
+            // Compare names instead of calling `T::class()`, since this may
+            // be run while that is still registering the class.
+            if cls.name().to_bytes() == T::__name().as_bytes() {
+                // SAFETY: Classes are never deallocated.
+                let cls: &'static AnyClass = unsafe { &*(cls as *const AnyClass) };
+                T::initialize(cls);
+            }
+        }
+
+        // SAFETY: The signature is correct.
+        unsafe {
+            self.builder.add_class_method(
+                sel!(initialize),
+                initialize::<T> as extern "C-unwind" fn(_, _),
+            );
+        }
+    }
+
+    #[cfg(any(doc, not(feature = "unstable-objfw")))]
+    pub fn add_property<P>(
+        &mut self,
+        name: &str,
+        getter: Option<Sel>,
+        setter: Option<Sel>,
+        readonly: bool,
+    ) -> (Sel, Option<Sel>)
+    where
+        P: DeclaredProperty<Class = T>,
+    {
+        let attributes = property_attributes::<P>(getter, setter, readonly);
+        let attributes: Vec<_> = attributes
+            .iter()
+            .map(|(name, value)| (&**name, &**value))
+            .collect();
+        let c_name = CString::new(name).expect("property name must not contain NUL bytes");
+        let success = self.builder.add_property(&c_name, &attributes);
+        assert!(success, "failed adding property {name}");
+
+        let getter = getter.unwrap_or_else(|| Sel::register(&c_name));
+        // SAFETY: The getter has the correct signature.
+        unsafe {
+            self.builder.add_method(
+                getter,
+                property_getter::<P> as extern "C-unwind" fn(_, _) -> _,
+            );
+        }
+
+        let setter = if readonly {
+            None
+        } else {
+            let setter = setter.unwrap_or_else(|| default_setter(name));
+            // SAFETY: The setter has the correct signature.
+            unsafe {
+                self.builder.add_method(
+                    setter,
+                    property_setter::<P> as unsafe extern "C-unwind" fn(_, _, _),
+                );
+            }
+            Some(setter)
+        };
+
+        (getter, setter)
+    }
+
+    #[inline]
+    pub fn register(mut self) -> RegisteredOffsets {
+        // Only add `+initialize` if the class overrides the hook.
+        if T::__HAS_INITIALIZE {
+            self.add_initialize();
+        }
+
         register_with_ivars::<T>(self.builder)
     }
 }
 
+/// A registered class, and the offsets of its ivars and drop flag.
+pub type RegisteredOffsets = (&'static AnyClass, isize, isize);
+
+/// The storage of the class and the ivar and drop flag offsets of a declared
+/// class.
+///
+/// This is implemented by `__declare_class_storage!`, and is used by the
+/// `ClassType` and `DeclaredClass` implementations, which may be generated
+/// separately (e.g. by `#[methods]`).
+///
+/// # Safety
+///
+/// The offsets must be implemented correctly, see
+/// `DeclaredClass::__UNSAFE_OFFSETS_CORRECT`.
+pub unsafe trait DeclaredClassOffsets {
+    /// Get the class, registering it with `register` if it hasn't been
+    /// already.
+    ///
+    /// `register` also returns whether the class was newly declared, in
+    /// which case `DeclaredClass::load` is called.
+    fn __class(register: impl FnOnce() -> (RegisteredOffsets, bool)) -> &'static AnyClass;
+
+    /// The name of the class in the runtime, see `DeclaredClass::__name`.
+    fn __name() -> &'static str;
+
+    fn __ivars_offset() -> isize;
+
+    fn __drop_flag_offset() -> isize;
+}
+
+/// Implemented by `#[declared_class]` for each protocol in its `protocols`
+/// list.
+pub trait DeclaredProtocolConformance<P: ?Sized> {}
+
+/// Implemented by `#[methods]` on `unsafe impl Protocol for T` blocks.
+///
+/// Requires that the protocol is also listed in `#[declared_class]`, since
+/// that is what actually registers the methods.
+pub trait DeclaredProtocolMethods<P: ?Sized>:
+    DeclaredClass + DeclaredProtocolConformance<P>
+{
+    fn __objc2_register(builder: &mut ClassBuilderHelper<Self>);
+}
+
+/// Fallback for classes declared with `#[declared_class]` that don't have
+/// an inherent `#[methods]` block.
+///
+/// `#[methods]` on an inherent `unsafe impl T` block generates an inherent
+/// associated function with the same name, which takes precedence over this
+/// one. A second inherent block thus results in an error about a duplicate
+/// definition, whose name explains the problem.
+pub trait RegisterNoInherentMethods: DeclaredClass {
+    #[inline]
+    fn __objc2_only_one_inherent_methods_block_is_allowed(builder: &mut ClassBuilderHelper<Self>) {
+        let _ = builder;
+    }
+}
+
+impl<T: DeclaredClass> RegisterNoInherentMethods for T {}
+
+/// Helper for `declare_category!`, restricting the callee of the methods to
+/// the class that the category is declared on.
+#[derive(Debug)]
+pub struct CategoryBuilderHelper<T: ?Sized> {
+    builder: CategoryBuilder,
+    p: PhantomData<T>,
+}
+
+impl<T: ClassType> CategoryBuilderHelper<T> {
+    #[inline]
+    pub fn new(conflict: MethodConflict) -> Self {
+        Self {
+            builder: CategoryBuilder::new(T::class(), conflict),
+            p: PhantomData,
+        }
+    }
+
+    // Addition: This restricts to callee `T`
+    #[inline]
+    pub unsafe fn add_method<F>(&mut self, sel: Sel, func: F)
+    where
+        F: MethodImplementation<Callee = T>,
+    {
+        // SAFETY: Checked by caller
+        unsafe { self.builder.add_method(sel, func) };
+    }
+
+    #[inline]
+    pub unsafe fn add_class_method<F>(&mut self, sel: Sel, func: F)
+    where
+        F: MethodImplementation<Callee = AnyClass>,
+    {
+        // SAFETY: Checked by caller
+        unsafe { self.builder.add_class_method(sel, func) };
+    }
+
+    #[inline]
+    pub fn register(self) -> Category {
+        self.builder.register()
+    }
+}
+
 /// Helper for ensuring that:
 /// - Only methods on the protocol are overridden.
 /// - TODO: The methods have the correct signature.
@@ -319,6 +534,29 @@ impl<T: DeclaredClass> ClassProtocolMethodsBuilder<'_, T> {
         }
     }
 
+    #[cfg(any(doc, not(feature = "unstable-objfw")))]
+    pub fn add_property<P>(
+        &mut self,
+        name: &str,
+        getter: Option<Sel>,
+        setter: Option<Sel>,
+        readonly: bool,
+    ) where
+        P: DeclaredProperty<Class = T>,
+    {
+        let (_getter, _setter) = self
+            .builder
+            .add_property::<P>(name, getter, setter, readonly);
+
+        // Properties may implement protocol methods.
+        #[cfg(debug_assertions)]
+        for sel in core::iter::once(_getter).chain(_setter) {
+            if !self.registered_instance_methods.insert(sel) {
+                unreachable!("already added")
+            }
+        }
+    }
+
     #[cfg(debug_assertions)]
     pub fn finish(self) {
         let superclass = self.builder.builder.superclass();
@@ -46,13 +46,21 @@
 //! [unsound-read-padding]: https://play.rust-lang.org/?version=stable&mode=debug&edition=2021&gist=ea068e8d9e55801aa9520ea914eb2822
 
 use alloc::borrow::Cow;
+use alloc::boxed::Box;
 use alloc::ffi::CString;
 use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use core::any::type_name;
 use core::ffi::CStr;
+use core::marker::PhantomData;
 use core::mem;
 use core::ptr::{self, NonNull};
 
-use crate::encode::{Encode, Encoding};
+use super::RegisteredOffsets;
+use crate::debug::{register_opaque_ivar, visit_ivar, OpaqueIvar, StrongReferences};
+use crate::encode::{Encode, Encoding, EncodingBox};
+use crate::rc::record_declared_class;
 use crate::runtime::{AnyClass, AnyObject, ClassBuilder, MessageReceiver, Sel};
 use crate::{sel, ClassType, DeclaredClass};
 
@@ -69,11 +77,11 @@ pub(crate) enum DropFlag {
     ///
     /// [obj-init-zeroed]: https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/ProgrammingWithObjectiveC/WorkingwithObjects/WorkingwithObjects.html#//apple_ref/doc/uid/TP40011210-CH4-SW7
     /// [objc4-208-init]: https://github.com/apple-oss-distributions/objc4/blob/objc4-208/runtime/objc-class.m#L367
-    #[allow(dead_code)]
     Allocated = 0x00,
     /// Used when `mem::needs_drop::<T::Ivars>()`, or with debug assertions enabled.
     InitializedIvars = 0x0f,
-    /// Used when `mem::needs_drop::<T>()`, or with debug assertions enabled.
+    /// Used when `mem::needs_drop::<T>()`, when the class has a `dealloc`
+    /// hook, or with debug assertions enabled.
     Finalized = 0xff,
 }
 
@@ -87,22 +95,26 @@ pub trait DeclaredIvarsHelper {
     const HAS_DROP_FLAG: bool;
 }
 
+/// Whether we need the runtime to allocate memory for an ivar of type `T`.
+///
+/// We can avoid doing so if the type is a zero-sized type (ZST), and the
+/// required alignment is less than the alignment of a pointer (objects are
+/// guaranteed to have at least that alignment themselves).
+pub(crate) const fn needs_storage<T>() -> bool {
+    mem::size_of::<T>() > 0 || mem::align_of::<T>() > mem::align_of::<*mut AnyObject>()
+}
+
 impl<T: DeclaredClass> DeclaredIvarsHelper for T {
     /// Only add ivar if we need the runtime to allocate memory for it.
-    ///
-    /// We can avoid doing so if the type is a zero-sized type (ZST), and the
-    /// required alignment is less than the alignment of a pointer (objects
-    /// are guaranteed to have at least that alignment themselves).
-    const HAS_IVARS: bool = {
-        mem::size_of::<T::Ivars>() > 0
-            || mem::align_of::<T::Ivars>() > mem::align_of::<*mut AnyObject>()
-    };
-    /// Only add drop flag if the type or the ivars need it.
+    const HAS_IVARS: bool = needs_storage::<T::Ivars>();
+    /// Only add drop flag if the type or the ivars need it, or if the class
+    /// has a `dealloc` hook.
     ///
     /// `needs_drop::<T>` can reliably detect a direct implementation of
     /// `Drop`, since the type only includes `ManuallyDrop` or `PhantomData`
     /// fields.
-    const HAS_DROP_FLAG: bool = mem::needs_drop::<T>() || mem::needs_drop::<T::Ivars>();
+    const HAS_DROP_FLAG: bool =
+        mem::needs_drop::<T>() || mem::needs_drop::<T::Ivars>() || T::__HAS_DEALLOC;
 }
 
 /// Helper function for getting a pointer to the instance variable.
@@ -141,8 +153,9 @@ pub(crate) fn setup_dealloc<T: DeclaredClass>(builder: &mut ClassBuilder)
 where
     T::Super: ClassType,
 {
-    // Add dealloc if the class or the ivars need dropping.
-    if mem::needs_drop::<T>() || mem::needs_drop::<T::Ivars>() {
+    // Add dealloc if the class or the ivars need dropping, or if the class
+    // has a `dealloc` hook.
+    if T::HAS_DROP_FLAG {
         let func: unsafe extern "C-unwind" fn(_, _) = dealloc::<T>;
         // SAFETY: The function signature is correct, and method contract is
         // upheld inside `dealloc`.
@@ -167,10 +180,26 @@ where
     #[cold]
     fn cold_path() {}
 
-    // SAFETY: `dealloc` is only registered when there is a need for dropping,
-    // and hence a need for a drop flag.
+    // SAFETY: `dealloc` is only registered when there is a need for dropping
+    // or a `dealloc` hook, and hence a need for a drop flag.
     let drop_flag = unsafe { *ptr_to_drop_flag(this) };
 
+    // Run the hook first, while the ivars and the superclass are still
+    // intact.
+    if T::__HAS_DEALLOC {
+        match drop_flag {
+            // Only run the hook on fully initialized instances, same as
+            // `Drop` below.
+            DropFlag::Allocated | DropFlag::InitializedIvars => cold_path(),
+            // SAFETY: The instance is fully initialized, and valid for the
+            // duration of this method.
+            //
+            // TODO: This can unwind, is it correct to just let that
+            // propagate?
+            DropFlag::Finalized => <T as DeclaredClass>::dealloc(unsafe { this.as_ref() }),
+        }
+    }
+
     if mem::needs_drop::<T>() {
         match drop_flag {
             // Don't deallocate the current instance if it has not been fully
@@ -236,19 +265,18 @@ where
     }
 }
 
-/// Register the class, and get the ivar offsets.
-#[inline]
-pub(crate) fn register_with_ivars<T: DeclaredClass>(
-    mut builder: ClassBuilder,
-) -> (&'static AnyClass, isize, isize) {
-    let (ivar_name, drop_flag_name): (Cow<'static, CStr>, Cow<'static, CStr>) = {
+/// The names of the ivar and drop flag on the class.
+fn ivar_names<T: DeclaredClass>() -> (Cow<'static, CStr>, Cow<'static, CStr>) {
+    {
         if cfg!(feature = "gnustep-1-7") {
             // GNUStep does not support a subclass having an ivar with the
             // same name as a superclass, so let's use the class name as the
             // ivar name to ensure uniqueness.
             (
-                CString::new(format!("{}_ivars", T::NAME)).unwrap().into(),
-                CString::new(format!("{}_drop_flag", T::NAME))
+                CString::new(format!("{}_ivars", T::__name()))
+                    .unwrap()
+                    .into(),
+                CString::new(format!("{}_drop_flag", T::__name()))
                     .unwrap()
                     .into(),
             )
@@ -263,21 +291,47 @@ pub(crate) fn register_with_ivars<T: DeclaredClass>(
                 )
             }
         }
+    }
+}
+
+/// The encoding of an ivar that stores the Rust type `T`.
+///
+/// The runtime only needs the size and alignment, but the name of the type
+/// is included too, so that we can check that a class that was registered
+/// elsewhere stores the same type, see [`existing_with_ivars`].
+pub(crate) fn ivar_encoding<T>() -> EncodingBox {
+    let ty = match mem::align_of::<T>() {
+        1 => EncodingBox::UChar,
+        2 => EncodingBox::UShort,
+        4 => EncodingBox::UInt,
+        // The alignment of `u64` may not be 8 on all architectures
+        8 if mem::align_of::<u64>() == 8 => EncodingBox::ULongLong,
+        alignment => panic!(
+            "unsupported alignment {alignment} for `{}`",
+            type_name::<T>()
+        ),
     };
+    // Struct names in encodings may only contain identifier characters.
+    let name = type_name::<T>()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    // TODO: Consider not adding a encoding - Swift doesn't do it.
+    EncodingBox::Struct(
+        name,
+        vec![EncodingBox::Array(mem::size_of::<T>() as u64, Box::new(ty))],
+    )
+}
+
+/// Register the class, and get the ivar offsets.
+#[inline]
+pub(crate) fn register_with_ivars<T: DeclaredClass>(
+    mut builder: ClassBuilder,
+) -> (&'static AnyClass, isize, isize) {
+    let (ivar_name, drop_flag_name) = ivar_names::<T>();
 
     if T::HAS_IVARS {
-        // TODO: Consider not adding a encoding - Swift doesn't do it.
-        let ivar_encoding = Encoding::Array(
-            mem::size_of::<T::Ivars>() as u64,
-            match mem::align_of::<T::Ivars>() {
-                1 => &u8::ENCODING,
-                2 => &u16::ENCODING,
-                4 => &u32::ENCODING,
-                // The alignment of `u64` may not be 8 on all architectures
-                8 if mem::align_of::<u64>() == 8 => &u64::ENCODING,
-                alignment => panic!("unsupported alignment {alignment} for `{}::Ivars`", T::NAME),
-            },
-        );
+        let ivar_encoding = ivar_encoding::<T::Ivars>().to_string();
         unsafe { builder.add_ivar_inner::<T::Ivars>(&ivar_name, &ivar_encoding) };
     }
 
@@ -325,6 +379,124 @@ pub(crate) fn register_with_ivars<T: DeclaredClass>(
         0
     };
 
+    record_declared_class(cls);
+
+    (cls, ivars_offset, drop_flag_offset)
+}
+
+/// Helper for making `find_retain_cycles` follow the strong references in
+/// the ivars of a declared class, using autoref-based specialization.
+///
+/// `(&IvarsReferences::<T>::new()).register_references(registered)` records
+/// the ivars of the newly registered class if `T::Ivars` implements
+/// [`StrongReferences`], and does nothing otherwise.
+#[derive(Debug)]
+pub struct IvarsReferences<T: ?Sized>(PhantomData<T>);
+
+impl<T: ?Sized> IvarsReferences<T> {
+    #[inline]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+pub trait IvarsKnownReferences {
+    fn register_references(&self, registered: RegisteredOffsets);
+}
+
+impl<T: DeclaredClass> IvarsKnownReferences for IvarsReferences<T>
+where
+    T::Ivars: StrongReferences,
+{
+    fn register_references(&self, (cls, ivars_offset, drop_flag_offset): RegisteredOffsets) {
+        // The drop flag is needed to know whether the ivars are initialized.
+        if T::HAS_IVARS && T::HAS_DROP_FLAG {
+            let (ivar_name, _) = ivar_names::<T>();
+            register_opaque_ivar(
+                cls,
+                OpaqueIvar {
+                    name: ivar_name.to_string_lossy().into_owned(),
+                    data_offset: ivars_offset,
+                    flag_offset: drop_flag_offset,
+                    is_initialized: |flag| flag != DropFlag::Allocated as u8,
+                    visit: visit_ivar::<T::Ivars>,
+                },
+            );
+        }
+    }
+}
+
+pub trait IvarsUnknownReferences {
+    #[inline]
+    fn register_references(&self, _registered: RegisteredOffsets) {}
+}
+
+impl<T: ?Sized> IvarsUnknownReferences for &IvarsReferences<T> {}
+
+/// Get the ivar offsets of a class with the same name as `T` that was
+/// registered elsewhere, e.g. by another copy of the same library.
+///
+/// Panics if the class is not compatible with `T`.
+#[track_caller]
+pub(crate) fn existing_with_ivars<T: DeclaredClass>(
+    cls: &'static AnyClass,
+) -> (&'static AnyClass, isize, isize)
+where
+    T::Super: ClassType,
+{
+    #[track_caller]
+    fn incompatible(name: &str, reason: &str) -> ! {
+        panic!("cannot reuse existing class {name}: {reason}")
+    }
+
+    if cls.superclass() != Some(<T::Super as ClassType>::class()) {
+        incompatible(T::__name(), "it has a different superclass");
+    }
+
+    let (ivar_name, drop_flag_name) = ivar_names::<T>();
+
+    // Only look at the class' own ivars; `instance_variable` would also find
+    // ivars with the same name on the superclasses.
+    let mut ivars_offset = None;
+    let mut drop_flag_offset = None;
+    for ivar in cls.instance_variables().iter() {
+        let encoding = ivar.type_encoding().to_bytes();
+        if T::HAS_IVARS && ivar.name() == &*ivar_name {
+            if encoding != ivar_encoding::<T::Ivars>().to_string().as_bytes() {
+                incompatible(T::__name(), "its ivars have a different type");
+            }
+            ivars_offset = Some(ivar.offset());
+        } else if T::HAS_DROP_FLAG && ivar.name() == &*drop_flag_name {
+            if encoding != DropFlag::ENCODING.to_string().as_bytes() {
+                incompatible(T::__name(), "its drop flag has a different type");
+            }
+            drop_flag_offset = Some(ivar.offset());
+        } else {
+            // Also catches the case where the existing class has a drop flag
+            // but `T` doesn't, which means that they disagree about whether
+            // the ivars need to be dropped.
+            incompatible(
+                T::__name(),
+                &format!("it has an unexpected instance variable {:?}", ivar.name()),
+            );
+        }
+    }
+
+    let ivars_offset = if T::HAS_IVARS {
+        ivars_offset.unwrap_or_else(|| incompatible(T::__name(), "it does not have the ivars"))
+    } else {
+        0
+    };
+    let drop_flag_offset = if T::HAS_DROP_FLAG {
+        drop_flag_offset
+            .unwrap_or_else(|| incompatible(T::__name(), "it does not have a drop flag"))
+    } else {
+        0
+    };
+
+    record_declared_class(cls);
+
     (cls, ivars_offset, drop_flag_offset)
 }
 
@@ -391,7 +563,7 @@ pub(crate) unsafe fn set_finalized<T: DeclaredClass>(ptr: NonNull<T>) {
     }
 
     // Write to drop flag that we've fully initialized the class.
-    if T::HAS_DROP_FLAG && (mem::needs_drop::<T>() || cfg!(debug_assertions)) {
+    if T::HAS_DROP_FLAG && (mem::needs_drop::<T>() || T::__HAS_DEALLOC || cfg!(debug_assertions)) {
         // SAFETY: Just checked that the drop flag is available.
         unsafe { ptr_to_drop_flag(ptr).write(DropFlag::Finalized) }
     }
@@ -619,6 +791,118 @@ mod tests {
         ]);
     }
 
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_dealloc_hook() {
+        use std::sync::Mutex;
+
+        #[derive(Debug, PartialEq)]
+        enum Operation {
+            DeallocHook(u32),
+            DropIvar,
+            DropClass,
+        }
+
+        static OPERATIONS: Mutex<Vec<Operation>> = Mutex::new(Vec::new());
+
+        struct IvarThatImplsDrop(u32);
+
+        impl Default for IvarThatImplsDrop {
+            fn default() -> Self {
+                Self(42)
+            }
+        }
+
+        impl Drop for IvarThatImplsDrop {
+            fn drop(&mut self) {
+                OPERATIONS.lock().unwrap().push(Operation::DropIvar);
+            }
+        }
+
+        #[track_caller]
+        fn check<const N: usize>(expected: [Operation; N]) {
+            let mut operations = OPERATIONS.lock().unwrap();
+            assert_eq!(&**operations, expected);
+            operations.clear();
+        }
+
+        declare_class!(
+            struct DeallocHook;
+
+            unsafe impl ClassType for DeallocHook {
+                type Super = NSObject;
+                const NAME: &'static str = "DeallocHook";
+            }
+
+            impl DeclaredClass for DeallocHook {
+                type Ivars = IvarThatImplsDrop;
+
+                fn dealloc(&self) {
+                    // The ivars are still available, and the object can be
+                    // messaged.
+                    let _: usize = unsafe { msg_send![self, hash] };
+                    OPERATIONS
+                        .lock()
+                        .unwrap()
+                        .push(Operation::DeallocHook(self.ivars().0));
+                }
+            }
+
+            unsafe impl DeallocHook {
+                #[method_id(init)]
+                fn init(this: Allocated<Self>) -> Option<Retained<Self>> {
+                    unsafe { msg_send_id![super(this.set_ivars(IvarThatImplsDrop(7))), init] }
+                }
+            }
+        );
+
+        impl Drop for DeallocHook {
+            fn drop(&mut self) {
+                OPERATIONS.lock().unwrap().push(Operation::DropClass);
+            }
+        }
+
+        let _ = DeallocHook::alloc();
+        check([]);
+
+        let _ = unsafe { init_no_finalize(DeallocHook::alloc()) };
+        check([Operation::DropIvar]);
+
+        let _ = unsafe { init(DeallocHook::alloc()) };
+        check([
+            Operation::DeallocHook(7),
+            Operation::DropClass,
+            Operation::DropIvar,
+        ]);
+
+        // The hook alone is enough to generate `dealloc`.
+        declare_class!(
+            struct DeallocHookOnly;
+
+            unsafe impl ClassType for DeallocHookOnly {
+                type Super = NSObject;
+                const NAME: &'static str = "DeallocHookOnly";
+            }
+
+            impl DeclaredClass for DeallocHookOnly {
+                fn dealloc(&self) {
+                    OPERATIONS.lock().unwrap().push(Operation::DeallocHook(0));
+                }
+            }
+
+            unsafe impl DeallocHookOnly {
+                #[method_id(init)]
+                fn init(this: Allocated<Self>) -> Option<Retained<Self>> {
+                    unsafe { msg_send_id![super(this.set_ivars(())), init] }
+                }
+            }
+        );
+
+        assert!(!mem::needs_drop::<DeallocHookOnly>());
+        let _ = unsafe { init(DeallocHookOnly::alloc()) };
+        check([Operation::DeallocHook(0)]);
+    }
+
     #[test]
     fn test_no_generated_dealloc_if_not_needed() {
         #[allow(unused)]
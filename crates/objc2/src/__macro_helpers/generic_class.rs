@@ -0,0 +1,87 @@
+//! Storage for generic declared classes.
+//!
+//! Statics inside generic functions are shared between all instantiations of
+//! the function, so we can't use them to store the class and ivar offsets
+//! like `declare_class!` normally does. Instead, each instantiation of a
+//! generic class gets an entry in a global map keyed by its `TypeId`.
+use alloc::boxed::Box;
+use alloc::format;
+use core::any::TypeId;
+use core::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use crate::runtime::AnyClass;
+
+#[derive(Debug)]
+pub struct GenericClassData {
+    name: &'static str,
+    registered: OnceLock<(&'static AnyClass, isize, isize)>,
+}
+
+impl GenericClassData {
+    /// The name of this instantiation of the class in the runtime.
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the class and the ivar offsets, registering the class with the
+    /// given closure if it hasn't been already.
+    #[inline]
+    pub fn get_or_register(
+        &self,
+        register: impl FnOnce() -> (&'static AnyClass, isize, isize),
+    ) -> (&'static AnyClass, isize, isize) {
+        *self.registered.get_or_init(register)
+    }
+
+    /// Get the class and the ivar offsets of an already registered class.
+    #[inline]
+    #[track_caller]
+    pub fn registered(&self) -> (&'static AnyClass, isize, isize) {
+        // Monomorphized error handling
+        fn not_registered(name: &str) -> ! {
+            unreachable!("generic class {name} was not registered")
+        }
+
+        *self
+            .registered
+            .get()
+            .unwrap_or_else(|| not_registered(self.name))
+    }
+}
+
+static GENERIC_CLASSES: OnceLock<RwLock<HashMap<TypeId, &'static GenericClassData>>> =
+    OnceLock::new();
+
+/// Get the data for `T`, which is an instantiation of a generic class.
+///
+/// The runtime name of the class is `name`, followed by the names of the
+/// generic parameters in angle brackets, and a hash of the `TypeId` of `T`.
+/// The hash is needed since `type_name` is not guaranteed to be unique, e.g.
+/// lifetimes are erased, and two versions of the same crate give the same
+/// names.
+pub fn generic_class_data<T: ?Sized + 'static>(
+    name: &str,
+    params: &[&str],
+) -> &'static GenericClassData {
+    let classes = GENERIC_CLASSES.get_or_init(Default::default);
+    let id = TypeId::of::<T>();
+
+    if let Some(data) = classes.read().unwrap().get(&id) {
+        return data;
+    }
+
+    classes.write().unwrap().entry(id).or_insert_with(|| {
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        let name = format!("{name}<{}>#{:016x}", params.join(", "), hasher.finish());
+        // The data lives as long as the class, i.e. forever.
+        Box::leak(Box::new(GenericClassData {
+            name: Box::leak(name.into_boxed_str()),
+            registered: OnceLock::new(),
+        }))
+    })
+}
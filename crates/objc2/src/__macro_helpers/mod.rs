@@ -1,15 +1,16 @@
+pub use core::any::type_name;
 pub use core::borrow::Borrow;
 pub use core::cell::UnsafeCell;
 pub use core::convert::AsRef;
 pub use core::default::Default;
 pub use core::marker::{PhantomData, Sized};
 pub use core::mem::{size_of, ManuallyDrop, MaybeUninit};
-pub use core::ops::Deref;
+pub use core::ops::{Deref, FnOnce};
 pub use core::option::Option::{self, None, Some};
 pub use core::primitive::{bool, isize, str, u8};
-pub use core::{compile_error, concat, panic, stringify};
+pub use core::{assert, compile_error, concat, env, module_path, panic, stringify};
 // TODO: Use `core::cell::LazyCell`
-pub use std::sync::Once;
+pub use std::sync::{Once, OnceLock};
 
 mod cache;
 mod class;
@@ -17,12 +18,15 @@ mod common_selectors;
 mod convert;
 mod declare_class;
 pub(crate) mod declared_ivars;
+mod generic_class;
 mod image_info;
 mod method_family;
 mod module_info;
 mod msg_send;
 mod msg_send_retained;
 mod os_version;
+#[cfg(any(doc, not(feature = "unstable-objfw")))]
+mod property;
 mod sync_unsafe_cell;
 mod writeback;
 
@@ -31,9 +35,15 @@ pub use self::class::{MainThreadOnlyDoesNotImplSendSync, ValidThreadKind};
 pub use self::common_selectors::{alloc_sel, dealloc_sel, init_sel, new_sel};
 pub use self::convert::{ConvertArgument, ConvertArguments, ConvertReturn, TupleExtender};
 pub use self::declare_class::{
-    ClassBuilderHelper, ClassProtocolMethodsBuilder, IdReturnValue, MaybeOptionId, MessageRecieveId,
+    create_protocol_builder, inherited_protocol, reuse_existing_class, CategoryBuilderHelper,
+    ClassBuilderHelper, ClassProtocolMethodsBuilder, DeclaredClassOffsets,
+    DeclaredProtocolConformance, DeclaredProtocolMethods, IdReturnValue, MaybeOptionId,
+    MessageRecieveId, RegisterNoInherentMethods, RegisteredOffsets,
 };
-pub use self::declared_ivars::DeclaredIvarsHelper;
+pub use self::declared_ivars::{
+    DeclaredIvarsHelper, IvarsKnownReferences, IvarsReferences, IvarsUnknownReferences,
+};
+pub use self::generic_class::{generic_class_data, GenericClassData};
 pub use self::image_info::ImageInfo;
 pub use self::method_family::{
     retain_semantics, Alloc, CopyOrMutCopy, Init, New, Other, RetainSemantics,
@@ -42,6 +52,12 @@ pub use self::module_info::ModuleInfo;
 pub use self::msg_send::MsgSend;
 pub use self::msg_send_retained::{MaybeUnwrap, MsgSendId, MsgSendSuperId};
 pub use self::os_version::{is_available, AvailableVersion, OSVersion};
+#[cfg(any(doc, not(feature = "unstable-objfw")))]
+pub use self::property::{
+    DeclaredProperty, ObjectStorage, PropertyAssign, PropertyClassName, PropertyCopy,
+    PropertyDefault, PropertyKnownClassName, PropertyStorage, PropertyStrong,
+    PropertyUnknownClassName, PropertyWeak,
+};
 pub use self::sync_unsafe_cell::SyncUnsafeCell;
 
 /// Disallow using this passed in value in const and statics for forwards
@@ -51,6 +67,11 @@ pub fn disallow_in_static<T>(item: &'static T) -> &'static T {
     item
 }
 
+/// Intentionally empty, used to point an error at a duplicate
+/// `#[method_family = ...]` attribute, since `compile_error!` can only point
+/// at the whole macro invocation.
+pub mod method_family_specified_more_than_once {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -166,6 +166,11 @@ impl<T: MsgSend, U: ?Sized + Message> MsgSendId<T, Option<Retained<U>>> for New
         let ptr = obj.into_raw_receiver();
         // SAFETY: Checked by caller
         let obj = unsafe { MsgSend::send_message(ptr, sel, args) };
+        #[cfg(feature = "rc-tracing")]
+        // SAFETY: The object is valid or NULL.
+        unsafe {
+            crate::debug::trace(crate::debug::RcEventKind::Owned, obj)
+        };
         // SAFETY: The selector is `new`, so this has +1 retain count
         let obj = unsafe { Retained::from_raw(obj) };
 
@@ -191,6 +196,11 @@ impl<T: MsgSend, U: ?Sized + Message> MsgSendSuperId<T, Option<Retained<U>>> for
         let ptr = obj.into_raw_receiver();
         // SAFETY: Same as in `send_message_id`
         let obj = unsafe { MsgSend::send_super_message(ptr, superclass, sel, args) };
+        #[cfg(feature = "rc-tracing")]
+        // SAFETY: The object is valid or NULL.
+        unsafe {
+            crate::debug::trace(crate::debug::RcEventKind::Owned, obj)
+        };
         // SAFETY: Same as in `send_message_id`
         let obj = unsafe { Retained::from_raw(obj) };
         // SAFETY: Same as in `send_message_id`
@@ -275,6 +285,11 @@ impl<T: ?Sized + Message> MsgSendId<Allocated<T>, Option<Retained<T>>> for Init
         // We do this for efficiency, to avoid having a branch that the user
         // did not intend after every `alloc`.
         let obj = unsafe { MsgSend::send_message(ptr, sel, args) };
+        #[cfg(feature = "rc-tracing")]
+        // SAFETY: The object is valid or NULL.
+        unsafe {
+            crate::debug::trace(crate::debug::RcEventKind::Owned, obj)
+        };
         // SAFETY: The selector is `init`, so this has +1 retain count
         let obj = unsafe { Retained::from_raw(obj) };
         R::maybe_unwrap::<Self>(obj, (ptr.cast(), sel))
@@ -304,6 +319,11 @@ impl<T: DeclaredClass> MsgSendSuperId<PartialInit<T>, Option<Retained<T>>> for I
         if let Some(ptr) = NonNull::new(ptr) {
             unsafe { set_finalized(ptr) };
         }
+        #[cfg(feature = "rc-tracing")]
+        // SAFETY: The object is valid or NULL.
+        unsafe {
+            crate::debug::trace(crate::debug::RcEventKind::Owned, ptr)
+        };
         // SAFETY: Same as `send_message_id`
         let obj = unsafe { Retained::from_raw(ptr) };
         R::maybe_unwrap::<Self>(obj, (ptr.cast(), sel))
@@ -319,6 +339,11 @@ impl<T: MsgSend, U: ?Sized + Message> MsgSendId<T, Option<Retained<U>>> for Copy
     ) -> R {
         // SAFETY: Checked by caller
         let obj = unsafe { MsgSend::send_message(obj, sel, args) };
+        #[cfg(feature = "rc-tracing")]
+        // SAFETY: The object is valid or NULL.
+        unsafe {
+            crate::debug::trace(crate::debug::RcEventKind::Owned, obj)
+        };
         // SAFETY: The selector is `copy` or `mutableCopy`, so this has +1
         // retain count
         let obj = unsafe { Retained::from_raw(obj) };
@@ -341,6 +366,11 @@ impl<T: MsgSend, U: ?Sized + Message> MsgSendSuperId<T, Option<Retained<U>>> for
     ) -> R {
         // SAFETY: Same as in `send_message_id`
         let obj = unsafe { MsgSend::send_super_message(obj, superclass, sel, args) };
+        #[cfg(feature = "rc-tracing")]
+        // SAFETY: The object is valid or NULL.
+        unsafe {
+            crate::debug::trace(crate::debug::RcEventKind::Owned, obj)
+        };
         // SAFETY: Same as in `send_message_id`
         let obj = unsafe { Retained::from_raw(obj) };
         R::maybe_unwrap::<Self>(obj, ())
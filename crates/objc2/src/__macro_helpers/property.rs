@@ -0,0 +1,374 @@
+//! Helpers for `#[property(...)]` in `declare_class!`.
+use alloc::ffi::CString;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::marker::PhantomData;
+use core::ptr;
+
+use crate::encode::{Encode, EncodeArgument, EncodeReturn, Encoding};
+use crate::rc::{Retained, Weak};
+use crate::runtime::{Bool, Sel};
+use crate::{msg_send_id, ClassType, DeclaredClass, Message};
+
+/// `assign` memory management semantics.
+#[derive(Clone, Copy, Debug)]
+pub struct PropertyAssign;
+
+/// `strong` (or `retain`) memory management semantics.
+#[derive(Clone, Copy, Debug)]
+pub struct PropertyStrong;
+
+/// `copy` memory management semantics.
+#[derive(Clone, Copy, Debug)]
+pub struct PropertyCopy;
+
+/// `weak` memory management semantics.
+#[derive(Clone, Copy, Debug)]
+pub struct PropertyWeak;
+
+/// No memory management semantics specified; use the default for the
+/// storage (`assign` for scalars, `strong` for objects).
+#[derive(Clone, Copy, Debug)]
+pub struct PropertyDefault;
+
+/// A field in the ivars that can back a property with the given memory
+/// management semantics.
+pub trait PropertyStorage<Semantics> {
+    /// The property attribute describing the semantics, if any.
+    const ATTRIBUTE: &'static str;
+
+    /// The return type of the getter.
+    type Get: EncodeReturn;
+
+    /// The argument type of the setter.
+    type Set: EncodeArgument;
+
+    /// The type of object that is stored, used for the class name in the
+    /// type attribute. `()` for scalars.
+    type Object: ?Sized;
+
+    /// Whether the storage can be set to nil, i.e. whether the property may
+    /// have a setter.
+    const NULLABLE: bool = true;
+
+    fn get(&self, atomic: bool) -> Self::Get;
+
+    /// # Safety
+    ///
+    /// The value must be valid, e.g. a valid pointer to an object (or NULL).
+    unsafe fn set(&self, value: Self::Set);
+}
+
+macro_rules! assign {
+    ($($semantics:ty),*) => {$(
+        impl<T: Encode + Copy> PropertyStorage<$semantics> for Cell<T> {
+            const ATTRIBUTE: &'static str = "";
+            type Get = T;
+            type Set = T;
+            type Object = ();
+
+            #[inline]
+            fn get(&self, _atomic: bool) -> T {
+                Cell::get(self)
+            }
+
+            #[inline]
+            unsafe fn set(&self, value: T) {
+                Cell::set(self, value);
+            }
+        }
+
+        // Similar to `ConvertArgument`, use `Bool` for `bool` properties.
+        impl PropertyStorage<$semantics> for Cell<bool> {
+            const ATTRIBUTE: &'static str = "";
+            type Get = Bool;
+            type Set = Bool;
+            type Object = ();
+
+            #[inline]
+            fn get(&self, _atomic: bool) -> Bool {
+                Bool::new(Cell::get(self))
+            }
+
+            #[inline]
+            unsafe fn set(&self, value: Bool) {
+                Cell::set(self, value.as_bool());
+            }
+        }
+    )*};
+}
+
+assign!(PropertyAssign, PropertyDefault);
+
+/// Storage of a strong reference to an object.
+pub trait ObjectStorage {
+    type Item: Message;
+
+    /// Whether nil can be stored.
+    const NULLABLE: bool;
+
+    fn with_ptr<R>(&self, f: impl FnOnce(*mut Self::Item) -> R) -> R;
+
+    fn replace(&self, value: Option<Retained<Self::Item>>);
+}
+
+impl<T: Message> ObjectStorage for RefCell<Retained<T>> {
+    type Item = T;
+
+    // Properties with this storage are required to be `readonly`.
+    const NULLABLE: bool = false;
+
+    #[inline]
+    fn with_ptr<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(Retained::as_ptr(&self.borrow()).cast_mut())
+    }
+
+    #[inline]
+    fn replace(&self, value: Option<Retained<T>>) {
+        // Unreachable, since no setter is registered for this storage.
+        let value = value.expect("tried to set non-optional property to nil");
+        // Drop the old value outside the borrow.
+        let _old = RefCell::replace(self, value);
+    }
+}
+
+impl<T: Message> ObjectStorage for RefCell<Option<Retained<T>>> {
+    type Item = T;
+
+    const NULLABLE: bool = true;
+
+    #[inline]
+    fn with_ptr<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        let ptr = match &*self.borrow() {
+            Some(obj) => Retained::as_ptr(obj).cast_mut(),
+            None => ptr::null_mut(),
+        };
+        f(ptr)
+    }
+
+    #[inline]
+    fn replace(&self, value: Option<Retained<T>>) {
+        // Drop the old value outside the borrow.
+        let _old = RefCell::replace(self, value);
+    }
+}
+
+macro_rules! object {
+    ($(($semantics:ty, $attribute:literal, $copy:literal)),*) => {$(
+        impl<S: ObjectStorage> PropertyStorage<$semantics> for S {
+            const ATTRIBUTE: &'static str = $attribute;
+            type Get = *mut S::Item;
+            type Set = *mut S::Item;
+            type Object = S::Item;
+
+            const NULLABLE: bool = S::NULLABLE;
+
+            #[inline]
+            fn get(&self, atomic: bool) -> *mut S::Item {
+                if atomic {
+                    // Atomic getters return an autoreleased object, to keep
+                    // it alive even if the property is changed afterwards.
+                    // SAFETY: The pointer comes from a `Retained`, and is
+                    // thus valid (or NULL).
+                    let obj = self.with_ptr(|ptr| unsafe { Retained::retain(ptr) });
+                    Retained::autorelease_return_option(obj)
+                } else {
+                    self.with_ptr(|ptr| ptr)
+                }
+            }
+
+            #[inline]
+            unsafe fn set(&self, value: *mut S::Item) {
+                let value = if $copy {
+                    // SAFETY: Upheld by caller.
+                    let obj = unsafe { value.as_ref() };
+                    // SAFETY: `copy` returns an object of the same type,
+                    // as is required of `copy` properties.
+                    obj.map(|obj| unsafe { msg_send_id![obj, copy] })
+                } else {
+                    // SAFETY: Upheld by caller.
+                    unsafe { Retained::retain(value) }
+                };
+                self.replace(value);
+            }
+        }
+    )*};
+}
+
+object!(
+    (PropertyStrong, "&", false),
+    (PropertyCopy, "C", true),
+    (PropertyDefault, "&", false)
+);
+
+macro_rules! weak {
+    ($($semantics:ty),*) => {$(
+        impl<T: Message> PropertyStorage<$semantics> for RefCell<Weak<T>> {
+            const ATTRIBUTE: &'static str = "W";
+            type Get = *mut T;
+            type Set = *mut T;
+            type Object = T;
+
+            #[inline]
+            fn get(&self, _atomic: bool) -> *mut T {
+                // Loading a weak reference gives us a strong reference, so
+                // we must always autorelease it.
+                Retained::autorelease_return_option(self.borrow().load())
+            }
+
+            #[inline]
+            unsafe fn set(&self, value: *mut T) {
+                // SAFETY: Upheld by caller.
+                let value = unsafe { value.as_ref() }.map(Weak::new).unwrap_or_default();
+                // Drop the old value outside the borrow.
+                let _old = RefCell::replace(self, value);
+            }
+        }
+    )*};
+}
+
+weak!(PropertyWeak, PropertyDefault);
+
+/// A property declared with `#[property(...)]` in `declare_class!`.
+pub trait DeclaredProperty {
+    type Class: DeclaredClass;
+    type Semantics;
+    type Storage: PropertyStorage<Self::Semantics>;
+
+    const ATOMIC: bool;
+
+    /// The name of the class of the stored object, if known.
+    fn class_name() -> Option<&'static str>;
+
+    fn storage(this: &Self::Class) -> &Self::Storage;
+}
+
+/// Helper for finding the name of the class of an object property, using
+/// autoref-based specialization.
+///
+/// `(&PropertyClassName::<T>::new()).class_name()` returns the class name
+/// if `T` implements [`ClassType`], and `None` otherwise.
+#[derive(Debug)]
+pub struct PropertyClassName<T: ?Sized>(PhantomData<T>);
+
+impl<T: ?Sized> PropertyClassName<T> {
+    #[inline]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+pub trait PropertyKnownClassName {
+    fn class_name(&self) -> Option<&'static str>;
+}
+
+impl<T: ClassType> PropertyKnownClassName for PropertyClassName<T> {
+    #[inline]
+    fn class_name(&self) -> Option<&'static str> {
+        Some(T::NAME)
+    }
+}
+
+pub trait PropertyUnknownClassName {
+    #[inline]
+    fn class_name(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+impl<T: ?Sized> PropertyUnknownClassName for &PropertyClassName<T> {}
+
+type Get<P> =
+    <<P as DeclaredProperty>::Storage as PropertyStorage<<P as DeclaredProperty>::Semantics>>::Get;
+type Set<P> =
+    <<P as DeclaredProperty>::Storage as PropertyStorage<<P as DeclaredProperty>::Semantics>>::Set;
+
+pub(crate) extern "C-unwind" fn property_getter<P: DeclaredProperty>(
+    this: &P::Class,
+    _cmd: Sel,
+) -> Get<P> {
+    P::storage(this).get(P::ATOMIC)
+}
+
+pub(crate) unsafe extern "C-unwind" fn property_setter<P: DeclaredProperty>(
+    this: &P::Class,
+    _cmd: Sel,
+    value: Set<P>,
+) {
+    // SAFETY: The value comes from Objective-C, and is assumed to be of the
+    // correct type.
+    unsafe { P::storage(this).set(value) }
+}
+
+/// The default setter of a property, e.g. `setFoo:` for `foo`.
+pub(crate) fn default_setter(name: &str) -> Sel {
+    let mut chars = name.chars();
+    let first = chars.next().map(|c| c.to_ascii_uppercase());
+    let setter: String = "set"
+        .chars()
+        .chain(first)
+        .chain(chars)
+        .chain([':'])
+        .collect();
+    let setter = CString::new(setter).expect("property name must not contain NUL bytes");
+    Sel::register(&setter)
+}
+
+/// The attributes of a property, as expected by `class_addProperty`.
+///
+/// See [Apple's documentation][doc] for details.
+///
+/// [doc]: https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/ObjCRuntimeGuide/Articles/ocrtPropertyIntrospection.html
+pub(crate) fn property_attributes<P: DeclaredProperty>(
+    getter: Option<Sel>,
+    setter: Option<Sel>,
+    readonly: bool,
+) -> Vec<(CString, CString)> {
+    fn attr(name: &str, value: impl Into<Vec<u8>>) -> (CString, CString) {
+        (
+            CString::new(name).unwrap(),
+            CString::new(value).expect("property attribute must not contain NUL bytes"),
+        )
+    }
+
+    let mut ty = Get::<P>::ENCODING_RETURN.to_string();
+    if let (Encoding::Object, Some(name)) = (Get::<P>::ENCODING_RETURN, P::class_name()) {
+        ty = format!("{ty}\"{name}\"");
+    }
+
+    let mut attributes = Vec::new();
+    attributes.push(attr("T", ty));
+    if readonly {
+        attributes.push(attr("R", ""));
+    }
+    let semantics = <P::Storage as PropertyStorage<P::Semantics>>::ATTRIBUTE;
+    if !semantics.is_empty() {
+        attributes.push(attr(semantics, ""));
+    }
+    // The storage is never atomic, so don't advertise it as such, even if
+    // the getter returns an autoreleased object.
+    attributes.push(attr("N", ""));
+    if let Some(getter) = getter {
+        attributes.push(attr("G", getter.name().to_bytes()));
+    }
+    if let Some(setter) = setter {
+        attributes.push(attr("S", setter.name().to_bytes()));
+    }
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sel;
+
+    #[test]
+    fn test_default_setter() {
+        assert_eq!(default_setter("foo"), sel!(setFoo:));
+        assert_eq!(default_setter("isEnabled"), sel!(setIsEnabled:));
+        assert_eq!(default_setter("my_title"), sel!(setMy_title:));
+    }
+}
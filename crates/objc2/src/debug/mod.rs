@@ -0,0 +1,29 @@
+//! # Debugging utilities for memory management.
+//!
+//! These are intended for tests and diagnostics, not for production code
+//! paths.
+//!
+//! - [`find_retain_cycles`] walks the live object graph to find cycles of
+//!   strong references, and [`StrongReferences`] allows it to see into
+//!   instance variables that are opaque to the runtime.
+//! - With the `"rc-tracing"` Cargo feature, `set_rc_tracer` allows
+//!   observing every retain and release done through [`Retained`] and
+//!   [`Weak`].
+//!
+//! [`Retained`]: crate::rc::Retained
+//! [`Weak`]: crate::rc::Weak
+
+#[cfg(feature = "rc-tracing")]
+mod rc_tracing;
+mod retain_cycles;
+
+#[cfg(feature = "rc-tracing")]
+pub(crate) use self::rc_tracing::trace;
+#[cfg(feature = "rc-tracing")]
+pub use self::rc_tracing::{remove_rc_tracer, set_rc_tracer, RcEvent, RcEventKind};
+pub use self::retain_cycles::{
+    find_retain_cycles, Reference, ReferenceKind, RetainCycle, StrongReferences,
+};
+pub(crate) use self::retain_cycles::{
+    forget_class, register_opaque_ivar, visit_ivar, OpaqueIvar, VisitIvar,
+};
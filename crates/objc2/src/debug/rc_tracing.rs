@@ -0,0 +1,325 @@
+//! Tracing of retain and release events.
+//!
+//! The hooks are only compiled in with the `"rc-tracing"` Cargo feature, and
+//! only do an atomic load when no tracer is set.
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::cell::Cell;
+use core::ffi::CStr;
+use core::fmt;
+use core::mem;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::sync::RwLock;
+
+use crate::ffi::NSUInteger;
+use crate::runtime::AnyObject;
+use crate::{msg_send, sel};
+
+/// The kind of reference counting operation in an [`RcEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RcEventKind {
+    /// The object was retained, e.g. with [`Retained::retain`] or by cloning
+    /// a [`Retained`].
+    ///
+    /// [`Retained::retain`]: crate::rc::Retained::retain
+    /// [`Retained`]: crate::rc::Retained
+    Retain,
+    /// An autoreleased object was retained with
+    /// [`Retained::retain_autoreleased`], e.g. when returned from a method
+    /// called with [`msg_send_id!`].
+    ///
+    /// [`Retained::retain_autoreleased`]: crate::rc::Retained::retain_autoreleased
+    /// [`msg_send_id!`]: crate::msg_send_id
+    RetainAutoreleased,
+    /// An object with +1 retain count was received from a `new`, `init`,
+    /// `copy` or `mutableCopy` method called with [`msg_send_id!`].
+    ///
+    /// [`msg_send_id!`]: crate::msg_send_id
+    Owned,
+    /// The object is about to be released, because a [`Retained`] was
+    /// dropped.
+    ///
+    /// [`Retained`]: crate::rc::Retained
+    Release,
+    /// The object was autoreleased, e.g. with
+    /// [`Retained::autorelease_ptr`].
+    ///
+    /// [`Retained::autorelease_ptr`]: crate::rc::Retained::autorelease_ptr
+    Autorelease,
+    /// The object was autoreleased to be returned to Objective-C, with
+    /// [`Retained::autorelease_return`].
+    ///
+    /// [`Retained::autorelease_return`]: crate::rc::Retained::autorelease_return
+    AutoreleaseReturn,
+    /// The object was loaded and retained from a [`Weak`].
+    ///
+    /// [`Weak`]: crate::rc::Weak
+    WeakLoad,
+}
+
+/// A reference counting operation on an object, given to the tracer set with
+/// [`set_rc_tracer`].
+pub struct RcEvent<'a> {
+    kind: RcEventKind,
+    object: *const AnyObject,
+    class_name: &'a CStr,
+    retain_count: Option<usize>,
+    backtrace: &'a Backtrace,
+}
+
+impl<'a> RcEvent<'a> {
+    /// The kind of operation.
+    #[inline]
+    pub fn kind(&self) -> RcEventKind {
+        self.kind
+    }
+
+    /// The object that the operation was done on.
+    ///
+    /// For [`RcEventKind::Release`] and the autorelease events, the object
+    /// may have been deallocated once the tracer returns.
+    #[inline]
+    pub fn object(&self) -> *const AnyObject {
+        self.object
+    }
+
+    /// The name of the object's class.
+    #[inline]
+    pub fn class_name(&self) -> &'a CStr {
+        self.class_name
+    }
+
+    /// The retain count of the object after the operation, or before it in
+    /// the case of [`RcEventKind::Release`].
+    ///
+    /// This is [`None`] if the object does not respond to `retainCount`.
+    /// Note that the retain count of some objects, like constant strings, is
+    /// not meaningful.
+    #[inline]
+    pub fn retain_count(&self) -> Option<usize> {
+        self.retain_count
+    }
+
+    /// The backtrace of the operation, if backtraces are enabled with the
+    /// `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` environment variables.
+    #[inline]
+    pub fn backtrace(&self) -> Option<&'a Backtrace> {
+        match self.backtrace.status() {
+            BacktraceStatus::Captured => Some(self.backtrace),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for RcEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RcEvent")
+            .field("kind", &self.kind)
+            .field("object", &self.object)
+            .field("class_name", &self.class_name)
+            .field("retain_count", &self.retain_count)
+            .finish_non_exhaustive()
+    }
+}
+
+type Tracer = dyn Fn(&RcEvent<'_>) + Send + Sync;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TRACER: RwLock<Option<Arc<Tracer>>> = RwLock::new(None);
+
+std::thread_local! {
+    /// Whether the current thread is running the tracer, to avoid recursion
+    /// if the tracer retains or releases objects itself.
+    static IN_TRACER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Set the function that is called on every retain and release done
+/// through [`Retained`] and [`Weak`], replacing any previous tracer.
+///
+/// The tracer is called from whichever thread does the operation, and
+/// operations that the tracer itself does are not traced.
+///
+/// Only the operations done by this crate are traced, not those done by
+/// Objective-C code, or by [`Retained`]s that are passed to Objective-C.
+///
+/// [`Retained`]: crate::rc::Retained
+/// [`Weak`]: crate::rc::Weak
+///
+///
+/// # Examples
+///
+/// Check that an object is released.
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+///
+/// use objc2::debug::{remove_rc_tracer, set_rc_tracer, RcEventKind};
+/// use objc2::rc::Retained;
+/// use objc2::runtime::NSObject;
+///
+/// let obj = NSObject::new();
+/// let ptr = Retained::as_ptr(&obj) as usize;
+///
+/// let events = Arc::new(Mutex::new(Vec::new()));
+/// let events_clone = Arc::clone(&events);
+/// set_rc_tracer(move |event| {
+///     if event.object() as usize == ptr {
+///         events_clone.lock().unwrap().push(event.kind());
+///     }
+/// });
+///
+/// let clone = obj.clone();
+/// drop(clone);
+/// drop(obj);
+/// remove_rc_tracer();
+///
+/// assert_eq!(
+///     *events.lock().unwrap(),
+///     [RcEventKind::Retain, RcEventKind::Release, RcEventKind::Release],
+/// );
+/// ```
+pub fn set_rc_tracer(tracer: impl Fn(&RcEvent<'_>) + Send + Sync + 'static) {
+    let tracer: Box<Tracer> = Box::new(tracer);
+    let old = replace_tracer(Some(Arc::from(tracer)));
+    ENABLED.store(true, Ordering::Release);
+    drop(old);
+}
+
+/// Remove the tracer set with [`set_rc_tracer`].
+pub fn remove_rc_tracer() {
+    ENABLED.store(false, Ordering::Release);
+    drop(replace_tracer(None));
+}
+
+/// Replace the tracer, and return the old one.
+///
+/// The old tracer must be dropped after the lock has been released, since
+/// dropping it may release objects captured by it, which calls `trace`.
+fn replace_tracer(tracer: Option<Arc<Tracer>>) -> Option<Arc<Tracer>> {
+    let mut guard = TRACER.write().unwrap_or_else(|err| err.into_inner());
+    mem::replace(&mut *guard, tracer)
+}
+
+/// Report an operation on the object to the tracer, if one is set.
+///
+/// # Safety
+///
+/// The object must be valid or NULL.
+#[inline]
+pub(crate) unsafe fn trace<T: ?Sized>(kind: RcEventKind, obj: *mut T) {
+    if ENABLED.load(Ordering::Acquire) && !obj.is_null() {
+        // SAFETY: Checked by caller.
+        unsafe { trace_slow(kind, obj.cast()) };
+    }
+}
+
+#[cold]
+#[inline(never)]
+unsafe fn trace_slow(kind: RcEventKind, obj: *const AnyObject) {
+    let Some(tracer) = TRACER.read().unwrap_or_else(|err| err.into_inner()).clone() else {
+        return;
+    };
+    // The thread-local may have been destroyed if this is called from the
+    // destructor of another thread-local.
+    let Ok(false) = IN_TRACER.try_with(|in_tracer| in_tracer.replace(true)) else {
+        return;
+    };
+
+    struct Reset;
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            let _ = IN_TRACER.try_with(|in_tracer| in_tracer.set(false));
+        }
+    }
+
+    let _reset = Reset;
+
+    // SAFETY: Checked by caller.
+    let obj_ref = unsafe { &*obj };
+    let cls = obj_ref.class();
+    let retain_count = if cls.responds_to(sel!(retainCount)) {
+        // SAFETY: The object responds to `retainCount`, which returns
+        // `NSUInteger`.
+        let count: NSUInteger = unsafe { msg_send![obj_ref, retainCount] };
+        Some(count)
+    } else {
+        None
+    };
+    let backtrace = Backtrace::capture();
+
+    tracer(&RcEvent {
+        kind,
+        object: obj,
+        class_name: cls.name(),
+        retain_count,
+        backtrace: &backtrace,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::rc::{autoreleasepool, Allocated, Retained, Weak};
+    use crate::runtime::NSObject;
+    use crate::{msg_send_id, AllocAnyThread, ClassType};
+
+    // A single test, since the tracer is global.
+    #[test]
+    fn test_trace() {
+        let obj = NSObject::alloc();
+        let ptr = Allocated::as_ptr(&obj) as usize;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_clone = Arc::clone(&events);
+        set_rc_tracer(move |event| {
+            if event.object() as usize == ptr {
+                assert_eq!(event.class_name(), NSObject::class().name());
+                events_clone
+                    .lock()
+                    .unwrap()
+                    .push((event.kind(), event.retain_count()));
+                // Not traced.
+                let _ = unsafe { Retained::retain(event.object() as *mut AnyObject) };
+            }
+        });
+
+        // `-[NSObject init]` returns the same object.
+        let obj: Retained<NSObject> = unsafe { msg_send_id![obj, init] };
+        let weak = Weak::from_retained(&obj);
+        let loaded = weak.load().unwrap();
+        drop(loaded);
+        let clone = obj.clone();
+        autoreleasepool(|_| {
+            let _ = Retained::autorelease_ptr(clone);
+        });
+        let _: Retained<NSObject> = unsafe { msg_send_id![&obj, self] };
+        drop(obj);
+        remove_rc_tracer();
+        drop(weak);
+
+        let events = events.lock().unwrap();
+        let kinds: Vec<_> = events.iter().map(|(kind, _)| *kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                RcEventKind::Owned,
+                RcEventKind::WeakLoad,
+                RcEventKind::Release,
+                RcEventKind::Retain,
+                RcEventKind::Autorelease,
+                RcEventKind::RetainAutoreleased,
+                RcEventKind::Release,
+                RcEventKind::Release,
+            ]
+        );
+        assert_eq!(events[0].1, Some(1));
+        assert_eq!(events[1].1, Some(2));
+        assert_eq!(events.last().unwrap().1, Some(1));
+    }
+}
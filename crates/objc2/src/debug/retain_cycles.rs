@@ -0,0 +1,946 @@
+//! Finding cycles of strong references in the live object graph.
+//!
+//! This uses what the runtime knows about objects, i.e. the type encodings
+//! and layouts of instance variables, and the layout of blocks, along with
+//! the references reported by [`StrongReferences`] for instance variables
+//! that are opaque to the runtime.
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::ffi::{c_char, c_int, c_ulong, c_void};
+use core::fmt;
+use core::mem;
+use core::ptr::NonNull;
+use std::sync::Mutex;
+
+use crate::rc::{Retained, Weak};
+use crate::runtime::{AnyClass, AnyObject};
+use crate::Message;
+
+/// Types that can report the objects that they hold strong references to.
+///
+/// This is used by [`find_retain_cycles`] to follow references through
+/// instance variables that the Objective-C runtime cannot see into, namely
+/// the ivars of classes declared with [`declare_class!`] (if
+/// [`DeclaredClass::Ivars`] implements this trait), and typed ivars added
+/// with [`ClassBuilder::add_typed_ivar_with_references`].
+///
+/// [`declare_class!`]: crate::declare_class
+/// [`DeclaredClass::Ivars`]: crate::DeclaredClass::Ivars
+/// [`ClassBuilder::add_typed_ivar_with_references`]: crate::runtime::ClassBuilder::add_typed_ivar_with_references
+///
+///
+/// # Examples
+///
+/// Report the strong references in the ivars of a declared class.
+///
+/// ```
+/// use std::cell::{Cell, RefCell};
+///
+/// use objc2::debug::StrongReferences;
+/// use objc2::rc::{Retained, Weak};
+/// use objc2::runtime::{AnyObject, NSObject};
+///
+/// struct Ivars {
+///     count: Cell<usize>,
+///     delegate: RefCell<Option<Retained<NSObject>>>,
+///     parent: Weak<NSObject>,
+/// }
+///
+/// impl StrongReferences for Ivars {
+///     fn strong_references(&self, f: &mut dyn FnMut(&AnyObject)) {
+///         // `count` is not an object, and `parent` is a weak reference.
+///         self.delegate.strong_references(f);
+///     }
+/// }
+/// ```
+pub trait StrongReferences {
+    /// Call `f` with each object that `self` holds a strong reference to.
+    fn strong_references(&self, f: &mut dyn FnMut(&AnyObject));
+}
+
+impl<T: ?Sized + Message> StrongReferences for Retained<T> {
+    #[inline]
+    fn strong_references(&self, f: &mut dyn FnMut(&AnyObject)) {
+        // SAFETY: `Retained` always holds an object.
+        f(unsafe { &*Retained::as_ptr(self).cast::<AnyObject>() });
+    }
+}
+
+/// Weak references do not keep the object alive.
+impl<T: ?Sized> StrongReferences for Weak<T> {
+    #[inline]
+    fn strong_references(&self, _f: &mut dyn FnMut(&AnyObject)) {}
+}
+
+impl<T: StrongReferences> StrongReferences for Option<T> {
+    #[inline]
+    fn strong_references(&self, f: &mut dyn FnMut(&AnyObject)) {
+        if let Some(value) = self {
+            value.strong_references(f);
+        }
+    }
+}
+
+impl<T: ?Sized + StrongReferences> StrongReferences for Box<T> {
+    #[inline]
+    fn strong_references(&self, f: &mut dyn FnMut(&AnyObject)) {
+        (**self).strong_references(f);
+    }
+}
+
+/// The contents are skipped if the cell is currently mutably borrowed.
+impl<T: ?Sized + StrongReferences> StrongReferences for RefCell<T> {
+    #[inline]
+    fn strong_references(&self, f: &mut dyn FnMut(&AnyObject)) {
+        if let Ok(value) = self.try_borrow() {
+            value.strong_references(f);
+        }
+    }
+}
+
+impl<T: StrongReferences> StrongReferences for [T] {
+    fn strong_references(&self, f: &mut dyn FnMut(&AnyObject)) {
+        for value in self {
+            value.strong_references(f);
+        }
+    }
+}
+
+impl<T: StrongReferences, const N: usize> StrongReferences for [T; N] {
+    #[inline]
+    fn strong_references(&self, f: &mut dyn FnMut(&AnyObject)) {
+        self[..].strong_references(f);
+    }
+}
+
+impl<T: StrongReferences> StrongReferences for Vec<T> {
+    #[inline]
+    fn strong_references(&self, f: &mut dyn FnMut(&AnyObject)) {
+        self[..].strong_references(f);
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($t:ident),*) => {
+        impl<$($t: StrongReferences),*> StrongReferences for ($($t,)*) {
+            #[inline]
+            #[allow(non_snake_case, unused_variables)]
+            fn strong_references(&self, f: &mut dyn FnMut(&AnyObject)) {
+                let ($($t,)*) = self;
+                $($t.strong_references(f);)*
+            }
+        }
+    };
+}
+
+impl_tuple!();
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+impl_tuple!(A, B, C, D, E);
+impl_tuple!(A, B, C, D, E, F);
+
+/// Reports the strong references of an opaque ivar, stored at the pointer.
+pub(crate) type VisitIvar = unsafe fn(NonNull<u8>, &mut dyn FnMut(&AnyObject));
+
+/// # Safety
+///
+/// The pointer must point to an initialized `T`.
+pub(crate) unsafe fn visit_ivar<T: StrongReferences>(
+    ptr: NonNull<u8>,
+    f: &mut dyn FnMut(&AnyObject),
+) {
+    // SAFETY: Checked by caller.
+    unsafe { ptr.cast::<T>().as_ref() }.strong_references(f);
+}
+
+/// An ivar that is opaque to the runtime, but whose strong references can be
+/// found with [`StrongReferences`].
+#[derive(Clone)]
+pub(crate) struct OpaqueIvar {
+    pub(crate) name: String,
+    pub(crate) data_offset: isize,
+    /// The offset of the flag that says whether the ivar is initialized.
+    pub(crate) flag_offset: isize,
+    pub(crate) is_initialized: fn(u8) -> bool,
+    pub(crate) visit: VisitIvar,
+}
+
+/// The address of each class with opaque ivars, and the ivars.
+static OPAQUE_IVARS: Mutex<Vec<(usize, OpaqueIvar)>> = Mutex::new(Vec::new());
+
+fn class_address(cls: &AnyClass) -> usize {
+    let ptr: *const AnyClass = cls;
+    ptr as usize
+}
+
+/// Record an opaque ivar of a newly registered class.
+pub(crate) fn register_opaque_ivar(cls: &AnyClass, ivar: OpaqueIvar) {
+    OPAQUE_IVARS
+        .lock()
+        .unwrap()
+        .push((class_address(cls), ivar));
+}
+
+/// Stop tracking the class, when it is disposed.
+pub(crate) fn forget_class(cls: &AnyClass) {
+    let addr = class_address(cls);
+    OPAQUE_IVARS.lock().unwrap().retain(|(cls, _)| *cls != addr);
+}
+
+fn opaque_ivars(cls: &AnyClass) -> Vec<OpaqueIvar> {
+    let addr = class_address(cls);
+    // Copied out, so that the lock is not held while visiting.
+    OPAQUE_IVARS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(cls, _)| *cls == addr)
+        .map(|(_, ivar)| ivar.clone())
+        .collect()
+}
+
+/// How an object strongly references the next object in a [`RetainCycle`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ReferenceKind {
+    /// Through an instance variable with the given name.
+    Ivar(String),
+    /// Through a variable captured by a block, at the given byte offset
+    /// from the start of the block's captured variables.
+    BlockCapture {
+        /// The offset of the captured variable.
+        offset: usize,
+    },
+}
+
+/// A strong reference from an object, part of a [`RetainCycle`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Reference {
+    object: *const AnyObject,
+    class_name: String,
+    kind: ReferenceKind,
+}
+
+impl Reference {
+    /// The object that holds the reference.
+    ///
+    /// The object may have been deallocated since the cycle was found.
+    #[inline]
+    pub fn object(&self) -> *const AnyObject {
+        self.object
+    }
+
+    /// The name of the class of [the object][Self::object].
+    #[inline]
+    pub fn class_name(&self) -> &str {
+        &self.class_name
+    }
+
+    /// How the object references the next object in the cycle.
+    #[inline]
+    pub fn kind(&self) -> &ReferenceKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ReferenceKind::Ivar(name) => write!(f, "{}.{name}", self.class_name),
+            ReferenceKind::BlockCapture { offset } => {
+                write!(f, "{}[capture at offset {offset}]", self.class_name)
+            }
+        }
+    }
+}
+
+/// A cycle of strong references, found by [`find_retain_cycles`].
+///
+/// The last reference points back to the object of the first.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RetainCycle {
+    references: Vec<Reference>,
+}
+
+impl RetainCycle {
+    /// The references that make up the cycle.
+    #[inline]
+    pub fn references(&self) -> &[Reference] {
+        &self.references
+    }
+}
+
+impl fmt::Display for RetainCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for reference in &self.references {
+            write!(f, "{reference} -> ")?;
+        }
+        write!(f, "{}", self.references[0].class_name)
+    }
+}
+
+/// Find cycles of strong references that are reachable from `root`.
+///
+/// This follows at most `max_depth` references from the root object, and
+/// returns each cycle that it finds once.
+///
+/// Strong references are discovered through:
+/// - Instance variables with an object type encoding (`@`). On Apple
+///   platforms, the class' [ivar layout][AnyClass::ivar_layout] is used to
+///   exclude weak and unretained instance variables where available;
+///   otherwise all object instance variables except those in the
+///   [weak ivar layout][AnyClass::weak_ivar_layout] are assumed to be strong.
+/// - The ivars of classes declared with [`declare_class!`], if
+///   [`DeclaredClass::Ivars`] implements [`StrongReferences`], and typed
+///   ivars added with [`ClassBuilder::add_typed_ivar_with_references`].
+/// - Variables captured by blocks, if the block has an extended layout
+///   (which `clang` emits for blocks in Objective-C code).
+///
+/// Blocks created by `block2` (and blocks without an extended layout in
+/// general) do not say which of their captured variables are objects, so
+/// their captures are not followed. Instead, each pointer-sized word of the
+/// captured variables is compared against the objects that led to the
+/// block, which finds a cycle through the block as long as the search
+/// reaches the block from an object that it captures (e.g. when starting
+/// from an object that stores a block capturing itself, which is the most
+/// common cause of such cycles).
+///
+/// Notably, associated objects, `__block` variables, the contents of
+/// collections and other instance variables that are opaque to the runtime
+/// are not followed, since the runtime does not provide a way to enumerate
+/// these.
+///
+/// [`declare_class!`]: crate::declare_class
+/// [`DeclaredClass::Ivars`]: crate::DeclaredClass::Ivars
+/// [`ClassBuilder::add_typed_ivar_with_references`]: crate::runtime::ClassBuilder::add_typed_ivar_with_references
+///
+///
+/// # Platform support
+///
+/// **Only Apple's runtime can distinguish strong instance variables from
+/// weak and unretained ones.** On other runtimes (such as GNUstep), there is
+/// no such information, so every instance variable with an object type
+/// encoding is assumed to be strong, which can lead to false positives for
+/// cycles that go through `weak` or `unsafe_unretained` instance variables.
+///
+///
+/// # Safety
+///
+/// The objects reachable from `root` must not be mutated or deallocated
+/// while this runs, and their instance variables must be correctly described
+/// by their type encodings.
+///
+///
+/// # Examples
+///
+/// Print the cycles that are keeping an object alive.
+///
+/// ```
+/// use objc2::debug::find_retain_cycles;
+/// use objc2::runtime::NSObject;
+///
+/// let obj = NSObject::new();
+/// // SAFETY: The object is not accessed from other threads.
+/// for cycle in unsafe { find_retain_cycles(&obj, 10) } {
+///     println!("{cycle}");
+/// }
+/// ```
+pub unsafe fn find_retain_cycles(root: &AnyObject, max_depth: usize) -> Vec<RetainCycle> {
+    let mut finder = CycleFinder {
+        path: Vec::new(),
+        done: BTreeSet::new(),
+        cycles: Vec::new(),
+        max_depth,
+    };
+    finder.visit(NonNull::from(root), None);
+    finder.cycles
+}
+
+struct Frame {
+    obj: NonNull<AnyObject>,
+    /// How the object was reached from the previous frame.
+    via: Option<ReferenceKind>,
+}
+
+struct CycleFinder {
+    path: Vec<Frame>,
+    /// Objects whose references have all been explored.
+    done: BTreeSet<usize>,
+    cycles: Vec<RetainCycle>,
+    max_depth: usize,
+}
+
+impl CycleFinder {
+    /// Returns whether the search was cut short by the depth limit.
+    fn visit(&mut self, obj: NonNull<AnyObject>, via: Option<ReferenceKind>) -> bool {
+        self.path.push(Frame { obj, via });
+        let mut truncated = false;
+        // SAFETY: Upheld by the caller of `find_retain_cycles`.
+        let references = unsafe { strong_references(obj) };
+        for (offset, word) in references.unknown_captures {
+            // The word may not be an object, so only compare the address.
+            if let Some(start) = self
+                .path
+                .iter()
+                .position(|frame| address(frame.obj) == word)
+            {
+                self.record_cycle(start, ReferenceKind::BlockCapture { offset });
+            }
+        }
+        for (kind, next) in references.strong {
+            if let Some(start) = self.path.iter().position(|frame| frame.obj == next) {
+                self.record_cycle(start, kind);
+            } else if self.done.contains(&address(next)) {
+                // Already explored, any cycles have been recorded.
+            } else if self.path.len() > self.max_depth {
+                truncated = true;
+            } else {
+                truncated |= self.visit(next, Some(kind));
+            }
+        }
+        self.path.pop();
+        if !truncated {
+            self.done.insert(address(obj));
+        }
+        truncated
+    }
+
+    fn record_cycle(&mut self, start: usize, last: ReferenceKind) {
+        let frames = &self.path[start..];
+        let references = frames
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| Reference {
+                object: frame.obj.as_ptr(),
+                class_name: class_of(frame.obj).name().to_string_lossy().into_owned(),
+                kind: match frames.get(i + 1) {
+                    Some(next) => next.via.clone().unwrap(),
+                    None => last.clone(),
+                },
+            })
+            .collect::<Vec<_>>();
+        // The same cycle may be reached again from another object in it if
+        // the search was truncated (in which case the objects are not marked
+        // as done), or through another path into the cycle.
+        if !self
+            .cycles
+            .iter()
+            .any(|cycle| is_rotation(&cycle.references, &references))
+        {
+            self.cycles.push(RetainCycle { references });
+        }
+    }
+}
+
+/// Whether `a` contains the same references as `b`, possibly starting from a
+/// different object.
+fn is_rotation(a: &[Reference], b: &[Reference]) -> bool {
+    a.len() == b.len() && (0..a.len()).any(|start| a[start..].iter().chain(&a[..start]).eq(b))
+}
+
+fn address(obj: NonNull<AnyObject>) -> usize {
+    obj.as_ptr() as usize
+}
+
+fn class_of(obj: NonNull<AnyObject>) -> &'static AnyClass {
+    // SAFETY: The object is valid, and the class outlives it.
+    unsafe { &*(obj.as_ref().class() as *const AnyClass) }
+}
+
+/// Whether the pointer is a tagged pointer, which doesn't point to memory
+/// that can be inspected.
+fn is_tagged(obj: NonNull<AnyObject>) -> bool {
+    let addr = address(obj);
+    // Tagged pointers have either the low bits (x86_64, GNUstep) or the
+    // high bit (Apple's arm64) set.
+    addr % mem::align_of::<*const AnyObject>() != 0
+        || (cfg!(target_pointer_width = "64") && (addr as isize) < 0)
+}
+
+/// Read the object pointer stored at the given offset from `ptr`.
+///
+/// # Safety
+///
+/// The offset must contain a valid object pointer or NULL.
+unsafe fn read_object(ptr: NonNull<u8>, offset: usize) -> Option<NonNull<AnyObject>> {
+    let ptr: *const *mut AnyObject = unsafe { ptr.as_ptr().add(offset) }.cast();
+    NonNull::new(unsafe { ptr.read_unaligned() })
+}
+
+#[derive(Default)]
+struct References {
+    /// The references that are known to be strong.
+    strong: Vec<(ReferenceKind, NonNull<AnyObject>)>,
+    /// The offsets and values of the words captured by a block without an
+    /// extended layout, which may or may not be strong references.
+    unknown_captures: Vec<(usize, usize)>,
+}
+
+/// The strong references held by the object.
+///
+/// # Safety
+///
+/// The object and its instance variables must be valid.
+unsafe fn strong_references(obj: NonNull<AnyObject>) -> References {
+    let mut references = References::default();
+    if is_tagged(obj) {
+        return references;
+    }
+    let cls = class_of(obj);
+    let bytes: NonNull<u8> = obj.cast();
+
+    if is_block(cls) {
+        let start = mem::size_of::<BlockHeader>();
+        // SAFETY: The object is a block.
+        match unsafe { block_captures(bytes) } {
+            BlockCaptures::Strong(offsets) => {
+                for offset in offsets {
+                    // SAFETY: The layout describes a strong object pointer
+                    // here.
+                    if let Some(next) = unsafe { read_object(bytes, start + offset) } {
+                        references
+                            .strong
+                            .push((ReferenceKind::BlockCapture { offset }, next));
+                    }
+                }
+            }
+            BlockCaptures::Unknown { size } => {
+                for offset in (0..size / WORD).map(|i| i * WORD) {
+                    // SAFETY: The captured variables are `size` bytes long.
+                    let word = unsafe { bytes.as_ptr().add(start + offset) };
+                    let word = unsafe { word.cast::<usize>().read_unaligned() };
+                    references.unknown_captures.push((offset, word));
+                }
+            }
+        }
+        return references;
+    }
+
+    for cls in core::iter::once(cls).chain(cls.superclasses()) {
+        let ivars = cls.instance_variables();
+        let start =
+            ivars.iter().map(|ivar| ivar.offset()).min().unwrap_or(0) as usize & !(WORD - 1);
+        let strong_words = class_strong_words(cls);
+        for ivar in ivars.iter() {
+            if !ivar.type_encoding().to_bytes().starts_with(b"@") {
+                continue;
+            }
+            let offset = ivar.offset() as usize;
+            let word = (offset - start) / WORD;
+            let is_strong = match &strong_words {
+                StrongWords::Strong(words) => words.contains(&word),
+                StrongWords::AllExcept(words) => !words.contains(&word),
+            };
+            if !is_strong {
+                continue;
+            }
+            // SAFETY: The ivar holds an object, as described by its encoding.
+            if let Some(next) = unsafe { read_object(bytes, offset) } {
+                let name = ivar.name().to_string_lossy().into_owned();
+                references.strong.push((ReferenceKind::Ivar(name), next));
+            }
+        }
+
+        for ivar in opaque_ivars(cls) {
+            // SAFETY: The flag is a `u8` ivar of the object.
+            let flag = unsafe { *bytes.as_ptr().offset(ivar.flag_offset) };
+            if !(ivar.is_initialized)(flag) {
+                continue;
+            }
+            // SAFETY: The offset is that of an ivar of the object.
+            let data = unsafe { NonNull::new_unchecked(bytes.as_ptr().offset(ivar.data_offset)) };
+            let mut f = |next: &AnyObject| {
+                let kind = ReferenceKind::Ivar(ivar.name.clone());
+                references.strong.push((kind, NonNull::from(next)));
+            };
+            // SAFETY: The ivar is initialized, and of the type that the
+            // visitor was registered with.
+            unsafe { (ivar.visit)(data, &mut f) };
+        }
+    }
+    references
+}
+
+const WORD: usize = mem::size_of::<*const c_void>();
+
+#[cfg_attr(not(target_vendor = "apple"), allow(dead_code))]
+enum StrongWords {
+    /// The words that are known to be strong.
+    Strong(Vec<usize>),
+    /// All words except these are assumed to be strong.
+    AllExcept(Vec<usize>),
+}
+
+#[cfg(target_vendor = "apple")]
+fn class_strong_words(cls: &AnyClass) -> StrongWords {
+    if let Some(layout) = cls.ivar_layout() {
+        StrongWords::Strong(decode_ivar_layout(layout))
+    } else {
+        StrongWords::AllExcept(
+            cls.weak_ivar_layout()
+                .map_or(Vec::new(), decode_ivar_layout),
+        )
+    }
+}
+
+#[cfg(not(target_vendor = "apple"))]
+fn class_strong_words(_cls: &AnyClass) -> StrongWords {
+    StrongWords::AllExcept(Vec::new())
+}
+
+/// Decode a compressed ivar layout into the word indexes that it describes,
+/// relative to the start of the class' instance variables.
+///
+/// Each byte is `0xSN`; skip `S` words, then `N` words are scanned. The
+/// layout is terminated by a zero byte (which is not included in `layout`).
+#[cfg_attr(not(target_vendor = "apple"), allow(dead_code))]
+fn decode_ivar_layout(layout: &[u8]) -> Vec<usize> {
+    let mut words = Vec::new();
+    let mut index = 0;
+    for byte in layout {
+        index += usize::from(byte >> 4);
+        let count = usize::from(byte & 0xf);
+        words.extend(index..index + count);
+        index += count;
+    }
+    words
+}
+
+/// Whether the class is a block class (`NSBlock` on both Apple's runtime and
+/// GNUstep is the superclass of all block classes).
+fn is_block(cls: &AnyClass) -> bool {
+    core::iter::once(cls)
+        .chain(cls.superclasses())
+        .any(|cls| cls.name().to_bytes() == b"NSBlock")
+}
+
+// See <https://clang.llvm.org/docs/Block-ABI-Apple.html>, and `block2::abi`.
+const BLOCK_SMALL_DESCRIPTOR: c_int = 1 << 22;
+const BLOCK_HAS_COPY_DISPOSE: c_int = 1 << 25;
+const BLOCK_HAS_SIGNATURE: c_int = 1 << 30;
+const BLOCK_HAS_EXTENDED_LAYOUT: c_int = 1 << 31;
+
+#[repr(C)]
+#[allow(dead_code)] // Only some fields are read
+struct BlockHeader {
+    isa: *const AnyClass,
+    flags: c_int,
+    reserved: c_int,
+    invoke: *const c_void,
+    descriptor: *const BlockDescriptor,
+}
+
+#[repr(C)]
+#[allow(dead_code)] // Only some fields are read
+struct BlockDescriptor {
+    reserved: c_ulong,
+    size: c_ulong,
+    // Followed by `copy` and `dispose` if `BLOCK_HAS_COPY_DISPOSE`, and then
+    // `signature` and `layout` if `BLOCK_HAS_SIGNATURE`.
+}
+
+enum BlockCaptures {
+    /// The byte offsets of the strong object pointers captured by the block,
+    /// relative to the start of the captured variables.
+    Strong(Vec<usize>),
+    /// The block has no extended layout, only the size of the captured
+    /// variables is known.
+    Unknown { size: usize },
+}
+
+/// The captured variables of a block.
+///
+/// # Safety
+///
+/// The pointer must be a valid block.
+unsafe fn block_captures(block: NonNull<u8>) -> BlockCaptures {
+    // SAFETY: The pointer is a block.
+    let header = unsafe { block.cast::<BlockHeader>().as_ref() };
+    if header.flags & BLOCK_SMALL_DESCRIPTOR != 0 || header.descriptor.is_null() {
+        return BlockCaptures::Strong(Vec::new());
+    }
+
+    let descriptor = header.descriptor;
+    // SAFETY: The descriptor is valid.
+    let size = unsafe { (*descriptor).size as usize };
+    let captures_size = size.saturating_sub(mem::size_of::<BlockHeader>());
+
+    let required = BLOCK_HAS_SIGNATURE | BLOCK_HAS_EXTENDED_LAYOUT;
+    if header.flags & required != required {
+        return BlockCaptures::Unknown {
+            size: captures_size,
+        };
+    }
+
+    let mut fields = descriptor.wrapping_add(1).cast::<*const c_void>();
+    if header.flags & BLOCK_HAS_COPY_DISPOSE != 0 {
+        fields = fields.wrapping_add(2);
+    }
+    // SAFETY: The descriptor has the signature and layout fields, as the
+    // flags say so. The layout comes after the signature.
+    let layout = unsafe { fields.add(1).read() as usize };
+
+    let offsets = if layout < 0x1000 {
+        decode_inline_block_layout(layout)
+    } else {
+        // SAFETY: The layout is a NUL-terminated string.
+        let layout = unsafe { core::ffi::CStr::from_ptr(layout as *const c_char) };
+        decode_block_layout(layout.to_bytes())
+    };
+    BlockCaptures::Strong(
+        offsets
+            .into_iter()
+            .filter(|offset| offset + WORD <= captures_size)
+            .collect(),
+    )
+}
+
+/// Decode an inline extended block layout of the form `0xXYZ`: `X` strong
+/// pointers, followed by `Y` `__block` pointers and `Z` weak pointers.
+fn decode_inline_block_layout(layout: usize) -> Vec<usize> {
+    let strong = (layout >> 8) & 0xf;
+    (0..strong).map(|i| i * WORD).collect()
+}
+
+/// Decode an extended block layout string into the offsets of the strong
+/// pointers, relative to the start of the captured variables.
+///
+/// Each byte is `0xPN`, an operator `P` applying to `N + 1` bytes or words.
+fn decode_block_layout(layout: &[u8]) -> Vec<usize> {
+    const NON_OBJECT_BYTES: u8 = 1;
+    const NON_OBJECT_WORDS: u8 = 2;
+    const STRONG: u8 = 3;
+    const BYREF: u8 = 4;
+    const WEAK: u8 = 5;
+    const UNRETAINED: u8 = 6;
+
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    for byte in layout {
+        let count = usize::from(byte & 0xf) + 1;
+        match byte >> 4 {
+            NON_OBJECT_BYTES => offset += count,
+            NON_OBJECT_WORDS | BYREF | WEAK | UNRETAINED => offset += count * WORD,
+            STRONG => {
+                offsets.extend((0..count).map(|i| offset + i * WORD));
+                offset += count * WORD;
+            }
+            // Terminator or unknown operator; the rest are not objects.
+            _ => break,
+        }
+    }
+    offsets
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::ffi::CString;
+    use alloc::format;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use core::cell::OnceCell;
+    use core::ptr;
+
+    use super::*;
+    use crate::rc::Retained;
+    use crate::runtime::{ClassBuilder, NSObject};
+    use crate::{msg_send_id, ClassType};
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    /// A class with two object ivars, that don't retain their values.
+    struct Nodes<'a> {
+        cls: &'a AnyClass,
+    }
+
+    impl Nodes<'_> {
+        fn name(&self) -> &str {
+            self.cls.name().to_str().unwrap()
+        }
+
+        fn node(&self) -> Retained<AnyObject> {
+            unsafe { msg_send_id![self.cls, new] }
+        }
+
+        fn set(&self, obj: &AnyObject, name: &str, value: &AnyObject) {
+            let ivar = self.cls.instance_variable(&c(name)).unwrap();
+            let ptr =
+                unsafe { AnyObject::ivar_at_offset::<*const AnyObject>(obj.into(), ivar.offset()) };
+            unsafe { ptr.as_ptr().write(value) };
+        }
+
+        fn clear(&self, obj: &AnyObject) {
+            for name in ["_first", "_second"] {
+                let ivar = self.cls.instance_variable(&c(name)).unwrap();
+                let ptr = unsafe {
+                    AnyObject::ivar_at_offset::<*const AnyObject>(obj.into(), ivar.offset())
+                };
+                unsafe { ptr.as_ptr().write(ptr::null()) };
+            }
+        }
+    }
+
+    fn with_nodes(f: impl FnOnce(&Nodes<'_>)) {
+        // SAFETY: The tests don't let the nodes outlive the closure.
+        unsafe {
+            ClassBuilder::scoped(
+                NSObject::class(),
+                |builder| {
+                    builder.add_ivar::<u8>(&c("_flag"));
+                    // Raw pointers, so that the cycles don't actually leak.
+                    builder.add_ivar::<*mut AnyObject>(&c("_first"));
+                    builder.add_ivar::<*mut AnyObject>(&c("_second"));
+                },
+                |cls| f(&Nodes { cls }),
+            )
+        }
+    }
+
+    #[test]
+    fn test_no_cycles() {
+        with_nodes(|nodes| {
+            let a = nodes.node();
+            let b = nodes.node();
+            nodes.set(&a, "_first", &b);
+            nodes.set(&a, "_second", &b);
+            assert_eq!(unsafe { find_retain_cycles(&a, 10) }, []);
+            assert_eq!(unsafe { find_retain_cycles(&NSObject::new(), 10) }, []);
+            nodes.clear(&a);
+        });
+    }
+
+    #[test]
+    fn test_cycle() {
+        with_nodes(|nodes| {
+            let a = nodes.node();
+            let b = nodes.node();
+            let c = nodes.node();
+            nodes.set(&a, "_first", &b);
+            nodes.set(&b, "_second", &c);
+            nodes.set(&c, "_first", &b);
+
+            let cycles = unsafe { find_retain_cycles(&a, 10) };
+            assert_eq!(cycles.len(), 1);
+            let references = cycles[0].references();
+            assert_eq!(references.len(), 2);
+            assert_eq!(references[0].object(), &*b as *const AnyObject);
+            assert_eq!(references[0].kind(), &ReferenceKind::Ivar("_second".into()));
+            assert_eq!(references[1].object(), &*c as *const AnyObject);
+            assert_eq!(
+                cycles[0].to_string(),
+                format!("{0}._second -> {0}._first -> {0}", nodes.name()),
+            );
+
+            // Too shallow to find the cycle.
+            assert_eq!(unsafe { find_retain_cycles(&a, 1) }, []);
+
+            for obj in [&a, &b, &c] {
+                nodes.clear(obj);
+            }
+        });
+    }
+
+    #[test]
+    fn test_self_cycle() {
+        with_nodes(|nodes| {
+            let a = nodes.node();
+            nodes.set(&a, "_second", &a);
+            let cycles = unsafe { find_retain_cycles(&a, 0) };
+            assert_eq!(cycles.len(), 1);
+            assert_eq!(
+                cycles[0].to_string(),
+                format!("{0}._second -> {0}", nodes.name()),
+            );
+            nodes.clear(&a);
+        });
+    }
+
+    #[test]
+    fn test_cycle_found_once() {
+        with_nodes(|nodes| {
+            let a = nodes.node();
+            let b = nodes.node();
+            let c = nodes.node();
+            let d = nodes.node();
+            nodes.set(&a, "_first", &b);
+            nodes.set(&a, "_second", &c);
+            nodes.set(&b, "_first", &c);
+            nodes.set(&c, "_first", &b);
+            nodes.set(&c, "_second", &d);
+
+            // The search is truncated at `d` when going through `b` first, so
+            // the cycle is reached again through `c`.
+            let cycles = unsafe { find_retain_cycles(&a, 2) };
+            assert_eq!(cycles.len(), 1);
+            assert_eq!(
+                cycles[0].to_string(),
+                format!("{0}._first -> {0}._first -> {0}", nodes.name()),
+            );
+
+            for obj in [&a, &b, &c] {
+                nodes.clear(obj);
+            }
+        });
+    }
+
+    #[test]
+    fn test_typed_ivar() {
+        let ivar = OnceCell::new();
+        // SAFETY: The object does not outlive the closure.
+        unsafe {
+            ClassBuilder::scoped(
+                NSObject::class(),
+                |builder| {
+                    let handle = builder
+                        .add_typed_ivar_with_references::<Option<Retained<AnyObject>>>(&c("_next"));
+                    let _ = ivar.set(handle);
+                },
+                |cls| {
+                    let ivar = ivar.get().unwrap();
+                    let name = cls.name().to_str().unwrap();
+                    let a: Retained<AnyObject> = msg_send_id![cls, new];
+                    // Not yet initialized.
+                    assert_eq!(find_retain_cycles(&a, 10), []);
+
+                    ivar.set(&a, Some(a.clone()));
+                    let cycles = find_retain_cycles(&a, 10);
+                    assert_eq!(cycles.len(), 1);
+                    assert_eq!(cycles[0].to_string(), format!("{name}._next -> {name}"));
+
+                    // Break the cycle.
+                    drop(ivar.take(&a));
+                    assert_eq!(find_retain_cycles(&a, 10), []);
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn test_decode_ivar_layout() {
+        assert_eq!(decode_ivar_layout(&[]), []);
+        assert_eq!(decode_ivar_layout(&[0x02]), [0, 1]);
+        assert_eq!(decode_ivar_layout(&[0x11, 0x21]), [1, 4]);
+    }
+
+    #[test]
+    fn test_decode_block_layout() {
+        assert_eq!(decode_inline_block_layout(0x000), []);
+        assert_eq!(decode_inline_block_layout(0x211), [0, WORD]);
+        assert_eq!(decode_block_layout(&[0x31]), [0, WORD]);
+        assert_eq!(
+            decode_block_layout(&[0x13, 0x50, 0x30, 0x00, 0x30]),
+            [4 + WORD]
+        );
+        assert_eq!(
+            decode_block_layout(&[0x20, 0x60, 0x40, 0x32]),
+            vec![3 * WORD, 4 * WORD, 5 * WORD]
+        );
+    }
+}
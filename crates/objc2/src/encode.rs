@@ -82,7 +82,7 @@ use core::ptr::NonNull;
 use core::sync::atomic;
 
 #[doc(inline)]
-pub use objc2_encode::{Encoding, EncodingBox, ParseError};
+pub use objc2_encode::{Encoding, EncodingBox, EquivalenceOptions, ParseError, Value, ValueError};
 
 use crate::runtime::{AnyObject, Imp, Sel};
 
@@ -540,6 +540,291 @@ encode_args_impl!(
     p: P
 );
 
+/// Types that can be passed as variadic arguments.
+///
+/// In C, arguments in the variadic part of a function call undergo "default
+/// argument promotion"; integer types smaller than `int` are promoted to
+/// `int`, and `float` is promoted to `double`. The callee will then read
+/// the promoted type, so passing e.g. an `u8` or an `f32` directly is
+/// undefined behaviour.
+///
+/// This trait is therefore only implemented for types that are unaffected
+/// by promotion, and you will get a compile-time error if you try to pass
+/// anything else. Convert the value yourself instead, e.g. use
+/// `c_int::from(x)` for an `u8`, or `f64::from(x)` for an `f32`.
+///
+/// Note that [`bool`] and [`Bool`] are not supported either, since they're
+/// smaller than `int` on most platforms.
+///
+/// [`Bool`]: crate::runtime::Bool
+///
+///
+/// # Safety
+///
+/// The type must be passed in the same way as a variadic argument as it
+/// would be as a normal argument, that is, it must not be affected by
+/// default argument promotion.
+pub unsafe trait EncodeVariadic: EncodeArgument {}
+
+mod variadic_private {
+    pub trait Sealed {}
+}
+
+/// Types that represent the variadic part of a group of function arguments.
+///
+/// This is implemented for tuples of up to 12 arguments, where each argument
+/// implements [`EncodeVariadic`], as well as for [`NilTerminated`].
+///
+/// To pass variadic arguments to [`MessageReceiver::send_message`], wrap
+/// them in [`VarArgs`], and put them last in the argument tuple. Methods
+/// with up to 4 non-variadic arguments are supported.
+///
+/// [`MessageReceiver::send_message`]: crate::runtime::MessageReceiver::send_message
+pub trait VariadicArguments: variadic_private::Sealed {
+    #[doc(hidden)]
+    unsafe fn __invoke_0<R: EncodeReturn>(
+        msg_send_fn: Imp,
+        receiver: *mut AnyObject,
+        sel: Sel,
+        varargs: Self,
+    ) -> R;
+
+    #[doc(hidden)]
+    unsafe fn __invoke_1<R: EncodeReturn, A: EncodeArgument>(
+        msg_send_fn: Imp,
+        receiver: *mut AnyObject,
+        sel: Sel,
+        a: A,
+        varargs: Self,
+    ) -> R;
+
+    #[doc(hidden)]
+    unsafe fn __invoke_2<R: EncodeReturn, A: EncodeArgument, B: EncodeArgument>(
+        msg_send_fn: Imp,
+        receiver: *mut AnyObject,
+        sel: Sel,
+        a: A,
+        b: B,
+        varargs: Self,
+    ) -> R;
+
+    #[doc(hidden)]
+    unsafe fn __invoke_3<R: EncodeReturn, A: EncodeArgument, B: EncodeArgument, C: EncodeArgument>(
+        msg_send_fn: Imp,
+        receiver: *mut AnyObject,
+        sel: Sel,
+        a: A,
+        b: B,
+        c: C,
+        varargs: Self,
+    ) -> R;
+
+    #[doc(hidden)]
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn __invoke_4<
+        R: EncodeReturn,
+        A: EncodeArgument,
+        B: EncodeArgument,
+        C: EncodeArgument,
+        D: EncodeArgument,
+    >(
+        msg_send_fn: Imp,
+        receiver: *mut AnyObject,
+        sel: Sel,
+        a: A,
+        b: B,
+        c: C,
+        d: D,
+        varargs: Self,
+    ) -> R;
+}
+
+/// The variadic arguments to a method, such as the format arguments in
+/// `stringWithFormat:`.
+///
+/// This is used as the last element in the argument tuple given to
+/// [`MessageReceiver::send_message`] and similar, and is what
+/// `msg_send![obj, sel: arg, ...varargs]` expands to.
+///
+/// [`MessageReceiver::send_message`]: crate::runtime::MessageReceiver::send_message
+///
+///
+/// # Example
+///
+/// ```no_run
+/// use core::ffi::c_int;
+///
+/// use objc2::encode::VarArgs;
+/// use objc2::runtime::{AnyClass, AnyObject, MessageReceiver};
+/// use objc2::sel;
+///
+/// # let cls: &AnyClass = todo!();
+/// # let format: &AnyObject = todo!();
+/// let x: c_int = 42;
+/// let y: f64 = 3.5;
+/// let string: *mut AnyObject = unsafe {
+///     cls.send_message(sel!(stringWithFormat:), (format, VarArgs((x, y))))
+/// };
+/// ```
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct VarArgs<V>(pub V);
+
+/// Variadic arguments terminated by a `nil` (NULL) pointer, as is commonly
+/// used in methods like `arrayWithObjects:`.
+///
+///
+/// # Example
+///
+/// ```no_run
+/// use objc2::encode::{NilTerminated, VarArgs};
+/// use objc2::runtime::{AnyClass, AnyObject, MessageReceiver};
+/// use objc2::sel;
+///
+/// # let cls: &AnyClass = todo!();
+/// # let (a, b, c): (&AnyObject, &AnyObject, &AnyObject) = todo!();
+/// // Same as `[NSArray arrayWithObjects: a, b, c, nil]`
+/// let array: *mut AnyObject = unsafe {
+///     cls.send_message(sel!(arrayWithObjects:), (a, VarArgs(NilTerminated((b, c)))))
+/// };
+/// ```
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct NilTerminated<V>(pub V);
+
+/// Helper for implementing one of the `__invoke_N` methods on
+/// [`VariadicArguments`].
+macro_rules! variadic_invoke {
+    (
+        $name:ident;
+        ($($a:ident: $A:ident),*);
+        ($($v:ident: $V:ident),*);
+        ($($varargs:tt)*)
+        $(; $terminator:expr)?
+    ) => {
+        #[inline]
+        unsafe fn $name<R: EncodeReturn $(, $A: EncodeArgument)*>(
+            msg_send_fn: Imp,
+            receiver: *mut AnyObject,
+            sel: Sel,
+            $($a: $A,)*
+            $($varargs)*: Self,
+        ) -> R {
+            // The non-variadic and variadic arguments may be passed
+            // differently (e.g. on Apple's ARM64 ABI, variadic arguments are
+            // always passed on the stack), so we must make sure to cast the
+            // imp to the exact variadic signature.
+            //
+            // SAFETY: We're transmuting an `unsafe` function pointer to
+            // another `unsafe` function pointer.
+            let msg_send_fn: unsafe extern "C-unwind" fn(*mut AnyObject, Sel $(, $A)*, ...) -> R = unsafe {
+                mem::transmute(msg_send_fn)
+            };
+
+            // SAFETY: Caller upholds that the imp is safe to call with the
+            // given receiver, selector and arguments.
+            unsafe { msg_send_fn(receiver, sel $(, $a)* $(, $v)* $(, $terminator)?) }
+        }
+    };
+}
+
+macro_rules! variadic_args_impl {
+    (@impl ($($ty:tt)*) ($($varargs:tt)*) ($($v:ident: $V:ident),*) $(; $terminator:expr)?) => {
+        impl<$($V: EncodeVariadic),*> variadic_private::Sealed for $($ty)* {}
+
+        impl<$($V: EncodeVariadic),*> VariadicArguments for $($ty)* {
+            variadic_invoke!(__invoke_0; (); ($($v: $V),*); ($($varargs)*) $(; $terminator)?);
+            variadic_invoke!(__invoke_1; (a: A); ($($v: $V),*); ($($varargs)*) $(; $terminator)?);
+            variadic_invoke!(__invoke_2; (a: A, b: B); ($($v: $V),*); ($($varargs)*) $(; $terminator)?);
+            variadic_invoke!(__invoke_3; (a: A, b: B, c: C); ($($v: $V),*); ($($varargs)*) $(; $terminator)?);
+            variadic_invoke!(__invoke_4; (a: A, b: B, c: C, d: D); ($($v: $V),*); ($($varargs)*) $(; $terminator)?);
+        }
+    };
+    ($($v:ident: $V:ident),*) => {
+        variadic_args_impl!(@impl (($($V,)*)) (($($v,)*)) ($($v: $V),*));
+        variadic_args_impl!(
+            @impl (NilTerminated<($($V,)*)>) (NilTerminated(($($v,)*))) ($($v: $V),*);
+            core::ptr::null_mut::<AnyObject>()
+        );
+    };
+}
+
+variadic_args_impl!();
+variadic_args_impl!(t0: T0);
+variadic_args_impl!(t0: T0, t1: T1);
+variadic_args_impl!(t0: T0, t1: T1, t2: T2);
+variadic_args_impl!(t0: T0, t1: T1, t2: T2, t3: T3);
+variadic_args_impl!(t0: T0, t1: T1, t2: T2, t3: T3, t4: T4);
+variadic_args_impl!(t0: T0, t1: T1, t2: T2, t3: T3, t4: T4, t5: T5);
+variadic_args_impl!(t0: T0, t1: T1, t2: T2, t3: T3, t4: T4, t5: T5, t6: T6);
+variadic_args_impl!(t0: T0, t1: T1, t2: T2, t3: T3, t4: T4, t5: T5, t6: T6, t7: T7);
+variadic_args_impl!(t0: T0, t1: T1, t2: T2, t3: T3, t4: T4, t5: T5, t6: T6, t7: T7, t8: T8);
+variadic_args_impl!(
+    t0: T0,
+    t1: T1,
+    t2: T2,
+    t3: T3,
+    t4: T4,
+    t5: T5,
+    t6: T6,
+    t7: T7,
+    t8: T8,
+    t9: T9
+);
+variadic_args_impl!(
+    t0: T0,
+    t1: T1,
+    t2: T2,
+    t3: T3,
+    t4: T4,
+    t5: T5,
+    t6: T6,
+    t7: T7,
+    t8: T8,
+    t9: T9,
+    t10: T10
+);
+variadic_args_impl!(
+    t0: T0,
+    t1: T1,
+    t2: T2,
+    t3: T3,
+    t4: T4,
+    t5: T5,
+    t6: T6,
+    t7: T7,
+    t8: T8,
+    t9: T9,
+    t10: T10,
+    t11: T11
+);
+
+macro_rules! encode_variadic_args_impl {
+    ($invoke:ident; $($a:ident: $T:ident),*) => {
+        impl<$($T: EncodeArgument,)* V: VariadicArguments> args_private::Sealed for ($($T,)* VarArgs<V>,) {}
+
+        impl<$($T: EncodeArgument,)* V: VariadicArguments> EncodeArguments for ($($T,)* VarArgs<V>,) {
+            // Only the non-variadic arguments are part of the method's
+            // type-encoding.
+            const ENCODINGS: &'static [Encoding] = &[
+                $($T::ENCODING_ARGUMENT),*
+            ];
+
+            #[inline]
+            unsafe fn __invoke<R: EncodeReturn>(msg_send_fn: Imp, receiver: *mut AnyObject, sel: Sel, ($($a,)* varargs,): Self) -> R {
+                // SAFETY: Upheld by caller.
+                unsafe { V::$invoke(msg_send_fn, receiver, sel, $($a,)* varargs.0) }
+            }
+        }
+    };
+}
+
+encode_variadic_args_impl!(__invoke_0;);
+encode_variadic_args_impl!(__invoke_1; a: A);
+encode_variadic_args_impl!(__invoke_2; a: A, b: B);
+encode_variadic_args_impl!(__invoke_3; a: A, b: B, c: C);
+encode_variadic_args_impl!(__invoke_4; a: A, b: B, c: C, d: D);
+
 // TODO: Implement for `PhantomData` and `PhantomPinned`?
 
 /// Helper for implementing [`Encode`].
@@ -821,6 +1106,26 @@ unsafe impl<T: RefEncode + ?Sized> OptionEncode for &T {}
 unsafe impl<T: RefEncode + ?Sized> OptionEncode for &mut T {}
 unsafe impl<T: RefEncode + ?Sized> OptionEncode for NonNull<T> {}
 
+// SAFETY: These are at least as large as `int` or `double`, and are
+// therefore not affected by default argument promotion.
+unsafe impl EncodeVariadic for i32 {}
+unsafe impl EncodeVariadic for u32 {}
+unsafe impl EncodeVariadic for i64 {}
+unsafe impl EncodeVariadic for u64 {}
+unsafe impl EncodeVariadic for isize {}
+unsafe impl EncodeVariadic for usize {}
+unsafe impl EncodeVariadic for f64 {}
+
+// SAFETY: Pointers are not affected by default argument promotion.
+unsafe impl<T: RefEncode + ?Sized> EncodeVariadic for *const T {}
+unsafe impl<T: RefEncode + ?Sized> EncodeVariadic for *mut T {}
+unsafe impl<T: RefEncode + ?Sized> EncodeVariadic for &T {}
+unsafe impl<T: RefEncode + ?Sized> EncodeVariadic for &mut T {}
+unsafe impl<T: RefEncode + ?Sized> EncodeVariadic for NonNull<T> {}
+unsafe impl<T: RefEncode + ?Sized> EncodeVariadic for Option<&T> {}
+unsafe impl<T: RefEncode + ?Sized> EncodeVariadic for Option<&mut T> {}
+unsafe impl<T: RefEncode + ?Sized> EncodeVariadic for Option<NonNull<T>> {}
+
 /// Helper for implementing [`Encode`]/[`RefEncode`] for function pointers
 /// whose arguments implement [`Encode`].
 ///
@@ -1024,4 +1329,16 @@ mod tests {
         assert_eq!(<(i8,)>::ENCODINGS, &[i8::ENCODING]);
         assert_eq!(<(i8, u32)>::ENCODINGS, &[i8::ENCODING, u32::ENCODING]);
     }
+
+    #[test]
+    fn test_variadic_arguments() {
+        fn impls_variadic<T: VariadicArguments>() {}
+        impls_variadic::<()>();
+        impls_variadic::<(i32, f64, *const u8, Option<&AnyObject>, Sel)>();
+        impls_variadic::<NilTerminated<(&AnyObject, &AnyObject)>>();
+
+        // Only the non-variadic arguments are encoded
+        assert_eq!(<(VarArgs<(i32,)>,)>::ENCODINGS, &[] as &[Encoding]);
+        assert_eq!(<(i8, VarArgs<(i64, f64)>)>::ENCODINGS, &[i8::ENCODING]);
+    }
 }
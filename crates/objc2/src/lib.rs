@@ -182,6 +182,122 @@ pub use self::top_level_traits::{
 #[doc(hidden)]
 pub use objc2_proc_macros::__hash_idents;
 
+/// Declare a new Objective-C class.
+///
+/// This is an attribute macro version of [`declare_class!`], and must be
+/// used together with [`#[methods]`][macro@methods]. It requires the
+/// `"unstable-attribute-macros"` feature.
+///
+/// Compared to [`declare_class!`], the input is regular Rust syntax, which
+/// works better with tools like `rust-analyzer`, and mistakes are reported
+/// at the offending selector or argument instead of at the whole macro
+/// invocation. The generated code is the same.
+///
+/// The attribute is named `declared_class` rather than `declare_class`,
+/// since attribute macros live in the same namespace as [`declare_class!`],
+/// so the two could not both be exported from the crate root.
+///
+/// [`declare_class!`]: crate::declare_class
+///
+///
+/// # Specification
+///
+/// The attribute is applied to a unit struct, and takes the following
+/// arguments:
+/// - `super = ...`: The superclass, see [`ClassType::Super`]. Required.
+/// - `name = "..."`: The name of the class, see [`ClassType::NAME`].
+///   Required.
+/// - `thread_kind = ...`: See [`ClassType::ThreadKind`]. Defaults to the
+///   same thread kind as the superclass.
+/// - `inherits = [...]`: The superclasses of the superclass, used for
+///   `Deref` and similar, as with `#[inherits(...)]` in [`declare_class!`].
+/// - `protocols = [...]`: The protocols that the class implements. Each
+///   protocol must have an `unsafe impl Protocol for T` block with
+///   `#[methods]`, and is referred to by its name.
+/// - `conflict = ...`: What to do if a class with the same name already
+///   exists, see [`ClassConflict`]. Defaults to panicking.
+/// - `crate = ...`: The path to the `objc2` crate, for use if you have
+///   renamed the dependency or re-export the attribute from another crate.
+///   Defaults to `::objc2`. `#[methods(crate = ...)]` takes the same
+///   argument.
+///
+/// The [`DeclaredClass`] implementation must be written as an
+/// `impl DeclaredClass for T` block with `#[methods]`; this is where you
+/// specify the ivars and override the hooks, as in [`declare_class!`].
+///
+/// Methods are specified in an `unsafe impl T` block with `#[methods]`,
+/// using the same `#[method(...)]`, `#[method_id(...)]` and
+/// `#[method_family = ...]` attributes as in [`declare_class!`]. Only a
+/// single such block is supported per class; a second one results in an
+/// error about `__objc2_only_one_inherent_methods_block_is_allowed` being
+/// defined multiple times. Properties are not yet supported.
+///
+/// [`ClassType::Super`]: crate::ClassType::Super
+/// [`ClassConflict`]: crate::runtime::ClassConflict
+/// [`ClassType::NAME`]: crate::ClassType::NAME
+/// [`ClassType::ThreadKind`]: crate::ClassType::ThreadKind
+///
+///
+/// # Safety
+///
+/// The same safety requirements as in [`declare_class!`] apply; the
+/// `unsafe impl` blocks assert that the method signatures match what
+/// Objective-C expects.
+///
+///
+/// # Examples
+///
+/// ```
+/// use objc2::rc::{Allocated, Retained};
+/// use objc2::runtime::{NSObject, NSObjectProtocol};
+/// use objc2::{declared_class, methods, msg_send, msg_send_id, AllocAnyThread, DeclaredClass};
+///
+/// #[declared_class(
+///     super = NSObject,
+///     name = "MyAttributeObject",
+///     protocols = [NSObjectProtocol],
+/// )]
+/// struct MyObject;
+///
+/// #[methods]
+/// impl DeclaredClass for MyObject {
+///     type Ivars = u32;
+/// }
+///
+/// #[methods]
+/// unsafe impl MyObject {
+///     #[method_id(initWithNumber:)]
+///     fn init_with_number(this: Allocated<Self>, number: u32) -> Option<Retained<Self>> {
+///         let this = this.set_ivars(number);
+///         unsafe { msg_send_id![super(this), init] }
+///     }
+///
+///     #[method(number)]
+///     fn number(&self) -> u32 {
+///         *self.ivars()
+///     }
+/// }
+///
+/// #[methods]
+/// unsafe impl NSObjectProtocol for MyObject {}
+///
+/// let obj: Retained<MyObject> = unsafe {
+///     msg_send_id![MyObject::alloc(), initWithNumber: 42u32]
+/// };
+/// let number: u32 = unsafe { msg_send![&obj, number] };
+/// assert_eq!(number, 42);
+/// ```
+#[cfg(feature = "unstable-attribute-macros")]
+pub use objc2_proc_macros::declared_class;
+
+/// Add methods or the [`DeclaredClass`] implementation to a class declared
+/// with [`#[declared_class]`][macro@declared_class].
+///
+/// See [`#[declared_class]`][macro@declared_class] for details. Requires
+/// the `"unstable-attribute-macros"` feature.
+#[cfg(feature = "unstable-attribute-macros")]
+pub use objc2_proc_macros::methods;
+
 #[cfg(not(feature = "objc2-proc-macros"))]
 #[doc(hidden)]
 #[macro_export]
@@ -198,6 +314,7 @@ macro_rules! __hash_idents {
 pub mod __framework_prelude;
 #[doc(hidden)]
 pub mod __macro_helpers;
+pub mod debug;
 mod downcast;
 pub mod encode;
 pub mod exception;
@@ -206,6 +323,7 @@ mod macros;
 mod main_thread_marker;
 pub mod rc;
 pub mod runtime;
+pub mod sync;
 #[cfg(test)]
 mod test_utils;
 mod top_level_traits;
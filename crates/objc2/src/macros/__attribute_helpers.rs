@@ -47,8 +47,8 @@ macro_rules! __extract_and_apply_cfg_attributes {
     };
 }
 
-/// Extract `#[method(...)]` or `#[method_id(...)]` and the `#[optional]`
-/// attribute, and send it to another macro.
+/// Extract `#[method(...)]` or `#[method_id(...)]`, `#[method_family = ...]`
+/// and the `#[optional]` attribute, and send it to another macro.
 ///
 /// This will ensure that there is one and only one of the method attributes
 /// present.
@@ -66,7 +66,7 @@ macro_rules! __extract_and_apply_cfg_attributes {
 ///    (#[$method_or_method_id:ident($($sel:tt)*)])
 ///
 /// 2. The retain semantics, if any was present in the selector for
-///    `#[method_id(...)]`.
+///    `#[method_id(...)]`, or specified with `#[method_family = ...]`.
 ///
 ///    One of `New`, `Alloc`, `Init`, `CopyOrMutCopy` and `Other`.
 ///    ($($retain_semantics:ident)?)
@@ -118,6 +118,21 @@ macro_rules! __extract_custom_attributes_inner {
         $crate::__macro_helpers::compile_error!("must specify the desired selector using `#[method(...)]` or `#[method_id(...)]`");
     };
 
+    // Method family on `#[method(...)]`
+    {
+        // No attributes left to process
+        ()
+        (#[method($($sel:tt)*)])
+        ($retain_semantics:ident)
+        ($($m_optional:tt)*)
+        ($($m_checked:tt)*)
+
+        ($out_macro:path)
+        $($macro_args:tt)*
+    } => {
+        $crate::__macro_helpers::compile_error!("`#[method_family = ...]` is only supported together with `#[method_id(...)]`");
+    };
+
     // Base case
     {
         // No attributes left to process
@@ -257,6 +272,52 @@ macro_rules! __extract_custom_attributes_inner {
         $crate::__macro_helpers::compile_error!("cannot specify the `method`/`method_id` attribute twice");
     };
 
+    // `method_family` attribute
+    {
+        (
+            #[method_family = $family:ident]
+            $($rest:tt)*
+        )
+        ($($m_method:tt)*)
+        // If no existing retain semantics exist
+        ()
+        ($($m_optional:tt)*)
+        ($($m_checked:tt)*)
+
+        ($out_macro:path)
+        $($macro_args:tt)*
+    } => {
+        $crate::__method_family! {
+            ($family)
+
+            ($($rest)*)
+            ($($m_method)*)
+            ($($m_optional)*)
+            ($($m_checked)*)
+
+            ($out_macro)
+            $($macro_args)*
+        }
+    };
+    // Duplicate `method_family` attributes
+    {
+        (
+            #[method_family = $family:ident]
+            $($rest:tt)*
+        )
+        ($($m_method:tt)*)
+        ($($retain_semantics:tt)*)
+        ($($m_optional:tt)*)
+        ($($m_checked:tt)*)
+
+        ($out_macro:path)
+        $($macro_args:tt)*
+    } => {
+        $crate::__macro_helpers::compile_error!("`method_family` specified more than once");
+        // Also emit an error that points at the second attribute.
+        $crate::__macro_helpers::method_family_specified_more_than_once::$family! {}
+    };
+
     // `optional` attribute
     {
         (
@@ -332,3 +393,58 @@ macro_rules! __extract_custom_attributes_inner {
         }
     };
 }
+
+/// Map a method family to the corresponding retain semantics, and continue
+/// parsing attributes in `__extract_custom_attributes_inner!`.
+///
+/// <https://clang.llvm.org/docs/AutomaticReferenceCounting.html#method-families>
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __method_family {
+    {
+        @out
+        ($retain_semantics:ident)
+
+        ($($rest:tt)*)
+        ($($m_method:tt)*)
+        ($($m_optional:tt)*)
+        ($($m_checked:tt)*)
+
+        $($macro_args:tt)*
+    } => {
+        $crate::__extract_custom_attributes_inner! {
+            ($($rest)*)
+            ($($m_method)*)
+            ($retain_semantics)
+            ($($m_optional)*)
+            ($($m_checked)*)
+
+            $($macro_args)*
+        }
+    };
+    ((none) $($args:tt)*) => {
+        $crate::__method_family! { @out (Other) $($args)* }
+    };
+    ((new) $($args:tt)*) => {
+        $crate::__method_family! { @out (New) $($args)* }
+    };
+    ((alloc) $($args:tt)*) => {
+        $crate::__method_family! { @out (Alloc) $($args)* }
+    };
+    ((init) $($args:tt)*) => {
+        $crate::__method_family! { @out (Init) $($args)* }
+    };
+    ((copy) $($args:tt)*) => {
+        $crate::__method_family! { @out (CopyOrMutCopy) $($args)* }
+    };
+    ((mutableCopy) $($args:tt)*) => {
+        $crate::__method_family! { @out (CopyOrMutCopy) $($args)* }
+    };
+    (($unknown:ident) $($args:tt)*) => {
+        $crate::__macro_helpers::compile_error!($crate::__macro_helpers::concat!(
+            "unknown method family `",
+            $crate::__macro_helpers::stringify!($unknown),
+            "`, expected one of `none`, `new`, `alloc`, `init`, `copy` or `mutableCopy`",
+        ));
+    };
+}
@@ -122,16 +122,43 @@ macro_rules! __method_msg_send {
     };
 
     // Variadic method
+    (
+        ($receiver:expr)
+        ()
+        ($arg:ident : ...)
+
+        ($($sel_parsed:tt)+)
+        ($($arg_parsed:tt)*)
+    ) => {
+        $crate::__msg_send_helper! {
+            ($receiver)
+            (send_message)
+            ($($sel_parsed)*)
+            ($($arg_parsed)* $crate::encode::VarArgs($arg),)
+        }
+    };
     (
         ($receiver:expr)
         ($($sel:ident : _)?)
+        (...)
+
+        ($($sel_parsed:tt)*)
+        ($($arg_parsed:tt)*)
+    ) => ({
+        $crate::__macro_helpers::compile_error!(
+            "the variadic parameter must be named, e.g. `args: ...`"
+        )
+    });
+    (
+        ($receiver:expr)
+        ($sel:ident : _)
         ($($arg:ident :)? ...)
 
         ($($sel_parsed:tt)*)
         ($($arg_parsed:tt)*)
     ) => ({
         $crate::__macro_helpers::compile_error!(
-            "variadic methods are not yet supported"
+            "variadic methods with an error parameter are not supported"
         )
     });
 
@@ -286,9 +313,40 @@ macro_rules! __method_msg_send_id {
     };
 
     // Variadic method
+    (
+        ($receiver:expr)
+        ()
+        ($arg:ident : ...)
+
+        ($($sel_parsed:tt)+)
+        ($($arg_parsed:tt)*)
+        ($($retain_semantics:ident)?)
+    ) => {
+        $crate::__msg_send_id_helper! {
+            ($receiver)
+            ($($retain_semantics)?)
+            (MsgSendId)
+            (send_message_id)
+            ($($sel_parsed)*)
+            ($($arg_parsed)* $crate::encode::VarArgs($arg),)
+        }
+    };
     (
         ($receiver:expr)
         ($($sel:ident : _)?)
+        (...)
+
+        ($($sel_parsed:tt)*)
+        ($($arg_parsed:tt)*)
+        ($($retain_semantics:ident)?)
+    ) => ({
+        $crate::__macro_helpers::compile_error!(
+            "the variadic parameter must be named, e.g. `args: ...`"
+        )
+    });
+    (
+        ($receiver:expr)
+        ($sel:ident : _)
         ($($arg:ident :)? ...)
 
         ($($sel_parsed:tt)*)
@@ -296,7 +354,7 @@ macro_rules! __method_msg_send_id {
         ($($retain_semantics:ident)?)
     ) => ({
         $crate::__macro_helpers::compile_error!(
-            "variadic methods are not yet supported"
+            "variadic methods with an error parameter are not supported"
         )
     });
 
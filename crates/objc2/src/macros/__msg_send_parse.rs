@@ -68,6 +68,28 @@ macro_rules! __msg_send_parse {
             $($macro_args)*
         }
     };
+    // Variadic arguments, after all `selector: argument` pairs.
+    {
+        ($error_fn:ident)
+        ($($selector_output:tt)+)
+        ($($argument_output:tt)*)
+        (... $varargs:expr $(,)?)
+        ($fn:ident)
+
+        ($out_macro:path)
+        $($macro_args:tt)*
+    } => {
+        $crate::__msg_send_parse! {
+            ($error_fn)
+            ($($selector_output)*)
+            ($($argument_output)* $crate::encode::VarArgs($varargs),)
+            ()
+            ($fn)
+
+            ($out_macro)
+            $($macro_args)*
+        }
+    };
     {
         ($error_fn:ident)
         ($($selector_output:tt)*)
@@ -0,0 +1,348 @@
+/// Add Rust methods to an existing Objective-C class.
+///
+/// This is the Rust equivalent of an Objective-C category; it allows you to
+/// add (or replace) methods on a class that you did not declare yourself,
+/// such as [`NSObject`].
+///
+/// Methods are specified with the same `#[method(...)]` and
+/// `#[method_id(...)]` syntax as in [`declare_class!`], and are type-checked
+/// in the same way. Properties, ivars and protocol implementations are not
+/// supported, since these cannot be added to a class after it has been
+/// registered.
+///
+/// [`NSObject`]: crate::runtime::NSObject
+/// [`declare_class!`]: crate::declare_class
+///
+///
+/// # Specification
+///
+/// The macro consists of a unit struct used to name the category, an
+/// optional `const CONFLICT` item, and an `unsafe impl` block for the class
+/// that contains the methods.
+///
+/// The struct gets a `register` function, which adds the methods to the
+/// class. Unlike Objective-C categories, nothing happens before this is
+/// called; this makes it explicit when the methods become available. Only
+/// the first call adds the methods, subsequent calls return the same
+/// [`Category`].
+///
+/// The `CONFLICT` item is a [`MethodConflict`] that decides what happens when
+/// the class already implements one of the methods. It defaults to
+/// [`MethodConflict::Error`], which panics in `register`. With
+/// [`MethodConflict::Replace`], the previous implementation is saved, and
+/// can be retrieved with [`Category::replaced_method`].
+///
+/// The methods are not added as inherent methods on the class; call them
+/// through Objective-C with [`msg_send!`] instead.
+///
+/// [`Category`]: crate::runtime::Category
+/// [`Category::replaced_method`]: crate::runtime::Category::replaced_method
+/// [`MethodConflict`]: crate::runtime::MethodConflict
+/// [`MethodConflict::Error`]: crate::runtime::MethodConflict::Error
+/// [`MethodConflict::Replace`]: crate::runtime::MethodConflict::Replace
+/// [`msg_send!`]: crate::msg_send
+///
+///
+/// # Safety
+///
+/// The same safety requirements as the methods in [`declare_class!`] apply.
+///
+/// Additionally, replacing a method changes the behaviour of every instance
+/// of the class (and its subclasses), so the new implementation must uphold
+/// any invariants that other code relies on.
+///
+///
+/// # Examples
+///
+/// ```
+/// use objc2::rc::Retained;
+/// use objc2::runtime::{MethodConflict, NSObject};
+/// use objc2::{declare_category, msg_send, msg_send_id, ClassType};
+///
+/// declare_category!(
+///     /// Helper methods on `NSObject`.
+///     struct NSObjectHelpers;
+///
+///     const CONFLICT: MethodConflict = MethodConflict::Error;
+///
+///     unsafe impl NSObject {
+///         #[method(helperNumber)]
+///         fn helper_number(&self) -> u32 {
+///             42
+///         }
+///
+///         #[method_id(helperObject)]
+///         fn helper_object() -> Retained<NSObject> {
+///             NSObject::new()
+///         }
+///     }
+/// );
+///
+/// NSObjectHelpers::register();
+///
+/// let obj = NSObject::new();
+/// let number: u32 = unsafe { msg_send![&obj, helperNumber] };
+/// assert_eq!(number, 42);
+///
+/// let _obj: Retained<NSObject> = unsafe { msg_send_id![NSObject::class(), helperObject] };
+/// ```
+#[macro_export]
+macro_rules! declare_category {
+    {
+        $(#[$m:meta])*
+        $v:vis struct $name:ident;
+
+        $(const CONFLICT: $conflict_ty:ty = $conflict:expr;)?
+
+        unsafe impl $for:ty {
+            $($methods:tt)*
+        }
+    } => {
+        $(#[$m])*
+        $v struct $name;
+
+        // Use a private trait to add the methods to the (possibly foreign)
+        // class, such that `Self` refers to the class in the method bodies.
+        const _: () = {
+            trait __Objc2CategoryMethods {
+                $crate::__declare_category_output_signatures! {
+                    $($methods)*
+                }
+
+                fn __objc2_register(
+                    __objc2_builder: &mut $crate::__macro_helpers::CategoryBuilderHelper<$for>,
+                );
+            }
+
+            impl __Objc2CategoryMethods for $for {
+                $crate::__declare_class_output_methods! {
+                    $($methods)*
+                }
+
+                fn __objc2_register(
+                    __objc2_builder: &mut $crate::__macro_helpers::CategoryBuilderHelper<$for>,
+                ) {
+                    // In case the user's function is marked `deprecated`
+                    #[allow(deprecated)]
+                    // In case the user did not specify any methods
+                    #[allow(unused_unsafe)]
+                    // SAFETY: Upheld by caller
+                    unsafe {
+                        $crate::__declare_class_register_methods! {
+                            (__objc2_builder)
+                            ($for)
+
+                            $($methods)*
+                        }
+                    }
+                }
+            }
+
+            impl $name {
+                $(const CONFLICT: $conflict_ty = $conflict;)?
+
+                /// Add the methods in this category to the class.
+                ///
+                /// Only the first call adds the methods, subsequent calls
+                /// return the same category.
+                #[allow(dead_code)]
+                $v fn register() -> &'static $crate::runtime::Category {
+                    static CATEGORY: $crate::__macro_helpers::OnceLock<$crate::runtime::Category> =
+                        $crate::__macro_helpers::OnceLock::new();
+
+                    CATEGORY.get_or_init(|| {
+                        let mut __objc2_builder =
+                            $crate::__macro_helpers::CategoryBuilderHelper::<$for>::new(
+                                $crate::__declare_category_conflict!($(Self::CONFLICT; $conflict)?),
+                            );
+                        <$for as __Objc2CategoryMethods>::__objc2_register(&mut __objc2_builder);
+                        __objc2_builder.register()
+                    })
+                }
+            }
+        };
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_category_conflict {
+    ($conflict:expr; $__conflict:expr) => {
+        $conflict
+    };
+    () => {
+        // Default to erroring on conflicts
+        $crate::runtime::MethodConflict::Error
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_category_output_signatures {
+    // Base case
+    {} => {};
+
+    // Unsafe variant
+    {
+        $(#[$($m:tt)*])*
+        unsafe fn $name:ident($($params:tt)*) $(-> $ret:ty)? $body:block
+
+        $($rest:tt)*
+    } => {
+        $crate::__rewrite_self_param! {
+            ($($params)*)
+
+            ($crate::__extract_custom_attributes)
+            ($(#[$($m)*])*)
+
+            ($crate::__declare_category_signature_out)
+            (unsafe)
+            ($name)
+            ($($ret)?)
+        }
+
+        $crate::__declare_category_output_signatures! {
+            $($rest)*
+        }
+    };
+
+    // Safe variant
+    {
+        $(#[$($m:tt)*])*
+        fn $name:ident($($params:tt)*) $(-> $ret:ty)? $body:block
+
+        $($rest:tt)*
+    } => {
+        $crate::__rewrite_self_param! {
+            ($($params)*)
+
+            ($crate::__extract_custom_attributes)
+            ($(#[$($m)*])*)
+
+            ($crate::__declare_category_signature_out)
+            ()
+            ($name)
+            ($($ret)?)
+        }
+
+        $crate::__declare_category_output_signatures! {
+            $($rest)*
+        }
+    };
+
+    // Property
+    {
+        $(#[$($m:tt)*])*
+        $field:ident : $storage:ty;
+
+        $($rest:tt)*
+    } => {
+        $crate::__macro_helpers::compile_error!(
+            "properties are not supported in `declare_category!`"
+        );
+
+        $crate::__declare_category_output_signatures! {
+            $($rest)*
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_category_signature_out {
+    {
+        ($($qualifiers:tt)*)
+        ($name:ident)
+        ($($ret:ty)?)
+
+        ($builder_method:ident)
+        ($receiver:expr)
+        ($receiver_ty:ty)
+        ($($params_prefix:tt)*)
+        ($($params_rest:tt)*)
+
+        ($($m_method:tt)*)
+        ($($retain_semantics:tt)*)
+        ($($m_optional:tt)*)
+        ($($m_checked:tt)*)
+    } => {
+        $crate::__declare_class_rewrite_params! {
+            ($($params_rest)*)
+            ()
+            ()
+
+            ($crate::__declare_category_signature_out_inner)
+
+            ($($qualifiers)*)
+            ($name)
+            ($($ret)?)
+            ($($params_prefix)*)
+
+            ($($m_method)*)
+            ($($m_checked)*)
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_category_signature_out_inner {
+    // #[method(...)]
+    {
+        ($($qualifiers:tt)*)
+        ($name:ident)
+        ($($ret:ty)?)
+        ($($params_prefix:tt)*)
+
+        (#[method($($__sel:tt)*)])
+        ($($m_checked:tt)*)
+
+        ($($params_converted:tt)*)
+        ($($__body_prefix:tt)*)
+    } => {
+        $($m_checked)*
+        #[allow(patterns_in_fns_without_body)]
+        $($qualifiers)* extern "C-unwind" fn $name(
+            $($params_prefix)*
+            $($params_converted)*
+        ) $(-> <$ret as $crate::__macro_helpers::ConvertReturn>::__Inner)?;
+    };
+
+    // #[method_id(...)]
+    {
+        ($($qualifiers:tt)*)
+        ($name:ident)
+        ($ret:ty)
+        ($($params_prefix:tt)*)
+
+        (#[method_id($($__sel:tt)*)])
+        ($($m_checked:tt)*)
+
+        ($($params_converted:tt)*)
+        ($($__body_prefix:tt)*)
+    } => {
+        $($m_checked)*
+        #[allow(patterns_in_fns_without_body)]
+        $($qualifiers)* extern "C-unwind" fn $name(
+            $($params_prefix)*
+            $($params_converted)*
+        ) -> $crate::__macro_helpers::IdReturnValue;
+    };
+
+    // Missing return type, errors in `__declare_class_method_out_inner!`
+    {
+        ($($qualifiers:tt)*)
+        ($name:ident)
+        ()
+        ($($params_prefix:tt)*)
+
+        (#[method_id($($__sel:tt)*)])
+        ($($m_checked:tt)*)
+
+        ($($params_converted:tt)*)
+        ($($__body_prefix:tt)*)
+    } => {
+        $($m_checked)*
+        $($qualifiers)* extern "C-unwind" fn $name();
+    };
+}
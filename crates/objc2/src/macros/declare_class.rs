@@ -46,9 +46,11 @@
 /// [#267].
 ///
 /// If the type implements [`Drop`], the macro will generate a `dealloc`
-/// method for you, which will call `drop` automatically.
+/// method for you, which will call `drop` automatically. If you need to
+/// message the object during deallocation, use the [`dealloc`] hook instead.
 ///
 /// [#267]: https://github.com/madsmtm/objc2/issues/267
+/// [`dealloc`]: crate::DeclaredClass::dealloc
 ///
 ///
 /// ## `ClassType` implementation
@@ -58,14 +60,39 @@
 /// entire application.
 ///
 /// If you're developing a library, good practice here would be to include
-/// your crate name in the prefix (something like `"MyLibrary_MyClass"`).
+/// your crate name in the prefix (something like `"MyLibrary_MyClass"`), or
+/// to use [`unique_class_name!`], which also includes the version of the
+/// crate.
 ///
 /// The class is guaranteed to have been created and registered with the
 /// Objective-C runtime after the [`ClassType::class`] function has been
 /// called.
 ///
+/// By default, this panics if a class with the same name already exists.
+/// This can be changed by adding a `const CONFLICT: ClassConflict = ...;`
+/// item between the struct and the `ClassType` implementation, see
+/// [`ClassConflict`] for the available policies.
+///
 /// [`ClassType::NAME`]: crate::ClassType::NAME
+/// [`unique_class_name!`]: crate::unique_class_name
 /// [`ClassType::class`]: crate::ClassType::class
+/// [`ClassConflict`]: crate::runtime::ClassConflict
+///
+///
+/// ## Generic classes
+///
+/// The struct may have type parameters (optionally with a single trait
+/// bound each, as in `struct MyView<T: MyTrait>;`). Each instantiation of
+/// the type is then registered as a separate class the first time its
+/// [`ClassType::class`] is called, with the names of the type parameters
+/// and a hash of the type appended to [`ClassType::NAME`], e.g.
+/// `"MyView<my_crate::Row>#0123456789abcdef"`. Use the name of the class
+/// returned by [`ClassType::class`] if you need the full name.
+///
+/// The type parameters must be `'static`, and every `impl` block must be
+/// generic with the same names as on the struct. Looking up the class and
+/// the ivars of a generic class is slower than for a non-generic one, since
+/// this has to go through a global map instead of a `static`.
 ///
 ///
 /// ## `DeclaredClass` implementation
@@ -84,9 +111,25 @@
 /// `init`), you must override the subclass' designated initializers, and
 /// initialize your ivars properly in there.
 ///
+/// You may also override the [`load`] and [`initialize`] hooks (in that
+/// order) to run one-time setup for the class. `load` is run right after the
+/// class has been registered, while `initialize` is run as `+initialize`
+/// before the class receives its first message. Both are only run for this
+/// exact class, and are given the registered [`AnyClass`].
+///
+/// Finally, you may override the [`dealloc`][DeclaredClass::dealloc] hook,
+/// which is run when a fully initialized instance is deallocated. It runs
+/// before the type's `Drop` implementation and before the ivars are dropped,
+/// so the ivars and the superclass are still intact, and the object can be
+/// messaged (e.g. to remove it as an observer).
+///
+/// [DeclaredClass::dealloc]: crate::DeclaredClass::dealloc
 /// [`Ivars`]: crate::DeclaredClass::Ivars
 /// [`Cell`]: core::cell::Cell
 /// [`RefCell`]: core::cell::RefCell
+/// [`load`]: crate::DeclaredClass::load
+/// [`initialize`]: crate::DeclaredClass::initialize
+/// [`AnyClass`]: crate::runtime::AnyClass
 ///
 ///
 /// ## Inherent method definitions
@@ -113,6 +156,12 @@
 /// in the "init"-family, the `self`/`this` parameter must be
 /// `Allocated<Self>`.
 ///
+/// Similar to [`extern_methods!`], the selector's method family can be
+/// overridden with `#[method_family = ...]`; the method then returns with the
+/// retain semantics of that family instead. Callers must use the same family,
+/// so you should declare the method with the same attribute in
+/// [`extern_methods!`].
+///
 /// Putting other attributes on the method such as `cfg`, `allow`, `doc`,
 /// `deprecated` and so on is supported. However, note that `cfg_attr` may not
 /// work correctly, due to implementation difficulty - if you have a concrete
@@ -138,6 +187,75 @@
 /// [`runtime::Bool`]: crate::runtime::Bool
 ///
 ///
+/// ## Properties
+///
+/// Within the `impl` blocks you can also declare Objective-C properties,
+/// with a field-like syntax `name: Storage;`, where `name` is a field in
+/// your instance variables, and `Storage` is its type.
+///
+/// The field must be marked with the `#[property(...)]` attribute, which
+/// takes a comma-separated list of the usual Objective-C property
+/// attributes:
+/// - `copy`, `strong`/`retain`, `weak` or `assign` for the memory management
+///   semantics.
+/// - `atomic` or `nonatomic`. Properties are `atomic` by default, which here
+///   only means that the getter returns an autoreleased object instead of
+///   just the current pointer. The storage itself is never atomic, so the
+///   property is always advertised as `nonatomic` to the runtime.
+/// - `readonly` or `readwrite`.
+/// - `getter = name` and `setter = name:` to customize the selectors.
+///
+/// The getter (and the setter, unless the property is `readonly`) is then
+/// registered as a method on the class, along with the property metadata,
+/// so the property is visible to key-value coding and key-value observing.
+///
+/// The supported storage types are [`Cell<T>`] for scalars (with `assign`
+/// semantics), `RefCell<Retained<T>>` and `RefCell<Option<Retained<T>>>`
+/// for objects (with `strong` or `copy` semantics), and `RefCell<Weak<T>>`
+/// for `weak` properties. Since the setter may be called with nil,
+/// `RefCell<Retained<T>>` can only be used for `readonly` properties.
+///
+/// ```
+/// use std::cell::{Cell, RefCell};
+///
+/// use objc2::rc::Retained;
+/// use objc2::runtime::NSObject;
+/// use objc2::{declare_class, ClassType, DeclaredClass};
+///
+/// struct Ivars {
+///     count: Cell<i32>,
+///     enabled: Cell<bool>,
+///     object: RefCell<Option<Retained<NSObject>>>,
+/// }
+///
+/// declare_class!(
+///     struct MyPropertyObject;
+///
+///     unsafe impl ClassType for MyPropertyObject {
+///         type Super = NSObject;
+///         const NAME: &'static str = "MyPropertyObject";
+///     }
+///
+///     impl DeclaredClass for MyPropertyObject {
+///         type Ivars = Ivars;
+///     }
+///
+///     unsafe impl MyPropertyObject {
+///         #[property(assign, nonatomic)]
+///         count: Cell<i32>;
+///
+///         #[property(nonatomic, getter = isEnabled)]
+///         enabled: Cell<bool>;
+///
+///         #[property(strong, readonly)]
+///         object: RefCell<Option<Retained<NSObject>>>;
+///     }
+/// );
+/// ```
+///
+/// [`Cell<T>`]: core::cell::Cell
+///
+///
 /// ## Protocol implementations
 ///
 /// You can specify protocols that the class should implement, along with any
@@ -181,6 +299,13 @@
 ///   - It must not `retain` in the same scope that `&mut self` is active.
 ///   - TODO: And probably a few more. [Open an issue] if you would like
 ///     guidance on whether your implementation is correct.
+/// - If you override the `dealloc` hook, it must not `retain` the object
+///   past the lifetime of the hook.
+/// - If you set `const CONFLICT` to [`ClassConflict::Reuse`], any existing
+///   class with the same name must have been declared by the same code, i.e.
+///   by another copy of the same version of your crate, and must not be
+///   unloaded while the class is in use. Only the superclass and the type
+///   and layout of the instance variables are checked; the methods are not.
 ///
 /// `unsafe impl T { ... }` asserts that the types match those that are
 /// expected when the method is invoked from Objective-C. Note that unlike
@@ -194,6 +319,7 @@
 /// this definition has the same safety requirements as above.
 ///
 /// [`ClassType::Super`]: crate::ClassType::Super
+/// [`ClassConflict::Reuse`]: crate::runtime::ClassConflict::Reuse
 /// [Open an issue]: https://github.com/madsmtm/objc2/issues/new
 ///
 ///
@@ -386,9 +512,11 @@
 macro_rules! declare_class {
     {
         $(#[$m:meta])*
-        $v:vis struct $name:ident;
+        $v:vis struct $name:ident $(<$($t_struct:ident $(: $b_struct:ident)?),+ $(,)?>)?;
 
-        unsafe impl ClassType for $for_class:ty {
+        $(const CONFLICT: $conflict_ty:ty = $conflict:expr;)?
+
+        unsafe impl $(<$($t_for:ident $(: $b_for:ident)?),+ $(,)?>)? ClassType for $for_class:ty {
             $(#[inherits($($inheritance_rest:ty),+)])?
             type Super = $superclass:ty;
 
@@ -397,25 +525,97 @@ macro_rules! declare_class {
             const NAME: &'static str = $name_const:expr;
         }
 
-        impl DeclaredClass for $for_declared:ty {
+        impl $(<$($t_declared:ident $(: $b_declared:ident)?),+ $(,)?>)? DeclaredClass for $for_declared:ty {
             $(type Ivars = $ivars:ty;)?
+
+            $(fn load($load_cls:tt : $load_cls_ty:ty) $load_body:block)?
+
+            $(fn initialize($initialize_cls:tt : $initialize_cls_ty:ty) $initialize_body:block)?
+
+            $(fn dealloc(&$dealloc_self:tt) $dealloc_body:block)?
         }
 
         $($impls:tt)*
+    } => {
+        $crate::__declare_class_type! {
+            ($(#[$m])*)
+            ($v)
+            ($name<$($($t_struct $(: $b_struct)?),+)?>)
+            (impl<$($($t_for $(: $b_for)?),+)?> ClassType, for $for_class)
+            (super = $superclass)
+            (inherits = $($($inheritance_rest),+)?)
+            (thread_kind = $($thread_kind)?)
+            (name = $name_const)
+            (conflict = $($conflict_ty = $conflict)?)
+            (register = |__objc2_builder| {
+                // Implement protocols and methods
+                $crate::__declare_class_register_impls! {
+                    (__objc2_builder)
+                    $($impls)*
+                }
+            })
+        }
+
+        $crate::__declare_class_declared_class! {
+            impl<$($($t_declared $(: $b_declared)?),+)?> (DeclaredClass) for $for_declared {
+                $(type Ivars = $ivars;)?
+
+                $(fn load($load_cls: $load_cls_ty) $load_body)?
+
+                $(fn initialize($initialize_cls: $initialize_cls_ty) $initialize_body)?
+
+                $(fn dealloc(&$dealloc_self) $dealloc_body)?
+            }
+        }
+
+        // Methods
+        $crate::__declare_class_output_impls! {
+            $($impls)*
+        }
+    };
+}
+
+/// The struct and the `ClassType` implementation of a declared class.
+///
+/// Shared between `declare_class!` and `#[declared_class]`. The path to
+/// `ClassType` is passed in, such that `declare_class!` resolves it in the
+/// caller's scope like before. The `register` block is given the
+/// `ClassBuilderHelper` under the given name, and must add the methods and
+/// protocols to it.
+///
+/// The generic parameters may be empty, in which case the class and offsets
+/// are stored in statics, see `__declare_class_storage!`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_class_type {
+    {
+        ($(#[$m:meta])*)
+        ($($v:tt)*)
+        ($name:ident<$($t_struct:ident $(: $b_struct:ident)?),*>)
+        (impl<$($t_for:ident $(: $b_for:ident)?),*> $class_type:path, for $for_class:ty)
+        (super = $superclass:ty)
+        (inherits = $($inheritance_rest:ty),*)
+        (thread_kind = $($thread_kind:ty)?)
+        (name = $name_const:expr)
+        (conflict = $($conflict_ty:ty = $conflict:expr)?)
+        (register = |$builder:ident| $register:block)
     } => {
         $(#[$m])*
         #[repr(C)]
-        $v struct $name {
+        $($v)* struct $name<$($t_struct: 'static $(+ $b_struct)?),*> {
             // Superclasses are deallocated by calling `[super dealloc]`.
             __superclass: $crate::__macro_helpers::ManuallyDrop<$superclass>,
-            // Include ivars for proper auto traits.
-            __ivars: $crate::__macro_helpers::PhantomData<<Self as $crate::DeclaredClass>::Ivars>,
+            // Include ivars and generic parameters for proper auto traits.
+            __ivars: $crate::__macro_helpers::PhantomData<(
+                <Self as $crate::DeclaredClass>::Ivars,
+                $($t_struct,)*
+            )>,
         }
 
         $crate::__extern_class_impl_traits! {
             // SAFETY: Upheld by caller
-            unsafe impl () for $for_class {
-                INHERITS = [$superclass, $($($inheritance_rest,)+)? $crate::runtime::AnyObject];
+            unsafe impl ($($t_for: 'static $(+ $b_for)?),*) for $for_class {
+                INHERITS = [$superclass, $($inheritance_rest,)* $crate::runtime::AnyObject];
 
                 fn as_super(&self) {
                     &*self.__superclass
@@ -423,6 +623,74 @@ macro_rules! declare_class {
             }
         }
 
+        // Creation
+        unsafe impl<$($t_for: 'static $(+ $b_for)?),*> $class_type for $for_class {
+            type Super = $superclass;
+
+            type ThreadKind = $crate::__select_thread_kind!($($thread_kind)?);
+
+            const NAME: &'static $crate::__macro_helpers::str = $name_const;
+
+            fn class() -> &'static $crate::runtime::AnyClass {
+                let _ = <Self as $crate::__macro_helpers::ValidThreadKind<Self::ThreadKind>>::check;
+                let _ = <Self as $crate::__macro_helpers::MainThreadOnlyDoesNotImplSendSync<_>>::check;
+
+                <Self as $crate::__macro_helpers::DeclaredClassOffsets>::__class(|| {
+                    let __objc2_existing = $crate::__macro_helpers::reuse_existing_class::<Self>(
+                        $crate::__declare_class_conflict!($($conflict_ty = $conflict)?),
+                    );
+
+                    match __objc2_existing {
+                        // The class was already loaded if it is reused.
+                        $crate::__macro_helpers::Some(__objc2_existing) => (__objc2_existing, false),
+                        $crate::__macro_helpers::None => {
+                            let mut $builder = $crate::__macro_helpers::ClassBuilderHelper::<Self>::new();
+
+                            $register
+
+                            let __objc2_registered = $builder.register();
+
+                            {
+                                #[allow(unused_imports)]
+                                use $crate::__macro_helpers::{IvarsKnownReferences, IvarsUnknownReferences};
+                                (&$crate::__macro_helpers::IvarsReferences::<Self>::new())
+                                    .register_references(__objc2_registered);
+                            }
+
+                            (__objc2_registered, true)
+                        }
+                    }
+                })
+            }
+
+            #[inline]
+            fn as_super(&self) -> &Self::Super {
+                &*self.__superclass
+            }
+
+            const __INNER: () = ();
+        }
+
+        $crate::__declare_class_storage! {
+            impl<$($t_for $(: $b_for)?),*> for $for_class;
+        }
+
+        // SAFETY: Non-generic classes are always valid downcast targets, and
+        // each instantiation of a generic class is a separate class in the
+        // runtime, so checking the class of the object is enough to
+        // determine the generic parameters.
+        unsafe impl<$($t_for: 'static $(+ $b_for)?),*> $crate::DowncastTarget for $for_class {}
+    };
+}
+
+/// The storage of the class and offsets of a declared class.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_class_storage {
+    // Non-generic class, stored in statics.
+    {
+        impl<> for $for_class:ty;
+    } => {
         // Anonymous block to hide the shared statics
         const _: () = {
             static __OBJC2_CLASS: $crate::__macro_helpers::SyncUnsafeCell<
@@ -435,31 +703,22 @@ macro_rules! declare_class {
                 $crate::__macro_helpers::MaybeUninit<$crate::__macro_helpers::isize>
             > = $crate::__macro_helpers::SyncUnsafeCell::new($crate::__macro_helpers::MaybeUninit::uninit());
 
-            // Creation
-            unsafe impl ClassType for $for_class {
-                type Super = $superclass;
-
-                type ThreadKind = $crate::__select_thread_kind!($($thread_kind)?);
-
-                const NAME: &'static $crate::__macro_helpers::str = $name_const;
-
-                fn class() -> &'static $crate::runtime::AnyClass {
-                    let _ = <Self as $crate::__macro_helpers::ValidThreadKind<Self::ThreadKind>>::check;
-                    let _ = <Self as $crate::__macro_helpers::MainThreadOnlyDoesNotImplSendSync<_>>::check;
-
+            // SAFETY: The offsets are only read after the class has been
+            // registered, and are only written if the ivars or drop flag were
+            // added.
+            unsafe impl $crate::__macro_helpers::DeclaredClassOffsets for $for_class {
+                #[inline]
+                fn __class(
+                    register: impl $crate::__macro_helpers::FnOnce() -> (
+                        $crate::__macro_helpers::RegisteredOffsets,
+                        $crate::__macro_helpers::bool,
+                    ),
+                ) -> &'static $crate::runtime::AnyClass {
                     // TODO: Use `std::sync::OnceLock`
                     static REGISTER_CLASS: $crate::__macro_helpers::Once = $crate::__macro_helpers::Once::new();
 
                     REGISTER_CLASS.call_once(|| {
-                        let mut __objc2_builder = $crate::__macro_helpers::ClassBuilderHelper::<Self>::new();
-
-                        // Implement protocols and methods
-                        $crate::__declare_class_register_impls! {
-                            (__objc2_builder)
-                            $($impls)*
-                        }
-
-                        let (__objc2_cls, __objc2_ivar_offset, __objc2_drop_flag_offset) = __objc2_builder.register();
+                        let ((__objc2_cls, __objc2_ivar_offset, __objc2_drop_flag_offset), __objc2_new) = register();
 
                         // SAFETY: Modification is ensured by `Once` to happen
                         // before any access to the variables.
@@ -472,6 +731,10 @@ macro_rules! declare_class {
                                 __OBJC2_DROP_FLAG_OFFSET.get().write($crate::__macro_helpers::MaybeUninit::new(__objc2_drop_flag_offset));
                             }
                         }
+
+                        if __objc2_new {
+                            <Self as $crate::DeclaredClass>::load(__objc2_cls);
+                        }
                     });
 
                     // SAFETY: We just registered the class, so is now available
@@ -479,16 +742,10 @@ macro_rules! declare_class {
                 }
 
                 #[inline]
-                fn as_super(&self) -> &Self::Super {
-                    &*self.__superclass
+                fn __name() -> &'static $crate::__macro_helpers::str {
+                    <Self as $crate::ClassType>::NAME
                 }
 
-                const __INNER: () = ();
-            }
-
-            impl DeclaredClass for $for_declared {
-                type Ivars = $crate::__select_ivars!($($ivars)?);
-
                 #[inline]
                 fn __ivars_offset() -> $crate::__macro_helpers::isize {
                     // Only access ivar offset if we have an ivar.
@@ -521,20 +778,147 @@ macro_rules! declare_class {
                         0
                     }
                 }
-
-                // SAFETY: The offsets are implemented correctly
-                const __UNSAFE_OFFSETS_CORRECT: () = ();
             }
         };
+    };
 
-        // Methods
-        $crate::__declare_class_output_impls! {
-            $($impls)*
+    // Generic class. Statics are shared between all instantiations, so each
+    // instantiation is instead stored in a global map, and registered
+    // separately with the names of the generic parameters appended to the
+    // name.
+    {
+        impl<$($t:ident $(: $b:ident)?),+> for $for_class:ty;
+    } => {
+        // SAFETY: The offsets are only read after the class has been
+        // registered.
+        unsafe impl<$($t: 'static $(+ $b)?),+> $crate::__macro_helpers::DeclaredClassOffsets for $for_class {
+            fn __class(
+                register: impl $crate::__macro_helpers::FnOnce() -> (
+                    $crate::__macro_helpers::RegisteredOffsets,
+                    $crate::__macro_helpers::bool,
+                ),
+            ) -> &'static $crate::runtime::AnyClass {
+                let __objc2_data = $crate::__macro_helpers::generic_class_data::<Self>(
+                    <Self as $crate::ClassType>::NAME,
+                    &[$($crate::__macro_helpers::type_name::<$t>()),+],
+                );
+
+                let (__objc2_cls, _, _) = __objc2_data.get_or_register(|| {
+                    let (__objc2_registered, __objc2_new) = register();
+                    if __objc2_new {
+                        <Self as $crate::DeclaredClass>::load(__objc2_registered.0);
+                    }
+                    __objc2_registered
+                });
+
+                __objc2_cls
+            }
+
+            #[inline]
+            fn __name() -> &'static $crate::__macro_helpers::str {
+                $crate::__macro_helpers::generic_class_data::<Self>(
+                    <Self as $crate::ClassType>::NAME,
+                    &[$($crate::__macro_helpers::type_name::<$t>()),+],
+                )
+                .name()
+            }
+
+            #[inline]
+            fn __ivars_offset() -> $crate::__macro_helpers::isize {
+                if <Self as $crate::__macro_helpers::DeclaredIvarsHelper>::HAS_IVARS {
+                    $crate::__macro_helpers::generic_class_data::<Self>(
+                        <Self as $crate::ClassType>::NAME,
+                        &[$($crate::__macro_helpers::type_name::<$t>()),+],
+                    )
+                    .registered()
+                    .1
+                } else {
+                    // Fall back to an offset of zero, same as for
+                    // non-generic classes.
+                    0
+                }
+            }
+
+            #[inline]
+            fn __drop_flag_offset() -> $crate::__macro_helpers::isize {
+                if <Self as $crate::__macro_helpers::DeclaredIvarsHelper>::HAS_DROP_FLAG {
+                    $crate::__macro_helpers::generic_class_data::<Self>(
+                        <Self as $crate::ClassType>::NAME,
+                        &[$($crate::__macro_helpers::type_name::<$t>()),+],
+                    )
+                    .registered()
+                    .2
+                } else {
+                    0
+                }
+            }
+        }
+    };
+}
+
+/// The `DeclaredClass` implementation of a declared class.
+///
+/// Shared between `declare_class!` and `#[methods]`. The path to
+/// `DeclaredClass` is passed in, same as in `__declare_class_type!`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_class_declared_class {
+    {
+        $(#[$m:meta])*
+        impl $(<$($t:ident $(: $b:ident)?),*>)? ($declared_class:path) for $for:ty {
+            $(type Ivars = $ivars:ty;)?
+
+            $(fn load($load_cls:tt : $load_cls_ty:ty) $load_body:block)?
+
+            $(fn initialize($initialize_cls:tt : $initialize_cls_ty:ty) $initialize_body:block)?
+
+            $(fn dealloc(&$dealloc_self:tt) $dealloc_body:block)?
         }
+    } => {
+        $(#[$m])*
+        impl<$($($t: 'static $(+ $b)?),*)?> $declared_class for $for {
+            type Ivars = $crate::__select_ivars!($($ivars)?);
+
+            $(
+                fn load($load_cls: $load_cls_ty) $load_body
+            )?
+
+            $(
+                fn initialize($initialize_cls: $initialize_cls_ty) $initialize_body
+            )?
+
+            $(
+                fn dealloc(&$dealloc_self) $dealloc_body
+            )?
 
-        // SAFETY: This macro only allows non-generic classes and non-generic
-        // classes are always valid downcast targets.
-        unsafe impl $crate::DowncastTarget for $name {}
+            // Only add `+initialize` and `dealloc` if the user specified
+            // the hooks
+            $crate::__declare_class_has_initialize! {
+                $($initialize_body)?
+            }
+            $crate::__declare_class_has_dealloc! {
+                $($dealloc_body)?
+            }
+
+            #[inline]
+            fn __name() -> &'static $crate::__macro_helpers::str {
+                <Self as $crate::__macro_helpers::DeclaredClassOffsets>::__name()
+            }
+
+            #[inline]
+            fn __ivars_offset() -> $crate::__macro_helpers::isize {
+                <Self as $crate::__macro_helpers::DeclaredClassOffsets>::__ivars_offset()
+            }
+
+            #[inline]
+            fn __drop_flag_offset() -> $crate::__macro_helpers::isize {
+                <Self as $crate::__macro_helpers::DeclaredClassOffsets>::__drop_flag_offset()
+            }
+
+            // SAFETY: The offsets are implemented correctly, which is ensured
+            // by the `unsafe impl` of `DeclaredClassOffsets`.
+            const __UNSAFE_OFFSETS_CORRECT: () = ();
+        }
     };
 }
 
@@ -550,12 +934,171 @@ macro_rules! __select_ivars {
     };
 }
 
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_class_conflict {
+    ($conflict_ty:ty = $conflict:expr) => {{
+        const CONFLICT: $conflict_ty = $conflict;
+        CONFLICT
+    }};
+    () => {
+        // Default to erroring on conflicts
+        $crate::runtime::ClassConflict::Error
+    };
+}
+
+/// Create a class name that is unique to the current version of the crate.
+///
+/// This is intended for use as [`ClassType::NAME`] in [`declare_class!`],
+/// and expands to a string literal containing the module path of the
+/// invocation, the given identifier and the version of the crate, e.g.
+/// `"my_crate::views::MyView@1.2.3"`.
+///
+/// If the `"objc2-proc-macros"` Cargo feature is enabled (as it is by the
+/// `"unstable-*"` features that need it), a hash of the identifier and its
+/// location in the source code is appended too, e.g.
+/// `"my_crate::views::MyView@1.2.3#0123456789abcdef"`, so that the name also
+/// stays unique if the crate contains several identical invocations (such as
+/// from a `macro_rules!`). The hash is the same for every build of the same
+/// source code, so the readable part is still stable for debugging.
+///
+/// The Objective-C runtime only has a single, global namespace for classes,
+/// so if two versions of the same crate were linked into the same program,
+/// and the crate used a fixed name, declaring the class would panic in
+/// whichever version tried to do so last. With this, each version declares
+/// its own class instead.
+///
+/// Note that this doesn't help if the _same_ version of the crate is loaded
+/// twice, as can happen when several plugins are loaded into a host
+/// application; see [`ClassConflict::Reuse`] for that.
+///
+/// [`ClassType::NAME`]: crate::ClassType::NAME
+/// [`declare_class!`]: crate::declare_class
+/// [`ClassConflict::Reuse`]: crate::runtime::ClassConflict::Reuse
+///
+///
+/// # Examples
+///
+/// ```
+/// use objc2::runtime::NSObject;
+/// use objc2::{declare_class, unique_class_name, ClassType, DeclaredClass};
+///
+/// declare_class!(
+///     struct MyObject;
+///
+///     unsafe impl ClassType for MyObject {
+///         type Super = NSObject;
+///         const NAME: &'static str = unique_class_name!(MyObject);
+///     }
+///
+///     impl DeclaredClass for MyObject {}
+/// );
+///
+/// let name = MyObject::class().name().to_str().unwrap();
+/// assert!(name.contains(concat!("::MyObject@", env!("CARGO_PKG_VERSION"))));
+/// ```
+#[macro_export]
+macro_rules! unique_class_name {
+    ($name:ident) => {
+        $crate::__macro_helpers::concat!(
+            $crate::__macro_helpers::module_path!(),
+            "::",
+            $crate::__macro_helpers::stringify!($name),
+            "@",
+            $crate::__macro_helpers::env!("CARGO_PKG_VERSION"),
+            $crate::__unique_class_name_hash!($name),
+        )
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "objc2-proc-macros")]
+macro_rules! __unique_class_name_hash {
+    ($name:ident) => {
+        $crate::__macro_helpers::concat!("#", $crate::__hash_idents!($name))
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "objc2-proc-macros"))]
+macro_rules! __unique_class_name_hash {
+    ($name:ident) => {
+        ""
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_class_has_initialize {
+    () => {};
+    ($initialize_body:block) => {
+        const __HAS_INITIALIZE: $crate::__macro_helpers::bool = true;
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_class_has_dealloc {
+    () => {};
+    ($dealloc_body:block) => {
+        const __HAS_DEALLOC: $crate::__macro_helpers::bool = true;
+    };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! __declare_class_output_impls {
     // Base-case
     () => {};
 
+    // Generic, with protocol
+    (
+        $(#[$m:meta])*
+        unsafe impl<$($t:ident $(: $b:ident)?),+ $(,)?> $protocol:ident for $for:ty {
+            $($methods:tt)*
+        }
+
+        $($rest:tt)*
+    ) => {
+        // SAFETY: Upheld by caller
+        $(#[$m])*
+        unsafe impl<$($t: 'static $(+ $b)?),+> $protocol for $for {}
+
+        $(#[$m])*
+        impl<$($t: 'static $(+ $b)?),+> $for {
+            $crate::__declare_class_output_methods! {
+                $($methods)*
+            }
+        }
+
+        $crate::__declare_class_output_impls!{
+            $($rest)*
+        }
+    };
+
+    // Generic, without protocol
+    (
+        $(#[$m:meta])*
+        unsafe impl<$($t:ident $(: $b:ident)?),+ $(,)?> $for:ty {
+            $($methods:tt)*
+        }
+
+        $($rest:tt)*
+    ) => {
+        $(#[$m])*
+        impl<$($t: 'static $(+ $b)?),+> $for {
+            $crate::__declare_class_output_methods! {
+                $($methods)*
+            }
+        }
+
+        $crate::__declare_class_output_impls! {
+            $($rest)*
+        }
+    };
+
     // With protocol
     (
         $(#[$m:meta])*
@@ -658,6 +1201,18 @@ macro_rules! __declare_class_output_methods {
             $($rest)*
         }
     };
+
+    // Property, only registered in `__declare_class_register_methods!`
+    {
+        $(#[$($m:tt)*])*
+        $field:ident : $storage:ty;
+
+        $($rest:tt)*
+    } => {
+        $crate::__declare_class_output_methods! {
+            $($rest)*
+        }
+    };
 }
 
 #[doc(hidden)]
@@ -668,6 +1223,53 @@ macro_rules! __declare_class_register_impls {
         ($builder:ident)
     ) => {};
 
+    // Generic, with protocol. The generic parameters are in scope in
+    // `ClassType::class`, so they can simply be removed.
+    (
+        ($builder:ident)
+
+        $(#[$($m:tt)*])*
+        unsafe impl<$($t:ident $(: $b:ident)?),+ $(,)?> $protocol:ident for $for:ty {
+            $($methods:tt)*
+        }
+
+        $($rest:tt)*
+    ) => {
+        $crate::__declare_class_register_impls! {
+            ($builder)
+
+            $(#[$($m)*])*
+            unsafe impl $protocol for $for {
+                $($methods)*
+            }
+
+            $($rest)*
+        }
+    };
+
+    // Generic, without protocol
+    (
+        ($builder:ident)
+
+        $(#[$($m:tt)*])*
+        unsafe impl<$($t:ident $(: $b:ident)?),+ $(,)?> $for:ty {
+            $($methods:tt)*
+        }
+
+        $($rest:tt)*
+    ) => {
+        $crate::__declare_class_register_impls! {
+            ($builder)
+
+            $(#[$($m)*])*
+            unsafe impl $for {
+                $($methods)*
+            }
+
+            $($rest)*
+        }
+    };
+
     // With protocol
     (
         ($builder:ident)
@@ -694,6 +1296,7 @@ macro_rules! __declare_class_register_impls {
             unsafe {
                 $crate::__declare_class_register_methods! {
                     (__objc2_protocol_builder)
+                    ($for)
 
                     $($methods)*
                 }
@@ -731,6 +1334,7 @@ macro_rules! __declare_class_register_impls {
             unsafe {
                 $crate::__declare_class_register_methods! {
                     ($builder)
+                    ($for)
 
                     $($methods)*
                 }
@@ -750,11 +1354,13 @@ macro_rules! __declare_class_register_methods {
     // Base case
     {
         ($builder:ident)
+        ($for:ty)
     } => {};
 
     // Unsafe variant
     {
         ($builder:ident)
+        ($for:ty)
 
         $(#[$($m:tt)*])*
         unsafe fn $name:ident($($params:tt)*) $(-> $ret:ty)? $body:block
@@ -777,6 +1383,7 @@ macro_rules! __declare_class_register_methods {
 
         $crate::__declare_class_register_methods! {
             ($builder)
+            ($for)
 
             $($rest)*
         }
@@ -785,6 +1392,7 @@ macro_rules! __declare_class_register_methods {
     // Safe variant
     {
         ($builder:ident)
+        ($for:ty)
 
         $(#[$($m:tt)*])*
         fn $name:ident($($params:tt)*) $(-> $ret:ty)? $body:block
@@ -807,6 +1415,36 @@ macro_rules! __declare_class_register_methods {
 
         $crate::__declare_class_register_methods! {
             ($builder)
+            ($for)
+
+            $($rest)*
+        }
+    };
+
+    // Property
+    {
+        ($builder:ident)
+        ($for:ty)
+
+        $(#[$($m:tt)*])*
+        $field:ident : $storage:ty;
+
+        $($rest:tt)*
+    } => {
+        $crate::__declare_class_register_property! {
+            ($(#[$($m)*])*)
+            ()
+
+            ($builder)
+            ($for)
+            ($field)
+            ($storage)
+            ($(#[$($m)*])*)
+        }
+
+        $crate::__declare_class_register_methods! {
+            ($builder)
+            ($for)
 
             $($rest)*
         }
@@ -817,6 +1455,7 @@ macro_rules! __declare_class_register_methods {
     // This will still fail inside __declare_class_output_methods!
     {
         ($builder:ident)
+        ($for:ty)
 
         $_associated_item:item
 
@@ -830,6 +1469,308 @@ macro_rules! __declare_class_register_methods {
     }
 }
 
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_class_register_property {
+    // Found `#[property(...)]`
+    {
+        (#[property($($args:tt)*)] $($m_rest:tt)*)
+        ()
+
+        $($macro_args:tt)*
+    } => {
+        $crate::__declare_class_register_property! {
+            ($($m_rest)*)
+            (($($args)*))
+
+            $($macro_args)*
+        }
+    };
+
+    // Duplicate `#[property(...)]`
+    {
+        (#[property($($args:tt)*)] $($m_rest:tt)*)
+        ($($found:tt)+)
+
+        $($macro_args:tt)*
+    } => {
+        $crate::__macro_helpers::compile_error!("cannot specify the `property` attribute twice");
+    };
+
+    // Skip other attributes
+    {
+        (#[$($m:tt)*] $($m_rest:tt)*)
+        ($($found:tt)*)
+
+        $($macro_args:tt)*
+    } => {
+        $crate::__declare_class_register_property! {
+            ($($m_rest)*)
+            ($($found)*)
+
+            $($macro_args)*
+        }
+    };
+
+    // Done
+    {
+        ()
+        (($($args:tt)*))
+
+        $($macro_args:tt)*
+    } => {
+        $crate::__declare_class_property_args! {
+            ($($args)*)
+            ($crate::__macro_helpers::PropertyDefault)
+            (true)
+            (false)
+            ($crate::__macro_helpers::None)
+            ($crate::__macro_helpers::None)
+
+            $($macro_args)*
+        }
+    };
+
+    // No `#[property(...)]`
+    {
+        ()
+        ()
+
+        $($macro_args:tt)*
+    } => {
+        $crate::__macro_helpers::compile_error!("fields in `declare_class!` must have a `#[property(...)]` attribute");
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_class_property_args {
+    // Memory management semantics
+    {
+        (copy $(, $($rest:tt)*)?)
+        ($_semantics:ty)
+        $($state:tt)*
+    } => {
+        $crate::__declare_class_property_args! {
+            ($($($rest)*)?)
+            ($crate::__macro_helpers::PropertyCopy)
+            $($state)*
+        }
+    };
+    {
+        (strong $(, $($rest:tt)*)?)
+        ($_semantics:ty)
+        $($state:tt)*
+    } => {
+        $crate::__declare_class_property_args! {
+            ($($($rest)*)?)
+            ($crate::__macro_helpers::PropertyStrong)
+            $($state)*
+        }
+    };
+    {
+        (retain $(, $($rest:tt)*)?)
+        ($_semantics:ty)
+        $($state:tt)*
+    } => {
+        $crate::__declare_class_property_args! {
+            ($($($rest)*)?)
+            ($crate::__macro_helpers::PropertyStrong)
+            $($state)*
+        }
+    };
+    {
+        (weak $(, $($rest:tt)*)?)
+        ($_semantics:ty)
+        $($state:tt)*
+    } => {
+        $crate::__declare_class_property_args! {
+            ($($($rest)*)?)
+            ($crate::__macro_helpers::PropertyWeak)
+            $($state)*
+        }
+    };
+    {
+        (assign $(, $($rest:tt)*)?)
+        ($_semantics:ty)
+        $($state:tt)*
+    } => {
+        $crate::__declare_class_property_args! {
+            ($($($rest)*)?)
+            ($crate::__macro_helpers::PropertyAssign)
+            $($state)*
+        }
+    };
+
+    // Atomicity
+    {
+        (atomic $(, $($rest:tt)*)?)
+        ($semantics:ty)
+        ($_atomic:expr)
+        $($state:tt)*
+    } => {
+        $crate::__declare_class_property_args! {
+            ($($($rest)*)?)
+            ($semantics)
+            (true)
+            $($state)*
+        }
+    };
+    {
+        (nonatomic $(, $($rest:tt)*)?)
+        ($semantics:ty)
+        ($_atomic:expr)
+        $($state:tt)*
+    } => {
+        $crate::__declare_class_property_args! {
+            ($($($rest)*)?)
+            ($semantics)
+            (false)
+            $($state)*
+        }
+    };
+
+    // Mutability
+    {
+        (readonly $(, $($rest:tt)*)?)
+        ($semantics:ty)
+        ($atomic:expr)
+        ($_readonly:expr)
+        $($state:tt)*
+    } => {
+        $crate::__declare_class_property_args! {
+            ($($($rest)*)?)
+            ($semantics)
+            ($atomic)
+            (true)
+            $($state)*
+        }
+    };
+    {
+        (readwrite $(, $($rest:tt)*)?)
+        ($semantics:ty)
+        ($atomic:expr)
+        ($_readonly:expr)
+        $($state:tt)*
+    } => {
+        $crate::__declare_class_property_args! {
+            ($($($rest)*)?)
+            ($semantics)
+            ($atomic)
+            (false)
+            $($state)*
+        }
+    };
+
+    // Custom getter and setter
+    {
+        (getter = $getter:ident $(, $($rest:tt)*)?)
+        ($semantics:ty)
+        ($atomic:expr)
+        ($readonly:expr)
+        ($_getter:expr)
+        $($state:tt)*
+    } => {
+        $crate::__declare_class_property_args! {
+            ($($($rest)*)?)
+            ($semantics)
+            ($atomic)
+            ($readonly)
+            ($crate::__macro_helpers::Some($crate::sel!($getter)))
+            $($state)*
+        }
+    };
+    {
+        (setter = $setter:ident : $(, $($rest:tt)*)?)
+        ($semantics:ty)
+        ($atomic:expr)
+        ($readonly:expr)
+        ($getter:expr)
+        ($_setter:expr)
+        $($state:tt)*
+    } => {
+        $crate::__declare_class_property_args! {
+            ($($($rest)*)?)
+            ($semantics)
+            ($atomic)
+            ($readonly)
+            ($getter)
+            ($crate::__macro_helpers::Some($crate::sel!($setter:)))
+            $($state)*
+        }
+    };
+
+    // Done
+    {
+        ()
+        ($semantics:ty)
+        ($atomic:expr)
+        ($readonly:expr)
+        ($getter:expr)
+        ($setter:expr)
+
+        ($builder:ident)
+        ($for:ty)
+        ($field:ident)
+        ($storage:ty)
+        ($($m:tt)*)
+    } => {
+        $crate::__extract_and_apply_cfg_attributes! {
+            ($($m)*)
+
+            {
+                struct __Objc2Property;
+
+                impl $crate::__macro_helpers::DeclaredProperty for __Objc2Property {
+                    type Class = $for;
+                    type Semantics = $semantics;
+                    type Storage = $storage;
+
+                    const ATOMIC: $crate::__macro_helpers::bool = $atomic;
+
+                    #[inline]
+                    fn class_name() -> $crate::__macro_helpers::Option<&'static $crate::__macro_helpers::str> {
+                        #[allow(unused_imports)]
+                        use $crate::__macro_helpers::{PropertyKnownClassName, PropertyUnknownClassName};
+                        (&$crate::__macro_helpers::PropertyClassName::<
+                            <$storage as $crate::__macro_helpers::PropertyStorage<$semantics>>::Object,
+                        >::new()).class_name()
+                    }
+
+                    #[inline]
+                    fn storage(this: &$for) -> &$storage {
+                        &<$for as $crate::DeclaredClass>::ivars(this).$field
+                    }
+                }
+
+                const _: () = $crate::__macro_helpers::assert!(
+                    $readonly || <$storage as $crate::__macro_helpers::PropertyStorage<$semantics>>::NULLABLE,
+                    "properties stored in `RefCell<Retained<T>>` must be `readonly`, since the setter may be called with nil; use `RefCell<Option<Retained<T>>>` instead",
+                );
+
+                $builder.add_property::<__Objc2Property>(
+                    $crate::__macro_helpers::stringify!($field),
+                    $getter,
+                    $setter,
+                    $readonly,
+                );
+            }
+        }
+    };
+
+    // Unknown
+    {
+        ($unknown:tt $($rest:tt)*)
+        $($state:tt)*
+    } => {
+        $crate::__macro_helpers::compile_error!($crate::__macro_helpers::concat!(
+            "unknown property attribute `",
+            $crate::__macro_helpers::stringify!($unknown),
+            "`",
+        ));
+    };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! __declare_class_method_out {
@@ -1005,7 +1946,7 @@ macro_rules! __declare_class_method_out_inner {
         ($($params_prefix:tt)*)
 
         (#[method_id($($sel:tt)*)])
-        () // Specifying retain semantics is unsupported in declare_class! for now
+        ($($retain_semantics:ident)?)
         ($($__m_optional:tt)*)
         ($($m_checked:tt)*)
 
@@ -1025,14 +1966,10 @@ macro_rules! __declare_class_method_out_inner {
             let __objc2_result = $body;
 
             #[allow(unreachable_code)]
-            <$crate::__macro_helpers::RetainSemantics<{
-                $crate::__macro_helpers::retain_semantics(
-                    $crate::__sel_helper! {
-                        ()
-                        $($sel)*
-                    }
-                )
-            }> as $crate::__macro_helpers::MessageRecieveId<
+            <$crate::__declare_class_retain_semantics!(
+                ($($retain_semantics)?)
+                ($($sel)*)
+            ) as $crate::__macro_helpers::MessageRecieveId<
                 $receiver_ty,
                 $ret,
             >>::into_return(__objc2_result)
@@ -1065,6 +2002,26 @@ macro_rules! __declare_class_method_out_inner {
     };
 }
 
+/// The retain semantics of a `#[method_id(...)]`, either specified with
+/// `#[method_family = ...]`, or inferred from the selector.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_class_retain_semantics {
+    (($retain_semantics:ident) ($($sel:tt)*)) => {
+        $crate::__macro_helpers::$retain_semantics
+    };
+    (() ($($sel:tt)*)) => {
+        $crate::__macro_helpers::RetainSemantics<{
+            $crate::__macro_helpers::retain_semantics(
+                $crate::__sel_helper! {
+                    ()
+                    $($sel)*
+                }
+            )
+        }>
+    };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! __convert_result {
@@ -1122,12 +2079,12 @@ macro_rules! __declare_class_register_out {
 macro_rules! __declare_class_invalid_selectors {
     (#[method(dealloc)]) => {
         $crate::__macro_helpers::compile_error!(
-            "`#[method(dealloc)]` is not supported. Implement `Drop` for the type instead"
+            "`#[method(dealloc)]` is not supported. Implement `Drop` or `DeclaredClass::dealloc` for the type instead"
         )
     };
     (#[method_id(dealloc)]) => {
         $crate::__macro_helpers::compile_error!(
-            "`#[method_id(dealloc)]` is not supported. Implement `Drop` for the type instead"
+            "`#[method_id(dealloc)]` is not supported. Implement `Drop` or `DeclaredClass::dealloc` for the type instead"
         )
     };
     (#[method_id(alloc)]) => {
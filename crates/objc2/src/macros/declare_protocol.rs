@@ -0,0 +1,341 @@
+/// Create a new Objective-C protocol, and a trait to represent it.
+///
+/// This is similar to [`extern_protocol!`], except that the protocol is
+/// defined by Rust instead of already existing in the runtime; it is
+/// registered with [`ProtocolBuilder`] the first time
+/// [`ProtocolType::protocol`] is called.
+///
+/// This is useful if you want to define an interface in Rust that other
+/// Objective-C code (e.g. plugins) can conform to.
+///
+/// [`extern_protocol!`]: crate::extern_protocol
+/// [`ProtocolBuilder`]: crate::runtime::ProtocolBuilder
+/// [`ProtocolType::protocol`]: crate::ProtocolType::protocol
+///
+///
+/// # Specification
+///
+/// The syntax is the same as in [`extern_protocol!`], and the macro generates
+/// the same trait and [`ProtocolType`] implementation.
+///
+/// Each method is added to the protocol with a type encoding derived from
+/// its signature. Methods with a receiver become instance methods, and
+/// methods without one become class methods. Methods marked with
+/// `#[optional]` are added as optional methods, the rest as required.
+///
+/// Supertraits are added as inherited protocols, and must therefore all be
+/// protocols themselves.
+///
+/// The protocol can then be implemented in [`declare_class!`] like any other
+/// protocol.
+///
+/// [`ProtocolType`]: crate::ProtocolType
+/// [`declare_class!`]: crate::declare_class
+///
+///
+/// # Panics
+///
+/// Registering the protocol panics if a protocol with the same name already
+/// exists in the runtime.
+///
+///
+/// # Safety
+///
+/// The same requirements as for [`extern_protocol!`] apply when implementing
+/// the `unsafe` trait for a type; the type must represent an object that
+/// implements the protocol.
+///
+///
+/// # Examples
+///
+/// ```
+/// use objc2::rc::Retained;
+/// use objc2::runtime::{NSObject, NSObjectProtocol};
+/// use objc2::{declare_protocol, ProtocolType};
+///
+/// declare_protocol!(
+///     /// A plugin that can be loaded by the application.
+///     pub unsafe trait MyPlugin: NSObjectProtocol {
+///         #[method_id(pluginName)]
+///         fn plugin_name(&self) -> Retained<NSObject>;
+///
+///         #[optional]
+///         #[method(pluginVersion)]
+///         fn plugin_version(&self) -> u32;
+///
+///         #[method_id(createPlugin)]
+///         fn create_plugin() -> Retained<Self>;
+///     }
+///
+///     unsafe impl ProtocolType for dyn MyPlugin {
+///         const NAME: &'static str = "MyPlugin";
+///     }
+/// );
+///
+/// let protocol = <dyn MyPlugin>::protocol().unwrap();
+/// assert_eq!(protocol.name().to_str(), Ok("MyPlugin"));
+/// assert!(protocol.conforms_to(<dyn NSObjectProtocol>::protocol().unwrap()));
+/// ```
+#[doc(alias = "@protocol")]
+#[macro_export]
+macro_rules! declare_protocol {
+    (
+        $(#[$m:meta])*
+        $v:vis unsafe trait $name:ident $(: $conforms_to:ident $(+ $conforms_to_rest:ident)*)? {
+            $($methods:tt)*
+        }
+
+        $(#[$impl_m:meta])*
+        unsafe impl ProtocolType for dyn $for:ident {
+            $(const NAME: &'static str = $name_const:expr;)?
+        }
+    ) => {
+        $(#[$m])*
+        $v unsafe trait $name $(: $conforms_to $(+ $conforms_to_rest)*)? {
+            $crate::__extern_protocol_rewrite_methods! {
+                $($methods)*
+            }
+        }
+
+        $crate::__inner_extern_protocol!(
+            ($(#[$impl_m])*)
+            ($name)
+            (dyn $for)
+            ($crate::__select_name!($name; $($name_const)?))
+            {
+                #[inline]
+                fn protocol() -> $crate::__macro_helpers::Option<&'static $crate::runtime::AnyProtocol> {
+                    static PROTOCOL: $crate::__macro_helpers::OnceLock<&'static $crate::runtime::AnyProtocol> =
+                        $crate::__macro_helpers::OnceLock::new();
+
+                    $crate::__macro_helpers::Some(*PROTOCOL.get_or_init(|| {
+                        #[allow(unused_mut)]
+                        let mut __objc2_builder = $crate::__macro_helpers::create_protocol_builder(
+                            <Self as $crate::ProtocolType>::NAME,
+                        );
+
+                        $(
+                            __objc2_builder.add_protocol(
+                                $crate::__macro_helpers::inherited_protocol::<dyn $conforms_to>(),
+                            );
+                            $(
+                                __objc2_builder.add_protocol(
+                                    $crate::__macro_helpers::inherited_protocol::<dyn $conforms_to_rest>(),
+                                );
+                            )*
+                        )?
+
+                        $crate::__declare_protocol_register_methods! {
+                            (__objc2_builder)
+
+                            $($methods)*
+                        }
+
+                        __objc2_builder.register()
+                    }))
+                }
+            }
+        );
+    };
+}
+
+/// tt-munch each protocol method, and add it to the builder.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_protocol_register_methods {
+    // Base case
+    {
+        ($builder:ident)
+    } => {};
+
+    // Unsafe variant
+    {
+        ($builder:ident)
+
+        $(#[$($m:tt)*])*
+        $v:vis unsafe fn $name:ident($($params:tt)*) $(-> $ret:ty)?
+        $(where $($where:ty : $bound:path),+ $(,)?)?;
+
+        $($rest:tt)*
+    } => {
+        $crate::__rewrite_self_param! {
+            ($($params)*)
+
+            ($crate::__extract_custom_attributes)
+            ($(#[$($m)*])*)
+
+            ($crate::__declare_protocol_register_out)
+            ($builder)
+            ($($ret)?)
+        }
+
+        $crate::__declare_protocol_register_methods! {
+            ($builder)
+
+            $($rest)*
+        }
+    };
+
+    // Safe variant
+    {
+        ($builder:ident)
+
+        $(#[$($m:tt)*])*
+        $v:vis fn $name:ident($($params:tt)*) $(-> $ret:ty)?
+        $(where $($where:ty : $bound:path),+ $(,)?)?;
+
+        $($rest:tt)*
+    } => {
+        $crate::__rewrite_self_param! {
+            ($($params)*)
+
+            ($crate::__extract_custom_attributes)
+            ($(#[$($m)*])*)
+
+            ($crate::__declare_protocol_register_out)
+            ($builder)
+            ($($ret)?)
+        }
+
+        $crate::__declare_protocol_register_methods! {
+            ($builder)
+
+            $($rest)*
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_protocol_register_out {
+    {
+        ($builder:ident)
+        ($($ret:ty)?)
+
+        ($builder_method:ident)
+        ($__receiver:expr)
+        ($__receiver_ty:ty)
+        ($($__params_prefix:tt)*)
+        ($($params_rest:tt)*)
+
+        (#[$method_or_method_id:ident($($sel:tt)*)])
+        ($($retain_semantics:tt)*)
+        ($($m_optional:tt)*)
+        ($($m_checked:tt)*)
+    } => {
+        $crate::__extract_and_apply_cfg_attributes! {
+            ($($m_checked)*)
+
+            $crate::__declare_protocol_arg_types! {
+                ($($params_rest)*)
+                ()
+
+                ($builder)
+                ($builder_method)
+                ($crate::__declare_protocol_return_type!(
+                    #[$method_or_method_id]
+                    $($ret)?
+                ))
+                ($crate::sel!($($sel)*))
+                ($crate::__declare_protocol_required!($($m_optional)*))
+            }
+        }
+    };
+}
+
+/// Convert the parameters to a tuple of their encoded types, and add the
+/// method description.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_protocol_arg_types {
+    {
+        (_ : $param_ty:ty $(, $($params_rest:tt)*)?)
+        ($($args:tt)*)
+
+        $($macro_args:tt)*
+    } => {
+        $crate::__declare_protocol_arg_types! {
+            ($($($params_rest)*)?)
+            ($($args)* <$param_ty as $crate::__macro_helpers::ConvertArgument>::__Inner,)
+
+            $($macro_args)*
+        }
+    };
+    {
+        (mut $param:ident : $param_ty:ty $(, $($params_rest:tt)*)?)
+        ($($args:tt)*)
+
+        $($macro_args:tt)*
+    } => {
+        $crate::__declare_protocol_arg_types! {
+            ($($($params_rest)*)?)
+            ($($args)* <$param_ty as $crate::__macro_helpers::ConvertArgument>::__Inner,)
+
+            $($macro_args)*
+        }
+    };
+    {
+        ($param:ident : $param_ty:ty $(, $($params_rest:tt)*)?)
+        ($($args:tt)*)
+
+        $($macro_args:tt)*
+    } => {
+        $crate::__declare_protocol_arg_types! {
+            ($($($params_rest)*)?)
+            ($($args)* <$param_ty as $crate::__macro_helpers::ConvertArgument>::__Inner,)
+
+            $($macro_args)*
+        }
+    };
+    // Instance method
+    {
+        ()
+        ($($args:tt)*)
+
+        ($builder:ident)
+        (add_method)
+        ($ret:ty)
+        ($sel:expr)
+        ($required:expr)
+    } => {
+        $builder.add_method_description::<($($args)*), $ret>($sel, $required);
+    };
+    // Class method
+    {
+        ()
+        ($($args:tt)*)
+
+        ($builder:ident)
+        (add_class_method)
+        ($ret:ty)
+        ($sel:expr)
+        ($required:expr)
+    } => {
+        $builder.add_class_method_description::<($($args)*), $ret>($sel, $required);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_protocol_return_type {
+    (#[method]) => {
+        ()
+    };
+    (#[method] $ret:ty) => {
+        <$ret as $crate::__macro_helpers::ConvertReturn>::__Inner
+    };
+    (#[method_id] $($ret:ty)?) => {
+        $crate::__macro_helpers::IdReturnValue
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_protocol_required {
+    () => {
+        true
+    };
+    (#[optional]) => {
+        false
+    };
+}
@@ -0,0 +1,125 @@
+//! Helper macros for the `#[declared_class]` and `#[methods]` attributes.
+//!
+//! The attributes themselves live in `objc2-proc-macros`, and are mostly
+//! concerned with validating the input and giving good error messages; the
+//! actual code generation is shared with `declare_class!`.
+
+/// Output of `#[declared_class]`.
+///
+/// This is the same as `declare_class!`, except that the `DeclaredClass`
+/// implementation and the methods are generated by `#[methods]`, and are
+/// tied together with the helper traits in `__macro_helpers`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declared_class {
+    {
+        ($(#[$m:meta])*)
+        ($($v:tt)*)
+        ($name:ident)
+        (super = $superclass:ty)
+        (inherits = $($inheritance_rest:ty),*)
+        (thread_kind = $($thread_kind:ty)?)
+        (name = $name_const:expr)
+        (protocols = $($protocol:ident),*)
+        (conflict = $($conflict:expr)?)
+    } => {
+        $crate::__declare_class_type! {
+            ($(#[$m])*)
+            ($($v)*)
+            ($name<>)
+            (impl<> $crate::ClassType, for $name)
+            (super = $superclass)
+            (inherits = $($inheritance_rest),*)
+            (thread_kind = $($thread_kind)?)
+            (name = $name_const)
+            (conflict = $($crate::runtime::ClassConflict = $conflict)?)
+            (register = |__objc2_builder| {
+                // Implement methods from the inherent `#[methods]` block, if
+                // any. The inherent associated function takes precedence
+                // over the fallback in the trait.
+                {
+                    use $crate::__macro_helpers::RegisterNoInherentMethods as _;
+                    <Self>::__objc2_only_one_inherent_methods_block_is_allowed(&mut __objc2_builder);
+                }
+
+                // Implement protocols
+                $(
+                    <Self as $crate::__macro_helpers::DeclaredProtocolMethods<dyn $protocol>>::__objc2_register(
+                        &mut __objc2_builder,
+                    );
+                )*
+            })
+        }
+
+        $(
+            impl $crate::__macro_helpers::DeclaredProtocolConformance<dyn $protocol> for $name {}
+        )*
+    };
+}
+
+/// Output of `#[methods]` on an `unsafe impl` block.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declared_class_methods {
+    // With protocol
+    {
+        $(#[$m:meta])*
+        unsafe impl $protocol:ident for $for:ty {
+            $($methods:tt)*
+        }
+    } => {
+        $crate::__declare_class_output_impls! {
+            $(#[$m])*
+            unsafe impl $protocol for $for {
+                $($methods)*
+            }
+        }
+
+        $(#[$m])*
+        impl $crate::__macro_helpers::DeclaredProtocolMethods<dyn $protocol> for $for {
+            fn __objc2_register(
+                __objc2_builder: &mut $crate::__macro_helpers::ClassBuilderHelper<Self>,
+            ) {
+                $crate::__declare_class_register_impls! {
+                    (__objc2_builder)
+
+                    unsafe impl $protocol for $for {
+                        $($methods)*
+                    }
+                }
+            }
+        }
+    };
+
+    // Without protocol
+    {
+        $(#[$m:meta])*
+        unsafe impl $for:ty {
+            $($methods:tt)*
+        }
+    } => {
+        $crate::__declare_class_output_impls! {
+            $(#[$m])*
+            unsafe impl $for {
+                $($methods)*
+            }
+        }
+
+        $(#[$m])*
+        impl $for {
+            #[doc(hidden)]
+            #[inline]
+            pub(crate) fn __objc2_only_one_inherent_methods_block_is_allowed(
+                __objc2_builder: &mut $crate::__macro_helpers::ClassBuilderHelper<Self>,
+            ) {
+                $crate::__declare_class_register_impls! {
+                    (__objc2_builder)
+
+                    unsafe impl $for {
+                        $($methods)*
+                    }
+                }
+            }
+        }
+    };
+}
@@ -26,6 +26,19 @@
 /// [`Result`]. See the error section in [`msg_send!`] and [`msg_send_id!`]
 /// for details.
 ///
+/// The retain semantics of `#[method_id(...)]` are inferred from the
+/// selector's [method family]. If that is wrong, for example for a selector
+/// like `newsletterItem`, or for a method marked with
+/// `__attribute__((objc_method_family(none)))` in the header, you can
+/// override it with `#[method_family = ...]`, where the family is one of
+/// `none`, `new`, `alloc`, `init`, `copy` or `mutableCopy`.
+///
+/// Variadic methods can be declared by naming the variadic parameter and
+/// putting it last, as in `fn stringWithFormat(format: &NSString, args: ...)`.
+/// The parameter is then exposed as an `impl VariadicArguments`, usually a
+/// tuple of the arguments. See the variadic section in [`msg_send!`] for
+/// details.
+///
 /// If you use `objc2::MainThreadMarker` as a parameter type, the macro will
 /// ignore it, allowing you to neatly specify "this method must be run on the
 /// main thread". Note that due to type-system limitations, this is currently
@@ -46,6 +59,7 @@
 ///
 /// ["associated functions"]: https://doc.rust-lang.org/reference/items/associated-items.html#methods
 /// ["methods"]: https://doc.rust-lang.org/reference/items/associated-items.html#methods
+/// [method family]: https://clang.llvm.org/docs/AutomaticReferenceCounting.html#method-families
 /// [open an issue]: https://github.com/madsmtm/objc2/issues/new
 ///
 ///
@@ -235,14 +249,14 @@ macro_rules! __extern_methods_rewrite_methods {
 
         $($rest:tt)*
     } => {
-        $crate::__rewrite_self_param! {
+        $crate::__extern_methods_rewrite_variadic! {
             ($($params)*)
+            ()
 
-            ($crate::__extract_custom_attributes)
+            ($($params)*)
             ($(#[$($m)*])*)
-
-            ($crate::__extern_methods_method_out)
-            ($v unsafe fn $name($($params)*) $(-> $ret)?)
+            ($v unsafe fn $name)
+            ($(-> $ret)?)
             ($($($where : $bound ,)+)?)
         }
 
@@ -260,14 +274,14 @@ macro_rules! __extern_methods_rewrite_methods {
 
         $($rest:tt)*
     } => {
-        $crate::__rewrite_self_param! {
+        $crate::__extern_methods_rewrite_variadic! {
             ($($params)*)
+            ()
 
-            ($crate::__extract_custom_attributes)
+            ($($params)*)
             ($(#[$($m)*])*)
-
-            ($crate::__extern_methods_method_out)
-            ($v fn $name($($params)*) $(-> $ret)?)
+            ($v fn $name)
+            ($(-> $ret)?)
             ($($($where : $bound ,)+)?)
         }
 
@@ -291,6 +305,108 @@ macro_rules! __extern_methods_rewrite_methods {
     };
 }
 
+/// Rewrite a trailing `args: ...` parameter to `args: impl VariadicArguments`
+/// in the function signature.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __extern_methods_rewrite_variadic {
+    // Variadic parameter
+    {
+        ($arg:ident : ... $(,)?)
+        ($($output:tt)*)
+
+        $($macro_args:tt)*
+    } => {
+        $crate::__extern_methods_rewrite_variadic! {
+            ()
+            ($($output)* $arg: impl $crate::encode::VariadicArguments)
+
+            $($macro_args)*
+        }
+    };
+
+    // Receivers. Note that we capture `self` as an identifier, since it
+    // must keep the hygiene of the input.
+    {
+        (& $($lifetime:lifetime)? $self:ident $(, $($rest:tt)*)?)
+        ($($output:tt)*)
+
+        $($macro_args:tt)*
+    } => {
+        $crate::__extern_methods_rewrite_variadic! {
+            ($($($rest)*)?)
+            ($($output)* & $($lifetime)? $self,)
+
+            $($macro_args)*
+        }
+    };
+    {
+        (& $($lifetime:lifetime)? mut $self:ident $(, $($rest:tt)*)?)
+        ($($output:tt)*)
+
+        $($macro_args:tt)*
+    } => {
+        $crate::__extern_methods_rewrite_variadic! {
+            ($($($rest)*)?)
+            ($($output)* & $($lifetime)? mut $self,)
+
+            $($macro_args)*
+        }
+    };
+    {
+        ($(mut)? $self:ident $(, $($rest:tt)*)?)
+        ($($output:tt)*)
+
+        $($macro_args:tt)*
+    } => {
+        $crate::__extern_methods_rewrite_variadic! {
+            ($($($rest)*)?)
+            ($($output)* $self,)
+
+            $($macro_args)*
+        }
+    };
+
+    // Normal parameters
+    {
+        ($(mut)? $param:ident : $param_ty:ty $(, $($rest:tt)*)?)
+        ($($output:tt)*)
+
+        $($macro_args:tt)*
+    } => {
+        $crate::__extern_methods_rewrite_variadic! {
+            ($($($rest)*)?)
+            ($($output)* $param: $param_ty,)
+
+            $($macro_args)*
+        }
+    };
+
+    // Done, or something we don't know how to handle; output the rest
+    // unchanged, and let the rest of the macro handle any errors.
+    {
+        ($($rest:tt)*)
+        ($($output:tt)*)
+
+        ($($params:tt)*)
+        ($($m:tt)*)
+        ($($function_start:tt)*)
+        ($($function_end:tt)*)
+        ($($where:tt)*)
+    } => {
+        $crate::__rewrite_self_param! {
+            ($($params)*)
+
+            ($crate::__extract_custom_attributes)
+            ($($m)*)
+
+            ($crate::__extern_methods_method_out)
+            ($($function_start)*($($output)* $($rest)*) $($function_end)*)
+            ($($where)*)
+        }
+    };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! __extern_methods_method_out {
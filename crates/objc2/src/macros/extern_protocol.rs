@@ -14,6 +14,9 @@
 /// Note that that conforming to a protocol in a custom object requires
 /// putting the implementation inside the [`declare_class!`] invocation.
 ///
+/// This assumes that the protocol already exists in the runtime; use
+/// [`declare_protocol!`] to define a new protocol from Rust.
+///
 /// Objective-C has a smart feature where you can write `id<MyProtocol>`, and
 /// then work with the protocol as-if it was an object; this is very similar
 /// to `dyn` traits in Rust, and we implement it in a similar way, see
@@ -32,7 +35,9 @@
 ///
 /// This macro creates an `unsafe` trait with the specified methods. A default
 /// implementation of the method is generated based on the selector specified
-/// with `#[method(a:selector:)]` or `#[method_id(a:selector:)]`.
+/// with `#[method(a:selector:)]` or `#[method_id(a:selector:)]`. Like in
+/// [`extern_methods!`], the retain semantics of `#[method_id(...)]` can be
+/// overridden with `#[method_family = ...]`.
 ///
 /// Other protocols that this protocol conforms to / inherits can be specified
 /// as supertraits.
@@ -51,6 +56,7 @@
 /// [`ProtocolObject<dyn T>`]: crate::runtime::ProtocolObject
 /// [`ProtocolType`]: crate::ProtocolType
 /// [`declare_class!`]: crate::declare_class
+/// [`declare_protocol!`]: crate::declare_protocol
 /// [`extern_class!`]: crate::extern_class
 /// [`extern_methods!`]: crate::extern_methods
 ///
@@ -185,6 +191,7 @@ macro_rules! __inner_extern_protocol {
         ($name:ident)
         (dyn $for:ident)
         ($name_str:expr)
+        $({ $($protocol_items:tt)* })?
     ) => {
         $(#[$impl_m])*
         unsafe impl<T> $name for $crate::runtime::ProtocolObject<T>
@@ -198,6 +205,8 @@ macro_rules! __inner_extern_protocol {
         unsafe impl ProtocolType for dyn $for {
             const NAME: &'static $crate::__macro_helpers::str = $name_str;
             const __INNER: () = ();
+
+            $($($protocol_items)*)?
         }
 
         // SAFETY: Anything that implements the protocol is valid to convert
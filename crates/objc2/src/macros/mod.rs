@@ -3,7 +3,10 @@ mod __method_msg_send;
 mod __msg_send_parse;
 mod __rewrite_self_param;
 mod available;
+mod declare_category;
 mod declare_class;
+mod declare_protocol;
+mod declared_class;
 mod extern_category;
 mod extern_class;
 mod extern_methods;
@@ -827,6 +830,28 @@ macro_rules! __class_inner {
 /// [swift-error]: https://developer.apple.com/documentation/swift/about-imported-cocoa-error-parameters
 ///
 ///
+/// # Variadic methods
+///
+/// Methods like `stringWithFormat:` that take a variable number of arguments
+/// can be called by putting `...varargs` after the last `selector: argument`
+/// pair, where `varargs` is a tuple of the variadic arguments, as in
+/// `msg_send![cls, stringWithFormat: format, ...(x, y)]`.
+///
+/// The variadic arguments must implement [`EncodeVariadic`], which notably
+/// excludes types that would be affected by C's default argument promotion
+/// (such as `u8`, `f32` and `bool`). Cast these to `c_int` or `f64` first.
+///
+/// For `nil`-terminated lists such as in `arrayWithObjects:`, wrap the tuple
+/// in [`NilTerminated`].
+///
+/// Only the non-variadic arguments are checked against the method's
+/// type-encoding when debug assertions are enabled, so take extra care that
+/// the variadic arguments have the types that the method expects.
+///
+/// [`EncodeVariadic`]: crate::encode::EncodeVariadic
+/// [`NilTerminated`]: crate::encode::NilTerminated
+///
+///
 /// # Panics
 ///
 /// Unwinds if the underlying method throws and exception. If the
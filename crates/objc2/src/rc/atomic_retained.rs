@@ -0,0 +1,453 @@
+//! Atomically swappable `Retained`.
+//!
+//! A plain `AtomicPtr` is not enough, since a thread could load the pointer
+//! and then be preempted before retaining it, while another thread swaps in
+//! a new object and releases the old one, leaving the first thread with a
+//! dangling pointer.
+//!
+//! Instead, loads announce themselves in one of two reader counters, and
+//! writers wait for the loads that may have seen the old pointer to finish
+//! before releasing it. Writers flip which counter new loads use before
+//! waiting on the other one, so that a steady stream of loads cannot starve
+//! them; this is the same scheme as the classic userspace RCU.
+//!
+//! Loads are thus lock-free, while writers are serialized, and may have to
+//! wait for concurrent loads.
+use core::fmt;
+use core::hint;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use super::Retained;
+use crate::Message;
+
+/// An atomic, optional [`Retained`], that can be shared between threads.
+///
+/// This is the equivalent of `Mutex<Option<Retained<T>>>`, except that
+/// [`load`] never blocks, which makes it suitable for values that are read
+/// often, but only replaced occasionally, like configuration objects. It is
+/// similar to the `ArcSwapOption` type from the `arc-swap` crate.
+///
+/// Like [`Retained`], this is [`Send`] and [`Sync`] only if `T` is both, so
+/// objects that are only usable on the main thread cannot be shared this way.
+///
+/// See [`AtomicRetained`] for a version that is never empty.
+///
+/// [`load`]: Self::load
+///
+///
+/// # Examples
+///
+/// ```
+/// use objc2::rc::AtomicOptionRetained;
+/// use objc2::runtime::NSObject;
+///
+/// let slot = AtomicOptionRetained::<NSObject>::default();
+/// assert!(slot.load().is_none());
+///
+/// let obj = NSObject::new();
+/// slot.store(Some(obj.clone()));
+/// assert_eq!(slot.load(), Some(obj));
+/// ```
+pub struct AtomicOptionRetained<T: Message> {
+    ptr: AtomicPtr<T>,
+    /// The number of loads in progress, for each parity of `epoch`.
+    readers: [AtomicUsize; 2],
+    /// Selects which of `readers` new loads use.
+    epoch: AtomicUsize,
+    /// Serializes writers.
+    writer: Mutex<()>,
+    /// Owns a `Retained<T>`.
+    item: PhantomData<Retained<T>>,
+}
+
+// SAFETY: Same as `Retained<T>`, the object is loaded and released from
+// arbitrary threads.
+unsafe impl<T: Message + Send + Sync> Send for AtomicOptionRetained<T> {}
+// SAFETY: Same as `Retained<T>`.
+unsafe impl<T: Message + Send + Sync> Sync for AtomicOptionRetained<T> {}
+
+impl<T: Message> AtomicOptionRetained<T> {
+    /// Create a new slot containing the given object.
+    #[inline]
+    pub fn new(value: Option<Retained<T>>) -> Self {
+        Self {
+            ptr: AtomicPtr::new(into_ptr(value)),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            epoch: AtomicUsize::new(0),
+            writer: Mutex::new(()),
+            item: PhantomData,
+        }
+    }
+
+    /// Retain and return the current object.
+    ///
+    /// This never blocks, even if other threads are replacing the object.
+    #[doc(alias = "objc_retain")]
+    pub fn load(&self) -> Option<Retained<T>> {
+        let readers = &self.readers[self.epoch.load(Ordering::SeqCst) % 2];
+        readers.fetch_add(1, Ordering::SeqCst);
+        let ptr = self.ptr.load(Ordering::SeqCst);
+        // SAFETY: Writers wait for the reader count to drop before releasing
+        // the object, so it is still alive.
+        let obj = unsafe { Retained::retain(ptr) };
+        readers.fetch_sub(1, Ordering::SeqCst);
+        obj
+    }
+
+    /// Replace the object, releasing the previous one.
+    ///
+    /// This may wait for concurrent [`load`][Self::load]s to finish.
+    #[inline]
+    pub fn store(&self, value: Option<Retained<T>>) {
+        drop(self.swap(value));
+    }
+
+    /// Replace the object, and return the previous one.
+    ///
+    /// This may wait for concurrent [`load`][Self::load]s to finish.
+    pub fn swap(&self, value: Option<Retained<T>>) -> Option<Retained<T>> {
+        let _guard = self.writer.lock().unwrap_or_else(|err| err.into_inner());
+        let old = self.ptr.swap(into_ptr(value), Ordering::SeqCst);
+        self.wait_for_readers();
+        // SAFETY: The slot owned a +1 retain count of the old object, and no
+        // loads are using it anymore.
+        unsafe { Retained::from_raw(old) }
+    }
+
+    /// Replace the object with `new` if the current object is `current`,
+    /// comparing by pointer.
+    ///
+    /// On success, the previous object is returned. On failure, `new` is
+    /// given back.
+    ///
+    /// This may wait for concurrent [`load`][Self::load]s to finish.
+    #[allow(clippy::missing_errors_doc)] // Written differently in the docs
+    pub fn compare_exchange(
+        &self,
+        current: Option<&T>,
+        new: Option<Retained<T>>,
+    ) -> Result<Option<Retained<T>>, Option<Retained<T>>> {
+        let _guard = self.writer.lock().unwrap_or_else(|err| err.into_inner());
+        let current: *mut T = current.map_or(ptr::null_mut(), |obj| obj as *const T as *mut T);
+        let new = into_ptr(new);
+        match self
+            .ptr
+            .compare_exchange(current, new, Ordering::SeqCst, Ordering::SeqCst)
+        {
+            Ok(old) => {
+                self.wait_for_readers();
+                // SAFETY: Same as in `swap`.
+                Ok(unsafe { Retained::from_raw(old) })
+            }
+            // SAFETY: `new` was not stored, so we still own it.
+            Err(_) => Err(unsafe { Retained::from_raw(new) }),
+        }
+    }
+
+    /// Consume the slot, and return the object.
+    #[inline]
+    pub fn into_inner(mut self) -> Option<Retained<T>> {
+        let ptr = mem::replace(self.ptr.get_mut(), ptr::null_mut());
+        // SAFETY: The slot owned a +1 retain count, and has been emptied.
+        unsafe { Retained::from_raw(ptr) }
+    }
+
+    /// Wait for the loads that may have seen the previous pointer.
+    ///
+    /// Must be called with the writer lock held, after the pointer has been
+    /// replaced.
+    fn wait_for_readers(&self) {
+        // Loads that started before the pointer was replaced could be
+        // counted in either of the counters. Flip the epoch before waiting
+        // on each, so that new loads are counted in the other one.
+        for _ in 0..2 {
+            let epoch = self.epoch.fetch_add(1, Ordering::SeqCst);
+            let readers = &self.readers[epoch % 2];
+            let mut spins = 0u32;
+            while readers.load(Ordering::SeqCst) != 0 {
+                if spins < 100 {
+                    spins += 1;
+                    hint::spin_loop();
+                } else {
+                    std::thread::yield_now();
+                }
+            }
+        }
+    }
+}
+
+fn into_ptr<T: Message>(value: Option<Retained<T>>) -> *mut T {
+    value.map_or(ptr::null_mut(), Retained::into_raw)
+}
+
+impl<T: Message> Drop for AtomicOptionRetained<T> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: The slot owns a +1 retain count, and there are no loads in
+        // progress, since we have `&mut self`.
+        drop(unsafe { Retained::from_raw(*self.ptr.get_mut()) });
+    }
+}
+
+impl<T: Message> Default for AtomicOptionRetained<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl<T: Message> From<Option<Retained<T>>> for AtomicOptionRetained<T> {
+    #[inline]
+    fn from(value: Option<Retained<T>>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Message + fmt::Debug> fmt::Debug for AtomicOptionRetained<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicOptionRetained")
+            .field(&self.load())
+            .finish()
+    }
+}
+
+/// An atomic [`Retained`], that can be shared between threads.
+///
+/// This is the same as [`AtomicOptionRetained`], except that it always
+/// contains an object. It is similar to the `ArcSwap` type from the
+/// `arc-swap` crate.
+///
+///
+/// # Examples
+///
+/// Share a configuration object that may be replaced while it is being read.
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// use objc2::rc::{AtomicRetained, Retained};
+/// use objc2::runtime::NSObject;
+/// # use objc2::{declare_class, ClassType, DeclaredClass};
+/// #
+/// # declare_class!(
+/// #     struct Config;
+/// #
+/// #     unsafe impl ClassType for Config {
+/// #         type Super = NSObject;
+/// #         const NAME: &'static str = "AtomicRetainedExampleConfig";
+/// #     }
+/// #
+/// #     impl DeclaredClass for Config {}
+/// # );
+/// #
+/// # // SAFETY: The class has no state.
+/// # unsafe impl Send for Config {}
+/// # unsafe impl Sync for Config {}
+/// #
+/// # impl Config {
+/// #     fn new() -> Retained<Self> {
+/// #         unsafe { objc2::msg_send_id![Self::class(), new] }
+/// #     }
+/// # }
+///
+/// let config = Arc::new(AtomicRetained::new(Config::new()));
+///
+/// let reader = thread::spawn({
+///     let config = Arc::clone(&config);
+///     move || {
+///         for _ in 0..100 {
+///             let current: Retained<Config> = config.load();
+///             // Use `current` for this frame.
+///         }
+///     }
+/// });
+///
+/// config.store(Config::new());
+/// reader.join().unwrap();
+/// ```
+pub struct AtomicRetained<T: Message> {
+    inner: AtomicOptionRetained<T>,
+}
+
+impl<T: Message> AtomicRetained<T> {
+    /// Create a new slot containing the given object.
+    #[inline]
+    pub fn new(value: Retained<T>) -> Self {
+        Self {
+            inner: AtomicOptionRetained::new(Some(value)),
+        }
+    }
+
+    /// Retain and return the current object.
+    ///
+    /// This never blocks, even if other threads are replacing the object.
+    #[doc(alias = "objc_retain")]
+    #[inline]
+    pub fn load(&self) -> Retained<T> {
+        // SAFETY: The slot always contains an object.
+        unsafe { self.inner.load().unwrap_unchecked() }
+    }
+
+    /// Replace the object, releasing the previous one.
+    ///
+    /// This may wait for concurrent [`load`][Self::load]s to finish.
+    #[inline]
+    pub fn store(&self, value: Retained<T>) {
+        self.inner.store(Some(value));
+    }
+
+    /// Replace the object, and return the previous one.
+    ///
+    /// This may wait for concurrent [`load`][Self::load]s to finish.
+    #[inline]
+    pub fn swap(&self, value: Retained<T>) -> Retained<T> {
+        // SAFETY: The slot always contains an object.
+        unsafe { self.inner.swap(Some(value)).unwrap_unchecked() }
+    }
+
+    /// Replace the object with `new` if the current object is `current`,
+    /// comparing by pointer.
+    ///
+    /// On success, the previous object is returned. On failure, `new` is
+    /// given back.
+    ///
+    /// This may wait for concurrent [`load`][Self::load]s to finish.
+    #[allow(clippy::missing_errors_doc)] // Written differently in the docs
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: &T,
+        new: Retained<T>,
+    ) -> Result<Retained<T>, Retained<T>> {
+        // SAFETY: The slot always contains an object, and `new` is given
+        // back unchanged on failure.
+        match self.inner.compare_exchange(Some(current), Some(new)) {
+            Ok(old) => Ok(unsafe { old.unwrap_unchecked() }),
+            Err(new) => Err(unsafe { new.unwrap_unchecked() }),
+        }
+    }
+
+    /// Consume the slot, and return the object.
+    #[inline]
+    pub fn into_inner(self) -> Retained<T> {
+        // SAFETY: The slot always contains an object.
+        unsafe { self.inner.into_inner().unwrap_unchecked() }
+    }
+}
+
+impl<T: Message> From<Retained<T>> for AtomicRetained<T> {
+    #[inline]
+    fn from(value: Retained<T>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Message + fmt::Debug> fmt::Debug for AtomicRetained<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicRetained").field(&self.load()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use std::sync::Arc;
+    use std::thread;
+
+    use static_assertions::{assert_impl_all, assert_not_impl_any};
+
+    use super::*;
+    use crate::rc::{RcTestObject, ThreadTestData};
+    use crate::runtime::NSObject;
+
+    #[test]
+    fn auto_traits() {
+        assert_impl_all!(AtomicRetained<RcTestObject>: Send, Sync);
+        assert_impl_all!(AtomicOptionRetained<RcTestObject>: Send, Sync);
+        assert_not_impl_any!(AtomicRetained<NSObject>: Send, Sync);
+        assert_not_impl_any!(AtomicOptionRetained<NSObject>: Send, Sync);
+    }
+
+    #[test]
+    fn test_load_store() {
+        let obj = RcTestObject::new();
+        let mut expected = ThreadTestData::current();
+
+        let slot = AtomicOptionRetained::new(Some(obj.clone()));
+        expected.retain += 1;
+        expected.assert_current();
+
+        let loaded = slot.load().unwrap();
+        assert_eq!(loaded, obj);
+        expected.retain += 1;
+        expected.assert_current();
+        drop(loaded);
+        expected.release += 1;
+        expected.assert_current();
+
+        slot.store(None);
+        expected.release += 1;
+        expected.assert_current();
+        assert!(slot.load().is_none());
+
+        slot.store(Some(obj.clone()));
+        expected.retain += 1;
+        drop(slot);
+        expected.release += 1;
+        expected.assert_current();
+
+        drop(obj);
+        expected.release += 1;
+        expected.drop += 1;
+        expected.assert_current();
+    }
+
+    #[test]
+    fn test_swap_and_compare_exchange() {
+        let a = RcTestObject::new();
+        let b = RcTestObject::new();
+        let slot = AtomicRetained::new(a.clone());
+
+        let old = slot.swap(b.clone());
+        assert_eq!(old, a);
+
+        let new = slot.compare_exchange(&a, a.clone()).unwrap_err();
+        assert_eq!(new, a);
+        assert_eq!(slot.load(), b);
+
+        let old = slot.compare_exchange(&b, a.clone()).unwrap();
+        assert_eq!(old, b);
+        assert_eq!(slot.into_inner(), a);
+    }
+
+    #[test]
+    fn test_concurrent() {
+        let slot = Arc::new(AtomicRetained::new(RcTestObject::new()));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let slot = Arc::clone(&slot);
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        let obj = slot.load();
+                        let _ = obj.clone();
+                    }
+                })
+            })
+            .collect();
+
+        for _ in 0..100 {
+            slot.store(RcTestObject::new());
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        let slot = Arc::try_unwrap(slot).ok().unwrap();
+        let _obj: Retained<RcTestObject> = slot.into_inner();
+    }
+}
@@ -0,0 +1,677 @@
+//! Checking that objects are deallocated.
+//!
+//! Allocations and deallocations are observed by replacing `+allocWithZone:`
+//! and `-dealloc` on the root class of each tracked class (usually
+//! `NSObject`). Only the root class is hooked, since every `-dealloc` must
+//! eventually call the root class' implementation, and since hooking the
+//! same method on both a class and its superclass would make it impossible
+//! to know which original implementation to forward to.
+//!
+//! The hooks are installed the first time a class with that root is tracked,
+//! and are never removed again. When no scopes are active on any thread,
+//! they only do an atomic load and a lock-free lookup of the original
+//! implementation before forwarding to it.
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use super::{autoreleasepool, AutoreleasePool, AutoreleaseSafe};
+use crate::ffi::NSUInteger;
+use crate::runtime::{AnyClass, AnyObject, Imp, MethodImplementation, NSZone, Sel};
+use crate::{msg_send, sel};
+
+/// An object that was tracked by a [`LeakScope`] and is still alive.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Leak {
+    object: *const AnyObject,
+    class_name: String,
+    retain_count: Option<usize>,
+}
+
+impl Leak {
+    /// The object that is still alive.
+    #[inline]
+    pub fn object(&self) -> *const AnyObject {
+        self.object
+    }
+
+    /// The name of the object's class.
+    #[inline]
+    pub fn class_name(&self) -> &str {
+        &self.class_name
+    }
+
+    /// The retain count of the object when the leak was found, or [`None`]
+    /// if it does not respond to `retainCount`.
+    #[inline]
+    pub fn retain_count(&self) -> Option<usize> {
+        self.retain_count
+    }
+}
+
+impl fmt::Display for Leak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {:?}", self.class_name, self.object)?;
+        if let Some(retain_count) = self.retain_count {
+            write!(f, " (retain count {retain_count})")?;
+        }
+        Ok(())
+    }
+}
+
+/// A scope that tracks the instances of some classes allocated on the
+/// current thread, and panics if they are still alive when it ends.
+///
+/// Instances of subclasses of the given classes are tracked as well.
+/// Objects allocated on other threads are not tracked, but objects that are
+/// allocated on this thread and then deallocated on another are.
+///
+/// Allocations are observed through `+allocWithZone:`, which is what
+/// `+alloc` and `+new` use, so objects created in other ways (e.g. with
+/// `class_createInstance`) are not tracked.
+///
+/// Use [`LeakScope::declared`] to track the instances of all classes
+/// declared with [`declare_class!`] instead.
+///
+/// See also [`assert_deallocated`], which drains an autorelease pool before
+/// checking.
+///
+/// [`declare_class!`]: crate::declare_class
+///
+///
+/// # Panics
+///
+/// When dropped, this panics if any of the tracked objects are still alive,
+/// unless the thread is already panicking. Use [`into_leaks`] to end the
+/// scope without panicking.
+///
+/// [`into_leaks`]: Self::into_leaks
+///
+///
+/// # Examples
+///
+/// ```
+/// use objc2::rc::LeakScope;
+/// use objc2::runtime::NSObject;
+/// use objc2::ClassType;
+///
+/// let scope = LeakScope::new(&[NSObject::class()]);
+/// let obj = NSObject::new();
+/// assert_eq!(scope.leaks().len(), 1);
+/// drop(obj);
+/// assert!(scope.leaks().is_empty());
+/// // The scope ends here, and panics if `obj` was still alive.
+/// ```
+pub struct LeakScope {
+    id: usize,
+    /// Tracks allocations on the current thread.
+    _p: PhantomData<*mut ()>,
+}
+
+impl LeakScope {
+    /// Start tracking instances of the given classes.
+    pub fn new(classes: &[&AnyClass]) -> Self {
+        for cls in classes {
+            install_hooks(root_of(cls));
+        }
+        let classes = classes.iter().map(|cls| address(cls)).collect();
+        Self::start(classes, false)
+    }
+
+    /// Start tracking instances of all classes declared with
+    /// [`declare_class!`], including those that are only declared after the
+    /// scope has started.
+    ///
+    /// [`declare_class!`]: crate::declare_class
+    ///
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use objc2::rc::{LeakScope, Retained};
+    /// use objc2::runtime::NSObject;
+    /// use objc2::{declare_class, msg_send_id, ClassType, DeclaredClass};
+    ///
+    /// declare_class!(
+    ///     struct MyObject;
+    ///
+    ///     unsafe impl ClassType for MyObject {
+    ///         type Super = NSObject;
+    ///         const NAME: &'static str = "LeakScopeDeclaredExample";
+    ///     }
+    ///
+    ///     impl DeclaredClass for MyObject {}
+    /// );
+    ///
+    /// let scope = LeakScope::declared();
+    /// let obj: Retained<MyObject> = unsafe { msg_send_id![MyObject::class(), new] };
+    /// // `NSObject` is not declared with `declare_class!`.
+    /// let _untracked = NSObject::new();
+    /// assert_eq!(scope.leaks().len(), 1);
+    /// drop(obj);
+    /// ```
+    pub fn declared() -> Self {
+        hook_declared_classes();
+        Self::start(Vec::new(), true)
+    }
+
+    fn start(classes: Vec<usize>, declared: bool) -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+        ACTIVE_SCOPES.fetch_add(1, Ordering::AcqRel);
+        SCOPES.with(|scopes| {
+            scopes.borrow_mut().push(Scope {
+                id,
+                classes,
+                declared,
+            })
+        });
+
+        Self {
+            id,
+            _p: PhantomData,
+        }
+    }
+
+    /// The tracked objects that are still alive.
+    pub fn leaks(&self) -> Vec<Leak> {
+        // Keep the lock while inspecting the objects, since the `dealloc`
+        // hook takes it before deallocating them (possibly on another
+        // thread).
+        let live = live_objects();
+        live.iter()
+            .filter(|(_, ids)| ids.contains(&self.id))
+            .map(|(obj, _)| {
+                // SAFETY: The object is alive, since it is removed from the
+                // list when deallocated, which cannot happen while we hold
+                // the lock.
+                let obj = unsafe { &*(*obj as *const AnyObject) };
+                let cls = obj.class();
+                let retain_count = if cls.responds_to(sel!(retainCount)) {
+                    // SAFETY: The object responds to `retainCount`, which
+                    // returns `NSUInteger`.
+                    let count: NSUInteger = unsafe { msg_send![obj, retainCount] };
+                    Some(count)
+                } else {
+                    None
+                };
+                Leak {
+                    object: obj,
+                    class_name: cls.name().to_string_lossy().into_owned(),
+                    retain_count,
+                }
+            })
+            .collect()
+    }
+
+    /// End the scope, and return the tracked objects that are still alive
+    /// instead of panicking.
+    pub fn into_leaks(self) -> Vec<Leak> {
+        let leaks = self.leaks();
+        self.end();
+        mem::forget(self);
+        leaks
+    }
+
+    fn end(&self) {
+        // The thread-local may have been destroyed if this is dropped in the
+        // destructor of another thread-local.
+        let _ = SCOPES.try_with(|scopes| scopes.borrow_mut().retain(|scope| scope.id != self.id));
+        live_objects().retain(|_, ids| {
+            ids.retain(|id| *id != self.id);
+            !ids.is_empty()
+        });
+        // Only after the objects have been removed, since the `dealloc` hook
+        // skips looking at them when no scopes are active.
+        ACTIVE_SCOPES.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Drop for LeakScope {
+    fn drop(&mut self) {
+        let leaks = self.leaks();
+        self.end();
+        if !leaks.is_empty() && !std::thread::panicking() {
+            panic!("{}", leak_message(&leaks));
+        }
+    }
+}
+
+impl fmt::Debug for LeakScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LeakScope")
+            .field("leaks", &self.leaks())
+            .finish_non_exhaustive()
+    }
+}
+
+fn leak_message(leaks: &[Leak]) -> String {
+    let mut msg = format!("{} object(s) were not deallocated:", leaks.len());
+    for leak in leaks {
+        msg.push_str(&format!("\n- {leak}"));
+    }
+    msg
+}
+
+/// Run the closure inside an autorelease pool, and panic if any instances
+/// of the given classes that were allocated inside it are still alive after
+/// the pool has been drained.
+///
+/// This is useful for testing that objects are not leaked, e.g. through a
+/// retain cycle. See [`LeakScope`] for details on what is tracked.
+///
+///
+/// # Panics
+///
+/// Panics with the class and retain count of each object that is still
+/// alive.
+///
+///
+/// # Examples
+///
+/// ```
+/// use objc2::rc::{assert_deallocated, Retained};
+/// use objc2::runtime::NSObject;
+/// use objc2::ClassType;
+///
+/// assert_deallocated(&[NSObject::class()], |_pool| {
+///     let obj = NSObject::new();
+///     // Released when the pool is drained.
+///     let _ = Retained::autorelease_ptr(obj);
+/// });
+/// ```
+#[track_caller]
+pub fn assert_deallocated<T, F>(classes: &[&AnyClass], f: F) -> T
+where
+    for<'pool> F: AutoreleaseSafe + FnOnce(AutoreleasePool<'pool>) -> T,
+{
+    check_deallocated(LeakScope::new(classes), f)
+}
+
+/// Same as [`assert_deallocated`], but checks the instances of all classes
+/// declared with [`declare_class!`], see [`LeakScope::declared`].
+///
+/// [`declare_class!`]: crate::declare_class
+///
+///
+/// # Panics
+///
+/// Panics with the class and retain count of each object that is still
+/// alive.
+#[track_caller]
+pub fn assert_declared_deallocated<T, F>(f: F) -> T
+where
+    for<'pool> F: AutoreleaseSafe + FnOnce(AutoreleasePool<'pool>) -> T,
+{
+    check_deallocated(LeakScope::declared(), f)
+}
+
+#[track_caller]
+fn check_deallocated<T, F>(scope: LeakScope, f: F) -> T
+where
+    for<'pool> F: AutoreleaseSafe + FnOnce(AutoreleasePool<'pool>) -> T,
+{
+    let res = autoreleasepool(f);
+    let leaks = scope.into_leaks();
+    if !leaks.is_empty() {
+        panic!("{}", leak_message(&leaks));
+    }
+    res
+}
+
+struct Scope {
+    id: usize,
+    classes: Vec<usize>,
+    /// Whether all classes declared with `declare_class!` are tracked.
+    declared: bool,
+}
+
+impl Scope {
+    /// Whether the scope tracks instances of the class.
+    ///
+    /// `declared` caches whether the class is declared with
+    /// `declare_class!` between scopes, since finding that out takes a lock.
+    fn tracks(&self, cls: &AnyClass, declared: &mut Option<bool>) -> bool {
+        if self.declared && *declared.get_or_insert_with(|| is_declared(cls)) {
+            return true;
+        }
+        core::iter::once(cls)
+            .chain(cls.superclasses())
+            .any(|cls| self.classes.contains(&address(cls)))
+    }
+}
+
+std::thread_local! {
+    /// The active scopes on the current thread.
+    static SCOPES: RefCell<Vec<Scope>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The number of active scopes on all threads.
+static ACTIVE_SCOPES: AtomicUsize = AtomicUsize::new(0);
+
+/// The tracked objects that are alive, along with the scopes that track
+/// them.
+static LIVE_OBJECTS: OnceLock<Mutex<HashMap<usize, Vec<usize>>>> = OnceLock::new();
+
+fn live_objects() -> MutexGuard<'static, HashMap<usize, Vec<usize>>> {
+    LIVE_OBJECTS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+}
+
+/// A hooked root class, and the original implementations of its
+/// `+allocWithZone:` and `-dealloc`.
+///
+/// These are never modified once added to `ROOT_CLASSES`, and are leaked,
+/// such that the hooks can find them without taking a lock.
+struct RootClass {
+    cls: usize,
+    alloc_with_zone: Imp,
+    dealloc: Imp,
+    next: Option<&'static RootClass>,
+}
+
+/// The head of the list of hooked root classes.
+static ROOT_CLASSES: AtomicPtr<RootClass> = AtomicPtr::new(ptr::null_mut());
+
+/// Taken while installing hooks, to avoid hooking a root class twice.
+static INSTALL_HOOKS: Mutex<()> = Mutex::new(());
+
+/// The classes declared with `declare_class!`.
+static DECLARED_CLASSES: Mutex<DeclaredClasses> = Mutex::new(DeclaredClasses {
+    classes: Vec::new(),
+    hooked: false,
+});
+
+struct DeclaredClasses {
+    classes: Vec<&'static AnyClass>,
+    /// Whether the root classes of the declared classes should be hooked,
+    /// i.e. whether [`LeakScope::declared`] has been used.
+    hooked: bool,
+}
+
+/// Record a class that was declared with `declare_class!` (or reused in its
+/// place), for [`LeakScope::declared`].
+pub(crate) fn record_declared_class(cls: &'static AnyClass) {
+    let mut declared = DECLARED_CLASSES
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    declared.classes.push(cls);
+    if declared.hooked {
+        install_hooks(root_of(cls));
+    }
+}
+
+fn hook_declared_classes() {
+    let mut declared = DECLARED_CLASSES
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    if !declared.hooked {
+        declared.hooked = true;
+        for cls in &declared.classes {
+            install_hooks(root_of(cls));
+        }
+    }
+}
+
+fn is_declared(cls: &AnyClass) -> bool {
+    let declared = DECLARED_CLASSES
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    core::iter::once(cls)
+        .chain(cls.superclasses())
+        .any(|cls| declared.classes.iter().any(|declared| *declared == cls))
+}
+
+fn address(cls: &AnyClass) -> usize {
+    let ptr: *const AnyClass = cls;
+    ptr as usize
+}
+
+fn root_of(cls: &AnyClass) -> &AnyClass {
+    cls.superclasses().last().unwrap_or(cls)
+}
+
+fn root_classes() -> impl Iterator<Item = &'static RootClass> {
+    // SAFETY: The pointer is either NULL or a leaked `RootClass`.
+    let mut next = unsafe { ROOT_CLASSES.load(Ordering::Acquire).as_ref() };
+    core::iter::from_fn(move || {
+        let root = next?;
+        next = root.next;
+        Some(root)
+    })
+}
+
+fn original(cls: &AnyClass) -> &'static RootClass {
+    let root = address(root_of(cls));
+    root_classes()
+        .find(|hooked| hooked.cls == root)
+        .expect("root class was not hooked")
+}
+
+fn install_hooks(root: &AnyClass) {
+    let _guard = INSTALL_HOOKS.lock().unwrap_or_else(|err| err.into_inner());
+    if root_classes().any(|hooked| hooked.cls == address(root)) {
+        return;
+    }
+
+    let alloc_method = root
+        .metaclass()
+        .instance_method(sel!(allocWithZone:))
+        .unwrap_or_else(|| panic!("root class {root} does not implement +allocWithZone:"));
+    let dealloc_method = root
+        .instance_method(sel!(dealloc))
+        .unwrap_or_else(|| panic!("root class {root} does not implement -dealloc"));
+
+    // Record the originals before installing the hooks, so that the hooks
+    // can always find them.
+    let hooked: &'static RootClass = Box::leak(Box::new(RootClass {
+        cls: address(root),
+        alloc_with_zone: alloc_method.implementation(),
+        dealloc: dealloc_method.implementation(),
+        next: root_classes().next(),
+    }));
+    ROOT_CLASSES.store(
+        hooked as *const RootClass as *mut RootClass,
+        Ordering::Release,
+    );
+
+    let alloc_hook: unsafe extern "C-unwind" fn(_, _, _) -> _ = alloc_with_zone;
+    let dealloc_hook: unsafe extern "C-unwind" fn(_, _) = dealloc;
+    // SAFETY: The hooks have the same signature as the methods they
+    // replace, and forward to the original implementations.
+    unsafe {
+        alloc_method.set_implementation(alloc_hook.__imp());
+        dealloc_method.set_implementation(dealloc_hook.__imp());
+    }
+}
+
+type AllocWithZone = unsafe extern "C-unwind" fn(&AnyClass, Sel, *mut NSZone) -> *mut AnyObject;
+type Dealloc = unsafe extern "C-unwind" fn(*mut AnyObject, Sel);
+
+unsafe extern "C-unwind" fn alloc_with_zone(
+    cls: &AnyClass,
+    cmd: Sel,
+    zone: *mut NSZone,
+) -> *mut AnyObject {
+    let original = original(cls).alloc_with_zone;
+    // SAFETY: The original implementation has this signature.
+    let original = unsafe { mem::transmute::<Imp, AllocWithZone>(original) };
+    // SAFETY: Forwarding the arguments that we were called with.
+    let obj = unsafe { original(cls, cmd, zone) };
+
+    if !obj.is_null() && ACTIVE_SCOPES.load(Ordering::Acquire) != 0 {
+        let _ = SCOPES.try_with(|scopes| {
+            let mut declared = None;
+            let ids: Vec<usize> = scopes
+                .borrow()
+                .iter()
+                .filter(|scope| scope.tracks(cls, &mut declared))
+                .map(|scope| scope.id)
+                .collect();
+            if !ids.is_empty() {
+                live_objects().entry(obj as usize).or_default().extend(ids);
+            }
+        });
+    }
+    obj
+}
+
+unsafe extern "C-unwind" fn dealloc(this: *mut AnyObject, cmd: Sel) {
+    if ACTIVE_SCOPES.load(Ordering::Acquire) != 0 {
+        live_objects().remove(&(this as usize));
+    }
+
+    // SAFETY: The object is valid until the original `dealloc` has run.
+    let cls = unsafe { &*this }.class();
+    let original = original(cls).dealloc;
+    // SAFETY: The original implementation has this signature.
+    let original = unsafe { mem::transmute::<Imp, Dealloc>(original) };
+    // SAFETY: Forwarding the arguments that we were called with.
+    unsafe { original(this, cmd) }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+    use crate::rc::test_object::RcTestObject;
+    use crate::rc::Retained;
+    use crate::runtime::{ClassBuilder, NSObject};
+    use crate::{msg_send_id, ClassType};
+
+    #[test]
+    fn test_no_leaks() {
+        assert_deallocated(&[NSObject::class()], |_| {
+            let obj = NSObject::new();
+            let _clone = obj.clone();
+        });
+    }
+
+    #[test]
+    fn test_autoreleased() {
+        assert_deallocated(&[NSObject::class()], |_| {
+            let obj = NSObject::new();
+            let _ = Retained::autorelease_ptr(obj);
+        });
+    }
+
+    #[test]
+    fn test_leak() {
+        // SAFETY: The instances do not outlive the closure.
+        unsafe {
+            ClassBuilder::scoped(
+                NSObject::class(),
+                |_| {},
+                |cls| {
+                    let name = cls.name().to_str().unwrap();
+                    let scope = LeakScope::new(&[cls]);
+                    let untracked = NSObject::new();
+                    let obj: Retained<AnyObject> = msg_send_id![cls, new];
+                    let clone = obj.clone();
+                    let leaks = scope.into_leaks();
+                    assert_eq!(leaks.len(), 1);
+                    assert_eq!(leaks[0].object(), &*obj as *const AnyObject);
+                    assert_eq!(leaks[0].class_name(), name);
+                    assert_eq!(leaks[0].retain_count(), Some(2));
+                    assert_eq!(
+                        leaks[0].to_string(),
+                        format!("{name} at {:?} (retain count 2)", leaks[0].object()),
+                    );
+                    drop(clone);
+                    drop(obj);
+                    drop(untracked);
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn test_nested() {
+        let outer = LeakScope::new(&[NSObject::class()]);
+        let obj = NSObject::new();
+        let inner = LeakScope::new(&[NSObject::class()]);
+        let obj2 = NSObject::new();
+        assert_eq!(inner.leaks().len(), 1);
+        assert_eq!(outer.leaks().len(), 2);
+        drop(obj2);
+        drop(inner);
+        assert_eq!(outer.leaks().len(), 1);
+        drop(obj);
+        drop(outer);
+    }
+
+    #[test]
+    fn test_leaks_while_deallocating_on_other_thread() {
+        let scope = LeakScope::new(&[NSObject::class()]);
+        struct Objects(Vec<Retained<NSObject>>);
+        // SAFETY: `NSObject` can be released from any thread.
+        unsafe impl Send for Objects {}
+
+        let objects = Objects((0..100).map(|_| NSObject::new()).collect());
+        let handle = std::thread::spawn(move || {
+            let objects = objects;
+            for obj in objects.0 {
+                drop(obj);
+                std::thread::yield_now();
+            }
+        });
+        while !handle.is_finished() {
+            for leak in scope.leaks() {
+                assert_eq!(leak.class_name(), "NSObject");
+            }
+        }
+        handle.join().unwrap();
+        assert!(scope.into_leaks().is_empty());
+    }
+
+    #[test]
+    fn test_declared() {
+        let scope = LeakScope::declared();
+        let obj = RcTestObject::new();
+        let untracked = NSObject::new();
+        let leaks = scope.leaks();
+        assert_eq!(leaks.len(), 1);
+        assert_eq!(leaks[0].object(), Retained::as_ptr(&obj).cast());
+        assert_eq!(leaks[0].class_name(), RcTestObject::NAME);
+        drop(obj);
+        assert!(scope.leaks().is_empty());
+        drop(scope);
+        drop(untracked);
+
+        assert_declared_deallocated(|_| {
+            let obj = RcTestObject::new();
+            let _ = Retained::autorelease_ptr(obj);
+        });
+    }
+
+    #[test]
+    #[should_panic = "1 object(s) were not deallocated:\n- NSObject at"]
+    fn test_assert_deallocated_panics() {
+        let mut kept = None;
+        assert_deallocated(&[NSObject::class()], |_| {
+            kept = Some(NSObject::new());
+        });
+    }
+
+    #[test]
+    #[should_panic = "were not deallocated"]
+    fn test_scope_panics_on_drop() {
+        // Dropped after the scope.
+        let _obj;
+        let _scope = LeakScope::new(&[NSObject::class()]);
+        _obj = NSObject::new();
+    }
+}
@@ -50,7 +50,9 @@
 //! ```
 
 mod allocated_partial_init;
+mod atomic_retained;
 mod autorelease;
+mod leak_scope;
 mod retained;
 mod retained_forwarding_impls;
 mod retained_traits;
@@ -59,9 +61,12 @@ mod test_object;
 mod weak;
 
 pub use self::allocated_partial_init::{Allocated, PartialInit};
+pub use self::atomic_retained::{AtomicOptionRetained, AtomicRetained};
 pub use self::autorelease::{
     autoreleasepool, autoreleasepool_leaking, AutoreleasePool, AutoreleaseSafe,
 };
+pub(crate) use self::leak_scope::record_declared_class;
+pub use self::leak_scope::{assert_deallocated, assert_declared_deallocated, Leak, LeakScope};
 pub use self::retained::{Id, Retained};
 pub use self::retained_traits::{DefaultRetained, RetainedFromIterator, RetainedIntoIterator};
 #[cfg(test)]
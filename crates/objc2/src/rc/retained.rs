@@ -425,6 +425,11 @@ impl<T: Message> Retained<T> {
         // SAFETY: The caller upholds that the pointer is valid
         let res: *mut T = unsafe { objc_retain_fast(ptr.cast()) }.cast();
         debug_assert_eq!(res, ptr, "objc_retain did not return the same pointer");
+        #[cfg(feature = "rc-tracing")]
+        // SAFETY: The pointer is valid or NULL.
+        unsafe {
+            crate::debug::trace(crate::debug::RcEventKind::Retain, res)
+        };
         // SAFETY: We just retained the object, so it has +1 retain count
         unsafe { Self::from_raw(res) }
     }
@@ -560,6 +565,11 @@ impl<T: Message> Retained<T> {
             res, ptr,
             "objc_retainAutoreleasedReturnValue did not return the same pointer"
         );
+        #[cfg(feature = "rc-tracing")]
+        // SAFETY: The pointer is valid or NULL.
+        unsafe {
+            crate::debug::trace(crate::debug::RcEventKind::RetainAutoreleased, res)
+        };
 
         // SAFETY: Same as `Retained::retain`.
         unsafe { Self::from_raw(res) }
@@ -594,6 +604,11 @@ impl<T: Message> Retained<T> {
         //   implementation, so the object won't also be released there.
         let res: *mut T = unsafe { ffi::objc_autorelease(ptr.cast()) }.cast();
         debug_assert_eq!(res, ptr, "objc_autorelease did not return the same pointer");
+        #[cfg(feature = "rc-tracing")]
+        // SAFETY: The object is valid until the autorelease pool is drained.
+        unsafe {
+            crate::debug::trace(crate::debug::RcEventKind::Autorelease, res)
+        };
         res
     }
 
@@ -626,6 +641,14 @@ impl<T: Message> Retained<T> {
             .map(|this| ManuallyDrop::new(this).ptr.as_ptr())
             .unwrap_or_else(ptr::null_mut);
 
+        // Traced before the call, since `objc_autoreleaseReturnValue` must
+        // be the last call in the function for the optimization to work.
+        #[cfg(feature = "rc-tracing")]
+        // SAFETY: The pointer is valid or NULL.
+        unsafe {
+            crate::debug::trace(crate::debug::RcEventKind::AutoreleaseReturn, ptr)
+        };
+
         // SAFETY: Same as `autorelease_inner`, this is just an optimization.
         let res: *mut T = unsafe { ffi::objc_autoreleaseReturnValue(ptr.cast()) }.cast();
         debug_assert_eq!(
@@ -748,6 +771,12 @@ impl<T: ?Sized> Drop for Retained<T> {
         // but that would be confusing and inconsistent since we cannot really
         // guarantee that it is run if the `Retained<T>` is passed to Objective-C.
 
+        #[cfg(feature = "rc-tracing")]
+        // SAFETY: The object is valid until it is released.
+        unsafe {
+            crate::debug::trace(crate::debug::RcEventKind::Release, self.ptr.as_ptr())
+        };
+
         // SAFETY: The `ptr` is guaranteed to be valid and have at least one
         // retain count.
         unsafe { objc_release_fast(self.ptr.as_ptr().cast()) };
@@ -106,6 +106,11 @@ impl<T: Message> Weak<T> {
     pub fn load(&self) -> Option<Retained<T>> {
         let ptr = self.inner.get();
         let obj = unsafe { ffi::objc_loadWeakRetained(ptr) }.cast();
+        #[cfg(feature = "rc-tracing")]
+        // SAFETY: The object is valid or NULL.
+        unsafe {
+            crate::debug::trace(crate::debug::RcEventKind::WeakLoad, obj)
+        };
         // SAFETY: The object has +1 retain count
         unsafe { Retained::from_raw(obj) }
     }
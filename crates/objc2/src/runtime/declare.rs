@@ -1,27 +1,115 @@
 //! # Dynamically creating classes and protocols.
+use alloc::borrow::Cow;
 use alloc::ffi::CString;
 use alloc::format;
 use alloc::string::ToString;
+use alloc::vec::Vec;
 use core::ffi::CStr;
+use core::marker::PhantomData;
 use core::mem;
 use core::mem::ManuallyDrop;
 use core::ptr;
 use core::ptr::NonNull;
+use core::slice;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::debug::{visit_ivar, StrongReferences};
 use crate::encode::{Encode, EncodeArguments, EncodeReturn, Encoding};
 use crate::ffi;
-use crate::runtime::{AnyClass, AnyObject, AnyProtocol, Bool, Imp, MethodImplementation, Sel};
+use crate::runtime::typed_ivar::TypedIvar;
+use crate::runtime::{
+    AnyClass, AnyObject, AnyProtocol, Bool, Imp, IvarHandle, MethodImplementation, RegisteredClass,
+    Sel,
+};
 use crate::sel;
 use crate::Message;
 
-fn method_type_encoding(ret: &Encoding, args: &[Encoding]) -> CString {
+/// The maximum length of method type encodings that are computed at compile
+/// time. Longer encodings are computed when adding the method instead.
+const METHOD_TYPES_CAP: usize = 128;
+
+/// The method type encoding of a method, computed at compile time.
+struct MethodTypes<Ret, Args>(PhantomData<(Ret, Args)>);
+
+impl<Ret: EncodeReturn, Args: EncodeArguments> MethodTypes<Ret, Args> {
+    const LEN: usize = {
+        // First two arguments are always self and the selector
+        let mut len = Ret::ENCODING_RETURN.str_len()
+            + <*mut AnyObject>::ENCODING.str_len()
+            + Sel::ENCODING.str_len();
+        let mut i = 0;
+        while i < Args::ENCODINGS.len() {
+            len += Args::ENCODINGS[i].str_len();
+            i += 1;
+        }
+        len
+    };
+
+    // Only compute the encoding if it fits (with the NUL byte).
+    const ARRAY: [u8; METHOD_TYPES_CAP] = if Self::LEN < METHOD_TYPES_CAP {
+        let res = [0; METHOD_TYPES_CAP];
+        let (res, res_i) = write_encoding(res, 0, &Ret::ENCODING_RETURN);
+        let (res, res_i) = write_encoding(res, res_i, &<*mut AnyObject>::ENCODING);
+        let (mut res, mut res_i) = write_encoding(res, res_i, &Sel::ENCODING);
+        let mut i = 0;
+        while i < Args::ENCODINGS.len() {
+            (res, res_i) = write_encoding(res, res_i, &Args::ENCODINGS[i]);
+            i += 1;
+        }
+        res
+    } else {
+        [0; METHOD_TYPES_CAP]
+    };
+
+    const ARRAY_REF: &'static [u8; METHOD_TYPES_CAP] = &Self::ARRAY;
+
+    const TYPES: Option<&'static CStr> = if Self::LEN < METHOD_TYPES_CAP {
+        // SAFETY: The array is zero-initialized, and the encodings don't
+        // contain any NUL bytes, so the first `LEN` bytes followed by a NUL
+        // byte is a valid C string.
+        Some(unsafe {
+            CStr::from_bytes_with_nul_unchecked(slice::from_raw_parts(
+                Self::ARRAY_REF.as_ptr(),
+                Self::LEN + 1,
+            ))
+        })
+    } else {
+        None
+    };
+}
+
+/// Write the encoding to the array at the given index, returning the array
+/// and the index after the encoding.
+const fn write_encoding(
+    mut res: [u8; METHOD_TYPES_CAP],
+    mut res_i: usize,
+    enc: &Encoding,
+) -> ([u8; METHOD_TYPES_CAP], usize) {
+    let arr: [u8; METHOD_TYPES_CAP] = enc.str_array();
+    let mut i = 0;
+    while i < enc.str_len() {
+        res[res_i] = arr[i];
+        res_i += 1;
+        i += 1;
+    }
+    (res, res_i)
+}
+
+pub(super) fn method_type_encoding<Ret: EncodeReturn, Args: EncodeArguments>() -> Cow<'static, CStr>
+{
+    if let Some(types) = MethodTypes::<Ret, Args>::TYPES {
+        return Cow::Borrowed(types);
+    }
+
+    // The encoding was too long, compute it at runtime instead.
+    let ret = &Ret::ENCODING_RETURN;
     // First two arguments are always self and the selector
     let mut types = format!("{ret}{}{}", <*mut AnyObject>::ENCODING, Sel::ENCODING);
-    for enc in args {
+    for enc in Args::ENCODINGS {
         use core::fmt::Write;
         write!(&mut types, "{enc}").unwrap();
     }
-    CString::new(types).unwrap()
+    Cow::Owned(CString::new(types).unwrap())
 }
 
 trait Log2Alignment {
@@ -160,6 +248,7 @@ pub struct ClassBuilder {
     // Note: Don't ever construct a &mut AnyClass, since it is possible to
     // get this pointer using `AnyClass::classes`!
     cls: NonNull<AnyClass>,
+    pub(super) typed_ivars: Vec<TypedIvar>,
 }
 
 // SAFETY: The stuff that touch global state does so using locks internally.
@@ -188,8 +277,7 @@ impl ClassBuilder {
         unsafe { AnyClass::superclass_raw(self.cls.as_ptr()) }
     }
 
-    #[allow(unused)]
-    fn name(&self) -> &CStr {
+    pub(super) fn name(&self) -> &CStr {
         // SAFETY: Same as `superclass`
         unsafe { AnyClass::name_raw(self.cls.as_ptr()) }
     }
@@ -198,7 +286,10 @@ impl ClassBuilder {
     fn with_superclass(name: &CStr, superclass: Option<&AnyClass>) -> Option<Self> {
         let super_ptr = superclass.map_or(ptr::null(), |c| c).cast();
         let cls = unsafe { ffi::objc_allocateClassPair(super_ptr, name.as_ptr(), 0) };
-        NonNull::new(cls).map(|cls| Self { cls })
+        NonNull::new(cls).map(|cls| Self {
+            cls,
+            typed_ivars: Vec::new(),
+        })
     }
 
     /// Constructs a [`ClassBuilder`] with the given name and superclass.
@@ -261,16 +352,55 @@ impl ClassBuilder {
                 sel,
                 F::Arguments::ENCODINGS,
                 &F::Return::ENCODING_RETURN,
+                &method_type_encoding::<F::Return, F::Arguments>(),
                 func.__imp(),
             );
         }
     }
 
+    /// Adds a method with the given name and raw implementation.
+    ///
+    /// This is a lower-level version of [`add_method`][Self::add_method] for
+    /// implementations that are not Rust functions, for example ones created
+    /// from a block with `imp_implementationWithBlock`. The argument types
+    /// and the return type are given by `A` and `R`, and are checked in the
+    /// same way.
+    ///
+    ///
+    /// # Panics
+    ///
+    /// Panics in the same cases as [`add_method`][Self::add_method].
+    ///
+    ///
+    /// # Safety
+    ///
+    /// `imp` must be a valid implementation that takes the receiver, the
+    /// selector and arguments of type `A`, and returns `R`.
+    ///
+    /// The caller must ensure that the types match those that are expected
+    /// when the method is invoked from Objective-C.
+    pub unsafe fn add_method_imp<A, R>(&mut self, sel: Sel, imp: Imp)
+    where
+        A: EncodeArguments,
+        R: EncodeReturn,
+    {
+        unsafe {
+            self.add_method_inner(
+                sel,
+                A::ENCODINGS,
+                &R::ENCODING_RETURN,
+                &method_type_encoding::<R, A>(),
+                imp,
+            );
+        }
+    }
+
     unsafe fn add_method_inner(
         &mut self,
         sel: Sel,
         enc_args: &[Encoding],
         enc_ret: &Encoding,
+        types: &CStr,
         func: Imp,
     ) {
         let sel_args = sel.number_of_arguments();
@@ -286,8 +416,12 @@ impl ClassBuilder {
         #[cfg(debug_assertions)]
         if let Some(superclass) = self.superclass() {
             if let Some(method) = superclass.instance_method(sel) {
-                if let Err(err) = crate::verify::verify_method_signature(method, enc_args, enc_ret)
-                {
+                if let Err(err) = crate::verify::verify_method_signature(
+                    method,
+                    enc_args,
+                    enc_ret,
+                    crate::encode::EquivalenceOptions::new(),
+                ) {
                     panic!(
                         "declared invalid method -[{} {sel}]: {err}",
                         self.name().to_string_lossy()
@@ -296,7 +430,6 @@ impl ClassBuilder {
             }
         }
 
-        let types = method_type_encoding(enc_ret, enc_args);
         let success = unsafe { ffi::class_addMethod(self.as_mut_ptr(), sel, func, types.as_ptr()) };
         assert!(success.as_bool(), "failed to add method {sel}");
     }
@@ -326,6 +459,7 @@ impl ClassBuilder {
                 sel,
                 F::Arguments::ENCODINGS,
                 &F::Return::ENCODING_RETURN,
+                &method_type_encoding::<F::Return, F::Arguments>(),
                 func.__imp(),
             );
         }
@@ -336,6 +470,7 @@ impl ClassBuilder {
         sel: Sel,
         enc_args: &[Encoding],
         enc_ret: &Encoding,
+        types: &CStr,
         func: Imp,
     ) {
         let sel_args = sel.number_of_arguments();
@@ -351,8 +486,12 @@ impl ClassBuilder {
         #[cfg(debug_assertions)]
         if let Some(superclass) = self.superclass() {
             if let Some(method) = superclass.class_method(sel) {
-                if let Err(err) = crate::verify::verify_method_signature(method, enc_args, enc_ret)
-                {
+                if let Err(err) = crate::verify::verify_method_signature(
+                    method,
+                    enc_args,
+                    enc_ret,
+                    crate::encode::EquivalenceOptions::new(),
+                ) {
                     panic!(
                         "declared invalid method +[{} {sel}]: {err}",
                         self.name().to_string_lossy()
@@ -361,7 +500,6 @@ impl ClassBuilder {
             }
         }
 
-        let types = method_type_encoding(enc_ret, enc_args);
         let success =
             unsafe { ffi::class_addMethod(self.metaclass_mut(), sel, func, types.as_ptr()) };
         assert!(success.as_bool(), "failed to add class method {sel}");
@@ -376,22 +514,54 @@ impl ClassBuilder {
     /// happens if there already was an ivar with that name.
     pub fn add_ivar<T: Encode>(&mut self, name: &CStr) {
         // SAFETY: The encoding is correct
-        unsafe { self.add_ivar_inner::<T>(name, &T::ENCODING) }
+        unsafe { self.add_ivar_inner::<T>(name, &T::ENCODING.to_string()) }
     }
 
-    pub(crate) unsafe fn add_ivar_inner<T>(&mut self, name: &CStr, encoding: &Encoding) {
-        unsafe { self.add_ivar_inner_mono(name, mem::size_of::<T>(), T::LOG2_ALIGNMENT, encoding) }
+    /// Adds an ivar of any Rust type `T` with the provided name, and returns
+    /// a handle for accessing it on instances of the class.
+    ///
+    /// The ivar is opaque to the Objective-C runtime, so `T` does not need to
+    /// implement [`Encode`]. The ivar starts out uninitialized, and if it has
+    /// been initialized, it is dropped when the object is deallocated.
+    ///
+    /// Dropping is done in a `dealloc` method that is added to the class when
+    /// it is registered, if `T` needs dropping. The class must then not have
+    /// a `dealloc` method added in other ways, and cannot be a root class.
+    ///
+    /// See [`IvarHandle`] for an example.
+    ///
+    ///
+    /// # Panics
+    ///
+    /// Same as [`add_ivar`][Self::add_ivar], or if the alignment of `T` is
+    /// greater than 8.
+    pub fn add_typed_ivar<T>(&mut self, name: &CStr) -> IvarHandle<T> {
+        self.add_typed_ivar_inner(name, None)
     }
 
-    // Monomorphized version
-    unsafe fn add_ivar_inner_mono(
+    /// Same as [`add_typed_ivar`][Self::add_typed_ivar], but also makes
+    /// [`find_retain_cycles`] follow the strong references held by the ivar.
+    ///
+    /// [`find_retain_cycles`]: crate::debug::find_retain_cycles
+    ///
+    ///
+    /// # Panics
+    ///
+    /// Same as [`add_typed_ivar`][Self::add_typed_ivar].
+    pub fn add_typed_ivar_with_references<T: StrongReferences>(
         &mut self,
         name: &CStr,
-        size: usize,
-        align: u8,
-        encoding: &Encoding,
-    ) {
-        let encoding = CString::new(encoding.to_string()).unwrap();
+    ) -> IvarHandle<T> {
+        self.add_typed_ivar_inner(name, Some(visit_ivar::<T>))
+    }
+
+    pub(crate) unsafe fn add_ivar_inner<T>(&mut self, name: &CStr, encoding: &str) {
+        unsafe { self.add_ivar_inner_mono(name, mem::size_of::<T>(), T::LOG2_ALIGNMENT, encoding) }
+    }
+
+    // Monomorphized version
+    unsafe fn add_ivar_inner_mono(&mut self, name: &CStr, size: usize, align: u8, encoding: &str) {
+        let encoding = CString::new(encoding).unwrap();
 
         // Note: The Objective-C runtime contains functionality to do stuff
         // with "instance variable layouts", but we don't have to touch any of
@@ -428,16 +598,136 @@ impl ClassBuilder {
         success.as_bool()
     }
 
-    // fn add_property(&self, name: &CStr, attributes: &[ffi::objc_property_attribute_t]);
+    /// Adds a property with the given name and attributes.
+    ///
+    /// The attributes are pairs of names and values, see [Apple's
+    /// documentation on property type strings][doc] for the meaning of
+    /// these.
+    ///
+    /// Note that this only adds the property's metadata; the getter and
+    /// setter must be added separately with [`add_method`][Self::add_method].
+    ///
+    /// Returns whether the property was added (it may fail if the class
+    /// already has a property with that name).
+    ///
+    /// [doc]: https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/ObjCRuntimeGuide/Articles/ocrtPropertyIntrospection.html
+    #[cfg(any(doc, not(feature = "unstable-objfw")))]
+    pub fn add_property(&mut self, name: &CStr, attributes: &[(&CStr, &CStr)]) -> bool {
+        let attributes: Vec<_> = attributes
+            .iter()
+            .map(|(name, value)| ffi::objc_property_attribute_t {
+                name: name.as_ptr(),
+                value: value.as_ptr(),
+            })
+            .collect();
+        let success = unsafe {
+            ffi::class_addProperty(
+                self.as_mut_ptr(),
+                name.as_ptr(),
+                attributes.as_ptr(),
+                attributes.len() as _,
+            )
+        };
+        success.as_bool()
+    }
 
     /// Registers the [`ClassBuilder`], consuming it, and returns a reference
     /// to the newly registered [`AnyClass`].
     #[inline]
-    pub fn register(self) -> &'static AnyClass {
+    pub fn register(mut self) -> &'static AnyClass {
+        let state = self.setup_typed_ivars_dealloc();
+        let typed_ivars = mem::take(&mut self.typed_ivars);
         // Forget self, otherwise the class will be disposed in drop
         let mut this = ManuallyDrop::new(self);
         unsafe { ffi::objc_registerClassPair(this.as_mut_ptr()) };
-        unsafe { this.cls.as_ref() }
+        let cls = unsafe { this.cls.as_ref() };
+        Self::register_typed_ivars(cls, state, typed_ivars);
+        cls
+    }
+
+    /// Registers the [`ClassBuilder`], consuming it, and returns a handle
+    /// that can be used to remove the class from the runtime again.
+    ///
+    /// This is useful for classes that are only needed temporarily, e.g. in
+    /// tests, to avoid filling up the class list.
+    #[cfg(any(doc, not(feature = "unstable-objfw")))]
+    pub fn register_disposable(self) -> RegisteredClass {
+        let cls = self.register();
+        // SAFETY: The class was just registered, and nothing else has a
+        // handle to it.
+        unsafe { RegisteredClass::new(cls) }
+    }
+
+    /// Declare a temporary subclass of `superclass`, use it, and dispose it
+    /// again afterwards.
+    ///
+    /// The class is given a unique name derived from the name of the
+    /// superclass. Methods, ivars and so on are added in `build`, after which
+    /// the class is registered with [`register_disposable`], and passed to
+    /// `f`. The class is disposed when `f` returns, or left registered if
+    /// `f` panics.
+    ///
+    /// [`register_disposable`]: Self::register_disposable
+    ///
+    ///
+    /// # Panics
+    ///
+    /// Panics in the same cases as [`RegisteredClass::dispose`].
+    ///
+    ///
+    /// # Safety
+    ///
+    /// No instances or subclasses of the class may outlive `f`.
+    ///
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use objc2::runtime::{AnyClass, AnyObject, ClassBuilder, NSObject, Sel};
+    /// use objc2::rc::Retained;
+    /// use objc2::{msg_send, msg_send_id, sel, ClassType};
+    ///
+    /// extern "C-unwind" fn number(_this: &AnyObject, _cmd: Sel) -> u32 {
+    ///     42
+    /// }
+    ///
+    /// let n = unsafe {
+    ///     ClassBuilder::scoped(
+    ///         NSObject::class(),
+    ///         |builder| {
+    ///             builder.add_method(sel!(number), number as extern "C-unwind" fn(_, _) -> _);
+    ///         },
+    ///         |cls: &AnyClass| {
+    ///             let obj: Retained<AnyObject> = msg_send_id![cls, new];
+    ///             let n: u32 = msg_send![&obj, number];
+    ///             n
+    ///         },
+    ///     )
+    /// };
+    /// assert_eq!(n, 42);
+    /// ```
+    #[cfg(any(doc, not(feature = "unstable-objfw")))]
+    pub unsafe fn scoped<R>(
+        superclass: &AnyClass,
+        build: impl FnOnce(&mut Self),
+        f: impl FnOnce(&AnyClass) -> R,
+    ) -> R {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let mut builder = loop {
+            let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+            let name = format!("{superclass}_Scoped{n}");
+            let name = CString::new(name).expect("class name must not contain NUL");
+            if let Some(builder) = Self::new(&name, superclass) {
+                break builder;
+            }
+        };
+        build(&mut builder);
+        let cls = builder.register_disposable();
+        let res = f(&cls);
+        // SAFETY: Upheld by the caller.
+        unsafe { cls.dispose() };
+        res
     }
 }
 
@@ -493,7 +783,7 @@ impl ProtocolBuilder {
         &mut self,
         sel: Sel,
         enc_args: &[Encoding],
-        enc_ret: &Encoding,
+        types: &CStr,
         required: bool,
         instance_method: bool,
     ) {
@@ -504,7 +794,6 @@ impl ProtocolBuilder {
             "selector {sel} accepts {sel_args} arguments, but function accepts {}",
             enc_args.len(),
         );
-        let types = method_type_encoding(enc_ret, enc_args);
         unsafe {
             ffi::protocol_addMethodDescription(
                 self.as_mut_ptr(),
@@ -525,7 +814,7 @@ impl ProtocolBuilder {
         self.add_method_description_inner(
             sel,
             Args::ENCODINGS,
-            &Ret::ENCODING_RETURN,
+            &method_type_encoding::<Ret, Args>(),
             required,
             true,
         );
@@ -540,7 +829,7 @@ impl ProtocolBuilder {
         self.add_method_description_inner(
             sel,
             Args::ENCODINGS,
-            &Ret::ENCODING_RETURN,
+            &method_type_encoding::<Ret, Args>(),
             required,
             false,
         );
@@ -570,6 +859,293 @@ impl Drop for ProtocolBuilder {
     }
 }
 
+/// How a [`CategoryBuilder`] handles methods that the class already
+/// implements itself.
+///
+/// Overriding a method that is only inherited from a superclass is never
+/// considered a conflict.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MethodConflict {
+    /// Panic when adding the method.
+    #[default]
+    Error,
+    /// Keep the existing implementation, and don't add the new method.
+    Skip,
+    /// Replace the existing implementation.
+    ///
+    /// The previous implementation can be retrieved afterwards with
+    /// [`Category::replaced_method`] or [`Category::replaced_class_method`].
+    Replace,
+}
+
+/// How [`declare_class!`] handles a class with the same name already being
+/// registered with the runtime.
+///
+/// This usually happens when two copies of the same library are loaded into
+/// one process, for example by a plugin host, or when two crates
+/// accidentally use the same name. In the latter case, consider using
+/// [`unique_class_name!`] instead.
+///
+/// [`declare_class!`]: crate::declare_class
+/// [`unique_class_name!`]: crate::unique_class_name
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ClassConflict {
+    /// Panic when registering the class.
+    #[default]
+    Error,
+    /// Use the existing class instead of declaring a new one.
+    ///
+    /// The existing class must have the same superclass, and the same
+    /// instance variables as the class would have had if it was declared;
+    /// registering panics otherwise. The methods of the existing class are
+    /// kept as-is, and [`DeclaredClass::load`] is not called again.
+    ///
+    /// The instance variables are compared by name and by the type name and
+    /// layout of the stored Rust type. The methods are not checked, so this
+    /// is only sound if the existing class was declared by the same code,
+    /// i.e. by another copy of the same version of the library, and if that
+    /// copy is not unloaded while the class is in use. This must be upheld
+    /// by the `unsafe impl ClassType`, see the safety section of
+    /// [`declare_class!`].
+    ///
+    /// [`DeclaredClass::load`]: crate::DeclaredClass::load
+    /// [`declare_class!`]: crate::declare_class
+    Reuse,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct ReplacedMethod {
+    sel: Sel,
+    is_class_method: bool,
+    imp: Imp,
+}
+
+/// A type for adding methods to an existing, registered class.
+///
+/// This is the runtime equivalent of an Objective-C category. Unlike
+/// [`ClassBuilder`], the methods take effect immediately when they are
+/// added.
+///
+/// **Note**: You likely don't need the dynamicism that this provides!
+/// Consider using the [`declare_category!`][crate::declare_category] macro
+/// instead.
+#[derive(Debug)]
+pub struct CategoryBuilder {
+    cls: &'static AnyClass,
+    conflict: MethodConflict,
+    replaced: Vec<ReplacedMethod>,
+}
+
+impl CategoryBuilder {
+    /// Constructs a [`CategoryBuilder`] that adds methods to the given
+    /// class, using the given policy for methods that already exist.
+    #[inline]
+    pub fn new(cls: &'static AnyClass, conflict: MethodConflict) -> Self {
+        Self {
+            cls,
+            conflict,
+            replaced: Vec::new(),
+        }
+    }
+
+    /// Adds a method with the given name and implementation.
+    ///
+    /// Returns `false` if the class already implemented the method, and it
+    /// was skipped because of [`MethodConflict::Skip`].
+    ///
+    ///
+    /// # Panics
+    ///
+    /// Panics if the class already implements the method, and the conflict
+    /// policy is [`MethodConflict::Error`].
+    ///
+    /// May also panic if the method was detected to be invalid in some way;
+    /// for example if `debug_assertions` are enabled and the method is
+    /// overriding another method, we verify that their encodings are equal.
+    ///
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the types match those that are expected
+    /// when the method is invoked from Objective-C.
+    ///
+    /// Furthermore, replacing a method that is currently executing on
+    /// another thread is not thread-safe.
+    pub unsafe fn add_method<T, F>(&mut self, sel: Sel, func: F) -> bool
+    where
+        T: Message + ?Sized,
+        F: MethodImplementation<Callee = T>,
+    {
+        unsafe {
+            self.add_method_inner(
+                sel,
+                F::Arguments::ENCODINGS,
+                &F::Return::ENCODING_RETURN,
+                &method_type_encoding::<F::Return, F::Arguments>(),
+                func.__imp(),
+                false,
+            )
+        }
+    }
+
+    /// Adds a class method with the given name and implementation.
+    ///
+    /// Returns `false` if the class already implemented the method, and it
+    /// was skipped because of [`MethodConflict::Skip`].
+    ///
+    ///
+    /// # Panics
+    ///
+    /// Panics in the same cases as [`add_method`][Self::add_method].
+    ///
+    ///
+    /// # Safety
+    ///
+    /// Same as [`add_method`][Self::add_method].
+    pub unsafe fn add_class_method<F>(&mut self, sel: Sel, func: F) -> bool
+    where
+        F: MethodImplementation<Callee = AnyClass>,
+    {
+        unsafe {
+            self.add_method_inner(
+                sel,
+                F::Arguments::ENCODINGS,
+                &F::Return::ENCODING_RETURN,
+                &method_type_encoding::<F::Return, F::Arguments>(),
+                func.__imp(),
+                true,
+            )
+        }
+    }
+
+    unsafe fn add_method_inner(
+        &mut self,
+        sel: Sel,
+        enc_args: &[Encoding],
+        enc_ret: &Encoding,
+        types: &CStr,
+        func: Imp,
+        is_class_method: bool,
+    ) -> bool {
+        let sel_args = sel.number_of_arguments();
+        assert_eq!(
+            sel_args,
+            enc_args.len(),
+            "selector {sel} accepts {sel_args} arguments, but function accepts {}",
+            enc_args.len(),
+        );
+
+        let (cls, kind) = if is_class_method {
+            (self.cls.metaclass(), '+')
+        } else {
+            (self.cls, '-')
+        };
+
+        // Includes methods inherited from the superclass.
+        let existing = cls.instance_method(sel);
+
+        // Verify that, if the method is already present, that the encoding
+        // is correct.
+        #[cfg(debug_assertions)]
+        if let Some(method) = existing {
+            if let Err(err) = crate::verify::verify_method_signature(
+                method,
+                enc_args,
+                enc_ret,
+                crate::encode::EquivalenceOptions::new(),
+            ) {
+                panic!("declared invalid method {kind}[{} {sel}]: {err}", self.cls)
+            }
+        }
+        #[cfg(not(debug_assertions))]
+        let _ = enc_ret;
+
+        // The method is defined on the class itself if looking it up on the
+        // superclass yields a different method.
+        let defined_on_class = existing.is_some_and(|method| {
+            !cls.superclass()
+                .and_then(|superclass| superclass.instance_method(sel))
+                .is_some_and(|inherited| ptr::eq(inherited, method))
+        });
+
+        if defined_on_class {
+            match self.conflict {
+                MethodConflict::Error => {
+                    panic!("method {kind}[{} {sel}] already exists", self.cls)
+                }
+                MethodConflict::Skip => return false,
+                MethodConflict::Replace => {}
+            }
+        }
+
+        if let Some(method) = existing {
+            self.replaced.push(ReplacedMethod {
+                sel,
+                is_class_method,
+                imp: method.implementation(),
+            });
+        }
+
+        // SAFETY: The class is registered, and `class_replaceMethod` adds the
+        // method if the class doesn't already implement it.
+        let cls: *const AnyClass = cls;
+        unsafe { ffi::class_replaceMethod(cls as *mut AnyClass, sel, func, types.as_ptr()) };
+        true
+    }
+
+    /// Finishes adding methods, and returns a [`Category`] that records the
+    /// implementations that were replaced.
+    #[inline]
+    pub fn register(self) -> Category {
+        Category {
+            cls: self.cls,
+            replaced: self.replaced,
+        }
+    }
+}
+
+/// The result of adding methods to a class with a [`CategoryBuilder`].
+#[derive(Debug)]
+pub struct Category {
+    cls: &'static AnyClass,
+    replaced: Vec<ReplacedMethod>,
+}
+
+impl Category {
+    /// The class that the methods were added to.
+    #[inline]
+    pub fn class(&self) -> &'static AnyClass {
+        self.cls
+    }
+
+    fn replaced(&self, sel: Sel, is_class_method: bool) -> Option<Imp> {
+        self.replaced
+            .iter()
+            .find(|method| method.sel == sel && method.is_class_method == is_class_method)
+            .map(|method| method.imp)
+    }
+
+    /// The implementation of the instance method that was in effect before
+    /// the category added its own.
+    ///
+    /// This may be an implementation inherited from a superclass, so it can
+    /// be used to call the original method. Returns [`None`] if the method
+    /// was not previously implemented, or if it was skipped.
+    #[inline]
+    pub fn replaced_method(&self, sel: Sel) -> Option<Imp> {
+        self.replaced(sel, false)
+    }
+
+    /// The implementation of the class method that was in effect before the
+    /// category added its own.
+    ///
+    /// See [`replaced_method`][Self::replaced_method] for details.
+    #[inline]
+    pub fn replaced_class_method(&self, sel: Sel) -> Option<Imp> {
+        self.replaced(sel, true)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use core::hash::Hasher;
@@ -592,6 +1168,34 @@ mod tests {
         CString::new(s).unwrap()
     }
 
+    #[test]
+    fn test_method_type_encoding() {
+        assert_eq!(MethodTypes::<(), ()>::TYPES, Some(&*c("v@:")));
+        assert_eq!(
+            MethodTypes::<*const i32, (i32, Sel)>::TYPES,
+            Some(&*c("^i@:i:"))
+        );
+        assert_eq!(*method_type_encoding::<f64, (u8,)>(), *c("d@:C"));
+
+        struct Long;
+
+        unsafe impl Encode for Long {
+            const ENCODING: Encoding = Encoding::Struct(
+                "AVeryLongStructNameThatIsLongerThanTheCapacityOfTheCompileTimeEncoding",
+                &[Encoding::Int],
+            );
+        }
+
+        // Too long to be computed at compile time
+        assert_eq!(MethodTypes::<Long, (Long,)>::TYPES, None);
+        let types = method_type_encoding::<Long, (Long,)>();
+        assert!(matches!(types, Cow::Owned(_)));
+        assert_eq!(
+            types.to_str().unwrap(),
+            format!("{}@:{}", Long::ENCODING, Long::ENCODING),
+        );
+    }
+
     #[test]
     fn test_alignment() {
         assert_eq!(<()>::LOG2_ALIGNMENT, 0);
@@ -805,6 +1409,99 @@ mod tests {
         let _builder = ClassBuilder::new(&c("TestClassBuilderDrop"), cls).unwrap();
     }
 
+    #[test]
+    fn test_register_disposable() {
+        let builder = ClassBuilder::new(&c("TestRegisterDisposable"), NSObject::class()).unwrap();
+        let cls = builder.register_disposable();
+        assert_eq!(AnyClass::get(&c("TestRegisterDisposable")), Some(&*cls));
+
+        let obj: Retained<AnyObject> = unsafe { msg_send_id![&*cls, new] };
+        drop(obj);
+
+        unsafe { cls.dispose() };
+        assert_eq!(AnyClass::get(&c("TestRegisterDisposable")), None);
+    }
+
+    #[test]
+    fn test_scoped() {
+        extern "C-unwind" fn number(_this: &NSObject, _cmd: Sel) -> u32 {
+            7
+        }
+
+        let (name, number) = unsafe {
+            ClassBuilder::scoped(
+                NSObject::class(),
+                |builder| {
+                    let number: extern "C-unwind" fn(_, _) -> _ = number;
+                    builder.add_method(sel!(number), number);
+                },
+                |cls| {
+                    assert_eq!(cls.superclass(), Some(NSObject::class()));
+                    let obj: Retained<NSObject> = msg_send_id![cls, new];
+                    let number: u32 = msg_send![&obj, number];
+                    (CString::from(cls.name()), number)
+                },
+            )
+        };
+        assert_eq!(number, 7);
+        assert!(name.to_str().unwrap().starts_with("NSObject_Scoped"));
+        assert_eq!(AnyClass::get(&name), None);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic = "instance(s) are still alive"]
+    fn test_dispose_with_live_instance() {
+        let builder =
+            ClassBuilder::new(&c("TestDisposeWithLiveInstance"), NSObject::class()).unwrap();
+        let cls = builder.register_disposable();
+        let obj: Retained<AnyObject> = unsafe { msg_send_id![&*cls, new] };
+        unsafe { cls.dispose() };
+        drop(obj);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_dispose_with_live_instance_then_release() {
+        let builder =
+            ClassBuilder::new(&c("TestDisposeWithLiveInstanceRelease"), NSObject::class()).unwrap();
+        let cls = builder.register_disposable();
+        let obj: Retained<AnyObject> = unsafe { msg_send_id![&*cls, new] };
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| unsafe {
+            cls.dispose();
+        }));
+        assert!(res.is_err());
+        // The class is still tracked, so the instance can be deallocated.
+        drop(obj);
+    }
+
+    #[test]
+    fn test_dispose_instance_not_from_alloc() {
+        let builder =
+            ClassBuilder::new(&c("TestDisposeInstanceNotFromAlloc"), NSObject::class()).unwrap();
+        let cls = builder.register_disposable();
+        // Not counted, since it doesn't go through `+allocWithZone:`.
+        let obj = unsafe { ffi::class_createInstance(&*cls, 0) };
+        let obj: Retained<AnyObject> = unsafe { Retained::from_raw(obj) }.unwrap();
+        drop(obj);
+        // Tracking still works afterwards.
+        let obj: Retained<AnyObject> = unsafe { msg_send_id![&*cls, new] };
+        drop(obj);
+        unsafe { cls.dispose() };
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic = "it has subclass"]
+    fn test_dispose_with_subclass() {
+        let builder = ClassBuilder::new(&c("TestDisposeWithSubclass"), NSObject::class()).unwrap();
+        let cls = builder.register_disposable();
+        let _subclass = ClassBuilder::new(&c("TestDisposeWithSubclassSub"), &cls)
+            .unwrap()
+            .register();
+        unsafe { cls.dispose() };
+    }
+
     #[test]
     fn test_custom_class() {
         // Registering the custom class is in test_utils
@@ -316,10 +316,11 @@ fn msg_send_check_class(
     args: &[crate::encode::Encoding],
     ret: &crate::encode::Encoding,
 ) {
+    use crate::encode::EquivalenceOptions;
     use crate::verify::{verify_method_signature, Inner, VerificationError};
 
     let err = if let Some(method) = cls.instance_method(sel) {
-        if let Err(err) = verify_method_signature(method, args, ret) {
+        if let Err(err) = verify_method_signature(method, args, ret, EquivalenceOptions::new()) {
             err
         } else {
             return;
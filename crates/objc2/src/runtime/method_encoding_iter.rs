@@ -1,19 +1,23 @@
 //! Utility for parsing an Objective-C method type encoding.
 //!
 //! TODO: Move this to `objc2-encode` when more stable.
+// Only exported with the `"unstable-fuzzing"` feature.
+#![cfg_attr(not(feature = "unstable-fuzzing"), allow(unreachable_pub))]
 use core::fmt;
 use core::num::ParseIntError;
 use std::error::Error;
 
 use crate::encode::{Encoding, EncodingBox, ParseError};
 
+/// Iterator over the encodings and stack offsets in a method type encoding.
 #[derive(Debug, PartialEq, Eq)]
-pub(crate) struct MethodEncodingIter<'a> {
+pub struct MethodEncodingIter<'a> {
     s: &'a str,
 }
 
 impl<'a> MethodEncodingIter<'a> {
-    pub(crate) fn new(s: &'a str) -> Self {
+    /// Parse the given method type encoding, e.g. `"v24@0:8i16"`.
+    pub fn new(s: &'a str) -> Self {
         Self { s }
     }
 
@@ -77,14 +81,23 @@ fn parse_stack_layout(s: &mut &str) -> Result<Option<isize>, ParseIntError> {
     stack_layout.parse().map(Some)
 }
 
+/// An error that occurred while parsing a method type encoding.
 #[derive(Debug, PartialEq, Eq, Hash)]
-pub(crate) enum EncodingParseError {
+#[non_exhaustive]
+pub enum EncodingParseError {
+    /// One of the encodings could not be parsed.
     ParseError(ParseError),
+    /// A stack offset was not a valid integer.
     InvalidStackLayoutInteger,
+    /// The encoding was empty.
     MissingReturn,
+    /// The encoding did not contain the receiver.
     MissingReceiver,
+    /// The encoding did not contain the selector.
     MissingSel,
+    /// The receiver was not encoded as an object.
     InvalidReceiver(EncodingBox),
+    /// The selector was not encoded as a selector.
     InvalidSel(EncodingBox),
 }
 
@@ -21,6 +21,7 @@ use core::ffi::c_uint;
 use core::ffi::{c_void, CStr};
 use core::fmt;
 use core::hash;
+use core::iter::FusedIterator;
 use core::panic::{RefUnwindSafe, UnwindSafe};
 use core::ptr::{self, NonNull};
 
@@ -38,13 +39,22 @@ mod nsobject;
 mod nsproxy;
 mod nszone;
 mod protocol_object;
+mod registered_class;
 mod retain_release_fast;
+mod typed_ivar;
 
+#[cfg(feature = "unstable-fuzzing")]
+#[doc(hidden)]
+pub use self::method_encoding_iter::{EncodingParseError, MethodEncodingIter};
+#[cfg(not(feature = "unstable-fuzzing"))]
 pub(crate) use self::method_encoding_iter::{EncodingParseError, MethodEncodingIter};
 pub(crate) use self::retain_release_fast::{objc_release_fast, objc_retain_fast};
-use crate::encode::{Encode, EncodeArguments, EncodeReturn, Encoding, OptionEncode, RefEncode};
+use crate::encode::{
+    Encode, EncodeArguments, EncodeReturn, EncodeVariadic, Encoding, EquivalenceOptions,
+    OptionEncode, RefEncode,
+};
 use crate::msg_send;
-use crate::verify::{verify_method_signature, Inner};
+use crate::verify::{verify_method_signature, verify_method_types, Inner};
 use crate::{ffi, DowncastTarget, Message};
 
 // Note: While this is not public, it is still a breaking change to remove,
@@ -53,12 +63,16 @@ use crate::{ffi, DowncastTarget, Message};
 pub use self::nsproxy::NSProxy as __NSProxy;
 
 pub use self::bool::Bool;
-pub use self::declare::{ClassBuilder, ProtocolBuilder};
+pub use self::declare::{
+    Category, CategoryBuilder, ClassBuilder, ClassConflict, MethodConflict, ProtocolBuilder,
+};
 pub use self::message_receiver::MessageReceiver;
 pub use self::method_implementation::MethodImplementation;
 pub use self::nsobject::{NSObject, NSObjectProtocol};
 pub use self::nszone::NSZone;
 pub use self::protocol_object::{ImplementedBy, ProtocolObject};
+pub use self::registered_class::RegisteredClass;
+pub use self::typed_ivar::IvarHandle;
 pub use crate::verify::VerificationError;
 
 #[allow(deprecated)]
@@ -269,6 +283,10 @@ unsafe impl Encode for Sel {
 
 unsafe impl OptionEncode for Sel {}
 
+// SAFETY: `Sel` is pointer-sized, and not affected by promotion.
+unsafe impl EncodeVariadic for Sel {}
+unsafe impl EncodeVariadic for Option<Sel> {}
+
 // RefEncode is not implemented for Sel, because there is literally no API
 // that takes &Sel, while the user could get confused and accidentally attempt
 // that.
@@ -576,6 +594,24 @@ impl Method {
         unsafe { ffi::method_getNumberOfArguments(self) as usize }
     }
 
+    /// Verify the argument and return types of this method.
+    ///
+    /// This is the same check as [`AnyClass::verify_sel`], for when you
+    /// already have the method.
+    #[allow(clippy::missing_errors_doc)] // Written differently in the docs
+    pub fn verify<A, R>(&self) -> Result<(), VerificationError>
+    where
+        A: EncodeArguments,
+        R: EncodeReturn,
+    {
+        verify_method_signature(
+            self,
+            A::ENCODINGS,
+            &R::ENCODING_RETURN,
+            EquivalenceOptions::new(),
+        )
+    }
+
     /// Returns the implementation of this method.
     #[doc(alias = "method_getImplementation")]
     pub fn implementation(&self) -> Imp {
@@ -777,6 +813,49 @@ impl AnyClass {
         unsafe { Self::superclass_raw(self) }
     }
 
+    /// Returns an iterator over the superclasses of self, starting with the
+    /// direct superclass and ending with the root class.
+    ///
+    /// Self is not included.
+    ///
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use objc2::runtime::NSObject;
+    /// use objc2::ClassType;
+    ///
+    /// let cls = NSObject::class();
+    /// assert_eq!(cls.superclasses().count(), 0);
+    ///
+    /// let metacls = cls.metaclass();
+    /// // The root metaclass inherits from the root class.
+    /// assert_eq!(metacls.superclasses().collect::<Vec<_>>(), [cls]);
+    /// ```
+    #[inline]
+    pub fn superclasses(&self) -> Superclasses<'_> {
+        Superclasses {
+            next: self.superclass(),
+        }
+    }
+
+    /// Returns the classes that directly inherit from self.
+    ///
+    /// This searches through all registered classes, and is thus fairly
+    /// expensive. If self is a metaclass, the metaclasses that directly
+    /// inherit from it are returned instead.
+    ///
+    /// The root metaclass is not included in the subclasses of the root
+    /// class, even though it technically inherits from it.
+    pub fn subclasses(&self) -> Vec<&'static Self> {
+        let is_metaclass = self.is_metaclass();
+        AnyClass::classes()
+            .iter()
+            .map(|cls| if is_metaclass { cls.metaclass() } else { cls })
+            .filter(|cls| cls.superclass() == Some(self))
+            .collect()
+    }
+
     /// Returns the metaclass of self.
     ///
     ///
@@ -892,6 +971,15 @@ impl AnyClass {
         }
     }
 
+    /// Describes the class methods implemented by self.
+    ///
+    /// Same as `cls.metaclass().instance_methods()`.
+    #[inline]
+    #[doc(alias = "class_copyMethodList")]
+    pub fn class_methods(&self) -> MallocSlice!(&Method) {
+        self.metaclass().instance_methods()
+    }
+
     /// Checks whether this class conforms to the specified protocol.
     #[inline]
     #[doc(alias = "class_conformsToProtocol")]
@@ -919,6 +1007,73 @@ impl AnyClass {
         }
     }
 
+    /// Returns the description of which instance variables of self hold
+    /// strong references, or [`None`] if there is no such description.
+    ///
+    /// The layout is a sequence of bytes, where the upper nibble of each
+    /// byte is the number of pointer-sized words to skip, and the lower
+    /// nibble is the number of consecutive words that are strong references.
+    /// The words are counted from the start of the instance variables that
+    /// self declares, not including those of the superclasses.
+    ///
+    /// This is only available on Apple's runtime, the other runtimes store
+    /// the layout in a different format.
+    #[inline]
+    #[doc(alias = "class_getIvarLayout")]
+    #[cfg(any(doc, target_vendor = "apple"))]
+    pub fn ivar_layout(&self) -> Option<&[u8]> {
+        let layout = unsafe { ffi::class_getIvarLayout(self) };
+        // SAFETY: The layout is a NUL-terminated string, that lives as long
+        // as the class.
+        unsafe { Self::layout_bytes(layout) }
+    }
+
+    /// # Safety
+    ///
+    /// The pointer must be NULL or a valid C-string, and the caller must
+    /// bound the lifetime.
+    #[cfg(any(doc, target_vendor = "apple"))]
+    unsafe fn layout_bytes<'a>(layout: *const u8) -> Option<&'a [u8]> {
+        if layout.is_null() {
+            None
+        } else {
+            Some(unsafe { CStr::from_ptr(layout.cast()) }.to_bytes())
+        }
+    }
+
+    /// Returns the description of which instance variables of self hold
+    /// weak references, or [`None`] if there is no such description.
+    ///
+    /// See [`ivar_layout`][Self::ivar_layout] for the format.
+    #[inline]
+    #[doc(alias = "class_getWeakIvarLayout")]
+    #[cfg(any(doc, target_vendor = "apple"))]
+    pub fn weak_ivar_layout(&self) -> Option<&[u8]> {
+        let layout = unsafe { ffi::class_getWeakIvarLayout(self) };
+        // SAFETY: Same as in `ivar_layout`.
+        unsafe { Self::layout_bytes(layout) }
+    }
+
+    /// Returns the path of the dynamic library or executable (the "image")
+    /// that defined self, or [`None`] if it was created at runtime.
+    ///
+    /// This can be used to find out which framework or bundle a class came
+    /// from.
+    #[inline]
+    #[doc(alias = "class_getImageName")]
+    #[cfg(any(doc, target_vendor = "apple"))]
+    pub fn image_name(&self) -> Option<&CStr> {
+        let name = unsafe { ffi::class_getImageName(self) };
+        if name.is_null() {
+            None
+        } else {
+            // SAFETY: The name is a valid C-string, that lives as long as
+            // the class (images containing Objective-C code are never
+            // unloaded).
+            Some(unsafe { CStr::from_ptr(name) })
+        }
+    }
+
     /// Check whether instances of this class respond to the given selector.
     ///
     /// This doesn't call `respondsToSelector:`, but works entirely within the
@@ -970,15 +1125,70 @@ impl AnyClass {
     /// ```
     #[allow(clippy::missing_errors_doc)] // Written differently in the docs
     pub fn verify_sel<A, R>(&self, sel: Sel) -> Result<(), VerificationError>
+    where
+        A: EncodeArguments,
+        R: EncodeReturn,
+    {
+        self.verify_sel_with::<A, R>(sel, EquivalenceOptions::new())
+    }
+
+    /// Verify argument and return types for a given selector, with the
+    /// given options for relaxing the encoding comparison.
+    ///
+    /// This is the same as [`verify_sel`][Self::verify_sel], except that it
+    /// allows e.g. ignoring signedness differences on a per-call basis,
+    /// instead of through the `"relax-sign-encoding"` Cargo feature.
+    ///
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use objc2::{class, sel};
+    /// use objc2::encode::EquivalenceOptions;
+    /// use objc2::runtime::AnyClass;
+    /// let cls = class!(NSObject);
+    /// let sel = sel!(hash);
+    /// // `hash` returns `NSUInteger`, but allow verifying it as `NSInteger`.
+    /// let options = EquivalenceOptions::new().ignore_signedness(true);
+    /// let result = cls.verify_sel_with::<(), isize>(sel, options);
+    /// assert!(result.is_ok());
+    /// ```
+    #[allow(clippy::missing_errors_doc)] // Written differently in the docs
+    pub fn verify_sel_with<A, R>(
+        &self,
+        sel: Sel,
+        options: EquivalenceOptions,
+    ) -> Result<(), VerificationError>
     where
         A: EncodeArguments,
         R: EncodeReturn,
     {
         let method = self.instance_method(sel).ok_or(Inner::MethodNotFound)?;
-        verify_method_signature(method, A::ENCODINGS, &R::ENCODING_RETURN)
+        verify_method_signature(method, A::ENCODINGS, &R::ENCODING_RETURN, options)
     }
 }
 
+/// An iterator over the superclasses of a class.
+///
+/// Created by [`AnyClass::superclasses`].
+#[derive(Debug, Clone)]
+pub struct Superclasses<'a> {
+    next: Option<&'a AnyClass>,
+}
+
+impl<'a> Iterator for Superclasses<'a> {
+    type Item = &'a AnyClass;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a AnyClass> {
+        let cls = self.next?;
+        self.next = cls.superclass();
+        Some(cls)
+    }
+}
+
+impl FusedIterator for Superclasses<'_> {}
+
 standard_pointer_impls!(AnyClass);
 
 unsafe impl RefEncode for AnyClass {
@@ -1106,6 +1316,47 @@ impl AnyProtocol {
     pub(crate) fn class_method_descriptions(&self, required: bool) -> Vec<MethodDescription> {
         self.method_descriptions_inner(required, false)
     }
+
+    /// Verify argument and return types for a given instance method in this
+    /// protocol or in one of the protocols it adopts.
+    ///
+    /// This is the same check as [`AnyClass::verify_sel`], except that the
+    /// type encoding is taken from the protocol's method description, so it
+    /// works before any class implements the method.
+    #[allow(clippy::missing_errors_doc)] // Same as in `AnyClass::verify_sel`
+    pub fn verify_sel<A, R>(&self, sel: Sel) -> Result<(), VerificationError>
+    where
+        A: EncodeArguments,
+        R: EncodeReturn,
+    {
+        let types = self
+            .instance_method_types(sel)
+            .ok_or(Inner::MethodNotFound)?;
+        let types = types.to_str().expect("method type encoding must be UTF-8");
+        verify_method_types(
+            MethodEncodingIter::new(types),
+            sel,
+            A::ENCODINGS,
+            &R::ENCODING_RETURN,
+            EquivalenceOptions::new(),
+        )
+    }
+
+    fn instance_method_types(&self, sel: Sel) -> Option<&'static CStr> {
+        for required in [true, false] {
+            // SAFETY: The protocol and selector are valid.
+            let desc = unsafe {
+                ffi::protocol_getMethodDescription(self, sel, Bool::new(required), Bool::YES)
+            };
+            // SAFETY: The types are a C-string if the selector is non-NULL.
+            if let Some(desc) = unsafe { MethodDescription::from_raw(desc) } {
+                return Some(desc.types);
+            }
+        }
+        self.adopted_protocols()
+            .iter()
+            .find_map(|proto| proto.instance_method_types(sel))
+    }
 }
 
 impl PartialEq for AnyProtocol {
@@ -1579,6 +1830,44 @@ mod tests {
         assert_eq!(subclass.superclass().unwrap(), cls);
     }
 
+    #[test]
+    fn test_class_hierarchy() {
+        let cls = test_utils::custom_class();
+        let subclass = test_utils::custom_subclass();
+        assert_eq!(cls.superclasses().count(), 0);
+        assert_eq!(subclass.superclasses().collect::<Vec<_>>(), [cls]);
+        assert_eq!(
+            subclass.metaclass().superclasses().collect::<Vec<_>>(),
+            [cls.metaclass(), cls]
+        );
+
+        assert!(cls.subclasses().contains(&subclass));
+        assert!(cls.metaclass().subclasses().contains(&subclass.metaclass()));
+        assert!(!cls.subclasses().contains(&cls));
+    }
+
+    #[test]
+    fn test_class_methods() {
+        let cls = test_utils::custom_class();
+        let methods = cls.class_methods();
+        assert!(methods.iter().any(|m| m.name() == sel!(classFoo)));
+        assert!(!methods.iter().any(|m| m.name() == sel!(foo)));
+    }
+
+    #[test]
+    #[cfg(target_vendor = "apple")]
+    fn test_class_image_and_layout() {
+        let cls = NSObject::class();
+        let image = cls.image_name().unwrap();
+        assert!(image.to_str().unwrap().contains("libobjc"));
+        let _ = cls.ivar_layout();
+        let _ = cls.weak_ivar_layout();
+
+        // Classes created at runtime don't belong to an image.
+        let cls = test_utils::custom_class();
+        assert_eq!(cls.image_name(), None);
+    }
+
     #[test]
     fn test_classes_count() {
         assert!(AnyClass::classes_count() > 0);
@@ -1635,6 +1924,36 @@ mod tests {
         assert_eq!(sub_proto.adopted_protocols()[0], super_proto);
     }
 
+    #[test]
+    fn test_protocol_verify_sel() {
+        // The selectors are broken somehow on GNUStep < 2.0
+        if cfg!(all(feature = "gnustep-1-7", not(feature = "gnustep-2-0"))) {
+            return;
+        }
+        let sub_proto = test_utils::custom_subprotocol();
+        assert!(sub_proto
+            .verify_sel::<(u32,), u32>(sel!(calculateFoo:))
+            .is_ok());
+        // Inherited from the adopted protocol
+        assert!(sub_proto.verify_sel::<(i32,), ()>(sel!(setBar:)).is_ok());
+        assert!(sub_proto
+            .verify_sel::<(), *const c_char>(sel!(getName))
+            .is_ok());
+
+        let err = sub_proto
+            .verify_sel::<(u32,), i8>(sel!(calculateFoo:))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected return to have type code 'I', but found 'c'"
+        );
+        // Class methods are not considered
+        let err = sub_proto
+            .verify_sel::<(i32, i32), i32>(sel!(addNumber:toNumber:))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "method not found");
+    }
+
     #[test]
     fn test_protocols() {
         // Ensure that a protocol has been registered on linux
@@ -0,0 +1,233 @@
+//! Classes that can be removed from the runtime again.
+//!
+//! In debug builds, instances of disposable classes are counted by
+//! overriding `+allocWithZone:` and `-dealloc`, so that disposing a class
+//! that still has instances can be caught.
+use core::fmt;
+use core::ops::Deref;
+use core::ptr::NonNull;
+
+use crate::ffi;
+use crate::runtime::AnyClass;
+
+/// A class that was registered with
+/// [`ClassBuilder::register_disposable`][crate::runtime::ClassBuilder::register_disposable],
+/// and which can be removed from the runtime again with
+/// [`dispose`][Self::dispose].
+///
+/// Dropping the handle without disposing it leaves the class registered,
+/// like [`ClassBuilder::register`][crate::runtime::ClassBuilder::register].
+// Not `Copy`, since the class can only be disposed once.
+#[allow(missing_copy_implementations)]
+pub struct RegisteredClass {
+    cls: NonNull<AnyClass>,
+}
+
+// SAFETY: Same as `&AnyClass`.
+unsafe impl Send for RegisteredClass {}
+unsafe impl Sync for RegisteredClass {}
+
+impl RegisteredClass {
+    /// # Safety
+    ///
+    /// The class must have just been registered, and must not be disposed
+    /// by anything else.
+    pub(crate) unsafe fn new(cls: &AnyClass) -> Self {
+        #[cfg(debug_assertions)]
+        instances::track(cls);
+        Self {
+            cls: NonNull::from(cls),
+        }
+    }
+
+    /// Remove the class and its metaclass from the runtime.
+    ///
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, this panics if the class has subclasses, or if there
+    /// are instances of it that were allocated with `+alloc` or
+    /// `+allocWithZone:` and not yet deallocated.
+    ///
+    /// Instances are not counted if the class implements either of
+    /// `+allocWithZone:` or `-dealloc` itself.
+    ///
+    ///
+    /// # Safety
+    ///
+    /// No instances or subclasses of the class may exist, and the class must
+    /// not be used after this, e.g. through a reference retrieved from
+    /// [`AnyClass::get`].
+    #[doc(alias = "objc_disposeClassPair")]
+    #[cfg(any(doc, not(feature = "unstable-objfw")))]
+    pub unsafe fn dispose(self) {
+        #[cfg(debug_assertions)]
+        {
+            if let Some(subclass) = AnyClass::classes()
+                .iter()
+                .find(|cls| cls.superclass() == Some(&*self))
+            {
+                panic!(
+                    "cannot dispose class {}: it has subclass {subclass}",
+                    &*self
+                );
+            }
+            // Only stop tracking the class if it has no instances, such
+            // that the live instances can still be deallocated afterwards.
+            if let Err(live) = instances::untrack(&self) {
+                panic!(
+                    "cannot dispose class {}: {live} instance(s) are still alive",
+                    &*self
+                );
+            }
+        }
+
+        super::typed_ivar::forget_class(&self);
+        crate::debug::forget_class(&self);
+
+        // SAFETY: The class was registered by us, and the caller upholds
+        // that it is no longer in use.
+        unsafe { ffi::objc_disposeClassPair(self.cls.as_ptr()) };
+    }
+}
+
+impl Deref for RegisteredClass {
+    type Target = AnyClass;
+
+    #[inline]
+    fn deref(&self) -> &AnyClass {
+        // SAFETY: The class is valid until it is disposed, which consumes
+        // the handle.
+        unsafe { self.cls.as_ref() }
+    }
+}
+
+impl AsRef<AnyClass> for RegisteredClass {
+    #[inline]
+    fn as_ref(&self) -> &AnyClass {
+        self
+    }
+}
+
+impl fmt::Debug for RegisteredClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegisteredClass")
+            .field("name", &self.name())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(debug_assertions)]
+mod instances {
+    use alloc::vec::Vec;
+    use std::sync::{Mutex, MutexGuard};
+
+    use crate::runtime::declare::method_type_encoding;
+    use crate::runtime::typed_ivar::address;
+    use crate::runtime::{AnyClass, AnyObject, MethodImplementation, NSZone, Sel};
+    use crate::{ffi, msg_send, sel};
+
+    /// The address of each tracked class, and the number of live instances.
+    static LIVE_INSTANCES: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+
+    /// Recovers from poisoning, since `dealloc` must keep working after a
+    /// panic elsewhere.
+    fn live_instances() -> MutexGuard<'static, Vec<(usize, usize)>> {
+        LIVE_INSTANCES.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Find the tracked class that `cls` is or inherits from, and adjust its
+    /// instance count.
+    fn update(cls: &AnyClass, f: impl FnOnce(&mut usize)) -> &AnyClass {
+        let tracked = {
+            let mut classes = live_instances();
+            let tracked = core::iter::once(cls)
+                .chain(cls.superclasses())
+                .find_map(|cls| {
+                    let i = classes
+                        .iter()
+                        .position(|(tracked, _)| *tracked == address(cls))?;
+                    Some((cls, i))
+                });
+            if let Some((_, i)) = tracked {
+                f(&mut classes[i].1);
+            }
+            tracked
+        };
+        // Panic after releasing the lock, to avoid poisoning it.
+        let (tracked, _) = tracked.expect("instance of disposable class was not tracked");
+        tracked
+    }
+
+    fn defines(cls: &AnyClass, sel: Sel) -> bool {
+        cls.instance_methods()
+            .iter()
+            .any(|method| method.name() == sel)
+    }
+
+    unsafe extern "C-unwind" fn alloc_with_zone(
+        cls: &AnyClass,
+        _cmd: Sel,
+        zone: *mut NSZone,
+    ) -> *mut AnyObject {
+        let tracked = update(cls, |count| *count += 1);
+        let superclass = tracked.superclass().unwrap().metaclass();
+        unsafe { msg_send![super(cls, superclass), allocWithZone: zone] }
+    }
+
+    unsafe extern "C-unwind" fn dealloc(this: *mut AnyObject, _cmd: Sel) {
+        // SAFETY: The object is valid until the superclass' `dealloc`.
+        let cls = unsafe { &*this }.class();
+        // Instances that were not created through `+allocWithZone:` (e.g.
+        // with `class_createInstance`) were not counted.
+        let tracked = update(cls, |count| *count = count.saturating_sub(1));
+        let superclass = tracked.superclass().unwrap();
+        unsafe { msg_send![super(this, superclass), dealloc] }
+    }
+
+    pub(super) fn track(cls: &AnyClass) {
+        // Root classes don't have a superclass to forward to.
+        if cls.superclass().is_none()
+            || defines(cls, sel!(dealloc))
+            || defines(cls.metaclass(), sel!(allocWithZone:))
+        {
+            return;
+        }
+
+        live_instances().push((address(cls), 0));
+
+        let alloc_with_zone: unsafe extern "C-unwind" fn(_, _, _) -> _ = alloc_with_zone;
+        let dealloc: unsafe extern "C-unwind" fn(_, _) = dealloc;
+        // SAFETY: The methods are not defined on the class itself, and the
+        // type encodings match the implementations.
+        unsafe {
+            add_method(cls.metaclass(), sel!(allocWithZone:), alloc_with_zone);
+            add_method(cls, sel!(dealloc), dealloc);
+        }
+    }
+
+    unsafe fn add_method<F: MethodImplementation>(cls: &AnyClass, sel: Sel, func: F) {
+        let ptr: *const AnyClass = cls;
+        let types = method_type_encoding::<F::Return, F::Arguments>();
+        // SAFETY: Adding methods to registered classes is allowed, the
+        // caller ensures that the method is not already defined.
+        let success =
+            unsafe { ffi::class_addMethod(ptr as *mut _, sel, func.__imp(), types.as_ptr()) };
+        assert!(success.as_bool(), "failed to add method {sel}");
+    }
+
+    /// Stop tracking the class, unless it has live instances, in which case
+    /// their number is returned.
+    pub(super) fn untrack(cls: &AnyClass) -> Result<(), usize> {
+        let addr = address(cls);
+        let mut classes = live_instances();
+        match classes.iter().position(|(cls, _)| *cls == addr) {
+            Some(i) if classes[i].1 != 0 => Err(classes[i].1),
+            Some(i) => {
+                classes.swap_remove(i);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,560 @@
+//! Instance variables of arbitrary Rust types on dynamically built classes.
+//!
+//! This is similar to what `declare_class!` does with `DeclaredClass::Ivars`
+//! (see `__macro_helpers::declared_ivars`), except that the ivars are added
+//! one at a time, and that the classes they belong to are not known
+//! statically.
+//!
+//! Each typed ivar is stored as an opaque byte array, along with a flag that
+//! tracks whether it has been initialized. If any of the ivars of a class
+//! need dropping, a `dealloc` method is added to the class that drops the
+//! initialized ivars before calling the superclass' `dealloc`.
+//!
+//! Since the same `dealloc` implementation is shared between all such
+//! classes, each class also gets a "dealloc state" flag, which is set when
+//! its `dealloc` has run on an instance. This allows `dealloc` to find out
+//! which class in the hierarchy it is currently running as.
+use alloc::boxed::Box;
+use alloc::ffi::CString;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::ffi::CStr;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::{self, NonNull};
+use std::sync::{Mutex, OnceLock};
+
+use crate::__macro_helpers::declared_ivars::{ivar_encoding, needs_storage};
+use crate::debug::{register_opaque_ivar, OpaqueIvar, VisitIvar};
+use crate::runtime::{AnyClass, AnyObject, ClassBuilder, MessageReceiver, Sel};
+use crate::sel;
+
+/// The initialization flag of an ivar.
+const UNINIT: u8 = 0;
+const INIT: u8 = 1;
+
+/// Information about a typed ivar that is needed by the builder.
+#[derive(Debug)]
+pub(crate) struct TypedIvar {
+    data: Option<CString>,
+    flag: CString,
+    drop: Option<unsafe fn(NonNull<u8>)>,
+    /// Reports the strong references in the ivar to `find_retain_cycles`.
+    references: Option<VisitIvar>,
+}
+
+unsafe fn drop_ivar<T>(ptr: NonNull<u8>) {
+    let ptr = if needs_storage::<T>() {
+        ptr.cast::<T>()
+    } else {
+        // Zero-sized types don't have storage, use a correctly aligned
+        // pointer instead.
+        NonNull::dangling()
+    };
+    // SAFETY: Checked by caller.
+    unsafe { ptr::drop_in_place(ptr.as_ptr()) }
+}
+
+impl ClassBuilder {
+    pub(crate) fn add_typed_ivar_inner<T>(
+        &mut self,
+        name: &CStr,
+        references: Option<VisitIvar>,
+    ) -> IvarHandle<T> {
+        let flag = CString::new(format!("{}_init", name.to_string_lossy())).unwrap();
+        let data = if needs_storage::<T>() {
+            let encoding = ivar_encoding::<T>().to_string();
+            // SAFETY: The encoding has the size and alignment of `T`.
+            unsafe { self.add_ivar_inner::<T>(name, &encoding) };
+            Some(CString::from(name))
+        } else {
+            None
+        };
+        self.add_ivar::<u8>(&flag);
+
+        self.typed_ivars.push(TypedIvar {
+            data: data.clone(),
+            flag: flag.clone(),
+            drop: if mem::needs_drop::<T>() {
+                Some(drop_ivar::<T>)
+            } else {
+                None
+            },
+            references,
+        });
+
+        IvarHandle {
+            data,
+            flag,
+            offsets: OnceLock::new(),
+            p: PhantomData,
+        }
+    }
+
+    /// Add the `dealloc` method and the dealloc state ivar, if any of the
+    /// typed ivars need it.
+    pub(crate) fn setup_typed_ivars_dealloc(&mut self) -> Option<CString> {
+        if self.typed_ivars.iter().all(|ivar| ivar.drop.is_none()) {
+            return None;
+        }
+        assert!(
+            self.superclass().is_some(),
+            "typed ivars that need dropping are not supported on root classes"
+        );
+
+        let state = CString::new(format!(
+            "{}_typed_ivars_dealloc_state",
+            self.name().to_string_lossy()
+        ))
+        .unwrap();
+        self.add_ivar::<u8>(&state);
+
+        let func: unsafe extern "C-unwind" fn(_, _) = dealloc;
+        // SAFETY: The signature is correct. Adding a method that already
+        // exists panics.
+        unsafe { self.add_method(sel!(dealloc), func) };
+        Some(state)
+    }
+
+    /// Record the ivar offsets of a class that was just registered, for use
+    /// in `dealloc`.
+    pub(crate) fn register_typed_ivars(
+        cls: &'static AnyClass,
+        state: Option<CString>,
+        ivars: Vec<TypedIvar>,
+    ) {
+        let offset = |name: &CStr| {
+            cls.instance_variable(name)
+                .unwrap_or_else(|| panic!("ivar {name:?} not found on class {cls}"))
+                .offset()
+        };
+        for ivar in &ivars {
+            if let (Some(data), Some(visit)) = (&ivar.data, ivar.references) {
+                register_opaque_ivar(
+                    cls,
+                    OpaqueIvar {
+                        name: data.to_string_lossy().into_owned(),
+                        data_offset: offset(data),
+                        flag_offset: offset(&ivar.flag),
+                        is_initialized: |flag| flag == INIT,
+                        visit,
+                    },
+                );
+            }
+        }
+
+        let Some(state) = state else {
+            return;
+        };
+        let ivars = ivars
+            .iter()
+            .filter_map(|ivar| {
+                Some(DroppedIvar {
+                    data_offset: ivar.data.as_deref().map(offset),
+                    flag_offset: offset(&ivar.flag),
+                    drop: ivar.drop?,
+                })
+            })
+            .collect::<Vec<_>>();
+        DEALLOC_CLASSES.lock().unwrap().push(DeallocClass {
+            cls: address(cls),
+            state_offset: offset(&state),
+            ivars: Box::leak(ivars.into_boxed_slice()),
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DroppedIvar {
+    /// `None` for zero-sized types, which don't have storage.
+    data_offset: Option<isize>,
+    flag_offset: isize,
+    drop: unsafe fn(NonNull<u8>),
+}
+
+#[derive(Debug)]
+struct DeallocClass {
+    cls: usize,
+    state_offset: isize,
+    ivars: &'static [DroppedIvar],
+}
+
+/// The classes that use [`dealloc`].
+static DEALLOC_CLASSES: Mutex<Vec<DeallocClass>> = Mutex::new(Vec::new());
+
+pub(super) fn address(cls: &AnyClass) -> usize {
+    let ptr: *const AnyClass = cls;
+    ptr as usize
+}
+
+/// Stop tracking the class, when it is disposed.
+pub(crate) fn forget_class(cls: &AnyClass) {
+    let addr = address(cls);
+    DEALLOC_CLASSES
+        .lock()
+        .unwrap()
+        .retain(|class| class.cls != addr);
+}
+
+fn byte_at(this: NonNull<AnyObject>, offset: isize) -> NonNull<u8> {
+    // SAFETY: The offsets are those of the object's ivars.
+    unsafe { AnyObject::ivar_at_offset::<u8>(this, offset) }
+}
+
+/// The `dealloc` method of classes with typed ivars that need dropping.
+unsafe extern "C-unwind" fn dealloc(this: NonNull<AnyObject>, cmd: Sel) {
+    // Find the first class in the hierarchy whose `dealloc` has not yet run,
+    // since `dealloc` runs on subclasses before superclasses.
+    let (superclass, ivars) = {
+        let classes = DEALLOC_CLASSES.lock().unwrap();
+        // SAFETY: The object is valid until the superclass' `dealloc`.
+        let mut cls = unsafe { this.as_ref() }.class();
+        loop {
+            if let Some(class) = classes.iter().find(|class| class.cls == address(cls)) {
+                let state = byte_at(this, class.state_offset).as_ptr();
+                // SAFETY: The state is a `u8`, and only accessed here.
+                if unsafe { *state } == UNINIT {
+                    unsafe { *state = INIT };
+                    break (cls.superclass().unwrap(), class.ivars);
+                }
+            }
+            cls = cls
+                .superclass()
+                .expect("dealloc called on class without typed ivars");
+        }
+    };
+
+    // Drop outside the lock, since that may deallocate other objects.
+    for ivar in ivars {
+        let flag = byte_at(this, ivar.flag_offset).as_ptr();
+        // SAFETY: The flag says whether the ivar is initialized, and this is
+        // the last time it is accessed.
+        if unsafe { *flag } == INIT {
+            unsafe { *flag = UNINIT };
+            let data = ivar
+                .data_offset
+                .map_or(NonNull::dangling(), |offset| byte_at(this, offset));
+            unsafe { (ivar.drop)(data) };
+        }
+    }
+
+    // SAFETY: The argument and return types are correct, and we only call
+    // this once per class.
+    unsafe { MessageReceiver::send_super_message(this, superclass, cmd, ()) }
+}
+
+/// A handle to an instance variable of type `T`, created with
+/// [`ClassBuilder::add_typed_ivar`].
+///
+/// Unlike [`ClassBuilder::add_ivar`], the type does not have to implement
+/// [`Encode`], since the ivar is opaque to the Objective-C runtime. The ivar
+/// starts out uninitialized, and is dropped when the object is deallocated if
+/// it has been initialized.
+///
+///
+/// # Safety of the methods
+///
+/// The methods that access the ivar are `unsafe`, since the handle cannot
+/// check that the object is an instance of the class (or a subclass of the
+/// class) that the ivar was added to, nor that accesses to the ivar don't
+/// alias or race.
+///
+///
+/// # Examples
+///
+/// ```
+/// use std::ffi::CStr;
+///
+/// use objc2::rc::Retained;
+/// use objc2::runtime::{AnyObject, ClassBuilder, NSObject};
+/// use objc2::{msg_send_id, ClassType};
+///
+/// let name = CStr::from_bytes_with_nul(b"IvarHandleExample\0").unwrap();
+/// let mut builder = ClassBuilder::new(name, NSObject::class()).unwrap();
+/// let ivar = builder.add_typed_ivar::<Vec<String>>(
+///     CStr::from_bytes_with_nul(b"names\0").unwrap(),
+/// );
+/// let cls = builder.register();
+///
+/// let obj: Retained<AnyObject> = unsafe { msg_send_id![cls, new] };
+///
+/// // SAFETY: The object is an instance of the class, and the ivar is not
+/// // accessed from elsewhere.
+/// unsafe {
+///     assert!(!ivar.is_initialized(&obj));
+///     ivar.set(&obj, vec!["a".to_string()]);
+///     ivar.get_mut(&obj).unwrap().push("b".to_string());
+///     assert_eq!(ivar.get(&obj).unwrap(), &["a", "b"]);
+/// }
+/// // The `Vec` is dropped when `obj` is deallocated.
+/// ```
+pub struct IvarHandle<T> {
+    data: Option<CString>,
+    flag: CString,
+    /// The offsets of the data and the flag, computed on first use.
+    offsets: OnceLock<(isize, isize)>,
+    p: PhantomData<fn() -> T>,
+}
+
+impl<T> IvarHandle<T> {
+    fn ptrs(&self, obj: &AnyObject) -> (NonNull<T>, NonNull<u8>) {
+        let (data_offset, flag_offset) = *self.offsets.get_or_init(|| {
+            let cls = obj.class();
+            let offset = |name: &CStr| {
+                cls.instance_variable(name)
+                    .unwrap_or_else(|| panic!("ivar {name:?} not found on class {cls}"))
+                    .offset()
+            };
+            (self.data.as_deref().map_or(0, offset), offset(&self.flag))
+        });
+        let obj = NonNull::from(obj);
+        let data = if self.data.is_some() {
+            byte_at(obj, data_offset).cast()
+        } else {
+            // Zero-sized types don't need storage.
+            NonNull::dangling()
+        };
+        (data, byte_at(obj, flag_offset))
+    }
+
+    /// Whether the ivar on the given object has been initialized.
+    ///
+    /// This panics if the object's class doesn't have the ivar; the ivar is
+    /// looked up by name the first time the handle is used.
+    ///
+    ///
+    /// # Safety
+    ///
+    /// The object must be an instance of the class that the ivar was added
+    /// to (or a subclass), since the offsets of the ivar are cached on first
+    /// use. The ivar must not be initialized or taken concurrently from
+    /// other threads.
+    pub unsafe fn is_initialized(&self, obj: &AnyObject) -> bool {
+        let (_, flag) = self.ptrs(obj);
+        // SAFETY: The flag is a `u8`, and is only written while no other
+        // references to the ivar exist (upheld by caller).
+        unsafe { *flag.as_ptr() == INIT }
+    }
+
+    /// Initialize the ivar, returning the previous value if it was already
+    /// initialized.
+    ///
+    ///
+    /// # Safety
+    ///
+    /// The object must be an instance of the class that the ivar was added
+    /// to (or a subclass), and there must be no other references to the
+    /// ivar, including from other threads.
+    pub unsafe fn set(&self, obj: &AnyObject, value: T) -> Option<T> {
+        let old = unsafe { self.take(obj) };
+        let (data, flag) = self.ptrs(obj);
+        // SAFETY: Upheld by caller.
+        unsafe { data.as_ptr().write(value) };
+        unsafe { *flag.as_ptr() = INIT };
+        old
+    }
+
+    /// Move the value out of the ivar, leaving it uninitialized.
+    ///
+    ///
+    /// # Safety
+    ///
+    /// Same as [`set`][Self::set].
+    pub unsafe fn take(&self, obj: &AnyObject) -> Option<T> {
+        let (data, flag) = self.ptrs(obj);
+        // SAFETY: Upheld by caller.
+        if unsafe { *flag.as_ptr() } == INIT {
+            unsafe { *flag.as_ptr() = UNINIT };
+            Some(unsafe { data.as_ptr().read() })
+        } else {
+            None
+        }
+    }
+
+    /// Get a reference to the ivar, if it has been initialized.
+    ///
+    ///
+    /// # Safety
+    ///
+    /// The object must be an instance of the class that the ivar was added
+    /// to (or a subclass), and the ivar must not be mutated while the
+    /// reference is alive. If `T` is not [`Sync`], the reference must not be
+    /// used from other threads.
+    pub unsafe fn get<'a>(&self, obj: &'a AnyObject) -> Option<&'a T> {
+        let (data, flag) = self.ptrs(obj);
+        // SAFETY: Upheld by caller.
+        if unsafe { *flag.as_ptr() } == INIT {
+            Some(unsafe { data.as_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// Get a mutable reference to the ivar, if it has been initialized.
+    ///
+    ///
+    /// # Safety
+    ///
+    /// Same as [`set`][Self::set], for as long as the reference is alive.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get_mut<'a>(&self, obj: &'a AnyObject) -> Option<&'a mut T> {
+        let (mut data, flag) = self.ptrs(obj);
+        // SAFETY: Upheld by caller.
+        if unsafe { *flag.as_ptr() } == INIT {
+            Some(unsafe { data.as_mut() })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> fmt::Debug for IvarHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IvarHandle")
+            .field("name", &self.data)
+            .field("flag", &self.flag)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::rc::Rc;
+    use alloc::string::{String, ToString};
+    use core::cell::{Cell, OnceCell};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::rc::Retained;
+    use crate::runtime::NSObject;
+    use crate::{msg_send_id, ClassType};
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    struct DropCounter(Rc<Cell<usize>>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_typed_ivar() {
+        let mut builder = ClassBuilder::new(&c("TestTypedIvar"), NSObject::class()).unwrap();
+        let string = builder.add_typed_ivar::<String>(&c("string"));
+        let number = builder.add_typed_ivar::<u64>(&c("number"));
+        let zst = builder.add_typed_ivar::<()>(&c("zst"));
+        let cls = builder.register();
+
+        let obj: Retained<AnyObject> = unsafe { msg_send_id![cls, new] };
+        unsafe {
+            assert!(!string.is_initialized(&obj));
+            assert!(!zst.is_initialized(&obj));
+            assert_eq!(string.get(&obj), None);
+            assert_eq!(string.set(&obj, "foo".to_string()), None);
+            string.get_mut(&obj).unwrap().push_str("bar");
+            assert_eq!(string.get(&obj).unwrap(), "foobar");
+            assert_eq!(string.set(&obj, "baz".to_string()).unwrap(), "foobar");
+
+            number.set(&obj, 42);
+            assert_eq!(number.take(&obj), Some(42));
+            assert_eq!(number.take(&obj), None);
+
+            zst.set(&obj, ());
+            assert_eq!(zst.get(&obj), Some(&()));
+
+            assert!(string.is_initialized(&obj));
+            assert!(!number.is_initialized(&obj));
+            assert!(zst.is_initialized(&obj));
+        }
+    }
+
+    #[test]
+    fn test_typed_ivar_dropped() {
+        let drops = Rc::new(Cell::new(0));
+
+        let mut builder = ClassBuilder::new(&c("TestTypedIvarDropped"), NSObject::class()).unwrap();
+        let a = builder.add_typed_ivar::<DropCounter>(&c("TestTypedIvarDropped_a"));
+        let cls = builder.register();
+
+        let mut builder = ClassBuilder::new(&c("TestTypedIvarDroppedSubclass"), cls).unwrap();
+        let b = builder.add_typed_ivar::<DropCounter>(&c("TestTypedIvarDroppedSubclass_b"));
+        let subcls = builder.register();
+
+        // Uninitialized ivars are not dropped.
+        let obj: Retained<AnyObject> = unsafe { msg_send_id![cls, new] };
+        drop(obj);
+        assert_eq!(drops.get(), 0);
+
+        let obj: Retained<AnyObject> = unsafe { msg_send_id![cls, new] };
+        unsafe { a.set(&obj, DropCounter(drops.clone())) };
+        drop(obj);
+        assert_eq!(drops.get(), 1);
+
+        // Ivars of both the subclass and the superclass are dropped.
+        let obj: Retained<AnyObject> = unsafe { msg_send_id![subcls, new] };
+        unsafe { a.set(&obj, DropCounter(drops.clone())) };
+        unsafe { b.set(&obj, DropCounter(drops.clone())) };
+        drop(obj);
+        assert_eq!(drops.get(), 3);
+
+        // Taken values are not dropped again.
+        let obj: Retained<AnyObject> = unsafe { msg_send_id![subcls, new] };
+        unsafe { b.set(&obj, DropCounter(drops.clone())) };
+        let value = unsafe { b.take(&obj) };
+        drop(obj);
+        assert_eq!(drops.get(), 3);
+        drop(value);
+        assert_eq!(drops.get(), 4);
+    }
+
+    #[test]
+    fn test_zero_sized_typed_ivar_dropped() {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        struct ZstDropCounter;
+
+        impl Drop for ZstDropCounter {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let ivar = OnceCell::new();
+        // SAFETY: The object does not outlive the closure.
+        unsafe {
+            ClassBuilder::scoped(
+                NSObject::class(),
+                |builder| {
+                    let _ = ivar.set(builder.add_typed_ivar::<ZstDropCounter>(&c("zst")));
+                },
+                |cls| {
+                    let ivar = ivar.get().unwrap();
+                    let obj: Retained<AnyObject> = msg_send_id![cls, new];
+                    assert!(ivar.set(&obj, ZstDropCounter).is_none());
+                    assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+                    drop(obj);
+                    assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+                },
+            )
+        }
+    }
+
+    #[test]
+    #[should_panic = "failed to add method dealloc"]
+    fn test_typed_ivar_with_dealloc() {
+        extern "C-unwind" fn dealloc(_this: &NSObject, _cmd: Sel) {}
+
+        let mut builder =
+            ClassBuilder::new(&c("TestTypedIvarWithDealloc"), NSObject::class()).unwrap();
+        let _ = builder.add_typed_ivar::<String>(&c("string"));
+        let dealloc: extern "C-unwind" fn(_, _) = dealloc;
+        unsafe { builder.add_method(sel!(dealloc), dealloc) };
+        let _ = builder.register();
+    }
+}
@@ -0,0 +1,258 @@
+//! # Object locks like `@synchronized`.
+//!
+//! Every Objective-C object has an associated recursive lock, which is what
+//! `@synchronized(obj) { ... }` uses. Some framework objects document that
+//! they must be accessed while holding this lock, to coordinate with
+//! Objective-C code that uses them too.
+//!
+//! The lock is taken with [`SyncGuard::new`] and released when the guard is
+//! dropped, or with the [`synchronized`] convenience function.
+//!
+//! See [Apple's documentation](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/Multithreading/ThreadSafety/ThreadSafety.html#//apple_ref/doc/uid/10000057i-CH8-SW3).
+use core::ffi::c_int;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ptr::NonNull;
+use std::error::Error;
+
+use crate::ffi;
+use crate::runtime::AnyObject;
+use crate::Message;
+
+/// The value that `objc_sync_enter` and `objc_sync_exit` return on success.
+///
+/// This is the same on all runtimes, but only Apple's defines a constant.
+const SYNC_SUCCESS: c_int = 0;
+
+/// An error returned by the runtime when taking or releasing an object's
+/// lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SyncError {
+    code: c_int,
+}
+
+impl SyncError {
+    fn from_code(code: c_int) -> Result<(), Self> {
+        if code == SYNC_SUCCESS {
+            Ok(())
+        } else {
+            Err(Self { code })
+        }
+    }
+
+    /// The raw error code returned by the runtime, such as
+    /// [`ffi::OBJC_SYNC_NOT_OWNING_THREAD_ERROR`].
+    #[inline]
+    pub fn code(&self) -> c_int {
+        self.code
+    }
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            -1 => write!(f, "the current thread does not own the object's lock"),
+            -2 => write!(f, "timed out waiting for the object's lock"),
+            -3 => write!(f, "the object's lock was not initialized"),
+            code => write!(f, "failed synchronizing on object (code {code})"),
+        }
+    }
+}
+
+impl Error for SyncError {}
+
+/// An RAII guard that holds the recursive lock of an object.
+///
+/// This is the equivalent of the scope of `@synchronized(obj) { ... }`; the
+/// lock is released when the guard is dropped, including when unwinding.
+///
+/// The lock is recursive, so the same thread may take it again while holding
+/// it, and must be released on the thread that took it, which is why this
+/// type is not [`Send`].
+///
+///
+/// # Examples
+///
+/// ```
+/// use objc2::runtime::NSObject;
+/// use objc2::sync::SyncGuard;
+///
+/// let obj = NSObject::new();
+/// let guard = SyncGuard::new(&*obj).unwrap();
+/// // The lock is recursive.
+/// let inner = SyncGuard::new(&*obj).unwrap();
+/// drop(inner);
+/// guard.unlock().unwrap();
+/// ```
+#[must_use = "the lock is released immediately if the guard is not used"]
+pub struct SyncGuard<'a> {
+    obj: &'a AnyObject,
+    /// Must be released on the same thread.
+    _p: PhantomData<*mut ()>,
+}
+
+impl<'a> SyncGuard<'a> {
+    /// Take the lock of the object, blocking until it is available.
+    #[doc(alias = "objc_sync_enter")]
+    #[allow(clippy::missing_errors_doc)] // Only fails in exceptional cases
+    pub fn new<T: ?Sized + Message>(obj: &'a T) -> Result<Self, SyncError> {
+        // SAFETY: Message types are objects.
+        let obj: &'a AnyObject = unsafe { NonNull::from(obj).cast().as_ref() };
+        // SAFETY: The object is valid.
+        SyncError::from_code(unsafe { ffi::objc_sync_enter(as_ptr(obj)) })?;
+        Ok(Self {
+            obj,
+            _p: PhantomData,
+        })
+    }
+
+    /// Release the lock, and return any error that the runtime reports.
+    ///
+    /// Dropping the guard does the same, but ignores errors.
+    #[doc(alias = "objc_sync_exit")]
+    #[allow(clippy::missing_errors_doc)] // Only fails in exceptional cases
+    pub fn unlock(self) -> Result<(), SyncError> {
+        let this = ManuallyDrop::new(self);
+        // SAFETY: The lock was taken in `new` on this thread.
+        SyncError::from_code(unsafe { ffi::objc_sync_exit(as_ptr(this.obj)) })
+    }
+}
+
+impl Drop for SyncGuard<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: The lock was taken in `new` on this thread.
+        let res = unsafe { ffi::objc_sync_exit(as_ptr(self.obj)) };
+        debug_assert!(
+            res == SYNC_SUCCESS || std::thread::panicking(),
+            "failed releasing object lock: {}",
+            SyncError { code: res },
+        );
+    }
+}
+
+impl fmt::Debug for SyncGuard<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyncGuard")
+            .field("obj", &(self.obj as *const AnyObject))
+            .finish_non_exhaustive()
+    }
+}
+
+fn as_ptr(obj: &AnyObject) -> *mut AnyObject {
+    let ptr: *const AnyObject = obj;
+    ptr as *mut AnyObject
+}
+
+/// Run the closure while holding the recursive lock of the object.
+///
+/// This is the equivalent of `@synchronized(obj) { ... }` in Objective-C.
+/// The lock is released even if the closure panics.
+///
+///
+/// # Errors
+///
+/// Returns an error if the runtime failed taking the lock.
+///
+/// Releasing the lock afterwards can only fail if the closure released it
+/// itself, e.g. by calling `objc_sync_exit` directly. The result of the
+/// closure is still returned in that case, since the closure has already
+/// run; the failure is instead reported with a debug assertion, same as
+/// when dropping a [`SyncGuard`].
+///
+///
+/// # Examples
+///
+/// ```
+/// use objc2::runtime::NSObject;
+/// use objc2::sync::synchronized;
+///
+/// let obj = NSObject::new();
+/// let res = synchronized(&*obj, || {
+///     // Access state shared with Objective-C code here.
+///     42
+/// });
+/// assert_eq!(res, Ok(42));
+/// ```
+pub fn synchronized<T: ?Sized + Message, R>(
+    obj: &T,
+    f: impl FnOnce() -> R,
+) -> Result<R, SyncError> {
+    let _guard = SyncGuard::new(obj)?;
+    Ok(f())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::*;
+    use crate::rc::Retained;
+    use crate::runtime::NSObject;
+
+    /// `NSObject` is thread-safe, but not marked as such.
+    struct AssertSend(Retained<NSObject>);
+
+    unsafe impl Send for AssertSend {}
+
+    #[test]
+    fn test_recursive() {
+        let obj = NSObject::new();
+        let res = synchronized(&*obj, || synchronized(&*obj, || 1).unwrap() + 1);
+        assert_eq!(res, Ok(2));
+    }
+
+    #[test]
+    fn test_exclusive() {
+        let obj = NSObject::new();
+        let guard = SyncGuard::new(&*obj).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let obj_clone = AssertSend(obj.clone());
+        let handle = thread::spawn(move || {
+            let obj = obj_clone;
+            synchronized(&*obj.0, || tx.send(()).unwrap()).unwrap();
+        });
+
+        thread::sleep(core::time::Duration::from_millis(50));
+        // The other thread is blocked.
+        assert!(rx.try_recv().is_err());
+        guard.unlock().unwrap();
+        rx.recv().unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_unlocked_on_panic() {
+        let obj = NSObject::new();
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            let _ = synchronized(&*obj, || panic!("oops"));
+        }));
+        assert!(res.is_err());
+
+        let obj_clone = AssertSend(obj.clone());
+        thread::spawn(move || {
+            let obj = obj_clone;
+            synchronized(&*obj.0, || ()).unwrap();
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_exit_without_enter() {
+        let obj = NSObject::new();
+        let res = SyncError::from_code(unsafe { ffi::objc_sync_exit(as_ptr(&obj)) });
+        let err = res.unwrap_err();
+        if cfg!(target_vendor = "apple") {
+            assert_eq!(
+                err.to_string(),
+                "the current thread does not own the object's lock"
+            );
+        }
+    }
+}
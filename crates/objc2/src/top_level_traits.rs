@@ -208,7 +208,8 @@ pub unsafe trait Message: RefEncode {
 //    represents any object, like [`AnyObject`][crate::runtime::AnyObject]).
 // 3. [`Self::ThreadKind`] must be correct. It is safe to default to the
 //    super class' thread kind, `<Self::Super as ClassType>::ThreadKind`.
-// 4. [`Self::NAME`] must be the name of the class that this type represents.
+// 4. [`Self::NAME`] must be the name of the class that this type represents
+//    (or its prefix, for generic declared classes).
 // 5. The class returned by [`Self::class`] must be the class that this type
 //    represents.
 pub unsafe trait ClassType: Message {
@@ -238,7 +239,12 @@ pub unsafe trait ClassType: Message {
 
     /// The name of the Objective-C class that this type represents.
     ///
-    /// `T::NAME` is the `const` version of `T::class().name()`.
+    /// `T::NAME` is the `const` version of `T::class().name()`, except for
+    /// generic classes declared with [`declare_class!`], where this is only
+    /// a prefix of the class name, which also contains the generic
+    /// parameters.
+    ///
+    /// [`declare_class!`]: crate::declare_class
     ///
     /// This must not contain any NUL bytes.
     //
@@ -279,8 +285,91 @@ pub unsafe trait ClassType: Message {
 // Safety is ensured by `__UNSAFE_OFFSETS_CORRECT`.
 pub trait DeclaredClass: ClassType {
     /// A type representing the instance variables that this class carries.
+    ///
+    /// If this implements [`StrongReferences`], [`find_retain_cycles`] will
+    /// follow the references held by the instance variables.
+    ///
+    /// [`StrongReferences`]: crate::debug::StrongReferences
+    /// [`find_retain_cycles`]: crate::debug::find_retain_cycles
     type Ivars: Sized;
 
+    /// Called once, right after the class has been registered with the
+    /// runtime.
+    ///
+    /// This is similar to `+load` in Objective-C, and can be used for
+    /// one-time setup that must happen before the class is used.
+    ///
+    /// This is run while [`ClassType::class`] is still registering the
+    /// class, and must therefore not call that function, directly or
+    /// indirectly (e.g. by allocating an instance with
+    /// [`AllocAnyThread::alloc`]); doing so may deadlock. Use the given class
+    /// instead.
+    ///
+    /// [`AllocAnyThread::alloc`]: crate::AllocAnyThread::alloc
+    #[inline]
+    fn load(cls: &'static AnyClass) {
+        let _ = cls;
+    }
+
+    /// Called once by the runtime before the class receives its first
+    /// message.
+    ///
+    /// This is similar to `+initialize` in Objective-C, except that it is
+    /// only called for this exact class, and not again for subclasses that
+    /// don't implement `+initialize` themselves.
+    ///
+    /// Specifying this in [`declare_class!`] adds an `+initialize` method to
+    /// the class; the default implementation does nothing, and is never
+    /// called.
+    ///
+    /// This may be run while [`ClassType::class`] is still registering the
+    /// class (e.g. if [`load`][Self::load] sends a message to it), so use
+    /// the given class instead of calling that function.
+    ///
+    /// [`declare_class!`]: crate::declare_class
+    #[inline]
+    fn initialize(cls: &'static AnyClass) {
+        let _ = cls;
+    }
+
+    /// Called when an instance is being deallocated, before the class' own
+    /// [`Drop`] implementation and before the instance variables are
+    /// dropped.
+    ///
+    /// This is similar to `-dealloc` in Objective-C. The instance variables
+    /// and the superclass are still intact at this point, so the object can
+    /// be messaged, for example to remove it as an observer or to unregister
+    /// it from a notification center.
+    ///
+    /// This is only called on instances that have been fully initialized.
+    /// Specifying this in [`declare_class!`] ensures that a `dealloc` method
+    /// is added to the class; the default implementation does nothing.
+    ///
+    /// The object must not be retained past the lifetime of this hook, see
+    /// the safety section of [`declare_class!`].
+    ///
+    /// [`declare_class!`]: crate::declare_class
+    #[inline]
+    fn dealloc(&self) {}
+
+    /// Whether [`DeclaredClass::initialize`] is overridden.
+    #[doc(hidden)]
+    const __HAS_INITIALIZE: bool = false;
+
+    /// Whether [`DeclaredClass::dealloc`] is overridden.
+    #[doc(hidden)]
+    const __HAS_DEALLOC: bool = false;
+
+    /// The name of the class in the runtime.
+    ///
+    /// This is [`ClassType::NAME`], except for generic classes, where it
+    /// also includes the generic parameters and a hash of the type.
+    #[doc(hidden)]
+    #[inline]
+    fn __name() -> &'static str {
+        Self::NAME
+    }
+
     // TODO: Add `ivars_ptr(this: NonNull<Self>) -> NonNull<Self::Ivars>`?
 
     /// Get a reference to the instance variable data that this object
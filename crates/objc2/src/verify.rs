@@ -2,8 +2,8 @@ use core::fmt;
 use core::hash::Hash;
 use std::error::Error;
 
-use crate::encode::{Encoding, EncodingBox};
-use crate::runtime::{EncodingParseError, Method};
+use crate::encode::{Encoding, EncodingBox, EquivalenceOptions};
+use crate::runtime::{EncodingParseError, Method, MethodEncodingIter, Sel};
 
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub(crate) enum Inner {
@@ -76,8 +76,13 @@ impl Error for VerificationError {}
 /// and allows signed types where unsigned types are excepted.
 ///
 /// Note: This is a top-level comparison; `*mut *mut c_void` or structures
-/// containing `*mut c_void` are not allowed differently than usual.
-fn relaxed_equivalent_to_box(encoding: &Encoding, expected: &EncodingBox) -> bool {
+/// containing `*mut c_void` are not allowed differently than usual (unless
+/// the given options allow it).
+fn relaxed_equivalent_to_box(
+    encoding: &Encoding,
+    expected: &EncodingBox,
+    options: EquivalenceOptions,
+) -> bool {
     if cfg!(feature = "relax-void-encoding")
         && matches!(encoding, Encoding::Pointer(&Encoding::Void))
         && matches!(expected, EncodingBox::Pointer(_))
@@ -107,19 +112,28 @@ fn relaxed_equivalent_to_box(encoding: &Encoding, expected: &EncodingBox) -> boo
         }
     }
 
-    encoding.equivalent_to_box(expected)
+    encoding.equivalent_to_box_with(expected, options)
 }
 
 pub(crate) fn verify_method_signature(
     method: &Method,
     args: &[Encoding],
     ret: &Encoding,
+    options: EquivalenceOptions,
 ) -> Result<(), VerificationError> {
-    let mut iter = method.types();
+    verify_method_types(method.types(), method.name(), args, ret, options)
+}
 
+pub(crate) fn verify_method_types(
+    mut iter: MethodEncodingIter<'_>,
+    sel: Sel,
+    args: &[Encoding],
+    ret: &Encoding,
+    options: EquivalenceOptions,
+) -> Result<(), VerificationError> {
     // TODO: Verify stack layout
     let (expected, _stack_layout) = iter.extract_return()?;
-    if !relaxed_equivalent_to_box(ret, &expected) {
+    if !relaxed_equivalent_to_box(ret, &expected, options) {
         return Err(Inner::MismatchedReturn(expected, ret.clone()).into());
     }
 
@@ -132,7 +146,7 @@ pub(crate) fn verify_method_signature(
         if let Some(res) = iter.next() {
             // TODO: Verify stack layout
             let (expected, _stack_layout) = res?;
-            if !relaxed_equivalent_to_box(actual, &expected) {
+            if !relaxed_equivalent_to_box(actual, &expected, options) {
                 return Err(Inner::MismatchedArgument(i, expected, actual.clone()).into());
             }
         } else {
@@ -145,7 +159,7 @@ pub(crate) fn verify_method_signature(
         return Err(Inner::MismatchedArgumentsCount(actual_count + remaining, actual_count).into());
     }
 
-    let expected_count = method.name().number_of_arguments();
+    let expected_count = sel.number_of_arguments();
     if expected_count != actual_count {
         return Err(Inner::MismatchedArgumentsCount(expected_count, actual_count).into());
     }
@@ -218,6 +232,20 @@ mod tests {
         };
         assert_eq!(res.map_err(|e| e.to_string()), expected);
 
+        // Relaxed on a per-call basis
+        let options = EquivalenceOptions::new().ignore_signedness(true);
+        let res = cls.verify_sel_with::<(), ffi::NSUInteger>(sel!(getNSInteger), options);
+        assert_eq!(res, Ok(()));
+        let res = cls.verify_sel_with::<(i32,), ()>(sel!(setFoo:), options);
+        assert_eq!(res, Ok(()));
+        let err = cls
+            .verify_sel_with::<(i64,), ()>(sel!(setFoo:), options)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected argument at index 0 to have type code 'I', but found 'q'"
+        );
+
         // Metaclass
         let metaclass = cls.metaclass();
         let err = metaclass
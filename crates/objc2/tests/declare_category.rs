@@ -0,0 +1,203 @@
+#![deny(deprecated, unreachable_code)]
+use core::mem;
+
+use objc2::rc::Retained;
+use objc2::runtime::{AnyClass, MethodConflict, NSObject, Sel};
+use objc2::{
+    declare_category, declare_class, msg_send, msg_send_id, sel, ClassType, DeclaredClass,
+};
+
+declare_category!(
+    struct NSObjectHelpers;
+
+    unsafe impl NSObject {
+        #[method(declareCategoryNumber)]
+        fn number(&self) -> u32 {
+            42
+        }
+
+        #[method(declareCategoryIsEqualToSelf:)]
+        fn is_equal_to_self(&self, other: &NSObject) -> bool {
+            self == other
+        }
+
+        #[method_id(declareCategoryNew)]
+        fn new_object() -> Retained<NSObject> {
+            NSObject::new()
+        }
+    }
+);
+
+#[test]
+fn test_add_methods() {
+    let category = NSObjectHelpers::register();
+    assert_eq!(category.class(), NSObject::class());
+    // Registering twice is fine
+    assert!(core::ptr::eq(category, NSObjectHelpers::register()));
+
+    let obj = NSObject::new();
+    let number: u32 = unsafe { msg_send![&obj, declareCategoryNumber] };
+    assert_eq!(number, 42);
+
+    let is_equal: bool = unsafe { msg_send![&obj, declareCategoryIsEqualToSelf: &*obj] };
+    assert!(is_equal);
+
+    let _obj: Retained<NSObject> = unsafe { msg_send_id![NSObject::class(), declareCategoryNew] };
+}
+
+macro_rules! declare_base {
+    ($name:ident) => {
+        declare_class!(
+            struct $name;
+
+            unsafe impl ClassType for $name {
+                type Super = NSObject;
+                const NAME: &'static str = stringify!($name);
+            }
+
+            impl DeclaredClass for $name {}
+
+            unsafe impl $name {
+                #[method(value)]
+                fn value(&self) -> u32 {
+                    1
+                }
+
+                #[method(classValue)]
+                fn class_value() -> u32 {
+                    1
+                }
+            }
+        );
+    };
+}
+
+fn value(cls: &AnyClass) -> u32 {
+    let obj: Retained<NSObject> = unsafe { msg_send_id![cls, new] };
+    unsafe { msg_send![&obj, value] }
+}
+
+fn class_value(cls: &AnyClass) -> u32 {
+    unsafe { msg_send![cls, classValue] }
+}
+
+declare_base!(DeclareCategoryError);
+
+declare_category!(
+    struct DeclareCategoryErrorCategory;
+
+    unsafe impl DeclareCategoryError {
+        #[method(value)]
+        fn value(&self) -> u32 {
+            2
+        }
+    }
+);
+
+#[test]
+#[should_panic = "method -[DeclareCategoryError value] already exists"]
+fn test_conflict_error() {
+    DeclareCategoryErrorCategory::register();
+}
+
+declare_base!(DeclareCategorySkip);
+
+declare_category!(
+    struct DeclareCategorySkipCategory;
+
+    const CONFLICT: MethodConflict = MethodConflict::Skip;
+
+    unsafe impl DeclareCategorySkip {
+        #[method(value)]
+        fn value(&self) -> u32 {
+            2
+        }
+
+        #[method(classValue)]
+        fn class_value() -> u32 {
+            2
+        }
+    }
+);
+
+#[test]
+fn test_conflict_skip() {
+    let category = DeclareCategorySkipCategory::register();
+    let cls = DeclareCategorySkip::class();
+    assert_eq!(value(cls), 1);
+    assert_eq!(class_value(cls), 1);
+    assert_eq!(category.replaced_method(sel!(value)), None);
+    assert_eq!(category.replaced_class_method(sel!(classValue)), None);
+}
+
+declare_base!(DeclareCategoryReplace);
+
+declare_category!(
+    struct DeclareCategoryReplaceCategory;
+
+    const CONFLICT: MethodConflict = MethodConflict::Replace;
+
+    unsafe impl DeclareCategoryReplace {
+        #[method(value)]
+        fn value(&self) -> u32 {
+            2
+        }
+
+        #[method(classValue)]
+        fn class_value() -> u32 {
+            2
+        }
+    }
+);
+
+#[test]
+fn test_conflict_replace() {
+    let category = DeclareCategoryReplaceCategory::register();
+    let cls = DeclareCategoryReplace::class();
+    assert_eq!(value(cls), 2);
+    assert_eq!(class_value(cls), 2);
+
+    // The old implementations are still available
+    let imp = category.replaced_method(sel!(value)).unwrap();
+    let imp: unsafe extern "C-unwind" fn(&DeclareCategoryReplace, Sel) -> u32 =
+        unsafe { mem::transmute(imp) };
+    let obj: Retained<DeclareCategoryReplace> = unsafe { msg_send_id![cls, new] };
+    assert_eq!(unsafe { imp(&obj, sel!(value)) }, 1);
+
+    let imp = category.replaced_class_method(sel!(classValue)).unwrap();
+    let imp: unsafe extern "C-unwind" fn(&AnyClass, Sel) -> u32 = unsafe { mem::transmute(imp) };
+    assert_eq!(unsafe { imp(cls, sel!(classValue)) }, 1);
+}
+
+declare_base!(DeclareCategoryInheritedBase);
+
+declare_class!(
+    struct DeclareCategoryInherited;
+
+    unsafe impl ClassType for DeclareCategoryInherited {
+        type Super = DeclareCategoryInheritedBase;
+        const NAME: &'static str = "DeclareCategoryInherited";
+    }
+
+    impl DeclaredClass for DeclareCategoryInherited {}
+);
+
+declare_category!(
+    struct DeclareCategoryInheritedCategory;
+
+    unsafe impl DeclareCategoryInherited {
+        #[method(value)]
+        fn value(&self) -> u32 {
+            3
+        }
+    }
+);
+
+#[test]
+fn test_override_inherited() {
+    // Overriding a method from the superclass is not a conflict.
+    let category = DeclareCategoryInheritedCategory::register();
+    assert_eq!(value(DeclareCategoryInheritedBase::class()), 1);
+    assert_eq!(value(DeclareCategoryInherited::class()), 3);
+    assert!(category.replaced_method(sel!(value)).is_some());
+}
@@ -1,9 +1,15 @@
 #![deny(deprecated, unreachable_code)]
+use core::cell::{Cell, RefCell};
+use core::ffi::CStr;
 use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicUsize, Ordering};
 
-use objc2::rc::Retained;
-use objc2::runtime::NSObject;
-use objc2::{declare_class, extern_methods, sel, ClassType, DeclaredClass};
+use objc2::rc::{autoreleasepool, Allocated, Retained};
+use objc2::runtime::{AnyClass, ClassBuilder, ClassConflict, NSObject, NSObjectProtocol};
+use objc2::{
+    declare_class, extern_methods, msg_send, msg_send_id, sel, unique_class_name, AllocAnyThread,
+    ClassType, DeclaredClass,
+};
 
 // Test that adding the `deprecated` attribute does not mean that warnings
 // when using the method internally are output.
@@ -483,3 +489,357 @@ fn test_pointer_receiver_allowed() {
 
     let _ = PointerReceiver::class();
 }
+
+struct PropertyIvars {
+    count: Cell<i32>,
+    enabled: Cell<bool>,
+    object: RefCell<Option<Retained<NSObject>>>,
+}
+
+declare_class!(
+    struct DeclareClassProperties;
+
+    unsafe impl ClassType for DeclareClassProperties {
+        type Super = NSObject;
+        const NAME: &'static str = "DeclareClassProperties";
+    }
+
+    impl DeclaredClass for DeclareClassProperties {
+        type Ivars = PropertyIvars;
+    }
+
+    unsafe impl DeclareClassProperties {
+        #[method_id(init)]
+        fn init(this: Allocated<Self>) -> Option<Retained<Self>> {
+            let this = this.set_ivars(PropertyIvars {
+                count: Cell::new(0),
+                enabled: Cell::new(false),
+                object: RefCell::new(None),
+            });
+            unsafe { msg_send_id![super(this), init] }
+        }
+
+        #[property(assign, nonatomic)]
+        count: Cell<i32>;
+
+        #[property(nonatomic, getter = isEnabled, setter = setIsEnabled:)]
+        enabled: Cell<bool>;
+
+        #[property(strong, readonly)]
+        object: RefCell<Option<Retained<NSObject>>>;
+    }
+);
+
+#[test]
+fn test_properties() {
+    let cls = DeclareClassProperties::class();
+    assert!(cls.responds_to(sel!(count)));
+    assert!(cls.responds_to(sel!(setCount:)));
+    assert!(cls.responds_to(sel!(isEnabled)));
+    assert!(cls.responds_to(sel!(setIsEnabled:)));
+    assert!(cls.responds_to(sel!(object)));
+    assert!(!cls.responds_to(sel!(setObject:)));
+
+    let attributes = |name: &CStr| unsafe {
+        let property = objc2::ffi::class_getProperty(cls, name.as_ptr());
+        assert!(!property.is_null());
+        CStr::from_ptr(objc2::ffi::property_getAttributes(property))
+            .to_str()
+            .unwrap()
+    };
+    assert_eq!(
+        attributes(CStr::from_bytes_with_nul(b"count\0").unwrap()),
+        "Ti,N"
+    );
+    assert_eq!(
+        attributes(CStr::from_bytes_with_nul(b"object\0").unwrap()),
+        "T@\"NSObject\",R,&,N"
+    );
+
+    let obj: Retained<DeclareClassProperties> =
+        unsafe { msg_send_id![DeclareClassProperties::alloc(), init] };
+
+    let _: () = unsafe { msg_send![&obj, setCount: 42i32] };
+    let count: i32 = unsafe { msg_send![&obj, count] };
+    assert_eq!(count, 42);
+    assert_eq!(obj.ivars().count.get(), 42);
+
+    let _: () = unsafe { msg_send![&obj, setIsEnabled: true] };
+    assert!(unsafe { msg_send![&obj, isEnabled] });
+
+    let object = NSObject::new();
+    *obj.ivars().object.borrow_mut() = Some(object.clone());
+    let res: Option<Retained<NSObject>> = unsafe { msg_send_id![&obj, object] };
+    assert_eq!(res, Some(object));
+}
+
+static LOAD_COUNT: AtomicUsize = AtomicUsize::new(0);
+static INITIALIZE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+declare_class!(
+    struct DeclareClassHooks;
+
+    unsafe impl ClassType for DeclareClassHooks {
+        type Super = NSObject;
+        const NAME: &'static str = "DeclareClassHooks";
+    }
+
+    impl DeclaredClass for DeclareClassHooks {
+        fn load(cls: &'static AnyClass) {
+            assert_eq!(cls.name().to_str(), Ok("DeclareClassHooks"));
+            LOAD_COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn initialize(cls: &'static AnyClass) {
+            // Use the given class instead of `Self::class()`, since this may
+            // be run while the class is still being registered.
+            assert_eq!(cls.name().to_str(), Ok("DeclareClassHooks"));
+            INITIALIZE_COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+);
+
+declare_class!(
+    struct DeclareClassHooksSubclass;
+
+    unsafe impl ClassType for DeclareClassHooksSubclass {
+        type Super = DeclareClassHooks;
+        const NAME: &'static str = "DeclareClassHooksSubclass";
+    }
+
+    impl DeclaredClass for DeclareClassHooksSubclass {}
+);
+
+#[test]
+fn test_class_hooks() {
+    assert_eq!(LOAD_COUNT.load(Ordering::Relaxed), 0);
+    let cls = DeclareClassHooks::class();
+    assert_eq!(LOAD_COUNT.load(Ordering::Relaxed), 1);
+    assert_eq!(INITIALIZE_COUNT.load(Ordering::Relaxed), 0);
+
+    let _ = DeclareClassHooks::class();
+    assert_eq!(LOAD_COUNT.load(Ordering::Relaxed), 1);
+
+    let _obj: Retained<NSObject> = unsafe { msg_send_id![cls, new] };
+    assert_eq!(INITIALIZE_COUNT.load(Ordering::Relaxed), 1);
+
+    // Not run again for subclasses
+    let _obj: Retained<NSObject> = unsafe { msg_send_id![DeclareClassHooksSubclass::class(), new] };
+    assert_eq!(LOAD_COUNT.load(Ordering::Relaxed), 1);
+    assert_eq!(INITIALIZE_COUNT.load(Ordering::Relaxed), 1);
+}
+
+declare_class!(
+    struct DeclareClassMethodFamily;
+
+    unsafe impl ClassType for DeclareClassMethodFamily {
+        type Super = NSObject;
+        const NAME: &'static str = "DeclareClassMethodFamily";
+    }
+
+    impl DeclaredClass for DeclareClassMethodFamily {}
+
+    unsafe impl DeclareClassMethodFamily {
+        // Would be inferred to be in the "new" family
+        #[method_id(newObjectNotOwned)]
+        #[method_family = none]
+        fn _new_object_not_owned() -> Retained<NSObject> {
+            NSObject::new()
+        }
+
+        // Would be inferred to not be in any family
+        #[method_id(makeOwnedObject)]
+        #[method_family = new]
+        fn _make_owned_object() -> Retained<NSObject> {
+            NSObject::new()
+        }
+
+        #[method_id(duplicate)]
+        #[method_family = copy]
+        fn _duplicate(&self) -> Retained<NSObject> {
+            NSObject::new()
+        }
+    }
+);
+
+extern_methods!(
+    unsafe impl DeclareClassMethodFamily {
+        #[method_id(newObjectNotOwned)]
+        #[method_family = none]
+        fn new_object_not_owned() -> Retained<NSObject>;
+
+        #[method_id(makeOwnedObject)]
+        #[method_family = new]
+        fn make_owned_object() -> Retained<NSObject>;
+
+        #[method_id(duplicate)]
+        #[method_family = copy]
+        fn duplicate(&self) -> Retained<NSObject>;
+    }
+);
+
+#[test]
+fn test_method_family() {
+    let obj = autoreleasepool(|_| DeclareClassMethodFamily::new_object_not_owned());
+    assert_eq!(obj.retainCount(), 1);
+
+    let obj = autoreleasepool(|_| DeclareClassMethodFamily::make_owned_object());
+    assert_eq!(obj.retainCount(), 1);
+
+    let this: Retained<DeclareClassMethodFamily> =
+        unsafe { msg_send_id![DeclareClassMethodFamily::class(), new] };
+    let obj = autoreleasepool(|_| this.duplicate());
+    assert_eq!(obj.retainCount(), 1);
+}
+
+declare_class!(
+    struct DeclareClassUniqueName;
+
+    unsafe impl ClassType for DeclareClassUniqueName {
+        type Super = NSObject;
+        const NAME: &'static str = unique_class_name!(DeclareClassUniqueName);
+    }
+
+    impl DeclaredClass for DeclareClassUniqueName {}
+);
+
+#[test]
+fn test_unique_class_name() {
+    let prefix = concat!(
+        "declare_class::DeclareClassUniqueName@",
+        env!("CARGO_PKG_VERSION")
+    );
+    let name = DeclareClassUniqueName::NAME;
+    if cfg!(feature = "objc2-proc-macros") {
+        let hash = name
+            .strip_prefix(prefix)
+            .unwrap()
+            .strip_prefix('#')
+            .unwrap();
+        assert_eq!(hash.len(), 16);
+    } else {
+        assert_eq!(name, prefix);
+    }
+    assert_eq!(DeclareClassUniqueName::class().name().to_str(), Ok(name));
+}
+
+fn register_existing(name: &[u8]) -> &'static AnyClass {
+    let name = CStr::from_bytes_with_nul(name).unwrap();
+    ClassBuilder::new(name, NSObject::class())
+        .unwrap()
+        .register()
+}
+
+static REUSE_LOAD_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+declare_class!(
+    struct DeclareClassReuse;
+
+    const CONFLICT: ClassConflict = ClassConflict::Reuse;
+
+    unsafe impl ClassType for DeclareClassReuse {
+        type Super = NSObject;
+        const NAME: &'static str = "DeclareClassReuse";
+    }
+
+    impl DeclaredClass for DeclareClassReuse {
+        fn load(_cls: &'static AnyClass) {
+            REUSE_LOAD_COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+);
+
+#[test]
+fn test_reuse_existing_class() {
+    let existing = register_existing(b"DeclareClassReuse\0");
+    assert_eq!(DeclareClassReuse::class(), existing);
+    assert_eq!(REUSE_LOAD_COUNT.load(Ordering::Relaxed), 0);
+
+    let obj: Retained<DeclareClassReuse> =
+        unsafe { msg_send_id![DeclareClassReuse::alloc(), init] };
+    assert_eq!(obj.class(), existing);
+}
+
+declare_class!(
+    struct DeclareClassReuseIncompatible;
+
+    const CONFLICT: ClassConflict = ClassConflict::Reuse;
+
+    unsafe impl ClassType for DeclareClassReuseIncompatible {
+        type Super = NSObject;
+        const NAME: &'static str = "DeclareClassReuseIncompatible";
+    }
+
+    impl DeclaredClass for DeclareClassReuseIncompatible {
+        type Ivars = Cell<u32>;
+    }
+);
+
+#[test]
+#[should_panic = "cannot reuse existing class DeclareClassReuseIncompatible: it does not have the ivars"]
+fn test_reuse_incompatible_class() {
+    let _ = register_existing(b"DeclareClassReuseIncompatible\0");
+    let _ = DeclareClassReuseIncompatible::class();
+}
+
+declare_class!(
+    struct DeclareClassGeneric<T>;
+
+    unsafe impl<T> ClassType for DeclareClassGeneric<T> {
+        type Super = NSObject;
+        const NAME: &'static str = "DeclareClassGeneric";
+    }
+
+    impl<T> DeclaredClass for DeclareClassGeneric<T> {
+        type Ivars = RefCell<Vec<T>>;
+    }
+
+    unsafe impl<T> DeclareClassGeneric<T> {
+        #[method(count)]
+        fn count(&self) -> usize {
+            self.ivars().borrow().len()
+        }
+    }
+);
+
+impl<T: 'static> DeclareClassGeneric<T> {
+    fn new(rows: Vec<T>) -> Retained<Self> {
+        let this = Self::alloc().set_ivars(RefCell::new(rows));
+        unsafe { msg_send_id![super(this), init] }
+    }
+}
+
+#[test]
+fn test_generic() {
+    let numbers = DeclareClassGeneric::new(vec![1u32, 2, 3]);
+    let strings = DeclareClassGeneric::new(vec![String::from("a")]);
+
+    let numbers_cls = DeclareClassGeneric::<u32>::class();
+    let strings_cls = DeclareClassGeneric::<String>::class();
+    assert_ne!(numbers_cls, strings_cls);
+    let numbers_name = numbers_cls.name().to_str().unwrap();
+    assert!(numbers_name.starts_with("DeclareClassGeneric<u32>#"));
+    let strings_name = strings_cls.name().to_str().unwrap();
+    assert!(strings_name.starts_with("DeclareClassGeneric<alloc::string::String>#"));
+    assert_eq!(numbers.class(), numbers_cls);
+
+    // Types with the same `type_name` are still different classes.
+    let local1 = {
+        struct Local;
+        DeclareClassGeneric::<Local>::class()
+    };
+    let local2 = {
+        struct Local;
+        DeclareClassGeneric::<Local>::class()
+    };
+    assert_ne!(local1, local2);
+
+    let count: usize = unsafe { msg_send![&numbers, count] };
+    assert_eq!(count, 3);
+    let count: usize = unsafe { msg_send![&strings, count] };
+    assert_eq!(count, 1);
+
+    let obj: Retained<NSObject> = numbers.into_super();
+    assert!(obj.downcast_ref::<DeclareClassGeneric<String>>().is_none());
+    assert!(obj.downcast::<DeclareClassGeneric<u32>>().is_ok());
+}
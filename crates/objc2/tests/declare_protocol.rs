@@ -0,0 +1,108 @@
+use core::ffi::CStr;
+
+use objc2::rc::Retained;
+use objc2::runtime::{AnyProtocol, NSObject, NSObjectProtocol, ProtocolObject};
+use objc2::{declare_class, declare_protocol, ClassType, DeclaredClass, ProtocolType};
+
+declare_protocol!(
+    #[allow(clippy::missing_safety_doc)]
+    unsafe trait DeclareProtocolBase {
+        #[method(baseValue)]
+        fn base_value(&self) -> u32;
+    }
+
+    unsafe impl ProtocolType for dyn DeclareProtocolBase {}
+);
+
+declare_protocol!(
+    #[allow(clippy::missing_safety_doc)]
+    unsafe trait DeclareProtocolPlugin: NSObjectProtocol + DeclareProtocolBase {
+        #[method(addOne:)]
+        fn add_one(&self, value: i32) -> i32;
+
+        #[method(isEnabled)]
+        fn is_enabled(&self) -> bool;
+
+        #[optional]
+        #[method_id(pluginName)]
+        fn plugin_name(&self) -> Retained<NSObject>;
+
+        #[method_id(createPlugin)]
+        fn create_plugin() -> Retained<Self>;
+    }
+
+    unsafe impl ProtocolType for dyn DeclareProtocolPlugin {
+        const NAME: &'static str = "DeclareProtocolPluginRenamed";
+    }
+);
+
+declare_class!(
+    struct DeclareProtocolImpl;
+
+    unsafe impl ClassType for DeclareProtocolImpl {
+        type Super = NSObject;
+        const NAME: &'static str = "DeclareProtocolImpl";
+    }
+
+    impl DeclaredClass for DeclareProtocolImpl {}
+
+    unsafe impl NSObjectProtocol for DeclareProtocolImpl {}
+
+    unsafe impl DeclareProtocolBase for DeclareProtocolImpl {
+        #[method(baseValue)]
+        fn base_value(&self) -> u32 {
+            7
+        }
+    }
+
+    unsafe impl DeclareProtocolPlugin for DeclareProtocolImpl {
+        #[method(addOne:)]
+        fn add_one(&self, value: i32) -> i32 {
+            value + 1
+        }
+
+        #[method(isEnabled)]
+        fn is_enabled(&self) -> bool {
+            true
+        }
+
+        #[method_id(createPlugin)]
+        fn create_plugin() -> Retained<Self> {
+            unsafe { objc2::msg_send_id![Self::class(), new] }
+        }
+    }
+);
+
+#[test]
+fn test_register() {
+    let base = <dyn DeclareProtocolBase>::protocol().unwrap();
+    assert_eq!(base.name().to_str(), Ok("DeclareProtocolBase"));
+
+    let protocol = <dyn DeclareProtocolPlugin>::protocol().unwrap();
+    assert_eq!(protocol.name().to_str(), Ok("DeclareProtocolPluginRenamed"));
+    // Registered only once
+    assert!(core::ptr::eq(
+        protocol,
+        <dyn DeclareProtocolPlugin>::protocol().unwrap()
+    ));
+    assert!(core::ptr::eq(
+        protocol,
+        AnyProtocol::get(CStr::from_bytes_with_nul(b"DeclareProtocolPluginRenamed\0").unwrap())
+            .unwrap()
+    ));
+
+    assert!(protocol.conforms_to(base));
+    assert!(protocol.conforms_to(<dyn NSObjectProtocol>::protocol().unwrap()));
+}
+
+#[test]
+fn test_implement() {
+    let cls = DeclareProtocolImpl::class();
+    assert!(cls.conforms_to(<dyn DeclareProtocolPlugin>::protocol().unwrap()));
+
+    let obj = <DeclareProtocolImpl as DeclareProtocolPlugin>::create_plugin();
+    let plugin: &ProtocolObject<dyn DeclareProtocolPlugin> = ProtocolObject::from_ref(&*obj);
+    assert_eq!(plugin.add_one(41), 42);
+    assert!(plugin.is_enabled());
+    assert_eq!(plugin.base_value(), 7);
+}
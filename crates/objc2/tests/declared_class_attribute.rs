@@ -0,0 +1,192 @@
+#![cfg(feature = "unstable-attribute-macros")]
+#![deny(deprecated, unreachable_code)]
+use core::cell::Cell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use objc2::rc::{Allocated, Retained};
+use objc2::runtime::{AnyClass, NSObject, NSObjectProtocol, ProtocolObject};
+use objc2::{
+    declare_protocol, declared_class, extern_methods, methods, msg_send, msg_send_id,
+    AllocAnyThread, ClassType, DeclaredClass, ProtocolType,
+};
+
+#[derive(Default)]
+struct Ivars {
+    number: Cell<u32>,
+}
+
+#[declared_class(
+    super = NSObject,
+    name = "DeclaredClassAttribute",
+    protocols = [NSObjectProtocol, DeclaredClassAttributeProtocol],
+)]
+/// With a doc comment.
+struct DeclaredClassAttribute;
+
+static DEALLOC_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+#[methods]
+impl DeclaredClass for DeclaredClassAttribute {
+    type Ivars = Ivars;
+
+    fn dealloc(&self) {
+        if self.ivars().number.get() == 21 {
+            DEALLOC_COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[methods]
+unsafe impl DeclaredClassAttribute {
+    #[method_id(init)]
+    fn init(this: Allocated<Self>) -> Option<Retained<Self>> {
+        let this = this.set_ivars(Ivars::default());
+        unsafe { msg_send_id![super(this), init] }
+    }
+
+    #[method(number)]
+    fn _number(&self) -> u32 {
+        self.ivars().number.get()
+    }
+
+    #[method(setNumber:)]
+    fn _set_number(&self, number: u32) {
+        self.ivars().number.set(number);
+    }
+
+    #[method(add:to:)]
+    fn _add(a: u32, b: u32) -> u32 {
+        a + b
+    }
+
+    #[method_id(newObjectNotOwned)]
+    #[method_family = none]
+    fn _new_object_not_owned() -> Retained<NSObject> {
+        NSObject::new()
+    }
+}
+
+#[methods]
+unsafe impl NSObjectProtocol for DeclaredClassAttribute {}
+
+declare_protocol!(
+    #[allow(clippy::missing_safety_doc)]
+    unsafe trait DeclaredClassAttributeProtocol {
+        #[method(protocolMethod)]
+        fn protocol_method(&self) -> u32;
+    }
+
+    unsafe impl ProtocolType for dyn DeclaredClassAttributeProtocol {}
+);
+
+#[methods]
+unsafe impl DeclaredClassAttributeProtocol for DeclaredClassAttribute {
+    #[method(protocolMethod)]
+    fn protocol_method(&self) -> u32 {
+        self.ivars().number.get() * 2
+    }
+}
+
+extern_methods!(
+    unsafe impl DeclaredClassAttribute {
+        #[method_id(new)]
+        fn new() -> Retained<Self>;
+
+        #[method(number)]
+        fn number(&self) -> u32;
+
+        #[method(setNumber:)]
+        fn set_number(&self, number: u32);
+
+        #[method(add:to:)]
+        fn add(a: u32, b: u32) -> u32;
+
+        #[method_id(newObjectNotOwned)]
+        #[method_family = none]
+        fn new_object_not_owned() -> Retained<NSObject>;
+    }
+);
+
+#[test]
+fn test_methods() {
+    let obj = DeclaredClassAttribute::new();
+    assert_eq!(obj.number(), 0);
+    obj.set_number(21);
+    assert_eq!(obj.number(), 21);
+    let proto: &ProtocolObject<dyn DeclaredClassAttributeProtocol> =
+        ProtocolObject::from_ref(&*obj);
+    assert_eq!(proto.protocol_method(), 42);
+    assert_eq!(DeclaredClassAttribute::add(1, 2), 3);
+    let _obj = DeclaredClassAttribute::new_object_not_owned();
+
+    let cls = DeclaredClassAttribute::class();
+    assert_eq!(cls.name().to_str(), Ok("DeclaredClassAttribute"));
+    assert_eq!(cls.superclass(), Some(NSObject::class()));
+    assert!(cls.conforms_to(<dyn NSObjectProtocol>::protocol().unwrap()));
+    assert!(cls.conforms_to(<dyn DeclaredClassAttributeProtocol>::protocol().unwrap()));
+
+    drop(obj);
+    assert_eq!(DEALLOC_COUNT.load(Ordering::Relaxed), 1);
+}
+
+static LOAD_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+// No inherent methods
+#[declared_class(super = DeclaredClassAttribute, inherits = [NSObject], name = "DeclaredClassAttributeSubclass")]
+pub(crate) struct DeclaredClassAttributeSubclass;
+
+#[methods]
+impl DeclaredClass for DeclaredClassAttributeSubclass {
+    fn load(cls: &'static AnyClass) {
+        assert_eq!(cls.name().to_str(), Ok("DeclaredClassAttributeSubclass"));
+        LOAD_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test_subclass() {
+    let cls = DeclaredClassAttributeSubclass::class();
+    assert_eq!(LOAD_COUNT.load(Ordering::Relaxed), 1);
+    assert_eq!(cls.superclass(), Some(DeclaredClassAttribute::class()));
+
+    let obj: Retained<DeclaredClassAttributeSubclass> =
+        unsafe { msg_send_id![DeclaredClassAttributeSubclass::alloc(), init] };
+    // Deref to the superclass, and the superclass of that
+    let _: &DeclaredClassAttribute = &obj;
+    let _: &NSObject = &obj;
+    let number: u32 = unsafe { msg_send![&obj, number] };
+    assert_eq!(number, 0);
+}
+
+mod renamed {
+    extern crate objc2 as renamed_objc2;
+
+    use renamed_objc2::runtime::NSObject;
+    use renamed_objc2::{declared_class, methods, DeclaredClass};
+
+    #[declared_class(
+        super = NSObject,
+        name = "DeclaredClassAttributeRenamed",
+        crate = renamed_objc2,
+    )]
+    pub(super) struct Renamed;
+
+    #[methods(crate = renamed_objc2)]
+    impl DeclaredClass for Renamed {}
+
+    #[methods(crate = renamed_objc2)]
+    unsafe impl Renamed {
+        #[method(answer)]
+        fn answer() -> u32 {
+            42
+        }
+    }
+}
+
+#[test]
+fn test_renamed_crate() {
+    let cls = renamed::Renamed::class();
+    assert_eq!(cls.name().to_str(), Ok("DeclaredClassAttributeRenamed"));
+    let answer: u32 = unsafe { msg_send![cls, answer] };
+    assert_eq!(answer, 42);
+}
@@ -0,0 +1,100 @@
+//! Test finding retain cycles through declared ivars and blocks.
+use core::cell::RefCell;
+
+use block2::RcBlock;
+use objc2::debug::{find_retain_cycles, ReferenceKind, StrongReferences};
+use objc2::rc::{Retained, Weak};
+use objc2::runtime::{AnyObject, NSObject};
+use objc2::{declare_class, msg_send_id, AllocAnyThread, ClassType, DeclaredClass};
+
+#[derive(Default)]
+struct NodeIvars {
+    next: RefCell<Option<Retained<Node>>>,
+    parent: RefCell<Option<Weak<Node>>>,
+    callback: RefCell<Option<RcBlock<dyn Fn()>>>,
+}
+
+impl StrongReferences for NodeIvars {
+    fn strong_references(&self, f: &mut dyn FnMut(&AnyObject)) {
+        self.next.strong_references(f);
+        self.parent.strong_references(f);
+        self.callback.strong_references(f);
+    }
+}
+
+declare_class!(
+    struct Node;
+
+    unsafe impl ClassType for Node {
+        type Super = NSObject;
+        const NAME: &'static str = "RetainCyclesDeclaredNode";
+    }
+
+    impl DeclaredClass for Node {
+        type Ivars = NodeIvars;
+    }
+);
+
+impl Node {
+    fn new() -> Retained<Self> {
+        let this = Self::alloc().set_ivars(NodeIvars::default());
+        unsafe { msg_send_id![super(this), init] }
+    }
+}
+
+fn ptr(obj: &Retained<Node>) -> *const AnyObject {
+    Retained::as_ptr(obj).cast()
+}
+
+fn is_ivar(kind: &ReferenceKind) -> bool {
+    matches!(kind, ReferenceKind::Ivar(name) if name.ends_with("ivars"))
+}
+
+#[test]
+fn test_declared_ivars() {
+    let a = Node::new();
+    let b = Node::new();
+    *a.ivars().next.borrow_mut() = Some(b.clone());
+    *b.ivars().parent.borrow_mut() = Some(Weak::from_retained(&a));
+    assert_eq!(unsafe { find_retain_cycles(&a, 10) }, []);
+
+    *b.ivars().next.borrow_mut() = Some(a.clone());
+    let cycles = unsafe { find_retain_cycles(&a, 10) };
+    assert_eq!(cycles.len(), 1);
+    let references = cycles[0].references();
+    assert_eq!(references.len(), 2);
+    assert_eq!(references[0].object(), ptr(&a));
+    assert!(is_ivar(references[0].kind()));
+    assert_eq!(references[0].class_name(), Node::NAME);
+    assert_eq!(references[1].object(), ptr(&b));
+    assert!(is_ivar(references[1].kind()));
+
+    // Break the cycle.
+    b.ivars().next.take();
+    assert_eq!(unsafe { find_retain_cycles(&a, 10) }, []);
+}
+
+#[test]
+fn test_block_capture() {
+    let a = Node::new();
+    let captured = a.clone();
+    let block: RcBlock<dyn Fn()> = RcBlock::new(move || {
+        let _ = &captured;
+    });
+    *a.ivars().callback.borrow_mut() = Some(block);
+
+    let cycles = unsafe { find_retain_cycles(&a, 10) };
+    assert_eq!(cycles.len(), 1);
+    let references = cycles[0].references();
+    assert_eq!(references.len(), 2);
+    assert_eq!(references[0].object(), ptr(&a));
+    assert!(is_ivar(references[0].kind()));
+    assert!(matches!(
+        references[1].kind(),
+        ReferenceKind::BlockCapture { .. }
+    ));
+
+    // Break the cycle.
+    a.ivars().callback.take();
+    assert_eq!(unsafe { find_retain_cycles(&a, 10) }, []);
+}
@@ -0,0 +1,64 @@
+//! Test calling variadic methods in Foundation.
+use core::ffi::c_int;
+
+use objc2::encode::NilTerminated;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyObject, NSObject};
+use objc2::{extern_class, extern_methods, msg_send, msg_send_id, ClassType};
+use objc2_foundation::{NSArray, NSString};
+
+extern_class!(
+    struct FormatString;
+
+    unsafe impl ClassType for FormatString {
+        type Super = NSObject;
+        const NAME: &'static str = "NSString";
+    }
+);
+
+extern_methods!(
+    unsafe impl FormatString {
+        #[method_id(stringWithFormat:)]
+        fn with_format(format: &NSString, args: ...) -> Retained<NSString>;
+    }
+);
+
+#[test]
+fn test_extern_methods_format() {
+    let format = NSString::from_str("%d, %ld and %@");
+    let obj = NSString::from_str("foo");
+    let s = FormatString::with_format(&format, (-3 as c_int, 42isize, &*obj));
+    assert_eq!(s.to_string(), "-3, 42 and foo");
+}
+
+#[test]
+fn test_msg_send_format() {
+    let format = NSString::from_str("%.1f%%");
+    let s: Retained<NSString> =
+        unsafe { msg_send_id![NSString::class(), stringWithFormat: &*format, ...(12.5f64,)] };
+    assert_eq!(s.to_string(), "12.5%");
+
+    // No variadic arguments.
+    let format = NSString::from_str("plain");
+    let s: Retained<NSString> =
+        unsafe { msg_send_id![NSString::class(), stringWithFormat: &*format, ...()] };
+    assert_eq!(s.to_string(), "plain");
+}
+
+#[test]
+fn test_nil_terminated() {
+    let a = NSObject::new();
+    let b = NSObject::new();
+    let c = NSObject::new();
+    let array: Retained<NSArray<AnyObject>> = unsafe {
+        msg_send_id![
+            NSArray::<AnyObject>::class(),
+            arrayWithObjects: &*a,
+            ...NilTerminated((&*b, &*c))
+        ]
+    };
+    let count: usize = unsafe { msg_send![&array, count] };
+    assert_eq!(count, 3);
+    let last: *mut AnyObject = unsafe { msg_send![&array, lastObject] };
+    assert_eq!(last, Retained::as_ptr(&c).cast::<AnyObject>().cast_mut());
+}
@@ -0,0 +1,77 @@
+#![cfg_attr(not(feature = "afl"), no_main)]
+use std::str::FromStr;
+
+use objc2::encode::{Encoding, EncodingBox, EquivalenceOptions};
+use objc2_encode::__with_static_encoding;
+
+fn run(a: &EncodingBox, b: &EncodingBox, options: EquivalenceOptions) {
+    let s = a.to_string();
+
+    // Check that formatting produces a valid encoding string, and that
+    // formatting it again produces the same string.
+    let parsed = EncodingBox::from_str(&s).expect("parsing formatted encoding");
+    assert_eq!(parsed.to_string(), s, "roundtrip");
+
+    // SAFETY: The references inside the encodings are not kept around.
+    unsafe {
+        __with_static_encoding(a, |static_a| {
+            __with_static_encoding(b, |static_b| {
+                check_static(a, static_a, b, static_b, &s, options);
+            });
+        });
+    }
+}
+
+fn check_static(
+    a: &EncodingBox,
+    static_a: &Encoding,
+    b: &EncodingBox,
+    static_b: &Encoding,
+    s: &str,
+    options: EquivalenceOptions,
+) {
+    assert_eq!(static_a.to_string(), s, "static and boxed formatting");
+
+    // Check that the string computed at compile time matches the formatted
+    // one.
+    const LEN: usize = 4096;
+    let len = static_a.str_len();
+    assert_eq!(len, s.len(), "static string length");
+    if len <= LEN {
+        let array = static_a.str_array::<LEN>();
+        assert_eq!(&array[..len], s.as_bytes(), "static string");
+    }
+
+    // Check that equivalence is reflexive
+    assert!(static_a.equivalent_to(static_a), "reflexive");
+    assert!(static_a.equivalent_to_box(a), "reflexive boxed");
+    assert!(static_a.equivalent_to_str(s), "reflexive string");
+    assert!(
+        static_a.equivalent_to_box_with(a, options),
+        "reflexive with options"
+    );
+
+    // Check that equivalence is symmetric
+    assert_eq!(
+        static_a.equivalent_to_box(b),
+        static_b.equivalent_to_box(a),
+        "symmetric"
+    );
+    assert_eq!(
+        static_a.equivalent_to_box_with(b, options),
+        static_b.equivalent_to_box_with(a, options),
+        "symmetric with options"
+    );
+}
+
+#[cfg(not(feature = "afl"))]
+libfuzzer_sys::fuzz_target!(|data: (EncodingBox, EncodingBox, EquivalenceOptions)| {
+    run(&data.0, &data.1, data.2);
+});
+
+#[cfg(feature = "afl")]
+fn main() {
+    afl::fuzz!(|data: (EncodingBox, EncodingBox, EquivalenceOptions)| {
+        run(&data.0, &data.1, data.2);
+    });
+}
@@ -0,0 +1,69 @@
+//! Fuzz parsing of method type encodings, such as `"v24@0:8i16"`.
+#![cfg_attr(not(feature = "afl"), no_main)]
+use std::fmt::Write;
+
+use arbitrary::Arbitrary;
+use objc2::encode::EncodingBox;
+use objc2::runtime::MethodEncodingIter;
+
+#[derive(Debug, Arbitrary)]
+struct MethodEncoding {
+    ret: EncodingBox,
+    ret_offset: Option<isize>,
+    args: Vec<(EncodingBox, Option<isize>)>,
+}
+
+/// Parse with the same parser that the runtime uses for verifying methods.
+fn parse(s: &str) -> Option<Vec<(EncodingBox, Option<isize>)>> {
+    MethodEncodingIter::new(s).collect::<Result<_, _>>().ok()
+}
+
+fn format(types: &[(EncodingBox, Option<isize>)]) -> String {
+    let mut s = String::new();
+    for (enc, offset) in types {
+        write!(&mut s, "{enc}").unwrap();
+        if let Some(offset) = offset {
+            write!(&mut s, "{offset}").unwrap();
+        }
+    }
+    s
+}
+
+fn is_bitfield(enc: &EncodingBox) -> bool {
+    match enc {
+        EncodingBox::BitField(..) => true,
+        EncodingBox::Pointer(t) | EncodingBox::Atomic(t) => is_bitfield(t),
+        _ => false,
+    }
+}
+
+fn run(method: MethodEncoding) {
+    let mut types = vec![(method.ret, method.ret_offset)];
+    types.extend(method.args);
+    let s = format(&types);
+
+    // Bitfields are not valid as return or argument types, and their
+    // trailing size can't be distinguished from the stack offset.
+    let has_bitfields = types.iter().any(|(enc, _)| is_bitfield(enc));
+
+    match parse(&s) {
+        // Adjacent encodings may be parsed differently if no stack offset
+        // separates them (e.g. `"@?"` is parsed as a block), but the string
+        // should still be the same.
+        Some(parsed) => assert_eq!(format(&parsed), s, "roundtrip"),
+        None => assert!(has_bitfields, "failed parsing {s:?}"),
+    }
+
+    // Also check that parsing arbitrary strings doesn't panic.
+    let _ = parse(&s[..s.len() / 2]);
+}
+
+#[cfg(not(feature = "afl"))]
+libfuzzer_sys::fuzz_target!(|method: MethodEncoding| run(method));
+
+#[cfg(feature = "afl")]
+fn main() {
+    afl::fuzz!(|method: MethodEncoding| {
+        run(method);
+    });
+}
@@ -0,0 +1,24 @@
+//! Test invalid arguments to `#[declared_class]`.
+use objc2::declared_class;
+
+#[declared_class(super = objc2::runtime::NSObject, name = "UnknownArgument", foo = bar)]
+struct UnknownArgument;
+
+#[declared_class(name = "MissingSuper")]
+struct MissingSuper;
+
+#[declared_class(super = objc2::runtime::NSObject, super = objc2::runtime::NSObject, name = "DuplicateArgument")]
+struct DuplicateArgument;
+
+#[declared_class(super = objc2::runtime::NSObject, name = "InvalidProtocol", protocols = [objc2::runtime::NSObjectProtocol])]
+struct InvalidProtocol;
+
+#[declared_class(super = objc2::runtime::NSObject, name = "WithFields")]
+struct WithFields {
+    field: u32,
+}
+
+#[declared_class(super = objc2::runtime::NSObject, name = "Generic")]
+struct Generic<T>;
+
+fn main() {}
@@ -0,0 +1,48 @@
+//! Test invalid methods in `#[methods]`.
+use objc2::runtime::NSObject;
+use objc2::{declared_class, methods, DeclaredClass};
+
+#[declared_class(super = NSObject, name = "InvalidMethods")]
+struct InvalidMethods;
+
+#[methods]
+impl DeclaredClass for InvalidMethods {}
+
+#[methods]
+impl InvalidMethods {
+    #[method(notUnsafe)]
+    fn not_unsafe() {}
+}
+
+#[methods]
+unsafe impl InvalidMethods {
+    #[method(takesTwo:arguments:)]
+    fn wrong_number_of_arguments(&self, arg: u32) {}
+}
+
+#[methods]
+unsafe impl InvalidMethods {
+    #[method_id(familyTwice)]
+    #[method_family = none]
+    #[method_family = new]
+    fn family_twice() -> objc2::rc::Retained<Self> {
+        unimplemented!()
+    }
+}
+
+#[methods]
+unsafe impl InvalidMethods {
+    #[method(dealloc)]
+    fn dealloc(&self) {}
+}
+
+#[methods]
+unsafe impl InvalidMethods {
+    #[method(withVisibility)]
+    pub fn with_visibility(&self) {}
+}
+
+#[methods(foo)]
+unsafe impl InvalidMethods {}
+
+fn main() {}
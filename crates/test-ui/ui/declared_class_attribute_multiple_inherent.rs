@@ -0,0 +1,23 @@
+//! Only one inherent `#[methods]` block is supported per class.
+use objc2::runtime::NSObject;
+use objc2::{declared_class, methods, DeclaredClass};
+
+#[declared_class(super = NSObject, name = "MultipleInherent")]
+struct MultipleInherent;
+
+#[methods]
+impl DeclaredClass for MultipleInherent {}
+
+#[methods]
+unsafe impl MultipleInherent {
+    #[method(first)]
+    fn first(&self) {}
+}
+
+#[methods]
+unsafe impl MultipleInherent {
+    #[method(second)]
+    fn second(&self) {}
+}
+
+fn main() {}
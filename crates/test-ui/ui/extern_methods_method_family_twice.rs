@@ -0,0 +1,21 @@
+use objc2::runtime::NSObject;
+use objc2::{extern_class, extern_methods, ClassType};
+
+extern_class!(
+    pub struct MyObject;
+
+    unsafe impl ClassType for MyObject {
+        type Super = NSObject;
+    }
+);
+
+extern_methods!(
+    unsafe impl MyObject {
+        #[method_id(a)]
+        #[method_family = none]
+        #[method_family = new]
+        fn a() -> objc2::rc::Retained<Self>;
+    }
+);
+
+fn main() {}
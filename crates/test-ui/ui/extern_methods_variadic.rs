@@ -1,6 +1,6 @@
 use objc2::rc::Retained;
 use objc2::runtime::NSObject;
-use objc2::{extern_class, extern_methods, ClassType};
+use objc2::{extern_class, extern_methods, msg_send, ClassType};
 
 extern_class!(
     pub struct MyObject;
@@ -13,29 +13,19 @@ extern_class!(
 extern_methods!(
     unsafe impl MyObject {
         #[method(a:)]
-        fn variadic_class(arg: i32, arg2: ...);
-    }
-);
-
-extern_methods!(
-    unsafe impl MyObject {
-        #[method(a:)]
-        fn variadic_instance(&self, arg: i32, ...);
-    }
-);
-
-extern_methods!(
-    unsafe impl MyObject {
-        #[method_id(a:)]
-        fn variadic_id(arg: i32, arg2: ...) -> Retained<NSObject>;
+        fn variadic_unnamed(arg: i32, ...);
     }
 );
 
 extern_methods!(
     unsafe impl MyObject {
         #[method(a:_)]
-        fn variadic_error(arg2: ...) -> Result<(), Retained<NSObject>>;
+        fn variadic_error(args: ...) -> Result<(), Retained<NSObject>>;
     }
 );
 
-fn main() {}
+fn main() {
+    let obj = NSObject::new();
+    // Would be promoted to `int` and `double`.
+    let _: () = unsafe { msg_send![&obj, a: 1i32, ...(2u8, 3.0f32)] };
+}